@@ -5,13 +5,16 @@ use criterion::Criterion;
 use rand::distributions::Uniform;
 use rand::{Rng, SeedableRng};
 
-use sadmc::mc::energy::{EnergyMC, EnergyMCParams};
+use sadmc::mc::energy::{Bins, EnergyMC, EnergyMCParams, EnergyMCParamsBuilder, State};
 use sadmc::mc::MonteCarlo;
 use sadmc::rng::vector;
+use sadmc::system::ising::{Ising, IsingParams};
 use sadmc::system::optsquare;
 use sadmc::system::square::{SquareWell, SquareWellNParams};
 use sadmc::system::units;
-use sadmc::system::{ConfirmSystem, Length, MovableSystem};
+use sadmc::system::{
+    ConfirmSystem, Energy, EnergySquared, Length, MovableSystem, MoveDistribution, Unitless,
+};
 
 fn gen_sw(n_atoms: usize) -> SquareWell {
     let mut sw_params = SquareWellNParams::default();
@@ -20,7 +23,7 @@ fn gen_sw(n_atoms: usize) -> SquareWell {
     // Randomize things a bit before beginning.
     let mut rng = sadmc::rng::MyRng::seed_from_u64(1);
     for _ in 0..n_atoms * 1000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm()
     }
     sw
@@ -33,7 +36,7 @@ fn gen_optsw(n_atoms: usize) -> optsquare::SquareWell {
     // Randomize things a bit before beginning.
     let mut rng = sadmc::rng::MyRng::seed_from_u64(1);
     for _ in 0..n_atoms * 1000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm()
     }
     sw
@@ -51,6 +54,30 @@ fn gen_energy_sad(n_atoms: usize) -> EnergyMC<optsquare::SquareWell> {
     mc
 }
 
+fn gen_million_bins() -> EnergyMC<Ising> {
+    let params = EnergyMCParamsBuilder::new()
+        .samc(1.0)
+        .quiet(true)
+        .build()
+        .unwrap();
+    let fname = ::std::path::PathBuf::from("bench.yaml");
+    let ising = Ising::from(IsingParams { N: 4 });
+    let mut mc = EnergyMC::<Ising>::from_params(params, ising, fname);
+    let n = 1_000_000;
+    mc.bins = Bins {
+        min: -1e3 * units::EPSILON,
+        width: 1e-3 * units::EPSILON,
+        histogram: vec![1; n].into(),
+        t_found: vec![0; n].into(),
+        lnw: (0..n).map(|i| Unitless::new((i as f64 * 1e-3).sqrt())).collect(),
+        energy_total: vec![Energy::new(0.0); n].into(),
+        energy_squared_total: vec![EnergySquared::new(0.0); n].into(),
+        extra: Default::default(),
+        edges: None,
+    };
+    mc
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let mut rng = sadmc::rng::MyRng::seed_from_u64(0);
     c.bench_function("sin_cos(x)", move |b| b.iter(|| rng.gen::<f64>().sin_cos()));
@@ -87,13 +114,71 @@ fn criterion_benchmark(c: &mut Criterion) {
         &[50, 100, 200, 400],
     );
 
+    c.bench_function_over_inputs(
+        "sad_ising_1000_moves_between_plugins",
+        move |b, &&block| {
+            let fname = ::std::path::PathBuf::from("bench.yaml");
+            let params = EnergyMCParamsBuilder::new()
+                .quiet(true)
+                .moves_between_plugins(block)
+                .build()
+                .unwrap();
+            let ising = Ising::from(IsingParams { N: 4 });
+            let mut mc = EnergyMC::<Ising>::from_params(params, ising, fname);
+            b.iter(|| {
+                let done = mc.moves + 1000;
+                while mc.moves < done {
+                    mc.move_block();
+                }
+            })
+        },
+        &[1, 16, 256],
+    );
+
+    c.bench_function("sad_prepare_for_10000_lower_energies", move |b| {
+        b.iter_with_setup(
+            || {
+                let fname = ::std::path::PathBuf::from("bench.yaml");
+                let ising = Ising::from(IsingParams { N: 4 });
+                EnergyMC::<Ising>::from_params(EnergyMCParams::default(), ising, fname)
+            },
+            |mut mc| {
+                for k in 0..10_000 {
+                    mc.prepare_for_state(State {
+                        E: -(k as f64) * units::EPSILON,
+                    })
+                    .unwrap();
+                }
+                mc
+            },
+        )
+    });
+
+    c.bench_function_over_inputs(
+        "canonical_averages_1e6_bins_threads",
+        move |b, &&threads| {
+            let mc = gen_million_bins();
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            b.iter(|| {
+                pool.install(|| {
+                    let T = units::EPSILON;
+                    (mc.free_energy(T), mc.mean_energy(T), mc.heat_capacity(T))
+                })
+            })
+        },
+        &[1, 2, 4, 8],
+    );
+
     c.bench_function_over_inputs(
         "SW_move_once_sw",
         move |b, &&n_atoms| {
             let mut sw = gen_sw(n_atoms);
             let mut rng = sadmc::rng::MyRng::seed_from_u64(2);
             b.iter(|| {
-                sw.plan_move(&mut rng, Length::new(0.1));
+                sw.plan_move(&mut rng, Length::new(0.1), MoveDistribution::Gaussian);
                 sw.confirm();
             })
         },
@@ -105,7 +190,7 @@ fn criterion_benchmark(c: &mut Criterion) {
             let mut sw = gen_optsw(n_atoms);
             let mut rng = sadmc::rng::MyRng::seed_from_u64(2);
             b.iter(|| {
-                sw.plan_move(&mut rng, Length::new(0.1));
+                sw.plan_move(&mut rng, Length::new(0.1), MoveDistribution::Gaussian);
                 sw.confirm();
             });
         },
@@ -5,6 +5,8 @@ use sadmc::mc::MonteCarlo;
 
 fn main() {
     let mut mc = EnergyMC::<Any>::from_args::<AnyParams>();
+    #[cfg(feature = "signals")]
+    mc.handle_signals();
     loop {
         mc.move_once();
     }
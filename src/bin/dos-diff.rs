@@ -0,0 +1,41 @@
+//! Compare the densities of states of two saved runs, e.g.
+//!
+//!     dos-diff old.yaml new.yaml
+//!
+//! Each file may be a saved `EnergyMC` run or a `DosSnapshot`.
+
+use sadmc::system::any::Any;
+
+use sadmc::mc::energy::{DosDiff, DosSnapshot, EnergyMC};
+use sadmc::mc::load_from;
+use std::path::Path;
+
+fn load(path: &Path) -> DosSnapshot {
+    if let Ok(mc) = load_from::<EnergyMC<Any>>(path) {
+        return mc.snapshot().unwrap_or_else(|e| {
+            eprintln!("Unable to compare {:?}: {}", path, e);
+            std::process::exit(1);
+        });
+    }
+    load_from(path).unwrap_or_else(|e| {
+        eprintln!("Unable to read {:?}: {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+fn main() {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: dos-diff FIRST SECOND");
+        std::process::exit(1);
+    }
+    let first = load(Path::new(&args[0]));
+    let second = load(Path::new(&args[1]));
+    match DosDiff::between(&first, &second) {
+        Ok(diff) => println!("{}", diff),
+        Err(e) => {
+            eprintln!("Unable to compare: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use sadmc::system::optsquare::{SquareWell, SquareWellNParams};
+
+use sadmc::mc::gcmc::Gcmc;
+use sadmc::mc::MonteCarlo;
+
+fn main() {
+    let mut mc = Gcmc::<SquareWell>::from_args::<SquareWellNParams>();
+    #[cfg(feature = "signals")]
+    mc.handle_signals();
+    loop {
+        mc.move_once();
+    }
+}
@@ -4,8 +4,20 @@ use sadmc::mc::energy::EnergyMC;
 use sadmc::mc::MonteCarlo;
 
 fn main() {
-    let mut mc = EnergyMC::<Any>::from_args::<AnyParams>();
+    let mut mc = match EnergyMC::<Any>::from_args_or_dry_run::<AnyParams>() {
+        Ok(mc) => mc,
+        Err(Ok(estimate)) => {
+            println!("Dry run: {}", estimate);
+            return;
+        }
+        Err(Err(e)) => {
+            println!("Dry run failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "signals")]
+    mc.handle_signals();
     loop {
-        mc.move_once();
+        mc.move_block();
     }
 }
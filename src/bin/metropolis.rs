@@ -0,0 +1,13 @@
+use sadmc::system::any::{Any, AnyParams};
+
+use sadmc::mc::metropolis::Metropolis;
+use sadmc::mc::MonteCarlo;
+
+fn main() {
+    let mut mc = Metropolis::<Any>::from_args::<AnyParams>();
+    #[cfg(feature = "signals")]
+    mc.handle_signals();
+    loop {
+        mc.move_once();
+    }
+}
@@ -0,0 +1,13 @@
+use sadmc::system::optsquare::{SquareWell, SquareWellNParams};
+
+use sadmc::mc::npt::Npt;
+use sadmc::mc::MonteCarlo;
+
+fn main() {
+    let mut mc = Npt::<SquareWell>::from_args::<SquareWellNParams>();
+    #[cfg(feature = "signals")]
+    mc.handle_signals();
+    loop {
+        mc.move_once();
+    }
+}
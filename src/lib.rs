@@ -10,6 +10,7 @@ extern crate serde_derive;
 
 pub mod atomicfile;
 pub mod mc;
+pub mod npy;
 pub mod prettyfloat;
 pub mod rng;
 pub mod rotation;
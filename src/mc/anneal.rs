@@ -0,0 +1,255 @@
+//! Simulated annealing, for finding low-energy configurations.
+//!
+//! This uses the same Metropolis moves as [`super::metropolis`], but
+//! lowers the temperature over the run, and keeps a copy of the lowest
+//! energy configuration found.
+
+#![allow(non_snake_case)]
+
+use super::*;
+use crate::system::*;
+
+use super::plugin::Plugin;
+use crate::prettyfloat::PrettyFloat;
+use dimensioned::Dimensionless;
+use rand::{Rng, SeedableRng};
+use std::default::Default;
+
+/// How the temperature falls over the run.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Lower the temperature by a fixed factor each move
+    Geometric,
+    /// Lower the temperature by a fixed amount each move
+    Linear,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::Geometric
+    }
+}
+
+impl Schedule {
+    /// The temperature after a fraction `frac` of the run.
+    pub fn temperature(&self, T_initial: Energy, T_final: Energy, frac: f64) -> Energy {
+        let frac = frac.max(0.0).min(1.0);
+        match *self {
+            Schedule::Geometric => (*(T_final / T_initial).value()).powf(frac) * T_initial,
+            Schedule::Linear => T_initial + frac * (T_final - T_initial),
+        }
+    }
+}
+
+/// The parameters needed to configure an annealing run.
+#[derive(Debug, AutoArgs)]
+pub struct AnnealParams {
+    /// The temperature to start at.
+    pub T_initial: Energy,
+    /// The temperature to finish at, after `max_iter` moves.
+    pub T_final: Energy,
+    /// How to lower the temperature (geometric by default).
+    pub _schedule: Option<Schedule>,
+    /// The seed for the random number generator.
+    pub seed: Option<u64>,
+    /// The typical distance to move an atom.
+    pub translation_scale: Option<Length>,
+    /// Parameters for reporting and terminating.  The `max_iter` is
+    /// required, since it sets the length of the schedule.
+    pub _report: plugin::ReportParams,
+    /// Parameters for saving.
+    pub _save: plugin::SaveParams,
+    /// Parameters for the time limit.
+    pub _time_limit: plugin::TimeLimitParams,
+}
+
+impl Default for AnnealParams {
+    fn default() -> Self {
+        AnnealParams {
+            T_initial: 10.0 * units::EPSILON,
+            T_final: 0.01 * units::EPSILON,
+            _schedule: None,
+            seed: None,
+            translation_scale: None,
+            _report: plugin::ReportParams::default(),
+            _save: plugin::SaveParams::default(),
+            _time_limit: plugin::TimeLimitParams::default(),
+        }
+    }
+}
+
+/// A simulated annealing run.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Anneal<S> {
+    /// The system we are simulating.
+    pub system: S,
+    /// The lowest energy configuration found so far.
+    pub best: S,
+    /// The energy of `best`.
+    pub best_energy: Energy,
+    /// The move at which `best` was found.
+    pub best_found: u64,
+    /// The temperature to start at.
+    pub T_initial: Energy,
+    /// The temperature to finish at.
+    pub T_final: Energy,
+    /// How the temperature falls.
+    pub schedule: Schedule,
+    /// The number of moves over which we cool.
+    pub max_iter: u64,
+    /// The number of moves that have been made.
+    pub moves: u64,
+    /// The number of moves that have been accepted.
+    pub accepted_moves: u64,
+    /// The typical distance to move an atom.
+    pub translation_scale: Length,
+    /// The random number generator.
+    pub rng: crate::rng::MyRng,
+    /// Where to save the resume file.
+    pub save_as: ::std::path::PathBuf,
+    report: plugin::Report,
+    save: plugin::Save,
+    time_limit: plugin::TimeLimit,
+    manager: plugin::PluginManager,
+}
+
+impl<S> Anneal<S> {
+    /// The temperature for the next move.
+    pub fn temperature(&self) -> Energy {
+        self.schedule.temperature(
+            self.T_initial,
+            self.T_final,
+            self.moves as f64 / self.max_iter as f64,
+        )
+    }
+}
+
+impl<S: MovableSystem + Clone + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo
+    for Anneal<S>
+{
+    type Params = AnnealParams;
+    type System = S;
+    fn from_params(params: AnnealParams, system: S, save_as: ::std::path::PathBuf) -> Self {
+        let max_iter = params
+            ._report
+            .max_iter
+            .expect("annealing needs --max-iter to set the length of the schedule");
+        if !(params.T_final > Energy::new(0.0)) || !(params.T_initial >= params.T_final) {
+            panic!(
+                "Invalid parameters: need T_initial >= T_final > 0, not {} and {}",
+                params.T_initial.pretty(),
+                params.T_final.pretty()
+            );
+        }
+        let translation_scale = params
+            .translation_scale
+            .unwrap_or_else(|| system.natural_move_scale());
+        Anneal {
+            best: system.clone(),
+            best_energy: system.energy(),
+            best_found: 0,
+            system,
+            T_initial: params.T_initial,
+            T_final: params.T_final,
+            schedule: params._schedule.unwrap_or_default(),
+            max_iter,
+            moves: 0,
+            accepted_moves: 0,
+            translation_scale,
+            rng: crate::rng::MyRng::seed_from_u64(params.seed.unwrap_or(0)),
+            save_as,
+            report: plugin::Report::from(params._report),
+            save: plugin::Save::from(params._save),
+            time_limit: plugin::TimeLimit::from(params._time_limit),
+            manager: plugin::PluginManager::new(),
+        }
+    }
+
+    fn update_from_params(&mut self, params: Self::Params) {
+        if let Some(max_iter) = params._report.max_iter {
+            self.max_iter = max_iter;
+        }
+        self.report.update_from(params._report);
+        self.save.update_from(params._save);
+        self.time_limit.update_from(params._time_limit);
+    }
+
+    fn move_once(&mut self) {
+        let T = self.temperature();
+        self.moves += 1;
+        let e1 = self.system.energy();
+        if let Some(e2) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
+            if e2 <= e1 || self.rng.gen::<f64>() < (-*((e2 - e1) / T).value()).exp() {
+                self.accepted_moves += 1;
+                self.system.confirm();
+            } else {
+                self.system.reject();
+            }
+        }
+        if self.system.energy() < self.best_energy {
+            self.best_energy = self.system.energy();
+            self.best_found = self.moves;
+            self.best = self.system.clone();
+        }
+
+        let plugins = [
+            &self.report as &dyn Plugin<Self>,
+            &Logger,
+            &self.save,
+            &self.time_limit,
+        ];
+        self.manager.run(self, &self.system, &plugins);
+    }
+    fn system(&self) -> &Self::System {
+        &self.system
+    }
+    fn system_mut(&mut self) -> &mut Self::System {
+        &mut self.system
+    }
+    fn num_moves(&self) -> u64 {
+        self.moves
+    }
+    fn num_accepted_moves(&self) -> u64 {
+        self.accepted_moves
+    }
+    fn save_as(&self) -> ::std::path::PathBuf {
+        self.save_as.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Logger;
+impl<S: MovableSystem + Clone + serde::Serialize + serde::de::DeserializeOwned>
+    Plugin<Anneal<S>> for Logger
+{
+    fn log(&self, mc: &Anneal<S>, _sys: &S) {
+        if mc.moves > 0 {
+            println!(
+                "    T = {:.3}: E = {:.6}, best {:.6} after {:.2} moves, accepted {:.2}%",
+                mc.temperature().pretty(),
+                mc.system.energy().pretty(),
+                mc.best_energy.pretty(),
+                PrettyFloat(mc.best_found as f64),
+                PrettyFloat(100.0 * mc.accepted_moves as f64 / mc.moves as f64),
+            );
+        }
+    }
+}
+
+#[test]
+fn schedules_run_from_initial_to_final() {
+    let hot = Energy::new(8.0);
+    let cold = Energy::new(0.5);
+    for &s in &[Schedule::Geometric, Schedule::Linear] {
+        assert_eq!(s.temperature(hot, cold, 0.0), hot);
+        assert!((s.temperature(hot, cold, 1.0) - cold).value_unsafe.abs() < 1e-12);
+        assert!((s.temperature(hot, cold, 2.0) - cold).value_unsafe.abs() < 1e-12);
+    }
+    assert_eq!(Schedule::Linear.temperature(hot, cold, 0.5), Energy::new(4.25));
+    let middle = Schedule::Geometric.temperature(hot, cold, 0.5);
+    assert!((middle - Energy::new(2.0)).value_unsafe.abs() < 1e-12);
+}
@@ -9,6 +9,8 @@ use super::plugin::Plugin;
 use crate::prettyfloat::PrettyFloat;
 use dimensioned::Dimensionless;
 use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
 use std::default::Default;
 
 /// Which experimental version of SAD are we doing?
@@ -39,13 +41,19 @@ impl SadVersion {
 }
 
 /// Parameters to configure a particular MC.
-#[derive(Debug, AutoArgs)]
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone)]
 #[allow(non_camel_case_types)]
 pub enum MethodParams {
     /// Sad
     Sad {
-        /// Use the SAD algorithm, with the specified minimum temperature of interest.
+        /// Use the SAD algorithm, with the specified minimum temperature
+        /// of interest, in units of epsilon.
         min_T: Energy,
+        /// The maximum temperature of interest, in units of epsilon.
+        /// Energies above the most probable energy at this temperature
+        /// are not considered important.  By default this is infinite,
+        /// so the important range extends up to the maximum entropy.
+        max_T: Option<Energy>,
     },
     /// Samc
     Samc {
@@ -57,6 +65,11 @@ pub enum MethodParams {
         /// After gamma drops to this value, begin a "production" run
         min_gamma: Option<f64>,
     },
+    /// Use Wang-Landau while also collecting a transition matrix
+    Wltm {
+        /// After gamma drops to this value, begin a "production" run
+        min_gamma: Option<f64>,
+    },
     /// Use the 1/t-Wang-Landau algorithm
     Inv_t_WL,
     /// Use the 1/t-Wang-Landau algorithm, nicer spelling
@@ -68,8 +81,23 @@ pub enum MethodParams {
     },
 }
 
+impl MethodParams {
+    /// The kind of method these parameters ask for.
+    pub fn kind(&self) -> MethodKind {
+        match self {
+            MethodParams::Sad { .. } => MethodKind::Sad,
+            MethodParams::Samc { .. } => MethodKind::Samc,
+            MethodParams::WL { .. }
+            | MethodParams::Wltm { .. }
+            | MethodParams::Inv_t_WL
+            | MethodParams::inv_t_wl => MethodKind::WangLandau,
+            MethodParams::_Canonical { .. } => MethodKind::Canonical,
+        }
+    }
+}
+
 /// Parameters to configure the moves.
-#[derive(Serialize, Deserialize, Debug, AutoArgs)]
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone)]
 pub enum MoveParams {
     /// The rms distance of moves
     TranslationScale(Length),
@@ -77,23 +105,405 @@ pub enum MoveParams {
     AcceptanceRate(f64),
 }
 
-/// The parameters needed to configure a simulation.
-#[derive(Debug, AutoArgs)]
+/// Parameters giving the relative probability of each type of move.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Default)]
+pub struct MoveWeightsParams {
+    /// The weight of moves that displace atoms (default 1).
+    pub displacement_weight: Option<f64>,
+    /// The weight of moves that swap the species of two atoms (default
+    /// 0), for systems with more than one species.
+    pub swap_weight: Option<f64>,
+}
+
+/// A type of move.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveType {
+    /// Displace one or more atoms.
+    Displacement,
+    /// Swap the species of two atoms.
+    Swap,
+}
+
+/// The relative probability of each type of move.  Each move, its
+/// type is drawn in proportion to these weights.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MoveWeights {
+    /// The weight of displacement moves.
+    pub displacement: f64,
+    /// The weight of swap moves.
+    pub swap: f64,
+}
+
+impl Default for MoveWeights {
+    fn default() -> Self {
+        MoveWeights {
+            displacement: 1.0,
+            swap: 0.0,
+        }
+    }
+}
+
+impl MoveWeights {
+    /// The weights given by `params`, with the defaults for any not
+    /// given.
+    pub fn from_params(params: &MoveWeightsParams) -> Self {
+        let default = MoveWeights::default();
+        MoveWeights {
+            displacement: params.displacement_weight.unwrap_or(default.displacement),
+            swap: params.swap_weight.unwrap_or(default.swap),
+        }
+    }
+    /// Check that the weights make sense, and that `system` supports
+    /// every type of move with a nonzero weight.
+    pub fn check<S: MovableSystem>(&self, system: &S) -> Result<(), String> {
+        let weights = [("displacement", self.displacement), ("swap", self.swap)];
+        for &(name, w) in weights.iter() {
+            if !(w >= 0.0 && w.is_finite()) {
+                return Err(format!("the {} weight must not be negative, not {}", name, w));
+            }
+        }
+        if self.displacement + self.swap <= 0.0 {
+            return Err("at least one type of move needs a positive weight".to_string());
+        }
+        if self.swap > 0.0 && !system.can_swap_species() {
+            return Err("this system cannot swap the species of atoms".to_string());
+        }
+        Ok(())
+    }
+    /// Draw the type of the next move.  We only use `rng` if there is
+    /// more than one type to choose from.
+    pub fn choose(&self, rng: &mut crate::rng::MyRng) -> MoveType {
+        if self.swap == 0.0 {
+            MoveType::Displacement
+        } else if self.displacement == 0.0
+            || rng.gen::<f64>() * (self.displacement + self.swap) < self.swap
+        {
+            MoveType::Swap
+        } else {
+            MoveType::Displacement
+        }
+    }
+}
+
+/// The factor by which to change a translation scale that gives
+/// acceptance rate `rate` when aiming for `target`, limited to within
+/// 20% so that a noisy rate cannot throw it far off.
+fn tuning_factor(rate: f64, target: f64) -> f64 {
+    let s = rate / target;
+    if s < 0.8 {
+        0.8
+    } else if s > 1.2 {
+        1.2
+    } else {
+        s
+    }
+}
+
+/// A separate translation scale along each axis, for moving atoms
+/// along one axis at a time with `MovableSystem::plan_move_along`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AxisScales {
+    /// The rms distance of moves along each axis.
+    pub scales: [Length; 3],
+    /// The recent acceptance rate of moves along each axis, found as
+    /// for `EnergyMC::acceptance_rate`.
+    pub acceptance_rates: [f64; 3],
+    /// The number of moves made along each axis.
+    pub moves: [u64; 3],
+}
+
+impl AxisScales {
+    /// Start with the same scale along every axis.
+    pub fn new(scale: Length) -> Self {
+        AxisScales {
+            scales: [scale; 3],
+            acceptance_rates: [0.5; 3], // arbitrary starting guess.
+            moves: [0; 3],
+        }
+    }
+    /// Pick an axis to move along, each equally likely.
+    fn choose(&self, rng: &mut crate::rng::MyRng) -> usize {
+        rng.gen_range(0, 3)
+    }
+    /// Count a move along `axis`.
+    fn record(&mut self, axis: usize, accepted: bool) {
+        self.moves[axis] += 1;
+        let recent_scale = (1.0 / self.moves[axis] as f64).sqrt();
+        self.acceptance_rates[axis] *= 1.0 - recent_scale;
+        if accepted {
+            self.acceptance_rates[axis] += recent_scale;
+        }
+    }
+    /// Adjust the scale along each axis towards the `target`
+    /// acceptance rate, using that axis's own rate.
+    pub fn tune(&mut self, target: f64) {
+        for (scale, &rate) in self.scales.iter_mut().zip(self.acceptance_rates.iter()) {
+            *scale *= tuning_factor(rate, target);
+        }
+    }
+}
+
+/// How to decide whether a histogram `H` is flat.  Both compare the
+/// counts in the visited bins with their mean `<H>`, leaving out bins
+/// that have never been visited.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Copy, PartialEq)]
+pub enum Flatness {
+    /// Flat when `min(H)/<H>` is at least this, the classic
+    /// Wang-Landau criterion (usually 0.8).
+    MinOverMean(f64),
+    /// Flat when the relative standard deviation
+    /// `sqrt(<H^2> - <H>^2)/<H>` is at most this.  This is less
+    /// sensitive to a single low bin.
+    RelStdDev(f64),
+}
+
+impl Default for Flatness {
+    fn default() -> Self {
+        Flatness::MinOverMean(0.8)
+    }
+}
+
+impl Flatness {
+    /// The quantity this criterion compares with its threshold, either
+    /// `min(H)/<H>` or `sqrt(<H^2> - <H>^2)/<H>`.  With no counts at
+    /// all the histogram is as far from flat as it can be: zero or
+    /// infinity respectively.
+    pub fn measure(&self, counts: &[u64]) -> f64 {
+        let n = counts.len() as f64;
+        let mean = counts.iter().sum::<u64>() as f64 / n;
+        match *self {
+            Flatness::MinOverMean(_) => match counts.iter().min() {
+                Some(&min) if mean > 0.0 => min as f64 / mean,
+                _ => 0.0,
+            },
+            Flatness::RelStdDev(_) => {
+                if counts.is_empty() || mean == 0.0 {
+                    return std::f64::INFINITY;
+                }
+                let variance = counts
+                    .iter()
+                    .map(|&h| (h as f64 - mean) * (h as f64 - mean))
+                    .sum::<f64>()
+                    / n;
+                variance.sqrt() / mean
+            }
+        }
+    }
+    /// Are these counts flat by this criterion?
+    pub fn is_flat(&self, counts: &[u64]) -> bool {
+        match *self {
+            Flatness::MinOverMean(f) => self.measure(counts) >= f,
+            Flatness::RelStdDev(f) => self.measure(counts) <= f,
+        }
+    }
+    fn name(&self) -> &'static str {
+        match *self {
+            Flatness::MinOverMean(_) => "min/mean",
+            Flatness::RelStdDev(_) => "std/mean",
+        }
+    }
+}
+
+/// What to do with bins visited fewer than `min_visits` times, whose
+/// `lnw` is too noisy to trust.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Copy, PartialEq, Eq)]
+pub enum RareBins {
+    /// Leave them out of canonical averages, and export NaN for their lnw
+    Exclude,
+    /// Keep them, but export which bins they are as `-rare.npy`
+    Flag,
+}
+
+impl Default for RareBins {
+    fn default() -> Self {
+        RareBins::Exclude
+    }
+}
+
+/// What to do when an update leaves `lnw` infinite or NaN, which would
+/// otherwise silently spoil every average that follows.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Copy, PartialEq, Eq)]
+pub enum NonFiniteLnw {
+    /// Stop the run, naming the bin and the move
+    Abort,
+    /// Put back the bin's last finite value, and warn
+    Clamp,
+}
+
+/// The parameters needed to configure a simulation.  These may also
+/// be read from a file with [`EnergyMCParams::from_config_file`].
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone)]
 pub struct EnergyMCParams {
     /// The actual method.
     pub _method: MethodParams,
-    /// The seed for the random number generator.
+    /// The seed for the random number generator.  If not given, a
+    /// random seed is chosen and saved with the run.
     pub seed: Option<u64>,
-    /// The energy binsize.
-    energy_bin: Option<Energy>,
+    /// The energy binsize.  Defaults to the system's `delta_energy`,
+    /// or to one epsilon for systems with continuous energies.
+    pub energy_bin: Option<Energy>,
+    /// For SAD on a system with continuous energies, make the energy
+    /// bin this fraction of `min_T`, so that the Boltzmann factor at
+    /// `min_T` changes by about this much across a bin.  Something like
+    /// 0.1 resolves the low temperatures well; a smaller fraction
+    /// gives finer resolution, but memory and the time to find every
+    /// bin grow as its inverse.  This is ignored for systems with a
+    /// `delta_energy`, and cannot be given with `energy_bin`.
+    pub energy_bin_fraction_of_min_T: Option<f64>,
     /// The lowest energy to allow.
     min_allowed_energy: Option<Energy>,
     /// The highest energy to allow.
     max_allowed_energy: Option<Energy>,
-    _moves: MoveParams,
+    /// The maximum number of energy bins to allow.
+    max_bins: Option<usize>,
+    /// After this many moves with uniform bins, adapt them once to the
+    /// shape of `lnw`: split bins where it curves sharply, and merge
+    /// pairs where it is nearly straight.  By default bins stay
+    /// uniform.
+    adapt_bins_after: Option<u64>,
+    /// Split bins where the second difference of `lnw` is larger than
+    /// this (default 1).
+    adapt_bins_split: Option<f64>,
+    /// Merge pairs of bins where the second difference of `lnw` is
+    /// smaller than this (default 0.01).
+    adapt_bins_merge: Option<f64>,
+    /// Start from the entropy in this file, as written from the
+    /// result of `EnergyMC::merge`, rather than from a single bin.
+    initial_lnw: Option<::std::path::PathBuf>,
+    /// Reject any move to an energy above this, so that no bins are
+    /// made there.  Unlike `max_allowed_energy`, this changes the
+    /// ensemble we sample: states above the ceiling are simply never
+    /// visited, so the system must start at or below it.
+    energy_ceiling: Option<Energy>,
+    /// Together with `fixed_max_energy`, make every bin from here up
+    /// at the start, and reject any move out of this window, so that
+    /// the bins never grow during the run.  The system must start
+    /// within the window.
+    fixed_min_energy: Option<Energy>,
+    /// The top of the fixed energy window.
+    fixed_max_energy: Option<Energy>,
+    /// How far to move atoms.  Defaults to the system's
+    /// `natural_move_scale`.
+    _moves: Option<MoveParams>,
+    /// How to draw the displacement of each move.
+    _move_distribution: Option<MoveDistribution>,
+    /// Move this many atoms at once in each move.
+    atoms_per_move: Option<usize>,
+    /// Move atoms along one axis at a time, with a separate translation
+    /// scale along each, which is tuned on its own when aiming for an
+    /// acceptance rate.  This suits systems much longer in some
+    /// directions than others, such as a slab.
+    anisotropic_moves: bool,
+    /// The relative probability of each type of move.
+    _move_weights: MoveWeightsParams,
+    /// Collect a transition matrix to check the density of states.
+    /// This costs memory for every pair of energies we move between.
+    collect_transition_matrix: bool,
+    /// Count the visits to each energy separately for a walker heading
+    /// up and one heading down, as needed for the optimized ensemble.
+    collect_directions: bool,
+    /// Check that updating `lnw` never lowers it at an energy within
+    /// the important range, and report any bins where it did.  This
+    /// is a debugging aid, and costs a little on every move.
+    check_lnw_increases: bool,
+    /// Check after every update that `lnw` is still finite, and either
+    /// abort or clamp if it is not.  This guards against gamma or a
+    /// change of range overflowing, and costs a little on every move.
+    non_finite_lnw: Option<NonFiniteLnw>,
+    /// Keep a copy of the lowest energy configuration found, for
+    /// systems that can copy theirs with `System::snapshot`.
+    record_best: bool,
+    /// Collect a joint histogram of the energy and this observable
+    /// from `System::data_to_collect`, so as to find its distribution
+    /// at any temperature.
+    joint_observable: Option<String>,
+    /// The lowest value of the joint observable to bin (default 0).
+    /// Lower values go in the lowest bin.
+    joint_observable_min: Option<f64>,
+    /// The highest value of the joint observable to bin (default 1).
+    /// Higher values go in the highest bin.
+    joint_observable_max: Option<f64>,
+    /// The number of bins for the joint observable (default 100).
+    /// The joint histogram holds at most this many counts for each
+    /// energy bin.
+    joint_observable_bins: Option<usize>,
+    /// Start SAD's important energy range up at the system's greatest
+    /// possible energy, if it is known, rather than waiting to find
+    /// the high energies.  This suits systems whose high energies
+    /// matter, such as the Ising model at negative temperature.
+    too_hi_from_bounds: bool,
+    /// The number of moves in each block when estimating the error of
+    /// the mean energy, which needs a canonical run.  Blocks are merged
+    /// in pairs once there are many of them.
+    energy_block_size: Option<u64>,
+    /// Estimate the autocorrelation time of the energy from lags of
+    /// up to this many moves, and give it in the final report.
+    autocorrelation_lags: Option<usize>,
+    /// Make up to this many moves in each call to `move_block` (and in
+    /// `run_for`), and only then check the plugins, which is cheaper
+    /// for systems with very fast moves.  The plugins still run after
+    /// exactly the same moves, so the run is the same as without it.
+    moves_between_plugins: Option<u64>,
+    /// How flat the Wang-Landau histogram must be before gamma is
+    /// halved.  By default min/mean must reach 0.8.
+    wl_flatness: Option<Flatness>,
+    /// Check that the moves keep detailed balance, which we rely on
+    /// since we accept moves without any correction for the proposal.
+    /// Before running we check that the move displacements are
+    /// symmetric, and then for each move that confirming or rejecting
+    /// it leaves the system at the right energy, and that the
+    /// displacements accepted are symmetric.  The run panics if any of
+    /// these fail.
+    check_move_symmetry: bool,
+    /// Print how many energy bins the run will make, and how much
+    /// memory their arrays will need, and exit without running.  The
+    /// energy range is the fixed window if given, and otherwise the
+    /// system's energy bounds.
+    #[serde(skip)]
+    dry_run: bool,
+    /// Begin with this many moves at infinite temperature, accepting
+    /// every allowed move and neither collecting statistics nor
+    /// changing the weights, to spread out the initial configuration.
+    burn_in_moves: Option<u64>,
+    /// Only trust the `lnw` of bins visited at least this many times
+    /// (default 1) in canonical averages and exports.  For SAD this is
+    /// as well as leaving out energies outside the important range.
+    min_visits: Option<u64>,
+    /// Whether to exclude or just flag the bins below `min_visits`.
+    rare_bins: Option<RareBins>,
+    /// Count max_iter and the other numbers of moves in sweeps, each of
+    /// which is the system's natural_sweep_length moves.
+    sweeps: bool,
+    /// Read the parameters from this YAML or JSON file, as described at
+    /// `EnergyMCParams::from_config_file`.  Flags given on the command
+    /// line override the file.
+    #[serde(skip)]
+    config: Option<::std::path::PathBuf>,
+    /// Keep the method given in the config file, or saved in the file
+    /// we resume from, rather than the one on the command line.  A
+    /// method flag is still needed, but is ignored.
+    #[serde(skip)]
+    keep_method: bool,
+    /// When resuming a run that used a different kind of method, switch
+    /// to the one given, carrying over the weights learned so far (see
+    /// `EnergyMC::switch_method`).  Without this, that is an error.
+    #[serde(skip)]
+    switch_method: bool,
     _report: plugin::ReportParams,
     _movies: plugin::MovieParams,
     _save: plugin::SaveParams,
+    _energy_check: plugin::EnergyCheckParams,
+    _time_limit: plugin::TimeLimitParams,
+    _sad_trace: plugin::SadTraceParams,
+    _npy_export: NpyExportParams,
+    _lnw_file: LnwFileParams,
+    _progress: plugin::ProgressParams,
+    _live_plot: plugin::LivePlotParams,
+    _final_report: FinalReportParams,
+    _stagnation: StagnationParams,
+    _exhaustion: ExhaustionParams,
+    _energy_trace: plugin::EnergyTraceParams,
+    _flush: plugin::FlushParams,
 }
 
 impl Default for EnergyMCParams {
@@ -101,16 +511,766 @@ impl Default for EnergyMCParams {
         EnergyMCParams {
             _method: MethodParams::Sad {
                 min_T: 0.2 * units::EPSILON,
+                max_T: None,
             },
             seed: None,
             min_allowed_energy: None,
             max_allowed_energy: None,
             energy_bin: None,
-            _moves: MoveParams::TranslationScale(0.05 * units::SIGMA),
+            energy_bin_fraction_of_min_T: None,
+            max_bins: None,
+            adapt_bins_after: None,
+            adapt_bins_split: None,
+            adapt_bins_merge: None,
+            initial_lnw: None,
+            energy_ceiling: None,
+            fixed_min_energy: None,
+            fixed_max_energy: None,
+            _moves: None,
+            _move_distribution: None,
+            atoms_per_move: None,
+            anisotropic_moves: false,
+            _move_weights: MoveWeightsParams::default(),
+            collect_transition_matrix: false,
+            collect_directions: false,
+            check_lnw_increases: false,
+            non_finite_lnw: None,
+            record_best: false,
+            joint_observable: None,
+            joint_observable_min: None,
+            joint_observable_max: None,
+            joint_observable_bins: None,
+            too_hi_from_bounds: false,
+            energy_block_size: None,
+            autocorrelation_lags: None,
+            moves_between_plugins: None,
+            wl_flatness: None,
+            burn_in_moves: None,
+            min_visits: None,
+            rare_bins: None,
+            check_move_symmetry: false,
+            dry_run: false,
+            sweeps: false,
+            config: None,
+            keep_method: false,
+            switch_method: false,
             _report: plugin::ReportParams::default(),
             _movies: plugin::MovieParams::default(),
             _save: plugin::SaveParams::default(),
+            _energy_check: plugin::EnergyCheckParams::default(),
+            _time_limit: plugin::TimeLimitParams::default(),
+            _sad_trace: plugin::SadTraceParams::default(),
+            _npy_export: NpyExportParams::default(),
+            _lnw_file: LnwFileParams::default(),
+            _progress: plugin::ProgressParams::default(),
+            _live_plot: plugin::LivePlotParams::default(),
+            _final_report: FinalReportParams::default(),
+            _stagnation: StagnationParams::default(),
+            _exhaustion: ExhaustionParams::default(),
+            _energy_trace: plugin::EnergyTraceParams::default(),
+            _flush: plugin::FlushParams::default(),
+        }
+    }
+}
+
+impl EnergyMCParams {
+    /// Check for parameters that cannot possibly work, such as a
+    /// non-positive temperature or bin size.
+    pub fn validate(&self) -> Result<(), String> {
+        // Energies from the command line are in units of epsilon, and
+        // may be given as expressions such as "1/3", so we check that
+        // what comes out is a sensible number.
+        let positive = |x: Energy| x.value_unsafe > 0.0 && x.value_unsafe.is_finite();
+        match self._method {
+            MethodParams::Sad { min_T, .. } if !positive(min_T) => {
+                return Err(format!(
+                    "SAD needs a finite positive min_T, not {}",
+                    min_T.pretty()
+                ));
+            }
+            MethodParams::Sad {
+                min_T,
+                max_T: Some(max_T),
+            } if !positive(max_T) || max_T <= min_T => {
+                return Err(format!(
+                    "SAD needs a finite max_T above min_T, not {} with min_T {}",
+                    max_T.pretty(),
+                    min_T.pretty()
+                ));
+            }
+            MethodParams::Samc { t0 } if !(t0 > 0.0) => {
+                return Err(format!("SAMC needs a positive t0, not {}", t0));
+            }
+            MethodParams::_Canonical { T } if !positive(T) => {
+                return Err(format!(
+                    "a canonical run needs a finite positive T, not {}",
+                    T.pretty()
+                ));
+            }
+            _ => (),
+        }
+        if let Some(width) = self.energy_bin {
+            if !positive(width) {
+                return Err(format!("energy_bin must be positive, not {}", width.pretty()));
+            }
+        }
+        if let Some(fraction) = self.energy_bin_fraction_of_min_T {
+            if self.energy_bin.is_some() {
+                return Err(
+                    "energy_bin and energy_bin_fraction_of_min_T cannot both be given".to_string(),
+                );
+            }
+            if !(fraction > 0.0 && fraction.is_finite()) {
+                return Err(format!(
+                    "energy_bin_fraction_of_min_T must be positive, not {}",
+                    fraction
+                ));
+            }
+            if !matches!(self._method, MethodParams::Sad { .. }) {
+                return Err("energy_bin_fraction_of_min_T only applies to SAD".to_string());
+            }
+        }
+        if let Some(ref name) = self.joint_observable {
+            let min = self.joint_observable_min.unwrap_or(0.0);
+            let max = self.joint_observable_max.unwrap_or(1.0);
+            if !(min < max && min.is_finite() && max.is_finite()) {
+                return Err(format!(
+                    "the range of joint observable {} must be finite, not {} to {}",
+                    name, min, max
+                ));
+            }
+            if self.joint_observable_bins == Some(0) {
+                return Err("joint_observable_bins must be positive".to_string());
+            }
+        } else if self.joint_observable_min.is_some()
+            || self.joint_observable_max.is_some()
+            || self.joint_observable_bins.is_some()
+        {
+            return Err(
+                "joint_observable_min, _max and _bins need a joint_observable to apply to"
+                    .to_string(),
+            );
+        }
+        if self.max_bins == Some(0) {
+            return Err("max_bins must be positive".to_string());
+        }
+        if self.keep_method && self.switch_method {
+            return Err("keep_method and switch_method cannot both be given".to_string());
+        }
+        if self.anisotropic_moves && self.atoms_per_move.map(|n| n > 1).unwrap_or(false) {
+            return Err("anisotropic_moves cannot be used with atoms_per_move".to_string());
+        }
+        if self.adapt_bins_after.is_some() {
+            let split = self.adapt_bins_split.unwrap_or(DEFAULT_ADAPT_SPLIT);
+            let merge = self.adapt_bins_merge.unwrap_or(DEFAULT_ADAPT_MERGE);
+            if !(merge >= 0.0 && merge < split && split.is_finite()) {
+                return Err(format!(
+                    "need 0 <= adapt_bins_merge < adapt_bins_split, not {} and {}",
+                    merge, split
+                ));
+            }
+            // These are all keyed by energy on a uniform grid.
+            let uniform_only = [
+                ("max_bins", self.max_bins.is_some()),
+                ("a transition matrix", self.collect_transition_matrix),
+                ("WLTM", matches!(self._method, MethodParams::Wltm { .. })),
+                ("collect_directions", self.collect_directions),
+                ("check_lnw_increases", self.check_lnw_increases),
+                ("a joint histogram", self.joint_observable.is_some()),
+                ("lnw_file_interval", self._lnw_file.lnw_file_interval.is_some()),
+            ];
+            for &(name, given) in uniform_only.iter() {
+                if given {
+                    return Err(format!("adaptive bins cannot be used with {}", name));
+                }
+            }
+        }
+        if self.energy_block_size == Some(0) {
+            return Err("energy_block_size must be positive".to_string());
+        }
+        let canonical = matches!(self._method, MethodParams::_Canonical { .. });
+        if self.energy_block_size.is_some() && !canonical {
+            return Err("energy_block_size only applies to canonical runs".to_string());
+        }
+        if self.autocorrelation_lags == Some(0) {
+            return Err("autocorrelation_lags must be positive".to_string());
+        }
+        let intervals = [
+            ("progress_interval", self._progress.progress_interval),
+            ("live_plot_interval", self._live_plot.live_plot_interval),
+        ];
+        for &(name, interval) in intervals.iter() {
+            if let Some(interval) = interval {
+                if !(interval >= 0.0 && interval.is_finite()) {
+                    return Err(format!(
+                        "{} must be a finite, non-negative number of seconds, not {}",
+                        name, interval
+                    ));
+                }
+            }
+        }
+        if let Some(moves) = self._final_report.validation_moves {
+            if moves < VALIDATION_BLOCKS {
+                return Err(format!(
+                    "validation_moves must be at least {}, not {}",
+                    VALIDATION_BLOCKS, moves
+                ));
+            }
+            if self._final_report.report_temperatures.is_empty() {
+                return Err("validation_moves needs report_temperatures".to_string());
+            }
+            if self._final_report.final_report.is_none() {
+                return Err("validation_moves needs a final_report to appear in".to_string());
+            }
+        }
+        if self.moves_between_plugins == Some(0) {
+            return Err("moves_between_plugins must be positive".to_string());
+        }
+        if let (Some(lo), Some(hi)) = (self.min_allowed_energy, self.max_allowed_energy) {
+            if lo >= hi {
+                return Err(format!(
+                    "min_allowed_energy {} must be below max_allowed_energy {}",
+                    lo.pretty(),
+                    hi.pretty()
+                ));
+            }
+        }
+        match (self.fixed_min_energy, self.fixed_max_energy) {
+            (Some(lo), Some(hi)) if lo >= hi => {
+                return Err(format!(
+                    "fixed_min_energy {} must be below fixed_max_energy {}",
+                    lo.pretty(),
+                    hi.pretty()
+                ));
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(
+                    "fixed_min_energy and fixed_max_energy must be given together".to_string(),
+                );
+            }
+            _ => (),
+        }
+        if self.atoms_per_move == Some(0) {
+            return Err("atoms_per_move must be at least 1".to_string());
+        }
+        Ok(())
+    }
+    /// Read parameters from a YAML or JSON file (chosen by extension),
+    /// whose keys are the field names of this struct, e.g.
+    ///
+    /// ```yaml
+    /// _method:
+    ///   Samc:
+    ///     t0: 1000
+    /// seed: 7
+    /// energy_bin: 0.1
+    /// _report:
+    ///   max_iter: 1000000
+    /// ```
+    ///
+    /// Anything not given takes its default value, including the
+    /// fields of a nested struct such as `_report`.
+    pub fn from_config_file(path: &::std::path::Path) -> Result<Self, SaveError> {
+        let file: serde_yaml::Value = load_from(path)?;
+        let format_error = |e: serde_yaml::Error| SaveError::Format(e.to_string());
+        let mut params = serde_yaml::to_value(EnergyMCParams::default()).map_err(format_error)?;
+        merge_yaml(&mut params, file);
+        serde_yaml::from_value(params).map_err(format_error)
+    }
+    /// If `config` names a file, these parameters with any read from
+    /// the file that we left at their defaults, so that command-line
+    /// flags override the file.  A flag can't be used to set back the
+    /// default value of something the file sets.  The method is always
+    /// the one from the command line, unless `keep_method` is set.
+    pub fn with_config_file(self) -> Result<Self, String> {
+        use serde_yaml::{Mapping, Value};
+        let path = match self.config {
+            Some(ref path) => path.clone(),
+            None => return Ok(self),
+        };
+        let file = EnergyMCParams::from_config_file(&path)
+            .map_err(|e| format!("error reading config file {:?}: {}", path, e))?;
+        let to_value = |p: &EnergyMCParams| serde_yaml::to_value(p).map_err(|e| e.to_string());
+        let mut flags = yaml_changes(&to_value(&EnergyMCParams::default())?, to_value(&self)?)
+            .unwrap_or_else(|| Value::Mapping(Mapping::new()));
+        if let Value::Mapping(ref mut flags) = flags {
+            let key = Value::from("_method");
+            if self.keep_method {
+                flags.remove(&key);
+            } else {
+                let method = serde_yaml::to_value(&self._method).map_err(|e| e.to_string())?;
+                flags.insert(key, method);
+            }
+        }
+        let mut merged = to_value(&file)?;
+        merge_yaml(&mut merged, flags);
+        let mut params: EnergyMCParams =
+            serde_yaml::from_value(merged).map_err(|e| e.to_string())?;
+        params.config = Some(path);
+        params.keep_method = self.keep_method;
+        params.switch_method = self.switch_method;
+        Ok(params)
+    }
+    /// The same parameters with every number of moves multiplied by
+    /// `sweep`, the number of moves in a sweep.  This leaves wall-clock
+    /// times and numbers of samples alone.  A system with no atoms has
+    /// sweeps of no moves, which we treat as one move so that nothing
+    /// positive becomes zero.
+    pub fn sweeps_to_moves(mut self, sweep: u64) -> Self {
+        let sweep = sweep.max(1);
+        let scale = |n: Option<u64>| n.map(|n| n * sweep);
+        self._report.max_iter = scale(self._report.max_iter);
+        self._energy_check.energy_check_interval = scale(self._energy_check.energy_check_interval);
+        self._sad_trace.sad_trace_interval = scale(self._sad_trace.sad_trace_interval);
+        self._stagnation.stagnation_moves = scale(self._stagnation.stagnation_moves);
+        self._exhaustion.exhausted_after = scale(self._exhaustion.exhausted_after);
+        self._energy_trace.energy_trace_interval = scale(self._energy_trace.energy_trace_interval);
+        self._lnw_file.lnw_file_interval = scale(self._lnw_file.lnw_file_interval);
+        self.energy_block_size = scale(self.energy_block_size);
+        self.burn_in_moves = scale(self.burn_in_moves);
+        self.adapt_bins_after = scale(self.adapt_bins_after);
+        self._final_report.validation_moves = scale(self._final_report.validation_moves);
+        self.sweeps = false;
+        self
+    }
+    /// The same parameters, but only allowing energies from `min` to
+    /// `max`, as for one window of [`super::windows::run_windows`].
+    pub fn restricted_to(mut self, min: Energy, max: Energy) -> Self {
+        self.min_allowed_energy = Some(min);
+        self.max_allowed_energy = Some(max);
+        self
+    }
+    /// The energy bin to use for `system`: `energy_bin` if given, then
+    /// the system's `delta_energy`, then `energy_bin_fraction_of_min_T`
+    /// times SAD's `min_T`, and otherwise one epsilon.
+    pub fn energy_bin_for<S: System>(&self, system: &S) -> Energy {
+        self.energy_bin
+            .or_else(|| system.delta_energy())
+            .or_else(|| self.energy_bin_from_min_T())
+            .unwrap_or(Energy::new(1.0))
+    }
+    fn energy_bin_from_min_T(&self) -> Option<Energy> {
+        match self._method {
+            MethodParams::Sad { min_T, .. } => self.energy_bin_fraction_of_min_T.map(|f| f * min_T),
+            _ => None,
+        }
+    }
+    /// Check that `energy_bin` suits `system`.  It is an error if the
+    /// bin would need more than `max_bins` bins (or an unreasonable
+    /// number if that is not set) to span the system's energies, and we
+    /// return a warning if the system has discrete energies and the bin
+    /// is not a multiple of their spacing, which would alias them.
+    pub fn check_energy_bin<S: System>(&self, system: &S) -> Result<Option<String>, String> {
+        let width = match self.energy_bin {
+            Some(width) => width,
+            None if system.delta_energy().is_some() => return Ok(None),
+            None => match self.energy_bin_from_min_T() {
+                Some(width) => width,
+                None => return Ok(None),
+            },
+        };
+        let magnitude = |e: Energy| Energy::new(e.value_unsafe.abs());
+        // A crude span of energy when we do not know both bounds.
+        let span = match (system.lowest_possible_energy(), system.greatest_possible_energy()) {
+            (Some(lo), Some(hi)) => hi - lo,
+            (Some(lo), None) => magnitude(lo),
+            (None, _) => magnitude(system.energy()),
+        };
+        let needed = *(span / width).value();
+        let max_bins = self.max_bins.map(|n| n as f64).unwrap_or(MAX_REASONABLE_BINS);
+        if needed > max_bins {
+            return Err(format!(
+                "energy_bin {} would need about {:.0} bins to span {}",
+                width.pretty(),
+                needed,
+                span.pretty()
+            ));
+        }
+        if let Some(de) = system.delta_energy() {
+            let ratio = *(width / de).value();
+            if ratio.round() < 1.0 || (ratio - ratio.round()).abs() > 1e-9 {
+                return Ok(Some(format!(
+                    "energy_bin {} is not a multiple of the energy spacing {}, so bins will alias",
+                    width.pretty(),
+                    de.pretty()
+                )));
+            }
+        }
+        Ok(None)
+    }
+    /// Predict the energy bins a run of `system` with these parameters
+    /// will have once it has made its bins out to the fixed window, or
+    /// failing that the system's energy bounds, along with the memory
+    /// needed by each of the arrays with an entry per bin.  Observables
+    /// are counted as those `system.data_to_collect` gives at the first
+    /// move.  Sparse data such as a transition matrix or joint
+    /// histogram are not included, since they depend on the moves made.
+    /// This is an error if neither range is known.
+    pub fn estimate_memory<S: System>(&self, system: &S) -> Result<MemoryEstimate, String> {
+        let width = self.energy_bin_for(system);
+        let (lo, hi, fixed) = match (self.fixed_min_energy, self.fixed_max_energy) {
+            (Some(lo), Some(hi)) => (lo, hi, true),
+            _ => match system.energy_bounds() {
+                (Some(lo), Some(hi)) => match self.energy_ceiling {
+                    Some(ceiling) if ceiling < hi => (lo, ceiling, false),
+                    _ => (lo, hi, false),
+                },
+                _ => {
+                    return Err(
+                        "the energy range is unknown, so give fixed_min_energy and fixed_max_energy"
+                            .to_string(),
+                    )
+                }
+            },
+        };
+        // The bins start with one around the initial energy, and grow
+        // out to each end as in `EnergyMC::prepare_for_state`.
+        let mut min = ((system.energy() / width).value().round() - 0.5) * width;
+        let mut num_bins = 1.0;
+        if lo < min {
+            let below = (*((min - lo) / width).value()).ceil();
+            min -= below * width;
+            num_bins += below;
+        }
+        let above = *((hi - min) / width).value() - num_bins;
+        if above >= 0.0 {
+            num_bins += above.floor() + 1.0;
+        }
+        let mut num_bins = num_bins as usize;
+        let mut capped = false;
+        if let Some(max_bins) = self.max_bins {
+            if num_bins > max_bins {
+                if fixed {
+                    return Err(format!(
+                        "the fixed window needs {} bins, more than max_bins {}",
+                        num_bins, max_bins
+                    ));
+                }
+                num_bins = max_bins;
+                capped = true;
+            }
+        }
+        let u64_array = num_bins * std::mem::size_of::<u64>();
+        let f64_array = num_bins * std::mem::size_of::<f64>();
+        let mut arrays = vec![
+            ("histogram".to_string(), u64_array),
+            ("t_found".to_string(), u64_array),
+            ("lnw".to_string(), f64_array),
+            ("energy_total".to_string(), f64_array),
+            ("energy_squared_total".to_string(), f64_array),
+            (
+                "have_visited_since_maxentropy".to_string(),
+                num_bins * std::mem::size_of::<bool>(),
+            ),
+            ("round_trips".to_string(), u64_array),
+            ("proposals_at".to_string(), u64_array),
+            ("accepts_at".to_string(), u64_array),
+            ("lnw_increment_total".to_string(), f64_array),
+            ("lnw_increment_squared".to_string(), f64_array),
+        ];
+        match self._method {
+            MethodParams::WL { .. }
+            | MethodParams::Wltm { .. }
+            | MethodParams::Inv_t_WL
+            | MethodParams::inv_t_wl => arrays.push(("wl_histogram".to_string(), u64_array)),
+            _ => (),
+        }
+        for (name, _) in system.data_to_collect(0) {
+            arrays.push((format!("{} counts", name), u64_array));
+            arrays.push((format!("{} totals", name), f64_array));
+        }
+        Ok(MemoryEstimate {
+            num_bins,
+            min,
+            width,
+            capped,
+            arrays,
+        })
+    }
+}
+
+/// The energy bins and memory a run is expected to need, from
+/// [`EnergyMCParams::estimate_memory`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryEstimate {
+    /// The number of energy bins.
+    pub num_bins: usize,
+    /// The lower edge of the lowest bin.
+    pub min: Energy,
+    /// The width of each bin.
+    pub width: Energy,
+    /// Whether `max_bins` stops the bins from reaching the energy
+    /// bounds, in which case they grow as energies are found, up to
+    /// `num_bins`.
+    pub capped: bool,
+    /// The name and size in bytes of each array with an entry per bin.
+    pub arrays: Vec<(String, usize)>,
+}
+
+impl MemoryEstimate {
+    /// The total size in bytes of the arrays.
+    pub fn total_bytes(&self) -> usize {
+        self.arrays.iter().map(|&(_, n)| n).sum()
+    }
+}
+
+/// A number of bytes in the largest unit that keeps it above one.
+fn format_bytes(bytes: usize) -> String {
+    let mut size = bytes as f64;
+    for unit in &["B", "kB", "MB", "GB"] {
+        if size < 1000.0 {
+            return format!("{:.3} {}", PrettyFloat(size), unit);
+        }
+        size /= 1000.0;
+    }
+    format!("{:.3} TB", PrettyFloat(size))
+}
+
+impl std::fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} energy bins of width {} from {} to {}",
+            if self.capped { "at most " } else { "" },
+            self.num_bins,
+            self.width.pretty(),
+            self.min.pretty(),
+            (self.min + self.num_bins as f64 * self.width).pretty()
+        )?;
+        for (name, bytes) in self.arrays.iter() {
+            write!(f, "\n    {:<30} {:>12}", name, format_bytes(*bytes))?;
+        }
+        write!(f, "\n    {:<30} {:>12}", "total", format_bytes(self.total_bytes()))
+    }
+}
+
+/// Overwrite `base` with `over`.  Where both are mappings, and `over`
+/// only has keys that `base` has, we merge key by key, so a struct may
+/// be given in part.  Otherwise `over` replaces `base`, which switches
+/// an enum (a mapping from its variant) to a different variant.
+fn merge_yaml(base: &mut serde_yaml::Value, over: serde_yaml::Value) {
+    use serde_yaml::Value;
+    match (base, over) {
+        (Value::Mapping(b), Value::Mapping(o)) if o.iter().all(|(k, _)| b.contains_key(k)) => {
+            for (k, v) in o {
+                merge_yaml(b.get_mut(&k).unwrap(), v);
+            }
+        }
+        (b, o) => *b = o,
+    }
+}
+
+/// The parts of `new` that differ from `old`, in a form that
+/// [`merge_yaml`] will apply, or `None` if they are the same.
+fn yaml_changes(old: &serde_yaml::Value, new: serde_yaml::Value) -> Option<serde_yaml::Value> {
+    use serde_yaml::{Mapping, Value};
+    if *old == new {
+        return None;
+    }
+    match (old, new) {
+        (Value::Mapping(o), Value::Mapping(n)) if n.iter().all(|(k, _)| o.contains_key(k)) => {
+            let mut changes = Mapping::new();
+            for (k, v) in n {
+                if let Some(c) = yaml_changes(o.get(&k).unwrap(), v) {
+                    changes.insert(k, c);
+                }
+            }
+            Some(Value::Mapping(changes))
         }
+        (_, n) => Some(n),
+    }
+}
+
+/// The most energy bins we consider reasonable when `max_bins` is not
+/// given.
+const MAX_REASONABLE_BINS: f64 = 1e7;
+
+/// A builder for `EnergyMCParams`, for setting up a run from code
+/// rather than the command line.  Anything not set keeps its value from
+/// `EnergyMCParams::default()`.
+///
+/// ```
+/// use sadmc::mc::energy::EnergyMCParamsBuilder;
+/// use sadmc::system::units;
+///
+/// let params = EnergyMCParamsBuilder::new()
+///     .sad(0.5 * units::EPSILON)
+///     .seed(7)
+///     .energy_bin(0.1 * units::EPSILON)
+///     .max_iter(1_000_000)
+///     .quiet(false)
+///     .build()
+///     .unwrap();
+/// assert_eq!(params.seed, Some(7));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EnergyMCParamsBuilder {
+    params: EnergyMCParams,
+}
+
+impl EnergyMCParamsBuilder {
+    /// Start from the default parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Use SAD with minimum temperature `min_T`.
+    pub fn sad(mut self, min_T: Energy) -> Self {
+        self.params._method = MethodParams::Sad { min_T, max_T: None };
+        self
+    }
+    /// Use SAD with minimum temperature `min_T`, treating energies as
+    /// important only up to the most probable energy at `max_T`.
+    pub fn sad_between(mut self, min_T: Energy, max_T: Energy) -> Self {
+        self.params._method = MethodParams::Sad {
+            min_T,
+            max_T: Some(max_T),
+        };
+        self
+    }
+    /// Use SAMC with parameter `t0`.
+    pub fn samc(mut self, t0: f64) -> Self {
+        self.params._method = MethodParams::Samc { t0 };
+        self
+    }
+    /// Use Wang-Landau, switching to production at `min_gamma` if given.
+    pub fn wl(mut self, min_gamma: Option<f64>) -> Self {
+        self.params._method = MethodParams::WL { min_gamma };
+        self
+    }
+    /// Collect a transition matrix alongside whatever method we use.
+    pub fn collect_transition_matrix(mut self) -> Self {
+        self.params.collect_transition_matrix = true;
+        self
+    }
+    /// Count visits by the direction of the walker, so that we can
+    /// find the optimized ensemble.
+    pub fn collect_directions(mut self) -> Self {
+        self.params.collect_directions = true;
+        self
+    }
+    /// Check that updates within the important range never lower
+    /// `lnw`.
+    pub fn check_lnw_increases(mut self) -> Self {
+        self.params.check_lnw_increases = true;
+        self
+    }
+    /// Check that `lnw` stays finite, and what to do if it does not.
+    pub fn non_finite_lnw(mut self, action: NonFiniteLnw) -> Self {
+        self.params.non_finite_lnw = Some(action);
+        self
+    }
+    /// Keep a copy of the lowest energy configuration found.
+    pub fn record_best(mut self) -> Self {
+        self.params.record_best = true;
+        self
+    }
+    /// Collect a joint histogram of the energy and the observable
+    /// `name`, in `num_bins` bins from `min` to `max`.
+    pub fn joint_histogram(mut self, name: &str, min: f64, max: f64, num_bins: usize) -> Self {
+        self.params.joint_observable = Some(name.to_string());
+        self.params.joint_observable_min = Some(min);
+        self.params.joint_observable_max = Some(max);
+        self.params.joint_observable_bins = Some(num_bins);
+        self
+    }
+    /// Like `wl`, but also collect a transition matrix.
+    pub fn wltm(mut self, min_gamma: Option<f64>) -> Self {
+        self.params._method = MethodParams::Wltm { min_gamma };
+        self
+    }
+    /// Make the energy bin this fraction of SAD's `min_T`, for systems
+    /// with continuous energies.
+    pub fn energy_bin_fraction_of_min_T(mut self, fraction: f64) -> Self {
+        self.params.energy_bin_fraction_of_min_T = Some(fraction);
+        self
+    }
+    /// Seed the random number generator.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.params.seed = Some(seed);
+        self
+    }
+    /// Set the energy bin size.
+    pub fn energy_bin(mut self, width: Energy) -> Self {
+        self.params.energy_bin = Some(width);
+        self
+    }
+    /// Adapt the bins to `lnw` after `moves` moves, splitting where its
+    /// second difference is above `split` and merging where it is
+    /// below `merge`.
+    pub fn adapt_bins(mut self, moves: u64, split: f64, merge: f64) -> Self {
+        self.params.adapt_bins_after = Some(moves);
+        self.params.adapt_bins_split = Some(split);
+        self.params.adapt_bins_merge = Some(merge);
+        self
+    }
+    /// Only allow energies in this range.
+    pub fn allowed_energies(mut self, min: Energy, max: Energy) -> Self {
+        self.params.min_allowed_energy = Some(min);
+        self.params.max_allowed_energy = Some(max);
+        self
+    }
+    /// Reject every move to an energy above `ceiling`.
+    pub fn energy_ceiling(mut self, ceiling: Energy) -> Self {
+        self.params.energy_ceiling = Some(ceiling);
+        self
+    }
+    /// Use this rms distance for moves.
+    pub fn translation_scale(mut self, d: Length) -> Self {
+        self.params._moves = Some(MoveParams::TranslationScale(d));
+        self
+    }
+    /// Tune the rms distance of moves to reach this acceptance rate.
+    pub fn acceptance_rate(mut self, rate: f64) -> Self {
+        self.params._moves = Some(MoveParams::AcceptanceRate(rate));
+        self
+    }
+    /// Move along one axis at a time, with a scale for each.
+    pub fn anisotropic_moves(mut self) -> Self {
+        self.params.anisotropic_moves = true;
+        self
+    }
+    /// Choose the type of each move in proportion to these weights.
+    pub fn move_weights(mut self, displacement: f64, swap: f64) -> Self {
+        self.params._move_weights = MoveWeightsParams {
+            displacement_weight: Some(displacement),
+            swap_weight: Some(swap),
+        };
+        self
+    }
+    /// Stop after this many moves.
+    pub fn max_iter(mut self, moves: u64) -> Self {
+        self.params._report.max_iter = Some(moves);
+        self
+    }
+    /// Start SAD's important range at the greatest possible energy.
+    pub fn too_hi_from_bounds(mut self) -> Self {
+        self.params.too_hi_from_bounds = true;
+        self
+    }
+    /// Begin with `moves` moves at infinite temperature.
+    pub fn burn_in_moves(mut self, moves: u64) -> Self {
+        self.params.burn_in_moves = Some(moves);
+        self
+    }
+    /// Make up to `moves` moves between checks on the plugins.
+    pub fn moves_between_plugins(mut self, moves: u64) -> Self {
+        self.params.moves_between_plugins = Some(moves);
+        self
+    }
+    /// On resume, switch to this method even if the run used another.
+    pub fn switch_method(mut self) -> Self {
+        self.params.switch_method = true;
+        self
+    }
+    /// Turn reporting on or off.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.params._report.quiet = quiet;
+        self
+    }
+    /// Finish, checking the parameters with `EnergyMCParams::validate`.
+    pub fn build(self) -> Result<EnergyMCParams, String> {
+        self.params.validate()?;
+        Ok(self.params)
     }
 }
 
@@ -132,44 +1292,649 @@ impl State {
         State { E: sys.energy() }
     }
 }
-/// A set of counts for a variable
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct BinCounts {
-    /// The total of the thing
-    total: Vec<f64>,
-    /// The count in each bin
-    count: Vec<u64>,
+/// An energy that does not fit within our energy bins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnergyError {
+    /// The energy is lower than the lowest bin.
+    BelowMin {
+        /// The offending energy
+        energy: Energy,
+        /// The lowest energy in any bin
+        min: Energy,
+    },
+    /// The energy is higher than the highest bin.
+    AboveMax {
+        /// The offending energy
+        energy: Energy,
+        /// The highest energy in any bin
+        max: Energy,
+    },
+    /// The energy is not a number at all.
+    NotANumber,
+    /// The energy is above the ceiling we were given.
+    AboveCeiling {
+        /// The offending energy
+        energy: Energy,
+        /// The highest energy we allow
+        ceiling: Energy,
+    },
+    /// The energy is outside the fixed energy window.
+    OutsideWindow {
+        /// The offending energy
+        energy: Energy,
+        /// The bottom of the window
+        min: Energy,
+        /// The top of the window
+        max: Energy,
+    },
+    /// The energy is beyond the system's exact bounds.
+    BeyondBounds {
+        /// The offending energy
+        energy: Energy,
+        /// The bound it lies beyond
+        bound: Energy,
+    },
+    /// Making room for this energy would need too many bins.
+    TooManyBins {
+        /// The offending energy
+        energy: Energy,
+        /// The number of bins we would need
+        needed: usize,
+        /// The maximum number of bins allowed
+        max_bins: usize,
+    },
 }
 
-/// Where we store the info about the energy grid
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Bins {
-    /// The lowest allowed energy in any bin.
-    pub min: Energy,
-    /// The energy bin size.
-    pub width: Energy,
-    /// The number of times we have been at each energy.
-    pub histogram: Vec<u64>,
-    /// The iteration when we found each energy.
-    pub t_found: Vec<u64>,
-    /// The ln weight for each energy bin.
-    pub lnw: Vec<Unitless>,
-    /// The total of all energes found in each bin
-    pub energy_total: Vec<Energy>,
-    /// The total square of all energes found in each bin
-    pub energy_squared_total: Vec<EnergySquared>,
-    /// Extra data we might want to collect occasionally
-    pub extra: std::collections::HashMap<Interned, BinCounts>,
+impl ::std::fmt::Display for EnergyError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            EnergyError::BelowMin { energy, min } => write!(
+                f,
+                "energy {} is below the lowest bin, which starts at {}",
+                energy.pretty(),
+                min.pretty()
+            ),
+            EnergyError::AboveMax { energy, max } => write!(
+                f,
+                "energy {} is above the highest bin, which ends at {}",
+                energy.pretty(),
+                max.pretty()
+            ),
+            EnergyError::NotANumber => write!(f, "energy is NaN"),
+            EnergyError::AboveCeiling { energy, ceiling } => write!(
+                f,
+                "energy {} is above the ceiling {}",
+                energy.pretty(),
+                ceiling.pretty()
+            ),
+            EnergyError::OutsideWindow { energy, min, max } => write!(
+                f,
+                "energy {} is outside the fixed window from {} to {}",
+                energy.pretty(),
+                min.pretty(),
+                max.pretty()
+            ),
+            EnergyError::BeyondBounds { energy, bound } => write!(
+                f,
+                "energy {} is beyond the system's bound {}",
+                energy.pretty(),
+                bound.pretty()
+            ),
+            EnergyError::TooManyBins {
+                energy,
+                needed,
+                max_bins,
+            } => write!(
+                f,
+                "energy {} would need {} bins, but only {} are allowed",
+                energy.pretty(),
+                needed,
+                max_bins
+            ),
+        }
+    }
 }
 
-/// A square well fluid.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct EnergyMC<S> {
-    /// The system we are simulating.
-    pub system: S,
-    /// The method we use
-    method: Method,
-    /// The number of moves that have been made.
+impl ::std::error::Error for EnergyError {}
+
+/// Why we could not create, resume or save an [`EnergyMC`].
+#[derive(Debug)]
+pub enum EnergyMcError {
+    /// The parameters cannot possibly work, as found by
+    /// `EnergyMCParams::validate` or `check_energy_bin`.
+    InvalidParams(String),
+    /// We could not make a bin for an energy we needed, e.g. because
+    /// it is below the lowest bin or would need too many bins.
+    Energy(EnergyError),
+    /// The initial `lnw` could not be read or used.
+    InitialLnw {
+        /// The file it came from
+        path: ::std::path::PathBuf,
+        /// What was wrong with it
+        reason: String,
+    },
+    /// A save file could not be read or written.
+    SerializationFailed {
+        /// The save file
+        path: ::std::path::PathBuf,
+        /// What went wrong
+        error: SaveError,
+    },
+    /// A save file was written in a newer format than we understand.
+    IncompatibleVersion {
+        /// The format version of the file
+        found: u32,
+        /// The newest format version we can read
+        supported: u32,
+    },
+}
+
+impl ::std::fmt::Display for EnergyMcError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            EnergyMcError::InvalidParams(e) => write!(f, "Invalid parameters: {}", e),
+            EnergyMcError::Energy(e) => write!(f, "{}", e),
+            EnergyMcError::InitialLnw { path, reason } => {
+                write!(f, "Invalid initial lnw {:?}: {}", path, reason)
+            }
+            EnergyMcError::SerializationFailed { path, error } => {
+                write!(f, "error with save file {:?}: {}", path, error)
+            }
+            EnergyMcError::IncompatibleVersion { found, supported } => write!(
+                f,
+                "save file format version {} is newer than version {}, the newest we can read",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl ::std::error::Error for EnergyMcError {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        match self {
+            EnergyMcError::Energy(e) => Some(e),
+            EnergyMcError::SerializationFailed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<EnergyError> for EnergyMcError {
+    fn from(e: EnergyError) -> Self {
+        EnergyMcError::Energy(e)
+    }
+}
+
+/// The format version of the `EnergyMC` save files we write.  Version 1
+/// is every file saved before we recorded a version.  Bump this when a
+/// change to the saved fields needs more than a `#[serde(default)]` to
+/// read older files, and teach [`EnergyMC::migrate`] how to fill in
+/// what they lack.
+pub const SAVE_FORMAT_VERSION: u32 = 2;
+
+fn unversioned() -> u32 {
+    1
+}
+
+/// A reason we cannot change the minimum (or maximum) temperature of a
+/// run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MinTError {
+    /// Only SAD has a minimum temperature.
+    NotSad,
+    /// The minimum temperature must be finite and positive.
+    NotPositive(Energy),
+    /// Raising `min_T` after we have learned about energies that are
+    /// only important at the current `min_T` would leave their weights
+    /// wrong.
+    WouldRaise {
+        /// The current minimum temperature
+        current: Energy,
+        /// The minimum temperature that was asked for
+        requested: Energy,
+    },
+    /// The maximum temperature must be finite and above `min_T`.
+    BadMaxT {
+        /// The current minimum temperature
+        min_T: Energy,
+        /// The maximum temperature that was asked for
+        max_T: Energy,
+    },
+}
+
+impl ::std::fmt::Display for MinTError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            MinTError::NotSad => write!(f, "only SAD has a minimum temperature"),
+            MinTError::NotPositive(min_T) => {
+                write!(f, "min_T must be finite and positive, not {}", min_T.pretty())
+            }
+            MinTError::WouldRaise { current, requested } => write!(
+                f,
+                "cannot raise min_T from {} to {} once important energies are known",
+                current.pretty(),
+                requested.pretty()
+            ),
+            MinTError::BadMaxT { min_T, max_T } => write!(
+                f,
+                "max_T must be finite and above min_T = {}, not {}",
+                min_T.pretty(),
+                max_T.pretty()
+            ),
+        }
+    }
+}
+
+impl ::std::error::Error for MinTError {}
+
+/// A set of counts for a variable
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BinCounts {
+    /// The total of the thing
+    total: VecDeque<f64>,
+    /// The count in each bin
+    count: VecDeque<u64>,
+}
+
+/// Where we store the info about the energy grid.  The per-bin data
+/// are stored in a `VecDeque` so that we can cheaply make room for
+/// newly discovered low energies at the front.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Bins {
+    /// The lowest allowed energy in any bin.
+    pub min: Energy,
+    /// The energy bin size.  Once the bins have been adapted, this is
+    /// the size of any new bins at either end.
+    pub width: Energy,
+    /// The edges of the bins, one more than there are bins, once they
+    /// have been adapted by [`EnergyMC::adapt_bins`] so they are not
+    /// all `width` wide.  This is `None` for uniform bins.
+    #[serde(default)]
+    pub edges: Option<VecDeque<Energy>>,
+    /// The number of times we have been at each energy.
+    pub histogram: VecDeque<u64>,
+    /// The iteration when we found each energy.
+    pub t_found: VecDeque<u64>,
+    /// The ln weight for each energy bin.
+    pub lnw: VecDeque<Unitless>,
+    /// The total of all energes found in each bin
+    pub energy_total: VecDeque<Energy>,
+    /// The total square of all energes found in each bin
+    pub energy_squared_total: VecDeque<EnergySquared>,
+    /// Extra data we might want to collect occasionally
+    pub extra: std::collections::HashMap<Interned, BinCounts>,
+}
+
+/// When and how to adapt the bins with [`EnergyMC::adapt_bins`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AdaptBins {
+    /// The number of moves after which to adapt the bins.
+    pub after: u64,
+    /// Split bins where the second difference of `lnw` is above this.
+    pub split: f64,
+    /// Merge bins where the second difference of `lnw` is below this.
+    pub merge: f64,
+}
+
+const DEFAULT_ADAPT_SPLIT: f64 = 1.0;
+const DEFAULT_ADAPT_MERGE: f64 = 0.01;
+
+impl AdaptBins {
+    /// The adaptation asked for by `params`, if any.
+    pub fn from_params(params: &EnergyMCParams) -> Option<Self> {
+        params.adapt_bins_after.map(|after| AdaptBins {
+            after,
+            split: params.adapt_bins_split.unwrap_or(DEFAULT_ADAPT_SPLIT),
+            merge: params.adapt_bins_merge.unwrap_or(DEFAULT_ADAPT_MERGE),
+        })
+    }
+}
+
+/// One of the new bins when the bins are regrouped: either old bins
+/// merged together, or the lower or upper half of one that is split.
+#[derive(Debug, Clone)]
+enum Piece {
+    Merged(std::ops::Range<usize>),
+    Lower(usize),
+    Upper(usize),
+}
+
+impl Piece {
+    /// The old bins this comes from.
+    fn range(&self) -> std::ops::Range<usize> {
+        match *self {
+            Piece::Merged(ref g) => g.clone(),
+            Piece::Lower(i) | Piece::Upper(i) => i..i + 1,
+        }
+    }
+    /// The fraction of what those old bins held that goes to this one.
+    fn share(&self) -> f64 {
+        match *self {
+            Piece::Merged(_) => 1.0,
+            Piece::Lower(_) | Piece::Upper(_) => 0.5,
+        }
+    }
+}
+
+/// A snapshot of the state of the SAD algorithm.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SadState {
+    /// The lowest important energy, below which we have not found a
+    /// state worth caring about at `min_T`.
+    pub too_lo: Energy,
+    /// The highest important energy, which has the maximum entropy.
+    pub too_hi: Energy,
+    /// The number of distinct energies found in the important range.
+    pub num_states: u64,
+    /// The last move at which `too_lo` or `too_hi` changed.
+    pub tL: u64,
+    /// The move at which we found the most recently found important energy.
+    pub tF: u64,
+    /// The last move at which `too_lo` changed.
+    pub t_lo: u64,
+    /// The last move at which `too_hi` changed.
+    pub t_hi: u64,
+}
+
+/// Which method a run is using, as given by [`EnergyMC::method_info`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodKind {
+    /// Statistical Association, Dynamical
+    Sad,
+    /// Stochastic Approximation Monte Carlo
+    Samc,
+    /// Wang-Landau, or 1/t-Wang-Landau before it switches to SAMC
+    WangLandau,
+    /// A canonical simulation at fixed temperature
+    Canonical,
+}
+
+/// A read-only view of the state of a run's method, for plugins and
+/// analysis code that need to know what the method is doing.  Fields
+/// that do not apply to the method in use are `None`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MethodInfo {
+    /// Which method this is.
+    pub kind: MethodKind,
+    /// The amount that will be added to `lnw` on the next visit.
+    pub gamma: f64,
+    /// SAD's minimum temperature of interest.
+    pub min_T: Option<Energy>,
+    /// SAD's maximum temperature of interest, if it has one.
+    pub max_T: Option<Energy>,
+    /// The state of SAD, as from [`EnergyMC::sad_state`].
+    pub sad: Option<SadState>,
+    /// SAMC's `t0`.
+    pub t0: Option<f64>,
+    /// The gamma at which Wang-Landau begins its production run.
+    pub min_gamma: Option<f64>,
+    /// Whether Wang-Landau will switch to SAMC as 1/t-Wang-Landau.
+    pub inv_t: Option<bool>,
+    /// The temperature of a canonical run.
+    pub temperature: Option<Energy>,
+}
+
+/// Which end of the important energy range we were at most recently.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Extreme {
+    /// We have not yet reached the low end.
+    Neither,
+    /// The low end.
+    Low,
+    /// The high end, after having been at the low end.
+    High,
+}
+
+/// The most energy blocks we keep before merging them in pairs.
+const MAX_ENERGY_BLOCKS: usize = 1024;
+
+/// Averages of the energy over consecutive blocks of moves, which give
+/// an error bar from a single run.  This assumes that each block is
+/// much longer than the correlation time, so that the block means are
+/// independent.  Their standard deviation over the square root of the
+/// number of blocks is then the standard error of the overall mean.  If
+/// blocks are too short this underestimates the error.  Once we have
+/// `MAX_ENERGY_BLOCKS` blocks we merge them in pairs and double the
+/// block size, which keeps the memory bounded and only makes the blocks
+/// more independent.  The energy is only a physical observable in a
+/// canonical run, so this is not collected for the flat-histogram
+/// methods.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct BlockAverages {
+    block_size: u64,
+    total: Energy,
+    count: u64,
+    means: Vec<Energy>,
+}
+
+impl Default for BlockAverages {
+    fn default() -> Self {
+        BlockAverages::new(1 << 16)
+    }
+}
+
+impl BlockAverages {
+    fn new(block_size: u64) -> Self {
+        assert!(block_size > 0);
+        BlockAverages {
+            block_size,
+            total: Energy::new(0.0),
+            count: 0,
+            means: Vec::new(),
+        }
+    }
+    fn visit(&mut self, e: Energy) {
+        self.total += e;
+        self.count += 1;
+        if self.count == self.block_size {
+            self.means.push(self.total / self.count as f64);
+            self.total = Energy::new(0.0);
+            self.count = 0;
+            if self.means.len() == MAX_ENERGY_BLOCKS {
+                self.means = self.means.chunks(2).map(|p| (p[0] + p[1]) * 0.5).collect();
+                self.block_size *= 2;
+            }
+        }
+    }
+    /// The mean of the complete blocks.
+    fn mean(&self) -> Option<Energy> {
+        if self.means.is_empty() {
+            return None;
+        }
+        Some(self.means.iter().fold(Energy::new(0.0), |a, &m| a + m) / self.means.len() as f64)
+    }
+    /// The standard error of `mean`, which needs at least two blocks.
+    fn standard_error(&self) -> Option<Energy> {
+        let n = self.means.len() as f64;
+        let mean = self.mean()?;
+        if self.means.len() < 2 {
+            return None;
+        }
+        let variance = self
+            .means
+            .iter()
+            .fold(EnergySquared::new(0.0), |a, &m| a + (m - mean) * (m - mean))
+            / (n - 1.0);
+        Some(Energy::new((variance.value_unsafe / n).sqrt()))
+    }
+}
+
+/// An online estimate of the integrated autocorrelation time of the
+/// energy, using Sokal's windowing method.  We keep the last `lags`
+/// energies in a ring buffer, along with running sums of the products
+/// of each energy with those before it, from which we find the
+/// autocorrelation function `rho(k)` about the overall mean.  The time
+/// is `tau = 1 + 2 sum_{k=1}^{M} rho(k)`, in moves, so that `n` moves
+/// are worth about `n/tau` independent samples.  The window `M` is the
+/// smallest with `M >= 5 tau`, which cuts off the noise in `rho(k)` at
+/// large `k`.
+///
+/// The estimate is biased low: the truncated tail leaves out roughly
+/// `exp(-5)` of `tau` for an exponential decay, and subtracting the
+/// sample mean lowers it by order `tau/n`.  Its statistical error is
+/// about `tau sqrt(2(2M+1)/n)`.  Each move costs `lags` operations.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Autocorrelation {
+    /// The first energy, which we subtract from every energy to keep
+    /// the sums from losing precision.
+    shift: Option<f64>,
+    count: u64,
+    sum: f64,
+    sum_squared: f64,
+    /// The first `lags` energies, to correct the sums for the mean.
+    first: Vec<f64>,
+    /// The last `lags` energies, most recent first.
+    recent: VecDeque<f64>,
+    /// The sum of the products of each energy with the one `k+1`
+    /// moves before it.
+    lag_products: Vec<f64>,
+}
+
+impl Autocorrelation {
+    fn new(lags: usize) -> Self {
+        assert!(lags > 0);
+        Autocorrelation {
+            shift: None,
+            count: 0,
+            sum: 0.0,
+            sum_squared: 0.0,
+            first: Vec::with_capacity(lags),
+            recent: VecDeque::with_capacity(lags),
+            lag_products: vec![0.0; lags],
+        }
+    }
+    fn visit(&mut self, e: Energy) {
+        let e = *(e / units::EPSILON).value();
+        let x = e - *self.shift.get_or_insert(e);
+        for (p, &y) in self.lag_products.iter_mut().zip(self.recent.iter()) {
+            *p += x * y;
+        }
+        self.count += 1;
+        self.sum += x;
+        self.sum_squared += x * x;
+        if self.first.len() < self.lag_products.len() {
+            self.first.push(x);
+        }
+        if self.recent.len() == self.lag_products.len() {
+            self.recent.pop_back();
+        }
+        self.recent.push_front(x);
+    }
+    /// The autocovariance at each lag from 0 to `lags`, about the mean
+    /// of all the energies.  This needs more than `lags` energies.
+    fn autocovariance(&self) -> Vec<f64> {
+        let n = self.count as f64;
+        let mean = self.sum / n;
+        let mut c = vec![self.sum_squared / n - mean * mean];
+        let mut head = 0.0;
+        let mut tail = 0.0;
+        for (k, &p) in self.lag_products.iter().enumerate() {
+            let pairs = self.count - (k as u64 + 1);
+            // The sums of the later and earlier energy of each pair.
+            head += self.first[k];
+            tail += self.recent[k];
+            let later = self.sum - head;
+            let earlier = self.sum - tail;
+            c.push((p - mean * (later + earlier)) / pairs as f64 + mean * mean);
+        }
+        c
+    }
+    /// The integrated autocorrelation time in moves, or `None` if we
+    /// have too few energies, they never change, or the window would
+    /// need more lags than we keep.
+    fn time(&self) -> Option<f64> {
+        if self.count <= self.lag_products.len() as u64 {
+            return None;
+        }
+        let c = self.autocovariance();
+        if c[0] <= 0.0 {
+            return None;
+        }
+        let mut tau = 1.0;
+        for (m, &ck) in c.iter().enumerate().skip(1) {
+            tau += 2.0 * ck / c[0];
+            if m as f64 >= 5.0 * tau {
+                return Some(tau);
+            }
+        }
+        None
+    }
+}
+
+/// Counts round trips from the lowest to the highest important energy
+/// and back again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RoundTrips {
+    last: Extreme,
+    count: u64,
+    lowest_seen: Option<Energy>,
+    highest_seen: Option<Energy>,
+}
+
+impl Default for RoundTrips {
+    fn default() -> Self {
+        RoundTrips {
+            last: Extreme::Neither,
+            count: 0,
+            lowest_seen: None,
+            highest_seen: None,
+        }
+    }
+}
+
+impl RoundTrips {
+    /// Record a visit to energy `e`.  The important range is given by
+    /// `range` if the method has one, and otherwise is the range of
+    /// energies seen so far.
+    fn visit(&mut self, e: Energy, range: Option<(Energy, Energy)>) {
+        let lowest = match self.lowest_seen {
+            Some(lo) if lo <= e => lo,
+            _ => e,
+        };
+        let highest = match self.highest_seen {
+            Some(hi) if hi >= e => hi,
+            _ => e,
+        };
+        self.lowest_seen = Some(lowest);
+        self.highest_seen = Some(highest);
+        let (lo, hi) = range.unwrap_or((lowest, highest));
+        if lo >= hi {
+            return;
+        }
+        if e <= lo {
+            if self.last == Extreme::High {
+                self.count += 1;
+            }
+            self.last = Extreme::Low;
+        } else if e >= hi && self.last == Extreme::Low {
+            self.last = Extreme::High;
+        }
+    }
+}
+
+/// The lowest energy configuration found, when we are recording it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BestConfiguration<S> {
+    /// The configuration, as copied by `System::snapshot`.
+    pub system: S,
+    /// Its energy.
+    pub energy: Energy,
+    /// The move at which it was found.
+    pub found: u64,
+}
+
+/// A square well fluid.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EnergyMC<S> {
+    /// The format version this was saved with, as described at
+    /// [`SAVE_FORMAT_VERSION`].
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    /// The system we are simulating.
+    pub system: S,
+    /// The method we use
+    method: Method,
+    /// The number of moves that have been made.
     pub moves: u64,
     /// The last move where we discovered a new energy.
     pub time_L: u64,
@@ -179,34 +1944,429 @@ pub struct EnergyMC<S> {
     min_allowed_energy: Option<Energy>,
     /// The highest energy to allow.
     max_allowed_energy: Option<Energy>,
+    /// The maximum number of energy bins to allow.
+    #[serde(default)]
+    max_bins: Option<usize>,
+    /// When to adapt the bins to `lnw`, until we have done so.
+    #[serde(default)]
+    adapt: Option<AdaptBins>,
+    /// The energy above which every move is rejected.  This changes
+    /// the ensemble, since those states are never visited.
+    #[serde(default)]
+    energy_ceiling: Option<Energy>,
+    /// The system's exact energy bounds, beyond which no move can go.
+    #[serde(default)]
+    energy_bounds: (Option<Energy>, Option<Energy>),
+    /// The fixed energy window, whose bins are all made at the start.
+    #[serde(default)]
+    fixed_window: Option<(Energy, Energy)>,
     /// The move plan
     pub move_plan: MoveParams,
     /// The current translation scale
     pub translation_scale: Length,
+    /// The distribution of move displacements.
+    #[serde(default)]
+    pub move_distribution: MoveDistribution,
+    /// The number of atoms to move at once, if more than one.
+    #[serde(default)]
+    pub atoms_per_move: Option<usize>,
+    /// The scale along each axis, if we move along one at a time.
+    #[serde(default)]
+    pub axis_scales: Option<AxisScales>,
+    /// The relative probability of each type of move.
+    #[serde(default)]
+    pub move_weights: MoveWeights,
     /// The "recent" acceptance rate.
     pub acceptance_rate: f64,
     /// The random number generator.
     pub rng: crate::rng::MyRng,
+    /// The mean energy in blocks of moves.
+    #[serde(default)]
+    energy_blocks: BlockAverages,
+    /// The autocorrelation of the energy, if we are estimating it.
+    #[serde(default)]
+    autocorrelation: Option<Autocorrelation>,
+    /// The most moves to make between checks on the plugins.
+    #[serde(default)]
+    moves_between_plugins: Option<u64>,
+    /// When the Wang-Landau histogram counts as flat.
+    #[serde(default)]
+    wl_flatness: Flatness,
+    /// The number of moves at infinite temperature before we begin
+    /// learning the weights.
+    #[serde(default)]
+    burn_in_moves: u64,
+    /// The fewest visits for a bin's `lnw` to be trusted.
+    #[serde(default)]
+    min_visits: u64,
+    /// What to do with bins visited fewer than `min_visits` times.
+    #[serde(default)]
+    rare_bins: RareBins,
+    /// The proposed moves between energies, if we are collecting them.
+    #[serde(default)]
+    transitions: Option<TransitionMatrix>,
+    /// The visits to each energy by direction, if we are counting them.
+    #[serde(default)]
+    directions: Option<DirectionCounts>,
+    /// The largest fall in `lnw` during an update within the important
+    /// range, for each bin where it fell, keyed like the transition
+    /// matrix, if we are checking.
+    #[serde(default)]
+    lnw_decreases: Option<BTreeMap<i64, f64>>,
+    /// What to do if an update leaves `lnw` non-finite, if we are
+    /// checking.
+    #[serde(default)]
+    non_finite_lnw: Option<NonFiniteLnw>,
+    /// The accepted displacements since we last checked that they are
+    /// symmetric, if we are checking.
+    #[serde(skip)]
+    move_symmetry: Option<SymmetryCounts>,
+    /// The joint histogram of energy and an observable, if we are
+    /// collecting one.
+    #[serde(default)]
+    joint: Option<JointHistogram>,
+    /// The lowest energy configuration found, if we are recording it.
+    /// This is a whole configuration, so rather than go in every save
+    /// file it is written beside it, to [`EnergyMC::best_path`].
+    #[serde(skip)]
+    best: Option<BestConfiguration<S>>,
+    /// The seed the random number generator started from, so that a
+    /// run with a randomly chosen seed can be reproduced.  This is
+    /// `None` for runs saved before we recorded it.
+    #[serde(default)]
+    pub seed: Option<u64>,
     /// Where to save the resume file.
     pub save_as: ::std::path::PathBuf,
     report: plugin::Report,
     movies: plugin::Movie,
     save: plugin::Save,
+    #[serde(default)]
+    energy_check: plugin::EnergyCheck,
+    #[serde(default)]
+    time_limit: plugin::TimeLimit,
+    #[serde(default)]
+    sad_trace: plugin::SadTrace,
+    #[serde(default)]
+    npy_export: NpyExport,
+    #[serde(default)]
+    lnw_file: LnwFile,
+    #[serde(default)]
+    progress: plugin::Progress,
+    #[serde(default)]
+    live_plot: plugin::LivePlot,
+    #[serde(default)]
+    final_report: FinalReport,
+    #[serde(default)]
+    stagnation: Stagnation,
+    #[serde(default)]
+    exhaustion: Exhaustion,
+    #[serde(default)]
+    energy_trace: plugin::EnergyTrace,
+    #[serde(skip, default)]
+    extra_plugins: plugin::PluginList<EnergyMC<S>>,
+    #[serde(skip, default)]
+    acceptance_rule: AcceptanceRule,
     manager: plugin::PluginManager,
 
     // The following were formerly part of Bins.  I joined them all
     // together in EnergyMC.
     /// The parameters describing the bins
     pub bins: Bins,
+    /// The lowest and highest bins that have been visited, kept up to
+    /// date as we go so they are cheap to find.
+    #[serde(default)]
+    occupied: Option<(usize, usize)>,
 
     /// Whether we have seen this since the last visit to maxentropy.
-    have_visited_since_maxentropy: Vec<bool>,
+    have_visited_since_maxentropy: VecDeque<bool>,
     /// How many round trips have we seen at this energy.
-    round_trips: Vec<u64>,
+    round_trips: VecDeque<u64>,
+    /// How many moves were proposed from each energy.
+    #[serde(default)]
+    proposals_at: VecDeque<u64>,
+    /// How many moves were accepted from each energy.
+    #[serde(default)]
+    accepts_at: VecDeque<u64>,
+    /// The total of the SAMC increments to each `lnw` in the 1/t regime.
+    #[serde(default)]
+    lnw_increment_total: VecDeque<f64>,
+    /// The total of the squares of those increments.
+    #[serde(default)]
+    lnw_increment_squared: VecDeque<f64>,
     /// The maximum entropy we have seen.
     max_S: Unitless,
     /// The index with the maximum entropy.
     max_S_index: usize,
+    /// Round trips between the extremes of the important energy range.
+    #[serde(default)]
+    extreme_round_trips: RoundTrips,
+    /// The gamma used in the most recent update of the weights.
+    #[serde(default)]
+    last_gamma: f64,
+}
+
+/// A rule deciding whether to accept a move from the first state to
+/// the second, for [`EnergyMC::set_acceptance_rule`].  Like added
+/// plugins, it must be `Send`.
+pub type AcceptanceFn = dyn FnMut(State, State, &Bins, &mut crate::rng::MyRng) -> bool + Send;
+
+/// The acceptance rule that replaces the method's, if any.
+#[derive(Default)]
+struct AcceptanceRule(Option<Box<AcceptanceFn>>);
+
+impl ::std::fmt::Debug for AcceptanceRule {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "AcceptanceRule(custom)"),
+            None => write!(f, "AcceptanceRule(method)"),
+        }
+    }
+}
+
+/// Counts of proposed moves between energy bins, whether or not they
+/// were accepted, from which detailed balance gives the density of
+/// states.  Bins are keyed by `round(E/width)`, so that the keys do not
+/// change when we add bins at low energy, and there is nothing to
+/// resize in `prepare_for_state`.  Most moves go to nearby
+/// bins, so each row only holds the bins actually proposed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+struct TransitionMatrix {
+    counts: BTreeMap<i64, BTreeMap<i64, u64>>,
+}
+
+impl TransitionMatrix {
+    fn record(&mut self, from: i64, to: i64) {
+        *self
+            .counts
+            .entry(from)
+            .or_insert_with(BTreeMap::new)
+            .entry(to)
+            .or_insert(0) += 1;
+    }
+    /// Solve for the ln of the density of states, which is zero at the
+    /// lowest key.  Detailed balance at infinite temperature says
+    /// `S_j - S_i = ln P_ij - ln P_ji`, which we solve by weighted
+    /// least squares over every pair with moves in both directions.
+    /// Keys that are not connected to the lowest key are left out.
+    fn ln_dos(&self) -> BTreeMap<i64, f64> {
+        let total = |i: i64| -> f64 { self.counts[&i].values().sum::<u64>() as f64 };
+        let mut neighbors: BTreeMap<i64, Vec<(i64, f64, f64)>> = BTreeMap::new();
+        for (&i, row) in self.counts.iter() {
+            for (&j, &cij) in row.range(i + 1..) {
+                let cji = self.counts.get(&j).and_then(|r| r.get(&i)).cloned().unwrap_or(0);
+                if cji == 0 {
+                    continue;
+                }
+                let (cij, cji) = (cij as f64, cji as f64);
+                let dS = (cij / total(i)).ln() - (cji / total(j)).ln();
+                let weight = 1.0 / (1.0 / cij + 1.0 / cji);
+                neighbors.entry(i).or_insert_with(Vec::new).push((j, dS, weight));
+                neighbors.entry(j).or_insert_with(Vec::new).push((i, -dS, weight));
+            }
+        }
+        let mut S = BTreeMap::new();
+        let first = match neighbors.keys().next() {
+            Some(&k) => k,
+            None => return S,
+        };
+        // A first guess following a spanning tree from the lowest key.
+        S.insert(first, 0.0);
+        let mut todo = VecDeque::from(vec![first]);
+        while let Some(i) = todo.pop_front() {
+            for &(j, dS, _) in neighbors[&i].iter() {
+                if !S.contains_key(&j) {
+                    S.insert(j, S[&i] + dS);
+                    todo.push_back(j);
+                }
+            }
+        }
+        // Then Gauss-Seidel to use every pair, keeping the lowest fixed.
+        for _ in 0..10000 {
+            let mut biggest_change: f64 = 0.0;
+            for (&i, ns) in neighbors.iter() {
+                if i == first || !S.contains_key(&i) {
+                    continue;
+                }
+                let (mut num, mut den) = (0.0, 0.0);
+                for &(j, dS, w) in ns.iter() {
+                    num += w * (S[&j] - dS);
+                    den += w;
+                }
+                let new = num / den;
+                biggest_change = biggest_change.max((new - S[&i]).abs());
+                S.insert(i, new);
+            }
+            if biggest_change < 1e-12 {
+                break;
+            }
+        }
+        S
+    }
+}
+
+/// Counts of visits to each energy bin, split by which end of the
+/// important energy range the walker was at most recently.  A walker
+/// that was last at the low end is heading up, and one last at the
+/// high end is heading down.  Like the transition matrix, bins are
+/// keyed by `round(E/width)`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+struct DirectionCounts {
+    up: BTreeMap<i64, u64>,
+    down: BTreeMap<i64, u64>,
+}
+
+impl DirectionCounts {
+    fn record(&mut self, key: i64, last: Extreme) {
+        match last {
+            Extreme::Low => *self.up.entry(key).or_insert(0) += 1,
+            Extreme::High => *self.down.entry(key).or_insert(0) += 1,
+            Extreme::Neither => (),
+        }
+    }
+}
+
+/// A sparse histogram of how often we measured an observable from
+/// `System::data_to_collect` in each of `num_bins` equal bins from
+/// `min` to `max`, at each energy.  Values outside the range are
+/// counted in the end bins.  Each energy only holds the observable
+/// bins it has seen, so this needs no more than `num_bins` counts per
+/// energy bin, and usually far fewer.  Like the transition matrix,
+/// energies are keyed by `round(E/width)`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointHistogram {
+    /// The observable.
+    pub name: Interned,
+    /// The lower edge of the lowest observable bin.
+    pub min: f64,
+    /// The upper edge of the highest observable bin.
+    pub max: f64,
+    /// The number of observable bins.
+    pub num_bins: usize,
+    counts: BTreeMap<i64, BTreeMap<usize, u64>>,
+}
+
+impl JointHistogram {
+    fn new(name: Interned, min: f64, max: f64, num_bins: usize) -> Self {
+        JointHistogram {
+            name,
+            min,
+            max,
+            num_bins,
+            counts: BTreeMap::new(),
+        }
+    }
+    /// The observable bin that holds `x`.
+    pub fn bin(&self, x: f64) -> usize {
+        let j = ((x - self.min) / (self.max - self.min) * self.num_bins as f64).floor();
+        if j > 0.0 {
+            (j as usize).min(self.num_bins - 1)
+        } else {
+            0
+        }
+    }
+    /// The value of the observable at the center of bin `j`.
+    pub fn bin_center(&self, j: usize) -> f64 {
+        self.min + (j as f64 + 0.5) * (self.max - self.min) / self.num_bins as f64
+    }
+    /// The number of measurements in each observable bin, over all
+    /// energies.
+    pub fn observable_counts(&self) -> Vec<u64> {
+        let mut counts = vec![0; self.num_bins];
+        for row in self.counts.values() {
+            for (&j, &c) in row.iter() {
+                counts[j] += c;
+            }
+        }
+        counts
+    }
+    fn record(&mut self, key: i64, x: f64) {
+        let j = self.bin(x);
+        *self
+            .counts
+            .entry(key)
+            .or_insert_with(BTreeMap::new)
+            .entry(j)
+            .or_insert(0) += 1;
+    }
+}
+
+/// SAD's important range given the energy and `lnw` of each visited
+/// bin, in order of energy, as the positions in `visited` of `too_lo`
+/// and `too_hi`.  `too_hi` is where the entropy is greatest, or with a
+/// `max_T` the most probable energy at that temperature, and `too_lo`
+/// is the lowest energy from which it is no colder than `min_T`.
+fn sad_range(visited: &[(Energy, f64)], min_T: Energy, max_T: Option<Energy>) -> (usize, usize) {
+    let imax = (0..visited.len()).fold(0, |m, j| if visited[j].1 > visited[m].1 { j } else { m });
+    let mut hi = imax;
+    if let Some(max_T) = max_T {
+        for (j, w) in visited[..=imax].windows(2).enumerate().rev() {
+            // Energies are more probable at max_T than the one above
+            // them where lnw rises by less than ΔE/max_T.
+            let de = *((w[1].0 - w[0].0) / max_T).value();
+            if w[1].1 - w[0].1 >= de {
+                break;
+            }
+            hi = j;
+        }
+    }
+    let mut lo = hi;
+    for (j, w) in visited[..=hi].windows(2).enumerate().rev() {
+        // The change in lnw over min_T is more than one when the
+        // temperature there is below min_T.
+        let de = *((w[1].0 - w[0].0) / min_T).value();
+        if w[1].1 - w[0].1 > de {
+            break;
+        }
+        lo = j;
+    }
+    (lo, hi)
+}
+
+/// The optimized ensemble of Trebst, Huse and Troyer, given the `lnw`,
+/// and the visits heading up and down, for each bin in order of
+/// energy.  The fraction `f` of visits heading up falls from one at the
+/// low end to zero at the high end, and the weights that minimize the
+/// round trip time are the old ones times `sqrt(|df/dE|/H)`, where `H`
+/// is the number of labelled visits.  As our weights are `exp(-lnw)`,
+/// this subtracts half the log of that from `lnw`.  We find `df/dE` by
+/// differences between neighboring labelled bins, and take it to be
+/// no smaller than the smallest positive difference, since `f` should
+/// never rise.  Bins with no labelled visits get the same change as
+/// the nearest labelled bin below them (or above, at the bottom).
+fn optimized_ensemble(lnw: &[f64], up: &[u64], down: &[u64]) -> Vec<f64> {
+    let labelled: Vec<usize> = (0..lnw.len()).filter(|&i| up[i] + down[i] > 0).collect();
+    if labelled.len() < 2 {
+        return lnw.to_vec();
+    }
+    let f = |i: usize| up[i] as f64 / (up[i] + down[i]) as f64;
+    let slopes: Vec<f64> = (0..labelled.len())
+        .map(|k| {
+            let lo = labelled[k.saturating_sub(1)];
+            let hi = labelled[(k + 1).min(labelled.len() - 1)];
+            (f(lo) - f(hi)) / (hi - lo) as f64
+        })
+        .collect();
+    let floor = slopes
+        .iter()
+        .cloned()
+        .filter(|&d| d > 0.0)
+        .fold(std::f64::INFINITY, f64::min);
+    if !floor.is_finite() {
+        return lnw.to_vec();
+    }
+    let mut change = vec![None; lnw.len()];
+    for (&i, &d) in labelled.iter().zip(slopes.iter()) {
+        let h = (up[i] + down[i]) as f64;
+        change[i] = Some(-0.5 * (d.max(floor) / h).ln());
+    }
+    let mut last = change[labelled[0]].unwrap();
+    lnw.iter()
+        .zip(change.iter())
+        .map(|(&w, &c)| {
+            last = c.unwrap_or(last);
+            w + last
+        })
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -214,10 +2374,18 @@ enum Method {
     /// Sad
     Sad {
         min_T: Energy,
+        /// Above `too_hi` we weight canonically at `max_T`, or
+        /// uniformly if it is `None`.
+        #[serde(default)]
+        max_T: Option<Energy>,
         too_lo: Energy,
         too_hi: Energy,
         tL: u64,
         tF: u64,
+        #[serde(default)]
+        t_lo: u64,
+        #[serde(default)]
+        t_hi: u64,
         num_states: u64,
         highest_hist: u64,
         version: SadVersion,
@@ -232,7 +2400,7 @@ enum Method {
         highest_hist: u64,
         total_hist: u64,
         num_states: f64,
-        hist: Vec<u64>,
+        hist: VecDeque<u64>,
         min_energy: Energy,
         inv_t: bool,
         min_gamma: Option<f64>,
@@ -252,12 +2420,15 @@ impl Method {
         max_allowed_energy: Option<Energy>,
     ) -> Self {
         match p {
-            MethodParams::Sad { min_T } => Method::Sad {
+            MethodParams::Sad { min_T, max_T } => Method::Sad {
                 min_T,
+                max_T,
                 too_lo: E,
                 too_hi: E,
                 tL: 0,
                 tF: 0,
+                t_lo: 0,
+                t_hi: 0,
                 num_states: 1,
                 highest_hist: 1,
                 version: SadVersion::Sad,
@@ -280,11 +2451,19 @@ impl Method {
                 } else {
                     1.0
                 },
-                hist: Vec::new(),
+                hist: VecDeque::new(),
                 min_energy: E,
                 inv_t: false,
                 min_gamma,
             },
+            // The transition matrix itself is kept by the EnergyMC.
+            MethodParams::Wltm { min_gamma } => Method::new(
+                MethodParams::WL { min_gamma },
+                E,
+                dE,
+                min_allowed_energy,
+                max_allowed_energy,
+            ),
             MethodParams::Inv_t_WL | MethodParams::inv_t_wl => Method::WL {
                 gamma: 1.0,
                 lowest_hist: if min_allowed_energy.is_some() && max_allowed_energy.is_some() {
@@ -301,7 +2480,7 @@ impl Method {
                 } else {
                     1.0
                 },
-                hist: Vec::new(),
+                hist: VecDeque::new(),
                 min_energy: E,
                 inv_t: true,
                 min_gamma: None,
@@ -362,14 +2541,152 @@ impl Method {
     // }
 }
 
+/// The number of items each rayon task sums in order in
+/// [`ordered_sum`] and [`log_sum_exp`].  Fixing this, rather than
+/// letting rayon split the work by the number of threads, means the
+/// same additions happen in the same order however many threads there
+/// are, so canonical averages are bitwise reproducible.
+const REDUCTION_CHUNK: usize = 1 << 14;
+
+/// The number of blocks we split each Metropolis validation run into,
+/// to estimate the error in its mean energy.
+const VALIDATION_BLOCKS: u64 = 20;
+
+/// How far, relative to its size, `lnw` may fall in an update before
+/// `check_lnw_increases` reports it, to allow for roundoff.
+const LNW_DECREASE_TOLERANCE: f64 = 1e-12;
+
+/// The bins of `lnw` that one update wrote, with what they held
+/// before, so that checking an update need not copy all of `lnw`.
+#[derive(Debug)]
+struct LnwChange {
+    /// The bin we were at, and its old weight.
+    bin: (usize, Unitless),
+    /// The first of the bins SAD reset when it widened its range, and
+    /// their old weights.  This is empty for almost every move.
+    reset: (usize, Vec<Unitless>),
+}
+
+impl LnwChange {
+    /// Each bin written, with its weight before the update.
+    fn before(&self) -> impl Iterator<Item = (usize, Unitless)> + '_ {
+        let (start, ref old) = self.reset;
+        let bin = self.bin;
+        let reset = old.iter().enumerate().map(move |(k, &w)| (start + k, w));
+        std::iter::once(bin).chain(reset.filter(move |&(j, _)| j != bin.0))
+    }
+}
+
+/// How many accepted displacements `check_move_symmetry` counts
+/// before checking them and starting afresh.  This is enough to catch
+/// a drift of a twentieth of the move scale.
+const MOVE_SYMMETRY_SAMPLES: u64 = 100_000;
+
+/// The sum of `f` over `items`, in parallel.  Each chunk of
+/// `REDUCTION_CHUNK` items is summed in order, and then the chunk sums
+/// in order, so the result does not depend on the number of threads.
+fn ordered_sum<T: Sync>(items: &[T], f: impl Fn(&T) -> f64 + Sync) -> f64 {
+    let partial: Vec<f64> = items
+        .par_chunks(REDUCTION_CHUNK)
+        .map(|c| c.iter().map(&f).sum::<f64>())
+        .collect();
+    partial.iter().sum()
+}
+
+/// The log of the sum of `exp(f)` over `items`, in parallel and without
+/// overflow.  Each chunk is summed relative to its own maximum, and the
+/// chunks are then combined relative to the overall maximum, in the
+/// fixed order of [`ordered_sum`].
+fn log_sum_exp<T: Sync>(items: &[T], f: impl Fn(&T) -> f64 + Sync) -> f64 {
+    let partial: Vec<(f64, f64)> = items
+        .par_chunks(REDUCTION_CHUNK)
+        .map(|c| {
+            let max = c.iter().map(&f).fold(std::f64::NEG_INFINITY, f64::max);
+            (max, c.iter().map(|x| (f(x) - max).exp()).sum::<f64>())
+        })
+        .collect();
+    let max = partial
+        .iter()
+        .map(|&(m, _)| m)
+        .fold(std::f64::NEG_INFINITY, f64::max);
+    max + partial
+        .iter()
+        .map(|&(m, sum)| sum * (m - max).exp())
+        .sum::<f64>()
+        .ln()
+}
+
 impl Bins {
     fn index_to_state(&self, i: usize) -> State {
-        State {
-            E: self.min + (i as f64 + 0.5) * self.width,
+        match self.edges {
+            Some(ref edges) => State {
+                E: 0.5 * (edges[i] + edges[i + 1]),
+            },
+            None => State {
+                E: self.min + (i as f64 + 0.5) * self.width,
+            },
+        }
+    }
+    /// The lower edge of bin `i`, or the upper edge of the highest bin
+    /// if `i` is the number of bins.
+    pub fn edge(&self, i: usize) -> Energy {
+        match self.edges {
+            Some(ref edges) => edges[i],
+            None => self.min + self.width * (i as f64),
+        }
+    }
+    /// The width of bin `i`.
+    pub fn bin_width(&self, i: usize) -> Energy {
+        match self.edges {
+            Some(ref edges) => edges[i + 1] - edges[i],
+            None => self.width,
+        }
+    }
+    /// The energy at the top of the highest bin.
+    pub fn max_energy(&self) -> Energy {
+        self.edge(self.lnw.len())
+    }
+    /// The index of the bin holding `s`, or an error if it is outside
+    /// our bins.
+    pub fn state_to_index(&self, s: State) -> Result<usize, EnergyError> {
+        if s.E.value_unsafe.is_nan() {
+            return Err(EnergyError::NotANumber);
+        }
+        if s.E < self.min {
+            return Err(EnergyError::BelowMin {
+                energy: s.E,
+                min: self.min,
+            });
+        }
+        let i = match self.edges {
+            // The bin whose lower edge is the last one at or below s.
+            Some(ref edges) => match edges.binary_search_by(|e| e.partial_cmp(&s.E).unwrap()) {
+                Ok(i) => i,
+                Err(i) => i - 1,
+            },
+            None => *((s.E - self.min) / self.width).value() as usize,
+        };
+        if i >= self.lnw.len() {
+            return Err(EnergyError::AboveMax {
+                energy: s.E,
+                max: self.max_energy(),
+            });
         }
+        Ok(i)
     }
-    fn state_to_index(&self, s: State) -> usize {
-        *((s.E - self.min) / self.width).value() as usize
+    /// Find the index of an energy that we already know to be within
+    /// our bins, e.g. because `prepare_for_state` has been called.
+    fn index(&self, s: State) -> usize {
+        match self.state_to_index(s) {
+            Ok(i) => i,
+            Err(e) => panic!(
+                "no bin for a state we should have prepared for: {} ({} bins of width {} from {})",
+                e,
+                self.lnw.len(),
+                self.width.pretty(),
+                self.min.pretty()
+            ),
+        }
     }
     fn accumulate_extra(&mut self, k: Interned, idx: usize, value: f64) {
         if let Some(data) = self.extra.get_mut(&k) {
@@ -377,8 +2694,8 @@ impl Bins {
             data.total[idx] += value;
         } else {
             let values = BinCounts {
-                count: vec![0; self.lnw.len()],
-                total: vec![0.0; self.lnw.len()],
+                count: VecDeque::from(vec![0; self.lnw.len()]),
+                total: VecDeque::from(vec![0.0; self.lnw.len()]),
             };
             self.extra.insert(k, values);
             self.accumulate_extra(k, idx, value); // sloppy recursion...
@@ -387,78 +2704,1243 @@ impl Bins {
 }
 
 impl<S: System> EnergyMC<S> {
-    /// Find the index corresponding to a given energy.  This should
-    /// panic if the energy is less than `min`.
-    pub fn state_to_index(&self, s: State) -> usize {
+    /// Find the index corresponding to a given energy.  This returns
+    /// an error if the energy does not lie within any of our bins.
+    pub fn state_to_index(&self, s: State) -> Result<usize, EnergyError> {
         self.bins.state_to_index(s)
     }
+    fn index(&self, s: State) -> usize {
+        self.bins.index(s)
+    }
     /// Find the energy corresponding to a given index.
     pub fn index_to_state(&self, i: usize) -> State {
         self.bins.index_to_state(i)
     }
-    /// Make room in our arrays for a new energy value
-    pub fn prepare_for_state(&mut self, e: State) {
-        let e = e.E;
-        assert!(self.bins.width > Energy::new(0.0));
-        while e < self.bins.min {
-            // this is a little wasteful, but seems the easiest way to
-            // ensure we end up with enough room.
-            self.bins.histogram.insert(0, 0);
-            self.bins.t_found.insert(0, 0);
-            self.bins.lnw.insert(0, Unitless::new(0.0));
-            self.bins.energy_total.insert(0, Energy::new(0.0));
-            self.bins
-                .energy_squared_total
-                .insert(0, EnergySquared::new(0.0));
-            for v in self.bins.extra.iter_mut() {
-                v.1.count.insert(0, 0);
-                v.1.total.insert(0, 0.0);
-            }
-            self.have_visited_since_maxentropy.insert(0, true);
-            self.round_trips.insert(0, 1);
-            self.bins.min -= self.bins.width;
-        }
-        while e >= self.bins.min + self.bins.width * (self.bins.lnw.len() as f64) {
-            self.bins.lnw.push(Unitless::new(0.0));
-            self.bins.histogram.push(0);
-            self.bins.t_found.push(0);
-            for v in self.bins.extra.iter_mut() {
-                v.1.count.push(0);
-                v.1.total.push(0.0);
+    /// The entropy `lnw`, shifted so that the lowest energy bin we have
+    /// visited is zero.  This makes runs with different methods (which
+    /// leave different additive constants) directly comparable, and is
+    /// exact up to the log of the ground state degeneracy.  Bins that
+    /// have never been visited are shifted along with the rest, but
+    /// carry no information.  The raw values are in `bins.lnw`.
+    pub fn normalized_lnw(&self) -> Vec<Unitless> {
+        let zero = self
+            .bins
+            .histogram
+            .iter()
+            .position(|&h| h > 0)
+            .map(|i| self.bins.lnw[i])
+            .unwrap_or(Unitless::new(0.0));
+        self.bins.lnw.iter().map(|&lnw| lnw - zero).collect()
+    }
+    /// The log of the total number of states, if the system knows it
+    /// and it fits in a float.
+    fn ln_total_states(&self) -> Option<f64> {
+        self.system
+            .total_states()
+            .filter(|&n| n > 0.0 && n.is_finite())
+            .map(|n| n.ln())
+    }
+    /// The number of states in each bin, i.e. `exp(lnw)` rounded to a
+    /// whole number.  If the system knows its total number of states,
+    /// the visited bins are scaled to add up to that, which makes these
+    /// the actual counts for a converged run.  Otherwise they are
+    /// scaled as in `normalized_lnw`, so the lowest visited bin has one
+    /// state, which is only right if its degeneracy is one.  Bins that
+    /// have never been visited have no states.
+    pub fn state_counts(&self) -> Vec<f64> {
+        let lnw = self.normalized_lnw();
+        let shift = match self.ln_total_states() {
+            Some(ln_total) => {
+                let visited: Vec<f64> = lnw
+                    .iter()
+                    .zip(self.bins.histogram.iter())
+                    .filter(|&(_, &h)| h > 0)
+                    .map(|(l, _)| *l.value())
+                    .collect();
+                let max = visited.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max);
+                let ln_sum = max + visited.iter().map(|l| (l - max).exp()).sum::<f64>().ln();
+                ln_total - ln_sum
             }
-            self.bins.energy_total.push(Energy::new(0.0));
-            self.bins.energy_squared_total.push(EnergySquared::new(0.0));
-            self.have_visited_since_maxentropy.push(true);
-            self.round_trips.push(1);
-        }
+            None => 0.0,
+        };
+        lnw.iter()
+            .zip(self.bins.histogram.iter())
+            .map(|(l, &h)| if h > 0 { (*l.value() + shift).exp().round() } else { 0.0 })
+            .collect()
+    }
+    /// Compare our entropy with that of another run.  See [`DosDiff`].
+    pub fn diff_dos<T: System>(&self, other: &EnergyMC<T>) -> Result<DosDiff, String> {
+        DosDiff::between(&self.snapshot()?, &other.snapshot()?)
+    }
+    /// The bins that count towards canonical averages: those we have
+    /// visited (at least `min_visits` times, unless rare bins are only
+    /// flagged), and for SAD only those in the important energy range.
+    fn canonical_bins(&self) -> Vec<usize> {
+        let range = self.sad_state().map(|sad| (sad.too_lo, sad.too_hi));
+        (0..self.bins.histogram.len())
+            .filter(|&i| match self.rare_bins {
+                RareBins::Exclude => !self.is_rare(i) && self.bins.histogram[i] > 0,
+                RareBins::Flag => self.bins.histogram[i] > 0,
+            })
+            .filter(|&i| {
+                let e = self.index_to_state(i).E;
+                range.map_or(true, |(lo, hi)| e >= lo && e <= hi)
+            })
+            .collect()
+    }
+    /// The energies at which we evaluate canonical averages, with the
+    /// log of their Boltzmann weights at temperature `T` and the log of
+    /// the sum of those weights, found by log-sum-exp.  With one
+    /// subdivision these are just the bin centers.  With more, each bin
+    /// is split into `subdivisions` points with `lnw` interpolated
+    /// linearly between neighboring bin centers, which approximates an
+    /// integral over energy.  The weights are found in parallel, and
+    /// summed by [`log_sum_exp`], which gives the same answer with any
+    /// number of threads.
+    fn ln_boltzmann_weights(&self, T: Energy, subdivisions: usize) -> (Vec<(Energy, f64)>, f64) {
+        assert!(subdivisions > 0);
+        let bins = self.canonical_bins();
+        let centers: Vec<Energy> = bins.iter().map(|&i| self.index_to_state(i).E).collect();
+        let lnws: Vec<f64> = bins.iter().map(|&i| *self.bins.lnw[i].value()).collect();
+        let center = |j: usize| centers[j];
+        let lnw = |j: usize| lnws[j];
+        let widths: Vec<Energy> = bins.iter().map(|&i| self.bins.bin_width(i)).collect();
+        let weights: Vec<(Energy, f64)> = (0..bins.len())
+            .into_par_iter()
+            .flat_map(|j| (0..subdivisions).into_par_iter().map(move |k| (j, k)))
+            .map(|(j, k)| {
+                let offset = (k as f64 + 0.5) / subdivisions as f64 - 0.5;
+                let e = center(j) + offset * widths[j];
+                let neighbor = if offset < 0.0 && j > 0 {
+                    Some(j - 1)
+                } else if offset > 0.0 && j + 1 < bins.len() {
+                    Some(j + 1)
+                } else {
+                    None
+                };
+                let lnw_e = match neighbor {
+                    Some(n) => {
+                        let f = *((e - center(j)) / (center(n) - center(j))).value();
+                        lnw(j) + f * (lnw(n) - lnw(j))
+                    }
+                    None => lnw(j),
+                };
+                let w = lnw_e - *(e / T).value() - (subdivisions as f64).ln();
+                (e, w)
+            })
+            .collect();
+        let ln_z = log_sum_exp(&weights, |&(_, w)| w);
+        (weights, ln_z)
+    }
+    /// Has bin `i` been visited, but fewer than `min_visits` times?
+    pub fn is_rare(&self, i: usize) -> bool {
+        let h = self.bins.histogram[i];
+        h > 0 && h < self.min_visits
+    }
+    /// The canonical mean energy and its variance at temperature `T`.
+    fn canonical_moments(&self, T: Energy, subdivisions: usize) -> (Energy, EnergySquared) {
+        let (weights, ln_z) = self.ln_boltzmann_weights(T, subdivisions);
+        let mean = Energy::new(ordered_sum(&weights, |&(e, w)| {
+            e.value_unsafe * (w - ln_z).exp()
+        }));
+        let variance = EnergySquared::new(ordered_sum(&weights, |&(e, w)| {
+            let de = (e - mean).value_unsafe;
+            de * de * (w - ln_z).exp()
+        }));
+        (mean, variance)
+    }
+    /// The canonical probability of each bin at temperature `T`, as
+    /// `(energy, probability)` pairs over the visited bins in the
+    /// important range.  This is `P(E)` for plotting, e.g. to find the
+    /// two peaks near a first order transition.  The normalization is
+    /// found by log-sum-exp, so it is safe at low temperatures.
+    pub fn canonical_distribution(&self, T: Energy) -> Vec<(Energy, f64)> {
+        let (weights, ln_z) = self.ln_boltzmann_weights(T, 1);
+        weights
+            .into_par_iter()
+            .map(|(e, w)| (e, (w - ln_z).exp()))
+            .collect()
+    }
+    /// The Helmholtz free energy `F = -T ln Z` at temperature `T`.
+    /// Because `lnw` has an arbitrary additive constant, so does `F`
+    /// (it shifts by `-T` times that constant).  Use `normalized_lnw`
+    /// first if you need a definite zero.
+    pub fn free_energy(&self, T: Energy) -> Energy {
+        let (_, ln_z) = self.ln_boltzmann_weights(T, 1);
+        -T * ln_z
+    }
+    /// The entropy `S = (<E> - F)/T` at temperature `T`, in units of
+    /// the Boltzmann constant.  This has the same additive constant as
+    /// `lnw`.
+    pub fn entropy(&self, T: Energy) -> Unitless {
+        (self.mean_energy(T) - self.free_energy(T)) / T
+    }
+    /// The canonical average at temperature `T` of an observable
+    /// collected by `System::data_to_collect`, reweighting its mean in
+    /// each energy bin just as we do for the energy.  Bins in which it
+    /// was never measured are left out.  This is `None` if it has never
+    /// been measured at all.
+    pub fn mean_observable(&self, name: Interned, T: Energy) -> Option<f64> {
+        let data = self.bins.extra.get(&name)?;
+        let (weights, ln_z) = self.ln_boltzmann_weights(T, 1);
+        let measured: Vec<(f64, f64)> = self
+            .canonical_bins()
+            .into_iter()
+            .zip(weights.into_iter())
+            .filter(|&(i, _)| data.count[i] > 0)
+            .map(|(i, (_, w))| ((w - ln_z).exp(), data.total[i] / data.count[i] as f64))
+            .collect();
+        let total = ordered_sum(&measured, |&(p, x)| p * x);
+        let norm = ordered_sum(&measured, |&(p, _)| p);
+        if norm > 0.0 {
+            Some(total / norm)
+        } else {
+            None
+        }
+    }
+    /// The joint histogram of energy and an observable, if we are
+    /// collecting one.
+    pub fn joint_histogram(&self) -> Option<&JointHistogram> {
+        self.joint.as_ref()
+    }
+    /// The canonical probability at temperature `T` of each energy
+    /// and bin of the joint observable, given as the energy, the
+    /// observable bin and the probability, for each pair we have seen.
+    /// Within each energy bin, the observable is distributed as in the
+    /// joint histogram, and the energy bins are weighted as for the
+    /// mean energy.  Energy bins in which the observable was never
+    /// measured are left out.  This is `None` unless we collect a joint
+    /// histogram and have measured the observable.
+    pub fn joint_distribution(&self, T: Energy) -> Option<Vec<(Energy, usize, f64)>> {
+        let joint = self.joint.as_ref()?;
+        let (weights, ln_z) = self.ln_boltzmann_weights(T, 1);
+        let mut p = Vec::new();
+        for (i, (e, w)) in self.canonical_bins().into_iter().zip(weights.into_iter()) {
+            let key = self.transition_key(self.index_to_state(i));
+            if let Some(row) = joint.counts.get(&key) {
+                let visits = row.values().sum::<u64>() as f64;
+                let p_e = (w - ln_z).exp();
+                p.extend(row.iter().map(|(&j, &c)| (e, j, p_e * c as f64 / visits)));
+            }
+        }
+        let norm: f64 = p.iter().map(|&(_, _, x)| x).sum();
+        if !(norm > 0.0) {
+            return None;
+        }
+        for x in p.iter_mut() {
+            x.2 /= norm;
+        }
+        Some(p)
+    }
+    /// The canonical probability at temperature `T` of each bin of the
+    /// joint observable, summing `joint_distribution` over energy.
+    pub fn observable_distribution(&self, T: Energy) -> Option<Vec<f64>> {
+        let num_bins = self.joint.as_ref()?.num_bins;
+        let mut p = vec![0.0; num_bins];
+        for (_, j, x) in self.joint_distribution(T)? {
+            p[j] += x;
+        }
+        Some(p)
+    }
+    /// The canonical mean energy at temperature `T`, summed over bins.
+    pub fn mean_energy(&self, T: Energy) -> Energy {
+        self.canonical_moments(T, 1).0
+    }
+    /// The canonical heat capacity (in units of the Boltzmann constant)
+    /// at temperature `T`, summed over bins.
+    pub fn heat_capacity(&self, T: Energy) -> Unitless {
+        self.canonical_moments(T, 1).1 / (T * T)
+    }
+    /// Like `mean_energy`, but integrating over energy with `lnw`
+    /// interpolated between bins, using `subdivisions` points per bin.
+    /// This gives smoother curves when few bins are occupied.
+    pub fn mean_energy_interpolated(&self, T: Energy, subdivisions: usize) -> Energy {
+        self.canonical_moments(T, subdivisions).0
+    }
+    /// Like `heat_capacity`, but integrating over energy with `lnw`
+    /// interpolated between bins, using `subdivisions` points per bin.
+    pub fn heat_capacity_interpolated(&self, T: Energy, subdivisions: usize) -> Unitless {
+        self.canonical_moments(T, subdivisions).1 / (T * T)
+    }
+    /// The entropy found from the transition matrix, on the same bins
+    /// as `lnw` and zero at the lowest bin it reaches.  This is an
+    /// independent check on `lnw`, since it only uses which moves were
+    /// proposed, not which were accepted.  Bins it has not connected to
+    /// the lowest one are `None`, as is the whole thing if we are not
+    /// collecting a transition matrix.
+    pub fn dos_from_transition_matrix(&self) -> Option<Vec<Option<Unitless>>> {
+        let S = self.transitions.as_ref()?.ln_dos();
+        Some(
+            (0..self.bins.lnw.len())
+                .map(|i| {
+                    S.get(&self.transition_key(self.index_to_state(i)))
+                        .map(|&s| Unitless::new(s))
+                })
+                .collect(),
+        )
+    }
+    /// Weights for a follow-up run that minimize the time for a round
+    /// trip between the ends of the important energy range, as in the
+    /// optimized ensemble of Trebst, Huse and Troyer.  These are found
+    /// from how often the walker visited each energy heading up and
+    /// heading down, and are given in the form of [`EnergyMC::merge`],
+    /// so they can be saved as the `initial_lnw` of the next run, or
+    /// passed to `seed_lnw`.  This is `None` if we are not counting
+    /// directions.  On a flat landscape they are just our `lnw`.
+    pub fn optimized_ensemble_lnw(&self) -> Option<AlignedDos> {
+        let d = self.directions.as_ref()?;
+        let n = self.bins.lnw.len();
+        let keys: Vec<i64> = (0..n)
+            .map(|i| self.transition_key(self.index_to_state(i)))
+            .collect();
+        let count = |m: &BTreeMap<i64, u64>| -> Vec<u64> {
+            keys.iter().map(|k| m.get(k).cloned().unwrap_or(0)).collect()
+        };
+        let lnw: Vec<f64> = self.bins.lnw.iter().map(|w| *w.value()).collect();
+        Some(AlignedDos {
+            min: self.bins.min,
+            width: self.bins.width,
+            mean_lnw: optimized_ensemble(&lnw, &count(&d.up), &count(&d.down)),
+            std_lnw: vec![0.0; n],
+            std_error: vec![0.0; n],
+            num_runs: self.bins.histogram.iter().map(|&h| (h > 0) as usize).collect(),
+        })
+    }
+    /// The energies where `lnw` fell during an update within the
+    /// important range, with the largest fall at each, in order of
+    /// energy.  This is `None` unless we are checking, and should
+    /// otherwise always be empty.
+    pub fn lnw_decreases(&self) -> Option<Vec<(Energy, f64)>> {
+        let falls = self.lnw_decreases.as_ref()?;
+        Some(
+            (0..self.bins.lnw.len())
+                .map(|i| self.index_to_state(i))
+                .filter_map(|s| falls.get(&self.transition_key(s)).map(|&f| (s.E, f)))
+                .collect(),
+        )
+    }
+    /// The mean energy over complete blocks of moves, and its standard
+    /// error assuming the blocks are independent.  This is `None` until
+    /// we have two blocks.
+    pub fn block_mean_energy(&self) -> Option<(Energy, Energy)> {
+        Some((self.energy_blocks.mean()?, self.energy_blocks.standard_error()?))
+    }
+    /// The integrated autocorrelation time of the energy in moves, if
+    /// we are estimating it and have enough moves; see
+    /// `autocorrelation_lags`.
+    pub fn energy_autocorrelation_time(&self) -> Option<f64> {
+        self.autocorrelation.as_ref()?.time()
+    }
+    /// The current state of the SAD algorithm, or `None` if we are
+    /// using some other method.
+    pub fn sad_state(&self) -> Option<SadState> {
+        match self.method {
+            Method::Sad {
+                too_lo,
+                too_hi,
+                num_states,
+                tL,
+                tF,
+                t_lo,
+                t_hi,
+                ..
+            } => Some(SadState {
+                too_lo,
+                too_hi,
+                num_states,
+                tL,
+                tF,
+                t_lo,
+                t_hi,
+            }),
+            _ => None,
+        }
+    }
+    /// What method we are using, and its state.
+    pub fn method_info(&self) -> MethodInfo {
+        let mut info = MethodInfo {
+            kind: MethodKind::Sad,
+            gamma: self.gamma(),
+            min_T: None,
+            max_T: None,
+            sad: self.sad_state(),
+            t0: None,
+            min_gamma: None,
+            inv_t: None,
+            temperature: None,
+        };
+        match self.method {
+            Method::Sad { min_T, max_T, .. } => {
+                info.min_T = Some(min_T);
+                info.max_T = max_T;
+            }
+            Method::Samc { t0 } => {
+                info.kind = MethodKind::Samc;
+                info.t0 = Some(t0);
+            }
+            Method::WL {
+                min_gamma, inv_t, ..
+            } => {
+                info.kind = MethodKind::WangLandau;
+                info.min_gamma = min_gamma;
+                info.inv_t = Some(inv_t);
+            }
+            Method::Canonical { temperature } => {
+                info.kind = MethodKind::Canonical;
+                info.temperature = Some(temperature);
+            }
+        }
+        info
+    }
+    /// The state of the run so far.
+    pub fn summary(&self) -> RunSummary {
+        let (occupied_bins, holes) = self.occupied_bins_and_holes();
+        RunSummary {
+            moves: self.moves,
+            rejected_moves: self.moves - self.accepted_moves,
+            acceptance_rate: if self.moves > 0 {
+                self.accepted_moves as f64 / self.moves as f64
+            } else {
+                0.0
+            },
+            method: self.method_info(),
+            important_range: self.sad_state().map(|sad| (sad.too_lo, sad.too_hi)),
+            max_S: *self.max_S.value(),
+            round_trips: self.num_round_trips(),
+            num_bins: self.bins.lnw.len(),
+            occupied_bins,
+            holes,
+        }
+    }
+    /// The lowest energy that SAD currently considers important.  Bins
+    /// below this are still being explored, so their `lnw` should not
+    /// be trusted yet.  This is `None` for methods other than SAD.
+    pub fn min_important_energy(&self) -> Option<Energy> {
+        match self.method {
+            Method::Sad { too_lo, .. } => Some(too_lo),
+            _ => None,
+        }
+    }
+    /// Change the minimum temperature of a SAD run, e.g. to push a
+    /// resumed simulation to lower temperatures.  Lowering `min_T`
+    /// only widens the important range, so `too_lo` stays valid and
+    /// will move down as new low energies are found.  Raising it is
+    /// refused once more than one energy is known to be important, as
+    /// is a `min_T` that is not finite and positive.
+    pub fn set_min_T(&mut self, new_min_T: Energy) -> Result<(), MinTError> {
+        let positive = new_min_T.value_unsafe > 0.0 && new_min_T.value_unsafe.is_finite();
+        match self.method {
+            Method::Sad { .. } if !positive => Err(MinTError::NotPositive(new_min_T)),
+            Method::Sad {
+                ref mut min_T,
+                too_lo,
+                too_hi,
+                num_states,
+                ref mut latest_parameter,
+                ..
+            } => {
+                if new_min_T > *min_T && num_states > 1 {
+                    return Err(MinTError::WouldRaise {
+                        current: *min_T,
+                        requested: new_min_T,
+                    });
+                }
+                *min_T = new_min_T;
+                if *latest_parameter != 0.0 {
+                    *latest_parameter = *((too_hi - too_lo) / new_min_T).value();
+                }
+                Ok(())
+            }
+            _ => Err(MinTError::NotSad),
+        }
+    }
+    /// Change the maximum temperature of a SAD run, or with `None`
+    /// weight energies above `too_hi` uniformly.  Energies outside the
+    /// important range are weighted as we go, so this may change at
+    /// any time, but `max_T` must stay finite and above `min_T`.
+    pub fn set_max_T(&mut self, new_max_T: Option<Energy>) -> Result<(), MinTError> {
+        match self.method {
+            Method::Sad {
+                min_T,
+                ref mut max_T,
+                ..
+            } => {
+                if let Some(t) = new_max_T {
+                    if !(t > min_T && t.value_unsafe.is_finite()) {
+                        return Err(MinTError::BadMaxT { min_T, max_T: t });
+                    }
+                }
+                *max_T = new_max_T;
+                Ok(())
+            }
+            _ => Err(MinTError::NotSad),
+        }
+    }
+    /// Carry on with a different method, keeping the `lnw`, histogram
+    /// and energy bins learned so far, e.g. to refine a rough SAMC run
+    /// with SAD.  The new method starts from what we have learned
+    /// rather than from the current energy alone.  For SAD the
+    /// important range is found from `lnw` over the visited bins as in
+    /// [`EnergyMC::seed_lnw`], and `tF` is the latest time an energy
+    /// in that range was found, so gamma carries on from the moves
+    /// already made.  SAMC's gamma likewise counts every move so far,
+    /// while Wang-Landau restarts its own histogram at a gamma of one.
+    pub fn switch_method(&mut self, method: MethodParams) -> Result<(), String> {
+        let mut params = EnergyMCParams::default();
+        params._method = method.clone();
+        params.validate()?;
+        let old = self.method_info().kind;
+        if let MethodParams::Wltm { .. } = method {
+            if self.bins.edges.is_some() {
+                return Err("WLTM cannot be used with adaptive bins".to_string());
+            }
+            if self.transitions.is_none() {
+                self.transitions = Some(TransitionMatrix::default());
+            }
+        }
+        let mut new = Method::new(
+            method,
+            self.system.energy(),
+            self.bins.width,
+            self.min_allowed_energy,
+            self.max_allowed_energy,
+        );
+        let t = self.learning_time();
+        if let Method::Sad {
+            min_T,
+            max_T,
+            ref mut too_lo,
+            ref mut too_hi,
+            ref mut num_states,
+            ref mut tL,
+            ref mut tF,
+            ref mut t_lo,
+            ref mut t_hi,
+            ref mut highest_hist,
+            ref mut latest_parameter,
+            ..
+        } = new
+        {
+            let visited: Vec<usize> = (0..self.bins.histogram.len())
+                .filter(|&i| self.bins.histogram[i] > 0)
+                .collect();
+            let lnws: Vec<(Energy, f64)> = visited
+                .iter()
+                .map(|&i| (self.index_to_state(i).E, *self.bins.lnw[i].value()))
+                .collect();
+            let (lo, hi) = sad_range(&lnws, min_T, max_T);
+            let (ilo, ihi) = (visited[lo], visited[hi]);
+            *too_lo = lnws[lo].0;
+            *too_hi = lnws[hi].0;
+            *num_states = (hi - lo + 1) as u64;
+            *highest_hist = *self.bins.histogram.range(ilo..ihi + 1).max().unwrap();
+            *tF = (*self.bins.t_found.range(ilo..ihi + 1).max().unwrap()).max(1);
+            *tL = t;
+            *t_lo = t;
+            *t_hi = t;
+            *latest_parameter = *((*too_hi - *too_lo) / min_T).value();
+        }
+        self.method = new;
+        log::info!(
+            "switched from {:?} to {:?} after {} moves",
+            old,
+            self.method_info().kind,
+            self.moves
+        );
+        Ok(())
+    }
+    /// Start from a guess at the entropy, such as the result of
+    /// [`EnergyMC::merge`] on earlier runs, instead of a single flat
+    /// bin.  This must be done before the first move, and the guess
+    /// must use our energy bin on a grid aligned with ours.  Bins the
+    /// guess never visited keep their `lnw` but count for nothing.
+    ///
+    /// For SAD the important range is taken from the guess: `too_hi`
+    /// is where it has its maximum entropy (or its most probable energy
+    /// at `max_T`, if there is one), and `too_lo` is the lowest
+    /// energy from which it is no colder than `min_T`.  The guess is
+    /// treated as already converged, so gamma starts out falling as
+    /// `1/t` rather than waiting for new energies to be found.
+    pub fn seed_lnw(&mut self, guess: &AlignedDos) -> Result<(), String> {
+        if self.moves > 0 {
+            return Err("lnw can only be seeded before the first move".to_string());
+        }
+        if guess.width != self.bins.width {
+            return Err(format!(
+                "the guess has energy bin {}, but we use {}",
+                guess.width.pretty(),
+                self.bins.width.pretty()
+            ));
+        }
+        let offset = *((guess.min - self.bins.min) / self.bins.width).value();
+        if (offset - offset.round()).abs() > 1e-6 {
+            return Err("the guess has energy bins misaligned with ours".to_string());
+        }
+        let known: Vec<usize> = (0..guess.mean_lnw.len())
+            .filter(|&i| guess.num_runs.get(i).map_or(false, |&n| n > 0))
+            .collect();
+        if known.is_empty() {
+            return Err("the guess has no visited energies".to_string());
+        }
+        let n = guess.mean_lnw.len();
+        let e = self.system.energy();
+        self.bins.min = guess.min;
+        self.bins.lnw = guess.mean_lnw.iter().map(|&s| Unitless::new(s)).collect();
+        self.bins.histogram = vec![0; n].into();
+        self.bins.t_found = vec![0; n].into();
+        self.bins.energy_total = vec![Energy::new(0.0); n].into();
+        self.bins.energy_squared_total = vec![EnergySquared::new(0.0); n].into();
+        self.bins.extra.clear();
+        self.have_visited_since_maxentropy = vec![false; n].into();
+        self.round_trips = vec![1; n].into();
+        self.proposals_at = vec![0; n].into();
+        self.accepts_at = vec![0; n].into();
+        self.lnw_increment_total = vec![0.0; n].into();
+        self.lnw_increment_squared = vec![0.0; n].into();
+        self.prepare_for_state(State { E: e }).map_err(|e| e.to_string())?;
+        let i = self.index(State { E: e });
+        self.bins.histogram[i] = 1;
+        self.bins.energy_total[i] = e;
+        self.bins.energy_squared_total[i] = e * e;
+        self.occupied = Some((i, i));
+
+        // We may have added bins below the guess for the current energy.
+        let center = |j: usize| guess.energy(j) + 0.5 * guess.width;
+        let first = self.bins.index(State { E: center(0) });
+        let lnw = |j: usize| guess.mean_lnw[j];
+        let imax = known
+            .iter()
+            .cloned()
+            .fold(known[0], |m, j| if lnw(j) > lnw(m) { j } else { m });
+        self.max_S = Unitless::new(lnw(imax));
+        self.max_S_index = first + imax;
+        if let Method::Sad {
+            min_T,
+            max_T,
+            ref mut too_lo,
+            ref mut too_hi,
+            ref mut num_states,
+            ref mut tF,
+            ref mut highest_hist,
+            ref mut latest_parameter,
+            ..
+        } = self.method
+        {
+            let visited: Vec<(Energy, f64)> = known.iter().map(|&j| (center(j), lnw(j))).collect();
+            let (lo, hi) = sad_range(&visited, min_T, max_T);
+            *too_lo = visited[lo].0;
+            *too_hi = visited[hi].0;
+            *num_states = (hi - lo + 1) as u64;
+            *highest_hist = 1;
+            *tF = 1;
+            *latest_parameter = *((*too_hi - *too_lo) / min_T).value();
+        }
+        Ok(())
+    }
+    /// Merge every `factor` adjacent energy bins into one, so that the
+    /// bins are `factor` times wider, keeping the lowest bin edge.  The
+    /// histograms and energy totals are summed, and `lnw` is combined
+    /// by log-sum-exp over the visited bins, so that each wide bin
+    /// holds all the states of its narrow ones.  SAD's important range
+    /// snaps to the centers of the new bins that hold its ends.  This
+    /// can be done between moves, e.g. if `energy_bin` was too fine.
+    pub fn rebin(&mut self, factor: usize) -> Result<(), String> {
+        if factor == 0 {
+            return Err("cannot rebin by a factor of zero".to_string());
+        }
+        if factor == 1 {
+            return Ok(());
+        }
+        if self.bins.edges.is_some() {
+            return Err("cannot rebin bins that have been adapted".to_string());
+        }
+        let n = self.bins.lnw.len();
+        let pieces: Vec<Piece> = (0..n)
+            .step_by(factor)
+            .map(|start| Piece::Merged(start..n.min(start + factor)))
+            .collect();
+        self.regroup(&pieces);
+        let old_width = self.bins.width;
+        self.bins.width = old_width * factor as f64;
+        self.settle_after_regrouping();
+        // Transition matrix keys are round(E/width), so each old key
+        // goes to the key of the new bin holding its energy.
+        let (min, width) = (self.bins.min, self.bins.width);
+        let rekey = |k: i64| {
+            let i = (*((k as f64 * old_width - min) / width).value()).floor();
+            (*((min + (i + 0.5) * width) / width).value()).round() as i64
+        };
+        if let Some(ref mut tm) = self.transitions {
+            let mut counts: BTreeMap<i64, BTreeMap<i64, u64>> = BTreeMap::new();
+            for (&from, row) in tm.counts.iter() {
+                for (&to, &c) in row.iter() {
+                    *counts
+                        .entry(rekey(from))
+                        .or_insert_with(BTreeMap::new)
+                        .entry(rekey(to))
+                        .or_insert(0) += c;
+                }
+            }
+            tm.counts = counts;
+        }
+        if let Some(ref mut d) = self.directions {
+            for counts in [&mut d.up, &mut d.down].iter_mut() {
+                let mut rekeyed = BTreeMap::new();
+                for (&k, &c) in counts.iter() {
+                    *rekeyed.entry(rekey(k)).or_insert(0) += c;
+                }
+                **counts = rekeyed;
+            }
+        }
+        if let Some(ref mut joint) = self.joint {
+            let mut counts: BTreeMap<i64, BTreeMap<usize, u64>> = BTreeMap::new();
+            for (&k, row) in joint.counts.iter() {
+                let merged = counts.entry(rekey(k)).or_insert_with(BTreeMap::new);
+                for (&j, &c) in row.iter() {
+                    *merged.entry(j).or_insert(0) += c;
+                }
+            }
+            joint.counts = counts;
+        }
+        if let Some(ref mut falls) = self.lnw_decreases {
+            let mut rekeyed = BTreeMap::new();
+            for (&k, &fall) in falls.iter() {
+                let worst: &mut f64 = rekeyed.entry(rekey(k)).or_insert(0.0);
+                *worst = worst.max(fall);
+            }
+            *falls = rekeyed;
+        }
+        Ok(())
+    }
+    /// Replace the bins with `pieces` of them, adding up what the old
+    /// bins held, in the way described for [`EnergyMC::rebin`].  A bin
+    /// split in two gives half of its counts and totals to each half,
+    /// and half of its states, so each half's `lnw` is `ln 2` lower.
+    /// This leaves the bin edges and everything derived from the bins
+    /// for the caller to update.
+    fn regroup(&mut self, pieces: &[Piece]) {
+        let n = self.bins.lnw.len();
+        let sum = |v: &VecDeque<u64>| -> VecDeque<u64> {
+            pieces
+                .iter()
+                .map(|p| match *p {
+                    Piece::Merged(ref g) => v.range(g.clone()).sum(),
+                    Piece::Lower(i) => v[i] / 2,
+                    Piece::Upper(i) => v[i] - v[i] / 2,
+                })
+                .collect()
+        };
+        let sum_f64 = |v: &VecDeque<f64>| -> VecDeque<f64> {
+            pieces
+                .iter()
+                .map(|p| p.share() * v.range(p.range()).sum::<f64>())
+                .collect()
+        };
+        let histogram = &self.bins.histogram;
+        let lnw: VecDeque<Unitless> = pieces
+            .iter()
+            .map(|p| {
+                let g = p.range();
+                let visited: Vec<f64> = g
+                    .clone()
+                    .filter(|&i| histogram[i] > 0)
+                    .map(|i| *self.bins.lnw[i].value())
+                    .collect();
+                let lnws = if visited.is_empty() {
+                    g.map(|i| *self.bins.lnw[i].value()).collect()
+                } else {
+                    visited
+                };
+                let max = lnws.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max);
+                let ln_states = max + lnws.iter().map(|l| (l - max).exp()).sum::<f64>().ln();
+                Unitless::new(ln_states + p.share().ln())
+            })
+            .collect();
+        let t_found: VecDeque<u64> = pieces
+            .iter()
+            .map(|p| {
+                p.range()
+                    .filter(|&i| histogram[i] > 0)
+                    .map(|i| self.bins.t_found[i])
+                    .min()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let energy_total: VecDeque<Energy> = pieces
+            .iter()
+            .map(|p| {
+                p.share()
+                    * self
+                        .bins
+                        .energy_total
+                        .range(p.range())
+                        .fold(Energy::new(0.0), |a, &e| a + e)
+            })
+            .collect();
+        let energy_squared_total: VecDeque<EnergySquared> = pieces
+            .iter()
+            .map(|p| {
+                p.share()
+                    * self
+                        .bins
+                        .energy_squared_total
+                        .range(p.range())
+                        .fold(EnergySquared::new(0.0), |a, &e| a + e)
+            })
+            .collect();
+        let have_visited: VecDeque<bool> = pieces
+            .iter()
+            .map(|p| self.have_visited_since_maxentropy.range(p.range()).any(|&x| x))
+            .collect();
+        let round_trips: VecDeque<u64> = pieces
+            .iter()
+            .map(|p| self.round_trips.range(p.range()).cloned().max().unwrap_or(0))
+            .collect();
+        self.bins.histogram = sum(&self.bins.histogram);
+        self.bins.lnw = lnw;
+        self.bins.t_found = t_found;
+        self.bins.energy_total = energy_total;
+        self.bins.energy_squared_total = energy_squared_total;
+        for v in self.bins.extra.values_mut() {
+            v.count = sum(&v.count);
+            v.total = sum_f64(&v.total);
+        }
+        self.have_visited_since_maxentropy = have_visited;
+        self.round_trips = round_trips;
+        self.proposals_at = sum(&self.proposals_at);
+        self.accepts_at = sum(&self.accepts_at);
+        self.lnw_increment_total = sum_f64(&self.lnw_increment_total);
+        self.lnw_increment_squared = sum_f64(&self.lnw_increment_squared);
+        if let Method::WL {
+            ref mut hist,
+            ref mut min_energy,
+            ..
+        } = self.method
+        {
+            *hist = if hist.len() == n && *min_energy == self.bins.min {
+                sum(hist)
+            } else {
+                VecDeque::from(vec![0; pieces.len()])
+            };
+            *min_energy = self.bins.min;
+        }
+    }
+    /// Bring everything that depends on the bins up to date after
+    /// `regroup` and a change of edges: the occupied range
+    /// and `max_S`, and the method's ranges, which snap to the centers
+    /// of the bins that hold their ends.
+    fn settle_after_regrouping(&mut self) {
+        self.recompute_occupied();
+        let visited: Vec<usize> = (0..self.bins.lnw.len())
+            .filter(|&i| self.bins.histogram[i] > 0)
+            .collect();
+        if let Some(&imax) = visited
+            .iter()
+            .max_by(|&&i, &&j| self.bins.lnw[i].value().total_cmp(self.bins.lnw[j].value()))
+        {
+            self.max_S = self.bins.lnw[imax];
+            self.max_S_index = imax;
+        }
+        let bins = &self.bins;
+        let snap = |e: Energy| bins.index_to_state(bins.index(State { E: e })).E;
+        match self.method {
+            Method::Sad {
+                ref mut too_lo,
+                ref mut too_hi,
+                ref mut num_states,
+                ref mut highest_hist,
+                ..
+            } => {
+                *too_lo = snap(*too_lo);
+                *too_hi = snap(*too_hi);
+                let important = bins.index(State { E: *too_lo })..=bins.index(State { E: *too_hi });
+                *num_states = visited.iter().filter(|&&i| important.contains(&i)).count() as u64;
+                *highest_hist = bins.histogram.range(important).cloned().max().unwrap_or(1);
+            }
+            Method::WL {
+                ref mut lowest_hist,
+                ref mut highest_hist,
+                ref mut num_states,
+                ref hist,
+                ..
+            } => {
+                *lowest_hist = visited.iter().map(|&i| hist[i]).min().unwrap_or(0);
+                *highest_hist = hist.iter().cloned().max().unwrap_or(0).max(1);
+                // Without both bounds WL counts the energies it has
+                // visited, which merging or splitting bins changes.
+                *num_states = match (self.min_allowed_energy, self.max_allowed_energy) {
+                    (Some(mine), Some(maxe)) => match bins.edges {
+                        Some(_) => (0..bins.lnw.len())
+                            .map(|i| bins.index_to_state(i).E)
+                            .filter(|&e| e >= mine && e <= maxe)
+                            .count() as f64,
+                        None => *((maxe - mine) / bins.width).value(),
+                    },
+                    _ => (visited.len() as f64).max(1.0),
+                };
+            }
+            _ => (),
+        }
+    }
+    /// Adapt the bins to the shape of `lnw`: split each visited bin in
+    /// two where the second difference of `lnw` across it is larger
+    /// than `split`, and merge adjacent pairs of visited bins where it
+    /// is smaller than `merge` across both.  Counts and `lnw` are
+    /// shared out or added up so that the total number of states is
+    /// unchanged, with each half of a split bin getting half of it.
+    /// From then on bins are looked up by their edges, and new bins at
+    /// either end are `width` wide.  This can only be done once, on
+    /// uniform bins.
+    pub fn adapt_bins(&mut self, split: f64, merge: f64) -> Result<(), String> {
+        if self.bins.edges.is_some() {
+            return Err("the bins have already been adapted".to_string());
+        }
+        if self.transitions.is_some()
+            || self.directions.is_some()
+            || self.joint.is_some()
+            || self.lnw_decreases.is_some()
+        {
+            return Err("cannot adapt bins that other data are keyed by".to_string());
+        }
+        let n = self.bins.lnw.len();
+        let visited = |i: usize| self.bins.histogram[i] > 0;
+        let lnw = |i: usize| *self.bins.lnw[i].value();
+        // The second difference across each bin, where it and both its
+        // neighbors have been visited.
+        let curvature: Vec<Option<f64>> = (0..n)
+            .map(|i| {
+                if i > 0 && i + 1 < n && visited(i - 1) && visited(i) && visited(i + 1) {
+                    Some((lnw(i - 1) - 2.0 * lnw(i) + lnw(i + 1)).abs())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let flat = |i: usize| curvature[i].map(|c| c < merge).unwrap_or(false);
+        let mut pieces = Vec::new();
+        let mut edges = VecDeque::new();
+        let mut i = 0;
+        while i < n {
+            edges.push_back(self.bins.edge(i));
+            if curvature[i].map(|c| c > split).unwrap_or(false) {
+                pieces.push(Piece::Lower(i));
+                pieces.push(Piece::Upper(i));
+                edges.push_back(self.bins.index_to_state(i).E);
+                i += 1;
+            } else if i + 1 < n && flat(i) && flat(i + 1) {
+                pieces.push(Piece::Merged(i..i + 2));
+                i += 2;
+            } else {
+                pieces.push(Piece::Merged(i..i + 1));
+                i += 1;
+            }
+        }
+        edges.push_back(self.bins.max_energy());
+        self.regroup(&pieces);
+        self.bins.edges = Some(edges);
+        self.settle_after_regrouping();
+        Ok(())
+    }
+    /// The gamma (the amount added to `lnw`) used in the most recent
+    /// move, which shows whether we are still learning aggressively.
+    pub fn last_gamma(&self) -> f64 {
+        self.last_gamma
+    }
+    /// The fraction of moves accepted from each energy bin, or `None`
+    /// for bins from which no move has been proposed.
+    pub fn acceptance_by_bin(&self) -> Vec<Option<f64>> {
+        self.proposals_at
+            .iter()
+            .zip(self.accepts_at.iter())
+            .map(|(&p, &a)| if p > 0 { Some(a as f64 / p as f64) } else { None })
+            .collect()
+    }
+    /// The number of energy bins that have been visited, and the number
+    /// of holes, which are unvisited bins between the lowest and highest
+    /// visited energies.  Holes are a red flag that the walker is
+    /// tunneling past some energies, unless the system simply has no
+    /// states there.
+    pub fn occupied_bins_and_holes(&self) -> (usize, usize) {
+        match self.occupied {
+            Some((lo, hi)) => {
+                let occupied = self
+                    .bins
+                    .histogram
+                    .range(lo..=hi)
+                    .filter(|&&n| n > 0)
+                    .count();
+                (occupied, hi + 1 - lo - occupied)
+            }
+            None => (0, 0),
+        }
+    }
+    /// Count an increment of `gamma` to `lnw[i]` towards its error.
+    fn record_lnw_increment(&mut self, i: usize, gamma: f64) {
+        self.lnw_increment_total[i] += gamma;
+        self.lnw_increment_squared[i] += gamma * gamma;
+    }
+    /// An estimate of the statistical error in each `lnw`, or `None`
+    /// for bins with no increments counted.  We only count SAMC's
+    /// increments once gamma falls as 1/t, and treat the visits to each
+    /// bin as a Poisson process, so the variance of `lnw[i]` is the sum
+    /// of the squares of its increments.  This is only meaningful in
+    /// the 1/t regime, where gamma is small enough that successive
+    /// visits are nearly independent.
+    pub fn lnw_error(&self) -> Vec<Option<f64>> {
+        self.lnw_increments()
+            .into_iter()
+            .map(|inc| inc.map(|(_, variance)| variance.sqrt()))
+            .collect()
+    }
+    /// The total that SAMC has added to each `lnw` in the 1/t regime,
+    /// which is the mean change we expect from those visits, together
+    /// with its variance as in [`EnergyMC::lnw_error`].  This is `None`
+    /// for bins with no increments counted.
+    pub fn lnw_increments(&self) -> Vec<Option<(f64, f64)>> {
+        self.lnw_increment_total
+            .iter()
+            .zip(self.lnw_increment_squared.iter())
+            .map(|(&total, &sq)| if total > 0.0 { Some((total, sq)) } else { None })
+            .collect()
+    }
+    /// The index of the lowest energy bin that has been visited.
+    pub fn min_occupied(&self) -> Option<usize> {
+        self.occupied.map(|(lo, _)| lo)
+    }
+    /// The index of the highest energy bin that has been visited.
+    pub fn max_occupied(&self) -> Option<usize> {
+        self.occupied.map(|(_, hi)| hi)
+    }
+    /// Find the lowest and highest visited bins the slow way, for when
+    /// the histogram has been changed wholesale.
+    fn recompute_occupied(&mut self) {
+        let h = &self.bins.histogram;
+        self.occupied = match (h.iter().position(|&n| n > 0), h.iter().rposition(|&n| n > 0)) {
+            (Some(lo), Some(hi)) => Some((lo, hi)),
+            _ => None,
+        };
+    }
+    /// How flat the histogram is over the important energies (SAD's
+    /// range, or every energy for other methods), as the ratio of the
+    /// smallest count to the mean.  Bins never visited are left out,
+    /// since there may be no states there.
+    pub fn histogram_flatness(&self) -> f64 {
+        Flatness::default().measure(&self.important_histogram())
+    }
+    /// The histogram over the important energies, leaving out bins
+    /// that have never been visited, as used by `histogram_flatness`.
+    fn important_histogram(&self) -> Vec<u64> {
+        let range = match self.method {
+            Method::Sad { too_lo, too_hi, .. } => Some((
+                self.bins.index(State { E: too_lo }),
+                self.bins.index(State { E: too_hi }),
+            )),
+            _ => None,
+        };
+        self.bins
+            .histogram
+            .iter()
+            .enumerate()
+            .filter(|&(i, &n)| match range {
+                Some((lo, hi)) => n > 0 && (lo..=hi).contains(&i),
+                None => n > 0,
+            })
+            .map(|(_, &n)| n)
+            .collect()
+    }
+    /// The fraction of the bins between the lowest and highest visited
+    /// energies that have been visited, which is one when there are no
+    /// holes.
+    pub fn explored_fraction(&self) -> f64 {
+        let (occupied, holes) = self.occupied_bins_and_holes();
+        if occupied == 0 {
+            0.0
+        } else {
+            occupied as f64 / (occupied + holes) as f64
+        }
+    }
+    /// The number of round trips from the lowest to the highest
+    /// important energy and back.  For SAD these are `too_lo` and
+    /// `too_hi`; otherwise the lowest and highest energies seen.
+    pub fn num_round_trips(&self) -> u64 {
+        self.extreme_round_trips.count
+    }
+    /// Are we about to stop, because we have made all our moves, run
+    /// out of time, or been asked to shut down?
+    pub fn is_finishing(&self) -> bool {
+        self.report.am_all_done(self.moves, self.accepted_moves)
+            || self.time_limit.has_expired()
+            || plugin::shutdown_requested()
+    }
+    /// Write out any lines the traces are holding back, which we do
+    /// whenever we save and before the final report.
+    pub fn flush_files(&self) {
+        self.sad_trace.flush(&self.save_as);
+        self.energy_trace.flush();
+    }
+    /// Make room in our arrays for a new energy value.  If this would
+    /// require more than `max_bins` bins, or the energy is above the
+    /// `energy_ceiling`, beyond the system's `energy_bounds` or outside
+    /// the fixed window, an error is returned and the arrays are left
+    /// untouched.  With a fixed window there is never anything to make.
+    pub fn prepare_for_state(&mut self, s: State) -> Result<(), EnergyError> {
+        let e = s.E;
+        assert!(
+            self.bins.width > Energy::new(0.0),
+            "energy bins have non-positive width {}",
+            self.bins.width.pretty()
+        );
+        if e.value_unsafe.is_nan() {
+            return Err(EnergyError::NotANumber);
+        }
+        if let Some((min, max)) = self.fixed_window {
+            if e < min || e > max {
+                return Err(EnergyError::OutsideWindow {
+                    energy: e,
+                    min,
+                    max,
+                });
+            }
+            debug_assert!(self.bins.state_to_index(s).is_ok());
+            return Ok(());
+        }
+        if let Some(ceiling) = self.energy_ceiling {
+            if e > ceiling {
+                return Err(EnergyError::AboveCeiling {
+                    energy: e,
+                    ceiling,
+                });
+            }
+        }
+        match self.energy_bounds {
+            (Some(bound), _) if e < bound => {
+                return Err(EnergyError::BeyondBounds { energy: e, bound });
+            }
+            (_, Some(bound)) if e > bound => {
+                return Err(EnergyError::BeyondBounds { energy: e, bound });
+            }
+            _ => (),
+        }
+        if let Some(max_bins) = self.max_bins {
+            let below = if e < self.bins.min {
+                (*((self.bins.min - e) / self.bins.width).value()).ceil()
+            } else {
+                0.0
+            };
+            let above = *((e - self.bins.max_energy()) / self.bins.width).value();
+            let above = if above >= 0.0 { above.floor() + 1.0 } else { 0.0 };
+            let needed = self.bins.lnw.len() as f64 + below + above;
+            if needed > max_bins as f64 {
+                return Err(EnergyError::TooManyBins {
+                    energy: e,
+                    needed: needed as usize,
+                    max_bins,
+                });
+            }
+        }
+        while e < self.bins.min {
+            self.bins.histogram.push_front(0);
+            self.bins.t_found.push_front(0);
+            self.bins.lnw.push_front(Unitless::new(0.0));
+            self.bins.energy_total.push_front(Energy::new(0.0));
+            self.bins
+                .energy_squared_total
+                .push_front(EnergySquared::new(0.0));
+            for v in self.bins.extra.iter_mut() {
+                v.1.count.push_front(0);
+                v.1.total.push_front(0.0);
+            }
+            self.have_visited_since_maxentropy.push_front(true);
+            self.round_trips.push_front(1);
+            self.proposals_at.push_front(0);
+            self.accepts_at.push_front(0);
+            self.lnw_increment_total.push_front(0.0);
+            self.lnw_increment_squared.push_front(0.0);
+            self.occupied = self.occupied.map(|(lo, hi)| (lo + 1, hi + 1));
+            self.max_S_index += 1;
+            self.bins.min -= self.bins.width;
+            if let Some(ref mut edges) = self.bins.edges {
+                edges.push_front(self.bins.min);
+            }
+        }
+        while e >= self.bins.max_energy() {
+            if let Some(ref mut edges) = self.bins.edges {
+                let top = edges[edges.len() - 1];
+                edges.push_back(top + self.bins.width);
+            }
+            self.bins.lnw.push_back(Unitless::new(0.0));
+            self.bins.histogram.push_back(0);
+            self.bins.t_found.push_back(0);
+            for v in self.bins.extra.iter_mut() {
+                v.1.count.push_back(0);
+                v.1.total.push_back(0.0);
+            }
+            self.bins.energy_total.push_back(Energy::new(0.0));
+            self.bins
+                .energy_squared_total
+                .push_back(EnergySquared::new(0.0));
+            self.have_visited_since_maxentropy.push_back(true);
+            self.round_trips.push_back(1);
+            self.proposals_at.push_back(0);
+            self.accepts_at.push_back(0);
+            self.lnw_increment_total.push_back(0.0);
+            self.lnw_increment_squared.push_back(0.0);
+        }
+        Ok(())
+    }
+}
+
+impl<S: System> EnergyMC<S> {
+    /// The key of the transition matrix bin holding `s`.
+    fn transition_key(&self, s: State) -> i64 {
+        let center = self.index_to_state(self.index(s)).E;
+        (*(center / self.bins.width).value()).round() as i64
+    }
+    /// Count a proposed move for the transition matrix, if we have one.
+    fn record_transition(&mut self, e1: State, e2: State) {
+        if self.transitions.is_none() {
+            return;
+        }
+        let (from, to) = (self.transition_key(e1), self.transition_key(e2));
+        if let Some(ref mut tm) = self.transitions {
+            tm.record(from, to);
+        }
     }
-}
-
-impl<S: System> EnergyMC<S> {
     /// This decides whether to reject the move based on the actual
     /// method in use.
     fn reject_move(&mut self, e1: State, e2: State) -> bool {
-        let i1 = self.state_to_index(e1);
-        let i2 = self.state_to_index(e2);
+        self.record_transition(e1, e2);
+        if let Some(ref mut rule) = self.acceptance_rule.0 {
+            return !rule(e1, e2, &self.bins, &mut self.rng);
+        }
+        let i1 = self.index(e1);
+        let i2 = self.index(e2);
         match self.method {
             Method::Sad {
                 too_lo,
                 too_hi,
                 min_T,
+                max_T,
                 ..
             } => {
                 let lnw = &self.bins.lnw;
+                // Outside the important range we sample canonically, at
+                // min_T below it and at max_T above it.
+                let above = |e: Energy| match max_T {
+                    Some(max_T) => (e - too_hi) / max_T,
+                    None => Unitless::new(0.0),
+                };
                 let lnw1 = if e1.E < too_lo {
-                    lnw[self.state_to_index(State { E: too_lo })] + (e1.E - too_lo) / min_T
+                    lnw[self.index(State { E: too_lo })] + (e1.E - too_lo) / min_T
                 } else if e1.E > too_hi {
-                    lnw[self.state_to_index(State { E: too_hi })]
+                    lnw[self.index(State { E: too_hi })] + above(e1.E)
                 } else {
                     lnw[i1]
                 };
                 let lnw2 = if e2.E < too_lo {
-                    lnw[self.state_to_index(State { E: too_lo })] + (e2.E - too_lo) / min_T
+                    lnw[self.index(State { E: too_lo })] + (e2.E - too_lo) / min_T
                 } else if e2.E > too_hi {
-                    lnw[self.state_to_index(State { E: too_hi })]
+                    lnw[self.index(State { E: too_hi })] + above(e2.E)
                 } else {
                     lnw[i2]
                 };
@@ -467,6 +3949,7 @@ impl<S: System> EnergyMC<S> {
                     // Here we do changes that need only happen when
                     // we encounter an energy in our important range
                     // that we have never seen before.
+                    let t = self.learning_time();
                     match &mut self.method {
                         Method::Sad {
                             ref mut num_states,
@@ -476,9 +3959,9 @@ impl<S: System> EnergyMC<S> {
                         } => {
                             *latest_parameter = *((too_hi - too_lo) / min_T).value();
                             *num_states += 1;
-                            *tL = self.moves;
+                            *tL = t;
                         }
-                        _ => unreachable!(),
+                        _ => unreachable!("we are in the SAD branch"),
                     }
                 }
                 rejected
@@ -510,11 +3993,92 @@ impl<S: System> EnergyMC<S> {
             }
         }
     }
-    /// This updates the lnw based on the actual method in use.
-    fn update_weights(&mut self, energy: State) {
-        let i = self.state_to_index(energy);
+    /// Update the weights as `update_weights` does, and note every bin
+    /// where `lnw` fell that was within the range whose weights we are
+    /// learning.  Bins outside it are not checked, since SAD resets the
+    /// weights of energies it brings into its important range.
+    fn checked_update_weights(&mut self, energy: State) -> LnwChange {
+        let learning = match self.method {
+            Method::Canonical { .. } => None,
+            Method::Sad { too_lo, too_hi, .. } => Some((too_lo, too_hi)),
+            _ => Some((
+                Energy::new(std::f64::NEG_INFINITY),
+                Energy::new(std::f64::INFINITY),
+            )),
+        };
+        let change = self.update_weights(energy);
+        let (lo, hi) = match learning {
+            Some(range) => range,
+            None => return change,
+        };
+        let mut falls = Vec::new();
+        for (j, old) in change.before() {
+            let e = self.bins.index_to_state(j);
+            let fall = *(old - self.bins.lnw[j]).value();
+            let learning = lo <= e.E && e.E <= hi;
+            if learning && fall > LNW_DECREASE_TOLERANCE * old.value().abs().max(1.0) {
+                falls.push((e, fall));
+            }
+        }
+        for (e, fall) in falls {
+            log::warn!(
+                "lnw fell by {} at {} after {} moves",
+                fall,
+                e.E.pretty(),
+                self.moves
+            );
+            let key = self.transition_key(e);
+            if let Some(ref mut falls) = self.lnw_decreases {
+                let worst = falls.entry(key).or_insert(0.0);
+                *worst = worst.max(fall);
+            }
+        }
+        change
+    }
+    /// Make sure an update left every bin it wrote finite.  Most
+    /// updates only add gamma to one bin, but SAD can set many when it
+    /// widens its range.
+    fn check_lnw_finite(&mut self, change: &LnwChange, action: NonFiniteLnw) {
+        for (j, old) in change.before() {
+            let lnw = *self.bins.lnw[j].value();
+            if lnw.is_finite() {
+                continue;
+            }
+            let e = self.bins.index_to_state(j).E;
+            match action {
+                NonFiniteLnw::Abort => panic!(
+                    "lnw became {} at {} after {} moves, with gamma {}",
+                    lnw,
+                    e.pretty(),
+                    self.moves,
+                    self.last_gamma
+                ),
+                NonFiniteLnw::Clamp => {
+                    log::warn!(
+                        "lnw became {} at {} after {} moves, with gamma {}; keeping {}",
+                        lnw,
+                        e.pretty(),
+                        self.moves,
+                        self.last_gamma,
+                        old.value()
+                    );
+                    self.bins.lnw[j] = old;
+                }
+            }
+        }
+    }
+    /// This updates the lnw based on the actual method in use, and
+    /// returns the bins it wrote.
+    fn update_weights(&mut self, energy: State) -> LnwChange {
+        let i = self.index(energy);
+        let t = self.learning_time();
         let gamma = self.gamma(); // compute gamma out front...
+        self.last_gamma = gamma;
         let old_lnw = self.bins.lnw[i];
+        let mut change = LnwChange {
+            bin: (i, old_lnw),
+            reset: (i, Vec::new()),
+        };
         self.bins.lnw[i] += gamma;
         // let mut gamma_changed = false;
         let mut switch_to_samc: Option<f64> = None;
@@ -522,11 +4086,14 @@ impl<S: System> EnergyMC<S> {
             Method::Canonical {..} => (), // Nothing to update!
             Method::Sad {
                 min_T,
+                max_T,
                 ref mut too_lo,
                 ref mut too_hi,
                 ref mut num_states,
                 ref mut tL,
                 ref mut tF,
+                ref mut t_lo,
+                ref mut t_hi,
                 ref mut highest_hist,
                 ref mut latest_parameter,
                 ..
@@ -537,16 +4104,29 @@ impl<S: System> EnergyMC<S> {
                     self.bins.lnw[i] = old_lnw;
                 }
 
+                // An energy outside the range becomes important once it
+                // is visited more often than any energy within it.
+                // Since we sample canonically outside the range, this
+                // means it is more probable than too_lo at min_T, or
+                // than too_hi at max_T.  With no max_T, too_hi rises
+                // to the maximum entropy.
                 if histogram[i] > *highest_hist {
                     *highest_hist = histogram[i];
                     if energy.E > *too_hi {
-                        let ihi = self.bins.state_to_index(State { E: *too_hi });
+                        let ihi = self.bins.index(State { E: *too_hi });
                         for j in 0..histogram.len() {
                             let ej = self.bins.index_to_state(j).E;
                             let lnw = &mut self.bins.lnw;
                             if ej > *too_hi && ej <= energy.E {
+                                if change.reset.1.is_empty() {
+                                    change.reset.0 = j;
+                                }
+                                change.reset.1.push(lnw[j]);
                                 if histogram[j] != 0 {
                                     lnw[j] = lnw[ihi];
+                                    if let Some(max_T) = max_T {
+                                        lnw[j] += (ej - *too_hi) / max_T;
+                                    }
                                     *num_states += 1;
                                 } else {
                                     lnw[j] = Unitless::new(0.0);
@@ -554,16 +4134,27 @@ impl<S: System> EnergyMC<S> {
                             }
                         }
                         *latest_parameter = *((energy.E - *too_lo) / min_T).value();
-                        *tL = self.moves;
+                        *tL = t;
+                        *t_hi = t;
                         // The following rounds the energy to one of the bins.
-                        let bin_e = self.bins.index_to_state(self.bins.state_to_index(energy)).E;
+                        let bin_e = self.bins.index_to_state(self.bins.index(energy)).E;
+                        log::debug!(
+                            "sad: too_hi raised from {} to {} after {} moves",
+                            too_hi.pretty(),
+                            bin_e.pretty(),
+                            self.moves
+                        );
                         *too_hi = bin_e;
                     } else if energy.E < *too_lo {
-                        let ilo = self.bins.state_to_index(State { E: *too_lo });
+                        let ilo = self.bins.index(State { E: *too_lo });
                         for j in 0..histogram.len() {
                             let ej = self.bins.index_to_state(j).E;
                             let lnw = &mut self.bins.lnw;
                             if ej < *too_lo && ej >= energy.E {
+                                if change.reset.1.is_empty() {
+                                    change.reset.0 = j;
+                                }
+                                change.reset.1.push(lnw[j]);
                                 if histogram[j] != 0 {
                                     lnw[j] = lnw[ilo] + (ej - *too_lo) / min_T;
                                     if lnw[j] < Unitless::new(0.) {
@@ -576,42 +4167,58 @@ impl<S: System> EnergyMC<S> {
                             }
                         }
                         *latest_parameter = *((*too_hi - energy.E) / min_T).value();
-                        *tL = self.moves;
+                        *tL = t;
+                        *t_lo = t;
                         // The following rounds the energy to one of the bins.
-                        let bin_e = self.bins.index_to_state(self.bins.state_to_index(energy)).E;
+                        let bin_e = self.bins.index_to_state(self.bins.index(energy)).E;
+                        log::debug!(
+                            "sad: too_lo lowered from {} to {} after {} moves",
+                            too_lo.pretty(),
+                            bin_e.pretty(),
+                            self.moves
+                        );
                         *too_lo = bin_e;
                     }
                 }
-                if *tL == self.moves {
+                if *tL == t {
                     // gamma_changed = true;
                     // Set tF to the latest discovery time in the
                     // range of energies that we actually care about.
-                    let ilo = self.bins.state_to_index(State { E: *too_lo });
-                    let ihi = self.bins.state_to_index(State { E: *too_hi });
+                    let ilo = self.bins.index(State { E: *too_lo });
+                    let ihi = self.bins.index(State { E: *too_hi });
                     let old_tF = *tF;
-                    *tF = *self.bins.t_found[ilo..ihi + 1].iter().max().unwrap();
+                    *tF = *self.bins.t_found.range(ilo..ihi + 1).max().unwrap();
                     if old_tF == *tF {
                         // We didn't change gamma after all!
                         // gamma_changed = false;
                     } else {
+                        log::info!(
+                            "sad: tF changed from {} to {} with {} states in {} ... {}",
+                            old_tF,
+                            *tF,
+                            num_states,
+                            too_lo.pretty(),
+                            too_hi.pretty()
+                        );
                         // We just discovered a new important energy.
                         // Let's take this as an opportunity to revise our
                         // translation scale, and also to log the news.
                         if let MoveParams::AcceptanceRate(r) = self.move_plan {
-                            let s = self.acceptance_rate / r;
-                            let s = if s < 0.8 {
-                                0.8
-                            } else if s > 1.2 {
-                                1.2
-                            } else {
-                                s
-                            };
-                            self.translation_scale *= s;
+                            self.translation_scale *= tuning_factor(self.acceptance_rate, r);
+                            if let Some(ref mut axes) = self.axis_scales {
+                                axes.tune(r);
+                            }
                             if !self.report.quiet {
                                 println!(
                                     "        new translation scale: {:.3}",
                                     self.translation_scale
                                 );
+                                if let Some(ref axes) = self.axis_scales {
+                                    println!(
+                                        "        along each axis: {:.3} {:.3} {:.3}",
+                                        axes.scales[0], axes.scales[1], axes.scales[2]
+                                    );
+                                }
                                 println!(
                                     "        acceptance rate {:.1}% [long-term: {:.1}%]",
                                     100.0 * self.acceptance_rate,
@@ -627,8 +4234,7 @@ impl<S: System> EnergyMC<S> {
                                 self.bins.min.pretty(),
                                 too_lo.pretty(),
                                 too_hi.pretty(),
-                                (self.bins.min + self.bins.width * (histogram.len() - 1) as f64)
-                                    .pretty()
+                                self.bins.edge(histogram.len() - 1).pretty()
                             );
                         }
                     }
@@ -645,31 +4251,42 @@ impl<S: System> EnergyMC<S> {
                 num_states,
                 inv_t,
                 min_gamma,
+                ..
             } => {
                 if let Some(min_gamma) = min_gamma {
                     if *gamma < min_gamma {
                         // We are in a production run.
                         hist[i] += 1;
-                        return;
+                        return change;
                     }
                 }
-                if hist.len() != self.bins.lnw.len() {
+                // With bins made out to the energy bounds, a new energy
+                // does not change the number of bins, so we look for a
+                // first visit instead.  Other systems make bins for
+                // rejected proposals too, so there we go by the number.
+                let made_out =
+                    self.fixed_window.is_some() || matches!(self.energy_bounds, (Some(_), Some(_)));
+                if hist.len() != self.bins.lnw.len() || (made_out && self.bins.histogram[i] == 1) {
                     // Oops, we found a new energy, so let's regroup.
                     if hist.len() == 0 || (*gamma != 1.0 && *lowest_hist > 0) {
-                        println!(
-                            "    WL: Starting fresh with {} energies",
-                            self.bins.lnw.len()
+                        log::info!(
+                            "wl: resetting histogram with {} energies after {} moves",
+                            self.bins.lnw.len(),
+                            self.moves
                         );
                         // gamma_changed = true;
                         *gamma = 1.0;
                         *lowest_hist = 0;
                         *highest_hist = 0;
                         *total_hist = 0;
-                        *hist = vec![0; self.bins.lnw.len()];
+                        *hist = VecDeque::from(vec![0; self.bins.lnw.len()]);
                         *min_energy = self.bins.min;
                     } else {
                         if *min_energy > self.bins.min {
-                            println!("Found a new energy minimum: {:.5}", self.bins.min.pretty());
+                            log::debug!(
+                                "wl: extending histogram down to {}",
+                                self.bins.min.pretty()
+                            );
                         }
                         // We have to adjust our hist Vec, but can
                         // keep our counts! We just pretend we already
@@ -677,10 +4294,10 @@ impl<S: System> EnergyMC<S> {
                         // found...
                         while *min_energy > self.bins.min {
                             *min_energy -= self.bins.width;
-                            hist.insert(0, 0);
+                            hist.push_front(0);
                         }
                         while hist.len() < self.bins.lnw.len() {
-                            hist.push(0);
+                            hist.push_back(0);
                         }
                         *lowest_hist = hist.iter().cloned().min().unwrap();
                     }
@@ -691,27 +4308,53 @@ impl<S: System> EnergyMC<S> {
                 }
                 *total_hist += 1;
                 let histogram = &self.bins.histogram;
-                let max_energy = *min_energy + (hist.len() as f64) * self.bins.width;
-                if hist[i] == *lowest_hist + 1
-                    && hist.len() > 1
+                let max_energy = self.bins.edge(hist.len());
+                let covered = hist.len() > 1
                     && (self.min_allowed_energy.is_none()
                         || self.min_allowed_energy.unwrap() >= *min_energy)
                     && (self.max_allowed_energy.is_none()
-                        || self.max_allowed_energy.unwrap() <= max_energy)
+                        || self.max_allowed_energy.unwrap() <= max_energy);
+                let min_rose = covered
+                    && hist[i] == *lowest_hist + 1
                     && hist
                         .iter()
                         .enumerate()
                         .filter(|(i, _)| histogram[*i] != 0)
                         .map(|(_, &h)| h)
                         .min()
-                        == Some(*lowest_hist + 1)
-                {
-                    *lowest_hist = hist[i];
-                    if (inv_t && *lowest_hist > 0)
-                        || *lowest_hist as f64 >= 0.8 * *total_hist as f64 / num_states
-                    {
+                        == Some(*lowest_hist + 1);
+                // The spread can fall without the minimum rising, so we
+                // check it on its own, once per bin's worth of visits.
+                let check_spread = covered
+                    && matches!(self.wl_flatness, Flatness::RelStdDev(_))
+                    && *total_hist % hist.len() as u64 == 0;
+                if min_rose || check_spread {
+                    if min_rose {
+                        *lowest_hist = hist[i];
+                    }
+                    let flat = match self.wl_flatness {
+                        Flatness::MinOverMean(f) => {
+                            min_rose && *lowest_hist as f64 >= f * *total_hist as f64 / num_states
+                        }
+                        Flatness::RelStdDev(_) => {
+                            let visited: Vec<u64> = hist
+                                .iter()
+                                .enumerate()
+                                .filter(|(i, _)| histogram[*i] != 0)
+                                .map(|(_, &h)| h)
+                                .collect();
+                            self.wl_flatness.is_flat(&visited)
+                        }
+                    };
+                    if (inv_t && min_rose && *lowest_hist > 0) || flat {
                         // gamma_changed = true;
                         *gamma *= 0.5;
+                        log::info!(
+                            "wl: gamma halved to {} after {} moves with min histogram {}",
+                            *gamma,
+                            self.moves,
+                            *lowest_hist
+                        );
                         if *gamma > 1e-16 {
                             println!(
                                 "    WL: ({}) We have reached flatness {:.2} with min {}!",
@@ -740,17 +4383,22 @@ impl<S: System> EnergyMC<S> {
                             if *gamma < min_gamma {
                                 // Switch to a "production" run.
                                 *gamma = 0.0;
-                                println!("      WL: BEGINNING PRODUCTION MODE!");
+                                log::info!("wl: production mode after {} moves", self.moves);
                             }
                         }
                     }
-                    if inv_t && *gamma < (num_states as f64) / (self.moves as f64) {
-                        println!("    1/t-WL:  Switching to 1/t!");
+                    if inv_t && *gamma < (num_states as f64) / (t as f64) {
+                        log::info!("wl: switching to 1/t after {} moves", self.moves);
                         switch_to_samc = Some(num_states as f64);
                     }
                 }
             }
         }
+        if let Method::Samc { t0 } = self.method {
+            if t as f64 > t0 {
+                self.record_lnw_increment(i, gamma);
+            }
+        }
         if let Some(t0) = switch_to_samc {
             self.method = Method::Samc { t0 };
         }
@@ -758,11 +4406,12 @@ impl<S: System> EnergyMC<S> {
         //     self.movies.new_gamma(self.moves, gamma);
         //     self.movies.new_gamma(self.moves, self.gamma());
         // }
+        change
     }
 
     /// Estimate the temperature for a given energy
     pub fn temperature(&self, energy: State) -> Energy {
-        let i = self.state_to_index(energy);
+        let i = self.index(energy);
         let energy = energy.E;
         let lnwi = self.bins.lnw[i];
         let mut Tlo = Energy::new(0.);
@@ -796,6 +4445,11 @@ impl<S: System> EnergyMC<S> {
 }
 
 impl<S: System> EnergyMC<S> {
+    /// The number of moves since the burn-in ended, which is the time
+    /// the weight updates see.  Without a burn-in this is `moves`.
+    fn learning_time(&self) -> u64 {
+        self.moves.saturating_sub(self.burn_in_moves)
+    }
     fn gamma(&self) -> f64 {
         match self.method {
             Method::Canonical {..} => 0.0,
@@ -807,12 +4461,12 @@ impl<S: System> EnergyMC<S> {
                 ..
             } => version.compute_gamma(
                 latest_parameter,
-                self.moves as f64,
+                self.learning_time() as f64,
                 tF as f64,
                 num_states as f64,
             ),
             Method::Samc { t0 } => {
-                let t = self.moves as f64;
+                let t = self.learning_time() as f64;
                 if t > t0 {
                     t0 / t
                 } else {
@@ -824,22 +4478,75 @@ impl<S: System> EnergyMC<S> {
     }
 }
 
-impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo for EnergyMC<S> {
-    type Params = EnergyMCParams;
-    type System = S;
-    fn from_params(params: EnergyMCParams, mut system: S, save_as: ::std::path::PathBuf) -> Self {
-        let ewidth = params
-            .energy_bin
-            .unwrap_or(system.delta_energy().unwrap_or(Energy::new(1.0)));
-        // center zero energy in a bin!
-        let mut rng = crate::rng::MyRng::seed_from_u64(params.seed.unwrap_or(0));
-        // Let's spend a little effort getting an energy that is
-        // within our range of interest.  We are only aiming downward,
-        // because it is unusual that we have trouble getting an
-        // energy that is high enough.
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> EnergyMC<S> {
+    /// Create a simulation, as `MonteCarlo::from_params` does, but
+    /// return an error rather than panicking if the parameters cannot
+    /// work with this system.
+    pub fn try_from_params(
+        params: EnergyMCParams,
+        mut system: S,
+        save_as: ::std::path::PathBuf,
+    ) -> Result<Self, EnergyMcError> {
+        let params = params.with_config_file().map_err(EnergyMcError::InvalidParams)?;
+        params.validate().map_err(EnergyMcError::InvalidParams)?;
+        if params.keep_method && params.config.is_none() {
+            return Err(EnergyMcError::InvalidParams(
+                "keep_method needs a config file or a run to resume".to_string(),
+            ));
+        }
+        let params = if params.sweeps {
+            params.sweeps_to_moves(system.natural_sweep_length())
+        } else {
+            params
+        };
+        match params.check_energy_bin(&system) {
+            Err(e) => return Err(EnergyMcError::InvalidParams(e)),
+            Ok(Some(warning)) => println!("Warning: {}", warning),
+            Ok(None) => (),
+        }
+        let ewidth = params.energy_bin_for(&system);
+        let move_weights = MoveWeights::from_params(&params._move_weights);
+        move_weights
+            .check(&system)
+            .map_err(EnergyMcError::InvalidParams)?;
+        if params.anisotropic_moves && !system.can_move_along() {
+            return Err(EnergyMcError::InvalidParams(
+                "this system cannot move atoms along one axis, so needs isotropic moves"
+                    .to_string(),
+            ));
+        }
+        if params.adapt_bins_after.is_some() && system.delta_energy().is_some() {
+            return Err(EnergyMcError::InvalidParams(
+                "this system has discrete energies, so its bins cannot be adapted".to_string(),
+            ));
+        }
+        if params.atoms_per_move.map(|n| n > 1).unwrap_or(false) && !system.can_move_several() {
+            return Err(EnergyMcError::InvalidParams(
+                "this system can only move one atom at a time, so atoms_per_move must be 1"
+                    .to_string(),
+            ));
+        }
+        let move_distribution = params._move_distribution.unwrap_or_default();
+        // center zero energy in a bin!
+        let seed = crate::rng::seed_or_random(params.seed);
+        if params.seed.is_none() && !params._report.quiet {
+            println!("Using random seed {}", seed);
+        }
+        let mut rng = crate::rng::MyRng::seed_from_u64(seed);
+        let max_iter = params._report.max_iter;
+        let sweep_length = system.natural_sweep_length();
+        let initial_lnw = params.initial_lnw.clone();
+        let collect_transitions = match params._method {
+            MethodParams::Wltm { .. } => true,
+            _ => params.collect_transition_matrix,
+        };
+        // Let's spend a little effort getting an energy that is
+        // within our range of interest.  We are only aiming downward,
+        // because it is unusual that we have trouble getting an
+        // energy that is high enough.
         if let Some(maxe) = params.max_allowed_energy {
             for _ in 0..1e8 as u64 {
-                if let Some(newe) = system.plan_move(&mut rng, 0.05 * units::SIGMA) {
+                if let Some(newe) = system.plan_move(&mut rng, 0.05 * units::SIGMA, move_distribution) {
                     if newe < system.energy() {
                         system.confirm();
                     }
@@ -849,59 +4556,460 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
                 }
             }
         }
+        if let Some(ceiling) = params.energy_ceiling {
+            if system.energy() > ceiling {
+                return Err(EnergyMcError::Energy(EnergyError::AboveCeiling {
+                    energy: system.energy(),
+                    ceiling,
+                }));
+            }
+        }
+        if let (Some(min), Some(max)) = (params.fixed_min_energy, params.fixed_max_energy) {
+            let energy = system.energy();
+            if energy < min || energy > max {
+                return Err(EnergyMcError::Energy(EnergyError::OutsideWindow {
+                    energy,
+                    min,
+                    max,
+                }));
+            }
+        }
         let emin = ((system.energy() / ewidth).value().round() - 0.5) * ewidth;
-        EnergyMC {
+        if let MethodParams::Sad { min_T, .. } = params._method {
+            // The starting energy is a crude but cheap energy scale.
+            let scale = Energy::new(system.energy().value_unsafe.abs());
+            if scale > Energy::new(0.0) && min_T > scale {
+                println!(
+                    "Warning: min_T = {} is above the energy scale {} of this system",
+                    min_T.pretty(),
+                    scale.pretty()
+                );
+            }
+            for T in params._final_report.report_temperatures.iter() {
+                if *T < min_T {
+                    println!(
+                        "Warning: leaving T = {} out of the final report, since it is below min_T = {}",
+                        T.pretty(),
+                        min_T.pretty()
+                    );
+                }
+            }
+        }
+        let fixed_window = match (params.fixed_min_energy, params.fixed_max_energy) {
+            (Some(lo), Some(hi)) => Some((lo, hi)),
+            _ => None,
+        };
+        let translation_scale = match params._moves {
+            Some(MoveParams::TranslationScale(x)) => x,
+            _ => system.natural_move_scale(),
+        };
+        let best = if params.record_best {
+            let copy = system.snapshot().ok_or_else(|| {
+                EnergyMcError::InvalidParams(
+                    "record_best needs a system that can copy its configuration".to_string(),
+                )
+            })?;
+            Some(BestConfiguration {
+                energy: system.energy(),
+                found: 0,
+                system: copy,
+            })
+        } else {
+            None
+        };
+        let mut mc = EnergyMC {
             method: Method::new(
                 params._method,
                 system.energy(),
                 ewidth,
-                params.min_allowed_energy,
-                params.max_allowed_energy,
+                params.min_allowed_energy.or(params.fixed_min_energy),
+                params.max_allowed_energy.or(params.fixed_max_energy),
             ),
+            version: SAVE_FORMAT_VERSION,
             moves: 0,
             time_L: 0,
             accepted_moves: 0,
             acceptance_rate: 0.5, // arbitrary starting guess.
             min_allowed_energy: params.min_allowed_energy,
             max_allowed_energy: params.max_allowed_energy,
+            max_bins: params.max_bins,
+            adapt: AdaptBins::from_params(&params),
+            energy_ceiling: params.energy_ceiling,
+            energy_bounds: system.energy_bounds(),
+            fixed_window: None,
 
+            occupied: Some((0, 0)),
             bins: Bins {
-                histogram: vec![1],
-                t_found: vec![0],
-                lnw: vec![Unitless::new(0.0)],
-                energy_total: vec![system.energy()],
-                energy_squared_total: vec![system.energy() * system.energy()],
+                histogram: VecDeque::from(vec![1]),
+                t_found: VecDeque::from(vec![0]),
+                lnw: VecDeque::from(vec![Unitless::new(0.0)]),
+                energy_total: VecDeque::from(vec![system.energy()]),
+                energy_squared_total: VecDeque::from(vec![system.energy() * system.energy()]),
                 min: emin,
                 width: ewidth,
+                edges: None,
                 extra: std::collections::HashMap::new(),
             },
 
-            have_visited_since_maxentropy: vec![false],
-            round_trips: vec![1],
+            have_visited_since_maxentropy: VecDeque::from(vec![false]),
+            round_trips: VecDeque::from(vec![1]),
+            proposals_at: VecDeque::from(vec![0]),
+            lnw_increment_total: VecDeque::from(vec![0.0]),
+            lnw_increment_squared: VecDeque::from(vec![0.0]),
+            accepts_at: VecDeque::from(vec![0]),
             max_S: Unitless::new(0.),
             max_S_index: 0,
+            extreme_round_trips: RoundTrips::default(),
+            last_gamma: 0.0,
 
-            translation_scale: match params._moves {
-                MoveParams::TranslationScale(x) => x,
-                _ => 0.05 * units::SIGMA,
+            move_distribution,
+            atoms_per_move: params.atoms_per_move,
+            axis_scales: if params.anisotropic_moves {
+                Some(AxisScales::new(translation_scale))
+            } else {
+                None
             },
-            move_plan: params._moves,
+            move_weights,
+            translation_scale,
+            move_plan: params
+                ._moves
+                .unwrap_or_else(|| MoveParams::TranslationScale(system.natural_move_scale())),
             system: system,
 
             rng,
+            seed: Some(seed),
+            energy_blocks: params
+                .energy_block_size
+                .map(BlockAverages::new)
+                .unwrap_or_default(),
+            autocorrelation: params.autocorrelation_lags.map(Autocorrelation::new),
+            moves_between_plugins: params.moves_between_plugins,
+            wl_flatness: params.wl_flatness.unwrap_or_default(),
+            burn_in_moves: params.burn_in_moves.unwrap_or(0),
+            min_visits: params.min_visits.unwrap_or(1),
+            rare_bins: params.rare_bins.unwrap_or_default(),
+            transitions: if collect_transitions {
+                Some(TransitionMatrix::default())
+            } else {
+                None
+            },
+            directions: if params.collect_directions {
+                Some(DirectionCounts::default())
+            } else {
+                None
+            },
+            lnw_decreases: if params.check_lnw_increases {
+                Some(BTreeMap::new())
+            } else {
+                None
+            },
+            non_finite_lnw: params.non_finite_lnw,
+            move_symmetry: None,
+            joint: params.joint_observable.clone().map(|name| {
+                JointHistogram::new(
+                    Interned::from(name),
+                    params.joint_observable_min.unwrap_or(0.0),
+                    params.joint_observable_max.unwrap_or(1.0),
+                    params.joint_observable_bins.unwrap_or(100),
+                )
+            }),
+            best,
             save_as: save_as,
             report: plugin::Report::from(params._report),
             movies: plugin::Movie::from(params._movies),
             save: plugin::Save::from(params._save),
+            energy_check: plugin::EnergyCheck::from(params._energy_check),
+            time_limit: plugin::TimeLimit::from(params._time_limit),
+            sad_trace: plugin::SadTrace::new(params._sad_trace, &params._flush),
+            npy_export: NpyExport::from(params._npy_export),
+            lnw_file: LnwFile::from(params._lnw_file),
+            progress: plugin::Progress::new(params._progress, max_iter),
+            live_plot: plugin::LivePlot::from(params._live_plot),
+            final_report: FinalReport::from(params._final_report),
+            stagnation: Stagnation::from(params._stagnation),
+            exhaustion: Exhaustion::from(params._exhaustion),
+            energy_trace: plugin::EnergyTrace::new(
+                params._energy_trace,
+                &params._flush,
+                sweep_length,
+            ),
+            extra_plugins: plugin::PluginList::default(),
+            acceptance_rule: AcceptanceRule::default(),
             manager: plugin::PluginManager::new(),
+        };
+        if let Some(path) = initial_lnw {
+            let guess: AlignedDos = load_from(&path).map_err(|e| EnergyMcError::InitialLnw {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?;
+            mc.seed_lnw(&guess)
+                .map_err(|reason| EnergyMcError::InitialLnw { path, reason })?;
         }
+        if let Some((lo, hi)) = fixed_window {
+            for &e in &[lo, hi] {
+                mc.prepare_for_state(State { E: e })?;
+            }
+            mc.fixed_window = fixed_window;
+        } else if let (Some(lo), Some(hi)) = mc.energy_bounds {
+            // Make all our bins now rather than as we find energies.  If
+            // max_bins or the energy_ceiling forbid it, we just grow
+            // as usual.
+            for &e in &[lo, hi] {
+                if let Err(e) = mc.prepare_for_state(State { E: e }) {
+                    log::debug!("not making bins out to the energy bounds: {}", e);
+                }
+            }
+        }
+        if let (true, (_, Some(hi))) = (params.too_hi_from_bounds, mc.energy_bounds) {
+            mc.prepare_for_state(State { E: hi })?;
+            let top = mc.index_to_state(mc.index(State { E: hi })).E;
+            if let Method::Sad {
+                min_T,
+                too_lo,
+                ref mut too_hi,
+                ref mut latest_parameter,
+                ..
+            } = mc.method
+            {
+                if top > *too_hi {
+                    *too_hi = top;
+                    *latest_parameter = *((top - too_lo) / min_T).value();
+                }
+            }
+        }
+        if params.check_move_symmetry {
+            // Use a generator of our own, so the run is the same with
+            // or without the check.
+            let mut rng = crate::rng::MyRng::seed_from_u64(seed);
+            mc.move_distribution
+                .check_symmetry(&mut rng, 100_000)
+                .map_err(|e| {
+                    EnergyMcError::InvalidParams(format!("the moves break detailed balance: {}", e))
+                })?;
+            mc.move_symmetry = Some(SymmetryCounts::default());
+        }
+        Ok(mc)
     }
-    fn update_from_params(&mut self, params: Self::Params) {
+    /// Resume the simulation saved in `path`, updating it from
+    /// `params` as when resuming from the command line.
+    pub fn resume(path: &::std::path::Path, params: EnergyMCParams) -> Result<Self, EnergyMcError> {
+        let mut mc: Self = load_from(path).map_err(|error| EnergyMcError::SerializationFailed {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        mc.try_update_from_params(params)?;
+        mc.system.update_caches();
+        Ok(mc)
+    }
+    /// Update a resumed simulation from `params`, as
+    /// `MonteCarlo::update_from_params` does, but return an error
+    /// rather than panicking if they cannot apply.  Asking for a
+    /// different kind of method is an error unless `switch_method` is
+    /// set (or `keep_method`, which ignores the method asked for).
+    pub fn try_update_from_params(&mut self, params: EnergyMCParams) -> Result<(), EnergyMcError> {
+        self.migrate()?;
+        let params = params
+            .with_config_file()
+            .map_err(EnergyMcError::InvalidParams)?;
+        let params = if params.sweeps {
+            params.sweeps_to_moves(self.system.natural_sweep_length())
+        } else {
+            params
+        };
+        let kind = self.method_info().kind;
+        // A 1/t-Wang-Landau run becomes SAMC of its own accord.
+        let switched_itself = kind == MethodKind::Samc
+            && matches!(params._method, MethodParams::Inv_t_WL | MethodParams::inv_t_wl);
+        let other_kind = params._method.kind() != kind && !switched_itself;
+        if other_kind && !params.keep_method && !params.switch_method {
+            return Err(EnergyMcError::InvalidParams(format!(
+                "this run uses {:?}, not {:?}; give switch_method to switch, or keep_method",
+                kind,
+                params._method.kind()
+            )));
+        }
+        if self.bins.edges.is_none() && params.adapt_bins_after.is_some() {
+            if self.system.delta_energy().is_some() {
+                return Err(EnergyMcError::InvalidParams(
+                    "this system has discrete energies, so its bins cannot be adapted".to_string(),
+                ));
+            }
+            self.adapt = AdaptBins::from_params(&params);
+        }
+        if params.anisotropic_moves && self.axis_scales.is_none() {
+            if !self.system.can_move_along() {
+                return Err(EnergyMcError::InvalidParams(
+                    "this system cannot move atoms along one axis, so needs isotropic moves"
+                        .to_string(),
+                ));
+            }
+            self.axis_scales = Some(AxisScales::new(self.translation_scale));
+        }
+        if self.best.is_none() {
+            self.best = load_from(&Self::best_path(&self.save_as)).ok();
+        }
+        if params.record_best && self.best.is_none() {
+            match self.system.snapshot() {
+                Some(system) => {
+                    self.best = Some(BestConfiguration {
+                        energy: self.system.energy(),
+                        found: self.moves,
+                        system,
+                    })
+                }
+                None => println!("Warning: this system cannot copy its configuration for record_best"),
+            }
+        }
+        let max_iter = params._report.max_iter;
         self.report.update_from(params._report);
         self.save.update_from(params._save);
+        self.energy_check.update_from(params._energy_check);
+        self.time_limit.update_from(params._time_limit);
+        self.sad_trace.update_from(params._sad_trace, &params._flush);
+        self.npy_export.update_from(params._npy_export);
+        self.lnw_file.update_from(params._lnw_file);
+        self.progress.update_from(params._progress, max_iter);
+        self.live_plot.update_from(params._live_plot);
+        self.final_report.update_from(params._final_report);
+        self.stagnation.update_from(params._stagnation);
+        self.exhaustion.update_from(params._exhaustion);
+        let sweep_length = self.system.natural_sweep_length();
+        self.energy_trace
+            .update_from(params._energy_trace, &params._flush, sweep_length);
+        let w = &params._move_weights;
+        if w.displacement_weight.is_some() || w.swap_weight.is_some() {
+            let weights = MoveWeights::from_params(w);
+            match weights.check(&self.system) {
+                Ok(()) => self.move_weights = weights,
+                Err(e) => println!("Keeping the old move weights: {}", e),
+            }
+        }
+        if params.moves_between_plugins.is_some() {
+            self.moves_between_plugins = params.moves_between_plugins;
+        }
+        if let Some(flatness) = params.wl_flatness {
+            self.wl_flatness = flatness;
+        }
+        if let Some(min_visits) = params.min_visits {
+            self.min_visits = min_visits;
+        }
+        if let Some(rare_bins) = params.rare_bins {
+            self.rare_bins = rare_bins;
+        }
+        if params.non_finite_lnw.is_some() {
+            self.non_finite_lnw = params.non_finite_lnw;
+        }
+        if params.check_move_symmetry && self.move_symmetry.is_none() {
+            self.move_symmetry = Some(SymmetryCounts::default());
+        }
+        if !params.keep_method {
+            if other_kind {
+                self.switch_method(params._method.clone()).map_err(|e| {
+                    EnergyMcError::InvalidParams(format!("cannot switch from {:?}: {}", kind, e))
+                })?;
+                println!("Switched from {:?} to {:?}", kind, self.method_info().kind);
+            } else if let MethodParams::Sad { min_T, max_T } = params._method {
+                if let Err(e) = self.set_min_T(min_T) {
+                    println!("Keeping the old min_T: {}", e);
+                }
+                if let Err(e) = self.set_max_T(max_T) {
+                    println!("Keeping the old max_T: {}", e);
+                }
+            }
+        }
+        self.energy_bounds = self.system.energy_bounds();
+        Ok(())
     }
-
-    fn move_once(&mut self) {
+    /// Bring a run loaded from an older save file up to date, filling
+    /// in what the file lacks, or fail if the file is newer than we
+    /// understand.  Resuming does this already.
+    pub fn migrate(&mut self) -> Result<(), EnergyMcError> {
+        if self.version > SAVE_FORMAT_VERSION {
+            return Err(EnergyMcError::IncompatibleVersion {
+                found: self.version,
+                supported: SAVE_FORMAT_VERSION,
+            });
+        }
+        if self.occupied.is_none() {
+            // This was saved before we tracked the occupied bins.
+            self.recompute_occupied();
+        }
+        if self.proposals_at.len() != self.bins.histogram.len() {
+            // This was saved before we tracked acceptance by energy.
+            self.proposals_at = vec![0; self.bins.histogram.len()].into();
+            self.accepts_at = vec![0; self.bins.histogram.len()].into();
+        }
+        if self.lnw_increment_total.len() != self.bins.histogram.len() {
+            // This was saved before we tracked the SAMC increments.
+            self.lnw_increment_total = vec![0.0; self.bins.histogram.len()].into();
+            self.lnw_increment_squared = vec![0.0; self.bins.histogram.len()].into();
+        }
+        self.version = SAVE_FORMAT_VERSION;
+        Ok(())
+    }
+    /// Save a checkpoint, as `MonteCarlo::checkpoint` does, but return
+    /// an error rather than panicking if it cannot be written.
+    pub fn try_checkpoint(&self) -> Result<(), EnergyMcError> {
+        save_to(&self.save_as, self).map_err(|error| EnergyMcError::SerializationFailed {
+            path: self.save_as.clone(),
+            error,
+        })?;
+        if let Some(ref best) = self.best {
+            let path = Self::best_path(&self.save_as);
+            save_to(&path, best)
+                .map_err(|error| EnergyMcError::SerializationFailed { path, error })?;
+        }
+        Ok(())
+    }
+    /// The file the lowest energy configuration found is written to,
+    /// in the same format as `save_as`.
+    pub fn best_path(save_as: &std::path::Path) -> std::path::PathBuf {
+        let ext = save_as
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("yaml");
+        save_as.with_extension(format!("best.{}", ext))
+    }
+    /// Copy the configuration, now at energy `e`, if we are recording
+    /// the best and it is lower than any before.
+    fn record_if_best(&mut self, e: Energy) {
+        let lower = self.best.as_ref().map(|b| e < b.energy).unwrap_or(false);
+        if lower {
+            if let Some(system) = self.system.snapshot() {
+                self.best = Some(BestConfiguration {
+                    system,
+                    energy: e,
+                    found: self.moves,
+                });
+            }
+        }
+    }
+    /// Check, when asked to, that the move just made kept detailed
+    /// balance: confirming or rejecting it must leave the system at
+    /// `energy`, and once enough moves have been accepted their
+    /// displacements must be symmetric, since at a steady state each
+    /// is accepted as often as its reverse.
+    fn check_move_balance(&mut self, energy: State) {
+        let actual = self.system.energy();
+        assert!(
+            actual == energy.E,
+            "the moves break detailed balance: after move {} the system is at {} rather than {}",
+            self.moves,
+            actual.pretty(),
+            energy.E.pretty()
+        );
+        if let Some(ref mut symmetry) = self.move_symmetry {
+            if symmetry.samples() >= MOVE_SYMMETRY_SAMPLES {
+                if let Err(e) = symmetry.check() {
+                    panic!("the moves break detailed balance: {}", e);
+                }
+                *symmetry = SymmetryCounts::default();
+            }
+        }
+    }
+    /// Make one move and update our statistics, without running the
+    /// plugins, which `move_once` does after one or more of these.
+    fn step(&mut self) {
         self.moves += 1;
         // self.system.collect_data();
         if self.moves % (self.bins.histogram.len() as u64 * self.bins.histogram.len() as u64 * 1000)
@@ -912,7 +5020,39 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
         let e1 = State::new(&self.system);
         let recent_scale = (1.0 / self.moves as f64).sqrt();
         self.acceptance_rate *= 1. - recent_scale;
-        if let Some(e2) = self.system.plan_move(&mut self.rng, self.translation_scale) {
+        let swap = self.move_weights.choose(&mut self.rng) == MoveType::Swap;
+        // The axis to move along and its scale, if we move along one.
+        let along = match self.axis_scales {
+            Some(ref axes) if !swap => {
+                let axis = axes.choose(&mut self.rng);
+                Some((axis, axes.scales[axis]))
+            }
+            _ => None,
+        };
+        let planned = match (self.atoms_per_move, along) {
+            _ if swap => self.system.swap_species(&mut self.rng),
+            (_, Some((axis, scale))) => {
+                self.system
+                    .plan_move_along(&mut self.rng, axis, scale, self.move_distribution)
+            }
+            (Some(n), None) if n > 1 => self.system.plan_move_n(
+                &mut self.rng,
+                self.translation_scale,
+                self.move_distribution,
+                n,
+            ),
+            _ => self.system.plan_move(
+                &mut self.rng,
+                self.translation_scale,
+                self.move_distribution,
+            ),
+        };
+        let mut accepted = false;
+        let mut considered = false;
+        // The state we end up in, which saves asking the system for
+        // its energy again, as that may be costly.
+        let mut energy = e1;
+        if let Some(e2) = planned {
             let mut out_of_bounds = false;
             if let Some(maxe) = self.max_allowed_energy {
                 out_of_bounds = e2 > maxe && e2 > e1.E;
@@ -920,32 +5060,101 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
             if let Some(mine) = self.min_allowed_energy {
                 out_of_bounds = out_of_bounds || (e2 < mine && e2 < e1.E)
             }
-            if !out_of_bounds {
+            if out_of_bounds {
+                log::trace!("move to {} is out of bounds", e2.pretty());
+            } else {
                 let e2 = State { E: e2 };
-                self.prepare_for_state(e2);
-
-                if !self.reject_move(e1, e2) {
-                    self.accepted_moves += 1;
-                    self.acceptance_rate += recent_scale;
-                    self.system.confirm();
+                if let Err(e) = self.prepare_for_state(e2) {
+                    log::trace!("cannot move to {}: {:?}", e2.E.pretty(), e);
+                } else {
+                    considered = true;
+                    if self.moves <= self.burn_in_moves || !self.reject_move(e1, e2) {
+                        if let Some(ref mut symmetry) = self.move_symmetry {
+                            if let Some(d) = self.system.planned_displacement() {
+                                let scale = along.map_or(self.translation_scale, |(_, s)| s);
+                                symmetry.record(vector3d::Vector3d::new(
+                                    *(d.x / scale).value(),
+                                    *(d.y / scale).value(),
+                                    *(d.z / scale).value(),
+                                ));
+                            }
+                        }
+                        self.accepted_moves += 1;
+                        self.acceptance_rate += recent_scale;
+                        self.system.confirm();
+                        accepted = true;
+                        energy = e2;
+                    }
                 }
             }
         }
-        let energy = State::new(&self.system);
-        let i = self.state_to_index(energy);
+        if !considered {
+            // A move we could never make counts as staying put.
+            self.record_transition(e1, e1);
+        }
+        if !accepted {
+            self.system.reject();
+        }
+        if self.move_symmetry.is_some() {
+            self.check_move_balance(energy);
+        }
+        if let (Some((axis, _)), Some(axes)) = (along, self.axis_scales.as_mut()) {
+            axes.record(axis, accepted);
+        }
+        if accepted {
+            self.record_if_best(energy.E);
+        }
+        let i1 = self.index(e1);
+        self.proposals_at[i1] += 1;
+        if accepted {
+            self.accepts_at[i1] += 1;
+        }
+        if self.moves <= self.burn_in_moves {
+            // During the burn-in we accept every allowed move, as at
+            // infinite temperature, and learn nothing.
+            return;
+        }
+        let i = if accepted { self.index(energy) } else { i1 };
 
         // track the time we found each energy.
         if self.bins.histogram[i] == 0 {
-            self.bins.t_found[i] = self.moves;
+            self.bins.t_found[i] = self.learning_time();
+            self.time_L = self.moves;
+            self.occupied = Some(match self.occupied {
+                Some((lo, hi)) => (lo.min(i), hi.max(i)),
+                None => (i, i),
+            });
+            log::info!(
+                "found new energy {} after {} moves",
+                energy.E.pretty(),
+                self.moves
+            );
         }
         self.bins.histogram[i] += 1;
         self.bins.energy_total[i] += energy.E;
         self.bins.energy_squared_total[i] += energy.E * energy.E;
-        for (k, d) in self.system.data_to_collect(self.moves).into_iter() {
+        let data = self.system.data_to_collect(self.moves);
+        if !data.is_empty() && self.joint.is_some() {
+            let key = self.transition_key(energy);
+            if let Some(ref mut joint) = self.joint {
+                let name = joint.name;
+                for &(_, d) in data.iter().filter(|&&(k, _)| k == name) {
+                    joint.record(key, d);
+                }
+            }
+        }
+        for (k, d) in data.into_iter() {
             self.bins.accumulate_extra(k, i, d);
         }
 
-        self.update_weights(energy);
+        let change = if self.lnw_decreases.is_some() {
+            self.checked_update_weights(energy)
+        } else {
+            self.update_weights(energy)
+        };
+        if let Some(action) = self.non_finite_lnw {
+            self.check_lnw_finite(&change, action);
+        }
 
         if self.bins.lnw[i] > self.max_S {
             self.max_S = self.bins.lnw[i];
@@ -954,7 +5163,7 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
                 *x = true;
             }
         } else if i == self.max_S_index {
-            if self.state_to_index(e1) != i {
+            if i1 != i {
                 for x in self.have_visited_since_maxentropy.iter_mut() {
                     *x = false;
                 }
@@ -964,13 +5173,52 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
             self.round_trips[i] += 1;
         }
 
-        let plugins = [
-            &self.report as &dyn Plugin<Self>,
-            &Logger,
-            &self.movies,
-            &self.save,
-        ];
-        self.manager.run(self, &self.system, &plugins);
+        let range = match self.method {
+            Method::Sad { too_lo, too_hi, .. } => Some((too_lo, too_hi)),
+            _ => None,
+        };
+        self.extreme_round_trips.visit(energy.E, range);
+        if self.directions.is_some() {
+            let key = self.transition_key(energy);
+            let last = self.extreme_round_trips.last;
+            if let Some(ref mut d) = self.directions {
+                d.record(key, last);
+            }
+        }
+        if let Method::Canonical { .. } = self.method {
+            self.energy_blocks.visit(energy.E);
+        }
+        if let Some(ref mut a) = self.autocorrelation {
+            a.visit(energy.E);
+        }
+    }
+}
+
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo for EnergyMC<S> {
+    type Params = EnergyMCParams;
+    type System = S;
+    fn from_params(params: EnergyMCParams, system: S, save_as: ::std::path::PathBuf) -> Self {
+        Self::try_from_params(params, system, save_as).unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn dry_run(params: &EnergyMCParams, system: &S) -> Option<DryRun> {
+        if !params.dry_run {
+            return None;
+        }
+        let estimate = params
+            .clone()
+            .with_config_file()
+            .and_then(|params| params.estimate_memory(system));
+        Some(estimate.map(|estimate| estimate.to_string()))
+    }
+    fn update_from_params(&mut self, params: Self::Params) {
+        self.try_update_from_params(params)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    fn move_once(&mut self) {
+        if self.move_and_run_plugins(1).1 == plugin::Action::Exit {
+            ::std::process::exit(0);
+        }
     }
     fn system(&self) -> &Self::System {
         &self.system
@@ -987,6 +5235,18 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
     fn save_as(&self) -> ::std::path::PathBuf {
         self.save_as.clone()
     }
+    fn important_energy_range(&self) -> Option<(Energy, Energy)> {
+        self.sad_state().map(|sad| (sad.too_lo, sad.too_hi))
+    }
+    fn entropy_curve(&self) -> Vec<(Energy, f64, u64)> {
+        self.normalized_lnw()
+            .into_iter()
+            .zip(self.bins.histogram.iter())
+            .enumerate()
+            .filter(|&(_, (_, &h))| h > 0)
+            .map(|(i, (lnw, &h))| (self.index_to_state(i).E, *lnw.value(), h))
+            .collect()
+    }
 }
 
 fn report_wl_flatness(
@@ -994,7 +5254,7 @@ fn report_wl_flatness(
     highest_hist: u64,
     total_hist: u64,
     num_states: f64,
-    hist: &[u64],
+    hist: &VecDeque<u64>,
     bins: &Bins,
     min_energy: Energy,
 ) {
@@ -1044,10 +5304,10 @@ impl Method {
         match self {
             Method::Canonical {..} => (), // Nothing special to report!
             Method::Sad { too_lo, too_hi, .. } => {
-                let too_lo_count = mc.bins.histogram[mc.bins.state_to_index(State { E: *too_lo })];
-                let too_hi_count = mc.bins.histogram[mc.bins.state_to_index(State { E: *too_hi })];
+                let too_lo_count = mc.bins.histogram[mc.bins.index(State { E: *too_lo })];
+                let too_hi_count = mc.bins.histogram[mc.bins.index(State { E: *too_hi })];
                 print!(
-                    "SAD: {:.5} ({:.3}) -> {:.5} ({:.3})",
+                    "SAD: min important energy {:.5} ({:.3}) -> {:.5} ({:.3})",
                     too_lo.pretty(),
                     too_lo_count,
                     too_hi.pretty(),
@@ -1075,19 +5335,4848 @@ impl Method {
                 );
             }
         }
+        if let Some(tm_lnw) = mc.dos_from_transition_matrix() {
+            let lnw = mc.normalized_lnw();
+            let diffs: Vec<_> = mc
+                .canonical_bins()
+                .into_iter()
+                .filter_map(|i| tm_lnw[i].map(|s| *(lnw[i] - s).value()))
+                .collect();
+            if diffs.len() > 1 {
+                let mean = diffs.iter().sum::<f64>() / diffs.len() as f64;
+                let worst = diffs.iter().fold(0.0, |m: f64, d| m.max((d - mean).abs()));
+                print!(", TM differs from lnw by up to {:.3}", PrettyFloat(worst));
+            }
+        }
         println!(
-            " [gamma = {:.2}]",
-            crate::prettyfloat::PrettyFloat(mc.gamma())
+            " [gamma = {:.2}, {} round trips]",
+            crate::prettyfloat::PrettyFloat(mc.gamma()),
+            mc.num_round_trips()
         );
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Logger;
+/// A copy of the density of states at one moment of a run, e.g. for
+/// live plotting.  The simulation is single-threaded, so a monitoring
+/// thread should be handed snapshots rather than read `EnergyMC`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DosSnapshot {
+    /// The number of moves made when the snapshot was taken.
+    pub moves: u64,
+    /// The lowest energy of the first bin.
+    pub min: Energy,
+    /// The energy bin size.
+    pub width: Energy,
+    /// The ln weight of each bin.
+    pub lnw: Vec<f64>,
+    /// The number of moves spent in each bin.
+    pub histogram: Vec<u64>,
+}
+
+impl DosSnapshot {
+    /// The energy at the center of bin `i`.
+    pub fn energy(&self, i: usize) -> Energy {
+        self.min + (i as f64 + 0.5) * self.width
+    }
+}
+
+/// Just the entropy of a run, as written by [`LnwFile`]: enough to plot
+/// the density of states, and much smaller than a save file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompactDos {
+    /// The lowest energy of the first bin.
+    pub min: Energy,
+    /// The energy bin size.
+    pub width: Energy,
+    /// The ln weight of each bin.
+    pub lnw: Vec<f64>,
+}
+
+impl CompactDos {
+    /// The energy at the center of bin `i`.
+    pub fn energy(&self, i: usize) -> Energy {
+        self.min + (i as f64 + 0.5) * self.width
+    }
+    /// Read the entropy written by [`LnwFile`] to `path`.
+    pub fn load(path: &std::path::Path) -> Result<CompactDos, SaveError> {
+        load_from(path)
+    }
+}
+
+/// The ln weights of several runs, aligned on a common energy grid.
+///
+/// Each run is shifted by its own maximum entropy before averaging,
+/// since `lnw` is only defined up to an additive constant.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlignedDos {
+    /// The lowest energy of the first bin.
+    pub min: Energy,
+    /// The energy bin size.
+    pub width: Energy,
+    /// The mean over runs of lnw in each bin.
+    pub mean_lnw: Vec<f64>,
+    /// The sample standard deviation over runs of lnw in each bin.
+    pub std_lnw: Vec<f64>,
+    /// The standard error of `mean_lnw` in each bin.
+    pub std_error: Vec<f64>,
+    /// The number of runs that visited each bin.
+    pub num_runs: Vec<usize>,
+}
+
+impl AlignedDos {
+    /// The energy at the bottom of bin `i`.
+    pub fn energy(&self, i: usize) -> Energy {
+        self.min + (i as f64) * self.width
+    }
+}
+
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> EnergyMC<S> {
+    /// Add a plugin to be run alongside the built-in ones.  Added
+    /// plugins are not saved, so add them again after resuming.  They
+    /// must be `Send`, so that runs can still be made in parallel.
+    pub fn add_plugin(&mut self, p: Box<dyn Plugin<Self> + Send>) {
+        self.extra_plugins.push(p);
+        // Make sure the new plugin is consulted on the next move.
+        self.manager = plugin::PluginManager::new();
+    }
+    /// Replace the method's rule for accepting moves with `rule`, or
+    /// go back to the method's own rule with `None`.  This is for
+    /// prototyping new flat-histogram methods without changing this
+    /// crate.  The rule is given the current and proposed states, our
+    /// bins (for their `lnw`) and the random number generator, and
+    /// returns whether to accept.  The method still updates `lnw` as
+    /// usual after each move, but skips what it does on accepting a
+    /// move to a new energy, such as SAD counting the states it has
+    /// found.
+    ///
+    /// This is for experts only: nothing checks that the rule obeys
+    /// detailed balance, so the density of states may be silently
+    /// wrong.  Like added plugins, the rule is not saved, so set it
+    /// again after resuming.
+    pub fn set_acceptance_rule(&mut self, rule: Option<Box<AcceptanceFn>>) {
+        self.acceptance_rule = AcceptanceRule(rule);
+    }
+    /// Make exactly `n` more moves and return, even if that takes us
+    /// past `max_iter` or the maximum number of independent samples.
+    /// The other plugins run as usual, so we still log and checkpoint.
+    /// If one of them asks us to stop, e.g. the time limit or a
+    /// shutdown signal, we return early after saving, and give `false`.
+    pub fn run_for(&mut self, n: u64) -> bool {
+        let limited = self.report.without_limits();
+        let limited = std::mem::replace(&mut self.report, limited);
+        let block = self.moves_between_plugins.unwrap_or(1);
+        let mut finished = true;
+        let mut done = 0;
+        while done < n {
+            let (moves, action) = self.move_and_run_plugins(block.min(n - done));
+            done += moves;
+            if action == plugin::Action::Exit {
+                finished = false;
+                break;
+            }
+        }
+        self.report = limited;
+        // The plugin schedule was made without our limits.
+        self.manager = plugin::PluginManager::new();
+        finished
+    }
+    /// Make up to `moves_between_plugins` moves, stopping early if a
+    /// plugin is due, and then run the plugins.  This is cheaper than
+    /// `move_once` for systems with very fast moves, and makes the
+    /// same run.
+    pub fn move_block(&mut self) {
+        let block = self.moves_between_plugins.unwrap_or(1);
+        if self.move_and_run_plugins(block).1 == plugin::Action::Exit {
+            ::std::process::exit(0);
+        }
+    }
+    /// Make up to `max` moves, as for `move_block`, and run the plugins.
+    /// This gives the number of moves made, and `Action::Exit` rather
+    /// than exiting when a plugin asks us to stop.
+    fn move_and_run_plugins(&mut self, max: u64) -> (u64, plugin::Action) {
+        let mut moves = max.min(self.manager.moves_until_due()).max(1);
+        if let Some(adapt) = self.adapt {
+            moves = moves.min(adapt.after.saturating_sub(self.moves).max(1));
+        }
+        for _ in 0..moves {
+            self.step();
+        }
+        if let Some(adapt) = self.adapt {
+            if self.moves >= adapt.after {
+                self.adapt = None;
+                match self.adapt_bins(adapt.split, adapt.merge) {
+                    Ok(()) => println!("Adapted the energy bins, now {}", self.bins.lnw.len()),
+                    Err(e) => println!("Warning: not adapting the energy bins: {}", e),
+                }
+            }
+        }
+
+        let plugins = [
+            &self.report as &dyn Plugin<Self>,
+            &Logger,
+            &self.movies,
+            &self.save,
+            &self.energy_check,
+            &self.time_limit,
+            &self.sad_trace,
+            &self.npy_export,
+            &self.lnw_file,
+            &self.progress,
+            &self.live_plot,
+            &self.final_report,
+            &self.stagnation,
+            &self.exhaustion,
+            &self.energy_trace,
+        ];
+        let action = self.manager.run_or_stop(
+            moves,
+            self,
+            &self.system,
+            &plugins,
+            self.extra_plugins.plugins(),
+        );
+        (moves, action)
+    }
+    /// Check our canonical mean energy at each of `temperatures` with
+    /// a Metropolis run from a copy of our current configuration, of
+    /// `moves` moves after a tenth as many to equilibrate.  The runs
+    /// are seeded from our seed, and saved next to us with
+    /// `-metropolis-i` added to the name.  As in the final report, for
+    /// SAD we leave out temperatures below `min_T`.
+    pub fn validate_with_metropolis(
+        &self,
+        temperatures: &[Energy],
+        moves: u64,
+    ) -> Result<Vec<ValidationRow>, String> {
+        use super::metropolis::{Metropolis, MetropolisParams};
+        let stem = self
+            .save_as
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("energy");
+        let ext = self.save_as.extension().and_then(|s| s.to_str()).unwrap_or("yaml");
+        FinalReport::canonical_table(self, temperatures)
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let system = self.system.snapshot().ok_or_else(|| {
+                    "Metropolis needs a system that can copy its configuration".to_string()
+                })?;
+                let params = MetropolisParams {
+                    T: row.T * units::EPSILON,
+                    seed: Some(self.seed.unwrap_or(0).wrapping_add(i as u64 + 1)),
+                    translation_scale: Some(self.translation_scale),
+                    ..MetropolisParams::default()
+                };
+                let save_as = self
+                    .save_as
+                    .with_file_name(format!("{}-metropolis-{}.{}", stem, i, ext));
+                let mut mc = Metropolis::from_params(params, system, save_as);
+                for _ in 0..moves / 10 {
+                    mc.move_once();
+                }
+                let mut blocks = BlockAverages::new((moves / VALIDATION_BLOCKS).max(1));
+                for _ in 0..moves {
+                    mc.move_once();
+                    blocks.visit(mc.system.energy());
+                }
+                match (blocks.mean(), blocks.standard_error()) {
+                    (Some(mean), Some(error)) => Ok(ValidationRow {
+                        T: row.T,
+                        E: row.E,
+                        metropolis_E: *(mean / units::EPSILON).value(),
+                        error: *(error / units::EPSILON).value(),
+                    }),
+                    _ => Err(format!("{} moves are too few to estimate an error", moves)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl<S> EnergyMC<S> {
+    /// The lowest energy configuration found, if we are recording it
+    /// with `record_best`.
+    pub fn best_configuration(&self) -> Option<&BestConfiguration<S>> {
+        self.best.as_ref()
+    }
+    /// Copy the current `lnw` and histogram, which can then be sent to
+    /// another thread or saved while the run continues.  This is an
+    /// error if the bins have been adapted, since a snapshot has
+    /// uniform bins.
+    pub fn snapshot(&self) -> Result<DosSnapshot, String> {
+        if self.bins.edges.is_some() {
+            return Err("a snapshot needs uniform bins, but these have been adapted".to_string());
+        }
+        Ok(DosSnapshot {
+            moves: self.moves,
+            min: self.bins.min,
+            width: self.bins.width,
+            lnw: self.bins.lnw.iter().map(|l| *l.value()).collect(),
+            histogram: self.bins.histogram.iter().cloned().collect(),
+        })
+    }
+    /// Copy just the current `lnw` and its energy bins.  This is an
+    /// error if the bins have been adapted, as for
+    /// [`EnergyMC::snapshot`].
+    pub fn compact_dos(&self) -> Result<CompactDos, String> {
+        if self.bins.edges.is_some() {
+            return Err(
+                "a compact DOS needs uniform bins, but these have been adapted".to_string(),
+            );
+        }
+        Ok(CompactDos {
+            min: self.bins.min,
+            width: self.bins.width,
+            lnw: self.bins.lnw.iter().map(|l| *l.value()).collect(),
+        })
+    }
+    /// Align the `lnw` of several runs on a common energy grid and
+    /// average them.  Bins that a run never visited do not contribute
+    /// to that bin's statistics.  Panics if the runs do not share a bin
+    /// width, or if their grids are offset by a fraction of a bin.
+    pub fn merge(runs: &[EnergyMC<S>]) -> AlignedDos {
+        assert!(runs.len() > 0, "cannot merge an empty set of runs");
+        let width = runs[0].bins.width;
+        let mut min = runs[0].bins.min;
+        let mut max = runs[0].bins.max_energy();
+        for mc in runs.iter() {
+            assert_eq!(mc.bins.width, width, "runs must share an energy bin");
+            assert!(mc.bins.edges.is_none(), "runs must have uniform bins");
+            if mc.bins.min < min {
+                min = mc.bins.min;
+            }
+            if mc.bins.max_energy() > max {
+                max = mc.bins.max_energy();
+            }
+        }
+        let num_bins = (*((max - min) / width).value()).round() as usize;
+        let mut total = vec![0.0; num_bins];
+        let mut total_squared = vec![0.0; num_bins];
+        let mut num_runs = vec![0; num_bins];
+        for mc in runs.iter() {
+            let offset = *((mc.bins.min - min) / width).value();
+            assert!(
+                (offset - offset.round()).abs() < 1e-6,
+                "runs have misaligned energy bins"
+            );
+            let offset = offset.round() as usize;
+            for (i, (lnw, &h)) in mc.bins.lnw.iter().zip(mc.bins.histogram.iter()).enumerate() {
+                if h > 0 {
+                    let x = *(*lnw - mc.max_S).value();
+                    total[i + offset] += x;
+                    total_squared[i + offset] += x * x;
+                    num_runs[i + offset] += 1;
+                }
+            }
+        }
+        let mut mean_lnw = vec![0.0; num_bins];
+        let mut std_lnw = vec![0.0; num_bins];
+        let mut std_error = vec![0.0; num_bins];
+        for i in 0..num_bins {
+            if num_runs[i] > 0 {
+                let n = num_runs[i] as f64;
+                mean_lnw[i] = total[i] / n;
+                if num_runs[i] > 1 {
+                    let variance = (total_squared[i] - n * mean_lnw[i] * mean_lnw[i]) / (n - 1.0);
+                    std_lnw[i] = variance.max(0.0).sqrt();
+                    std_error[i] = std_lnw[i] / n.sqrt();
+                }
+            }
+        }
+        AlignedDos {
+            min,
+            width,
+            mean_lnw,
+            std_lnw,
+            std_error,
+            num_runs,
+        }
+    }
+}
+
+/// The parameters for exporting the histogram and `lnw` for NumPy.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct NpyExportParams {
+    /// Write `-energy.npy`, `-histogram.npy` and `-lnw.npy` with this
+    /// prefix whenever we save.
+    pub npy_prefix: Option<::std::path::PathBuf>,
+    /// Also write `-states.npy`, the number of states in each bin (see
+    /// `EnergyMC::state_counts`).
+    pub npy_state_counts: bool,
+}
+
+/// A plugin that writes the bin energies (in units of epsilon), the
+/// histogram and `lnw` as `.npy` files whenever we save, including at
+/// the end of the run.  The `lnw` of bins below `min_visits` is NaN,
+/// or if they are only flagged, `-rare.npy` is one for each of them.
+/// For SAMC in the 1/t regime it also writes the estimated error in
+/// `lnw` as `-lnw-error.npy`, with NaN where there is no estimate.  If
+/// asked, it writes the number of states in each bin as
+/// `-states.npy`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NpyExport {
+    prefix: Option<::std::path::PathBuf>,
+    #[serde(default)]
+    state_counts: bool,
+    #[serde(skip)]
+    warned_about_total: std::cell::Cell<bool>,
+}
+
+impl From<NpyExportParams> for NpyExport {
+    fn from(params: NpyExportParams) -> Self {
+        NpyExport {
+            prefix: params.npy_prefix,
+            state_counts: params.npy_state_counts,
+            warned_about_total: std::cell::Cell::new(false),
+        }
+    }
+}
+impl NpyExport {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: NpyExportParams) {
+        *self = Self::from(params);
+    }
+    /// The file holding the array called `name`.
+    pub fn path(prefix: &std::path::Path, name: &str) -> std::path::PathBuf {
+        let mut p = prefix.as_os_str().to_owned();
+        p.push(format!("-{}.npy", name));
+        p.into()
+    }
+    fn export<S: System>(&self, mc: &EnergyMC<S>) -> std::io::Result<()> {
+        if let Some(ref prefix) = self.prefix {
+            let energy: Vec<f64> = (0..mc.bins.lnw.len())
+                .map(|i| *(mc.bins.index_to_state(i).E / units::EPSILON).value())
+                .collect();
+            let histogram: Vec<u64> = mc.bins.histogram.iter().cloned().collect();
+            let lnw: Vec<f64> = mc
+                .bins
+                .lnw
+                .iter()
+                .enumerate()
+                .map(|(i, l)| match mc.rare_bins {
+                    RareBins::Exclude if mc.is_rare(i) => std::f64::NAN,
+                    _ => *l.value(),
+                })
+                .collect();
+            crate::npy::write(NpyExport::path(prefix, "energy"), &energy)?;
+            crate::npy::write(NpyExport::path(prefix, "histogram"), &histogram)?;
+            crate::npy::write(NpyExport::path(prefix, "lnw"), &lnw)?;
+            if mc.rare_bins == RareBins::Flag {
+                let rare: Vec<u64> = (0..histogram.len()).map(|i| mc.is_rare(i) as u64).collect();
+                crate::npy::write(NpyExport::path(prefix, "rare"), &rare)?;
+            }
+            if mc.lnw_increment_total.iter().any(|&t| t > 0.0) {
+                let errors: Vec<f64> = mc
+                    .lnw_error()
+                    .into_iter()
+                    .map(|e| e.unwrap_or(std::f64::NAN))
+                    .collect();
+                crate::npy::write(NpyExport::path(prefix, "lnw-error"), &errors)?;
+            }
+            if self.state_counts {
+                if mc.ln_total_states().is_none() && !self.warned_about_total.get() {
+                    println!(
+                        "Warning: the total number of states is unknown, so -states.npy \
+                         has one state in the lowest energy bin"
+                    );
+                    self.warned_about_total.set(true);
+                }
+                crate::npy::write(NpyExport::path(prefix, "states"), &mc.state_counts())?;
+            }
+        }
+        Ok(())
+    }
+}
 impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
-    for Logger
+    for NpyExport
 {
-    fn log(&self, mc: &EnergyMC<S>, _sys: &S) {
-        mc.method.report(&mc);
+    fn save(&self, mc: &EnergyMC<S>, _sys: &S) {
+        self.export(mc).expect("error writing npy files");
+    }
+}
+
+/// The parameters for writing `lnw` alone to a small binary file.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct LnwFileParams {
+    /// Write the energy bins and `lnw` to a small binary file next to
+    /// the save file this often (in moves), for plotting while the run
+    /// continues.  Read it with `CompactDos::load`.
+    pub lnw_file_interval: Option<u64>,
+}
+
+/// A plugin that periodically overwrites a `.lnw.bin` file next to the
+/// save file with the [`CompactDos`] of the run, and again whenever we
+/// save.  This is cheap enough to do far more often than saving the
+/// whole run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LnwFile {
+    #[serde(skip, default)]
+    next_output: std::cell::Cell<u64>,
+    /// How many moves between writes.
+    interval: Option<u64>,
+}
+
+impl From<LnwFileParams> for LnwFile {
+    fn from(params: LnwFileParams) -> Self {
+        LnwFile {
+            next_output: std::cell::Cell::new(params.lnw_file_interval.unwrap_or(0)),
+            interval: params.lnw_file_interval,
+        }
+    }
+}
+impl LnwFile {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: LnwFileParams) {
+        *self = Self::from(params);
     }
+    /// The file the entropy is written to.
+    pub fn path(save_as: &std::path::Path) -> std::path::PathBuf {
+        save_as.with_extension("lnw.bin")
+    }
+    fn write<S>(&self, mc: &EnergyMC<S>) {
+        let path = LnwFile::path(&mc.save_as);
+        match mc.compact_dos() {
+            Ok(dos) => {
+                save_to(&path, &dos).unwrap_or_else(|e| panic!("error writing {:?}: {}", path, e))
+            }
+            Err(e) => println!("Warning: not writing {:?}: {}", path, e),
+        }
+    }
+}
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
+    for LnwFile
+{
+    fn run(&self, mc: &EnergyMC<S>, _sys: &S) -> plugin::Action {
+        if let Some(interval) = self.interval {
+            if mc.moves >= self.next_output.get() {
+                self.next_output.set(mc.moves + interval);
+                self.write(mc);
+            }
+        }
+        plugin::Action::None
+    }
+    fn run_period(&self) -> plugin::TimeToRun {
+        match self.interval {
+            Some(_) => plugin::TimeToRun::TotalMoves(self.next_output.get()),
+            None => plugin::TimeToRun::Never,
+        }
+    }
+    fn save(&self, mc: &EnergyMC<S>, _sys: &S) {
+        if self.interval.is_some() {
+            self.write(mc);
+        }
+    }
+}
+
+/// How to print the final report.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Text meant for people to read
+    Human,
+    /// One `key=value` pair per line
+    KeyValue,
+    /// A single JSON object
+    Json,
+}
+
+/// The parameters for the report printed when the run finishes.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct FinalReportParams {
+    /// Print a summary in this format when the run finishes.
+    pub final_report: Option<ReportFormat>,
+    /// How much to put in the final report: 1 gives the moves and
+    /// acceptance rate, 2 or more also the bins, max_S and SAD range.
+    pub final_report_verbosity: Option<u8>,
+    /// Add a table of canonical properties at these temperatures to
+    /// the final report.  For SAD, temperatures below `min_T` are left
+    /// out, since the entropy is not converged where they matter.
+    pub report_temperatures: Vec<Energy>,
+    /// When the run finishes, check the canonical mean energy at each
+    /// of `report_temperatures` with a Metropolis run of this many
+    /// moves, and report how far apart they are.
+    pub validation_moves: Option<u64>,
+}
+
+/// Canonical properties at one temperature, found by reweighting
+/// `lnw`.  Energies are in units of epsilon, and the free energy and
+/// entropy are relative to the lowest energy we have visited, as in
+/// [`EnergyMC::normalized_lnw`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CanonicalRow {
+    /// The temperature.
+    pub T: f64,
+    /// The Helmholtz free energy.
+    pub F: f64,
+    /// The mean energy.
+    pub E: f64,
+    /// The heat capacity, in units of the Boltzmann constant.
+    pub C_v: f64,
+    /// The entropy, in units of the Boltzmann constant.
+    pub S: f64,
+}
+
+/// The mean energy at one temperature found by reweighting `lnw`, and
+/// as measured by a Metropolis run there, from
+/// [`EnergyMC::validate_with_metropolis`].  Energies are in units of
+/// epsilon.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ValidationRow {
+    /// The temperature.
+    pub T: f64,
+    /// The mean energy from reweighting `lnw`.
+    pub E: f64,
+    /// The mean energy measured by Metropolis.
+    pub metropolis_E: f64,
+    /// The standard error of `metropolis_E`, from block averages.
+    pub error: f64,
+}
+
+impl ValidationRow {
+    /// How far the Metropolis mean energy is from ours, in standard
+    /// errors.
+    pub fn discrepancy(&self) -> f64 {
+        (self.metropolis_E - self.E) / self.error
+    }
+}
+
+/// The state of a run, as data rather than printed, from
+/// [`EnergyMC::summary`].  [`FinalReport`] formats this.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    /// The number of moves made.
+    pub moves: u64,
+    /// The number of moves that were rejected, or never made because
+    /// they would leave the allowed energies.
+    pub rejected_moves: u64,
+    /// The fraction of moves that were accepted.
+    pub acceptance_rate: f64,
+    /// The method and its state.
+    pub method: MethodInfo,
+    /// The energies SAD considers important, if that is the method.
+    pub important_range: Option<(Energy, Energy)>,
+    /// The maximum of `lnw`.
+    pub max_S: f64,
+    /// The round trips between the extremes of the important range,
+    /// as counted by [`EnergyMC::num_round_trips`].
+    pub round_trips: u64,
+    /// The number of energy bins.
+    pub num_bins: usize,
+    /// The number of energy bins that have been visited.
+    pub occupied_bins: usize,
+    /// The number of unvisited bins between visited ones.
+    pub holes: usize,
+}
+
+/// The numbers in a final report.  Energies are in units of epsilon,
+/// and fields that are `None` were either not asked for or are not
+/// available for this method.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FinalSummary {
+    /// The number of moves made.
+    pub moves: u64,
+    /// The fraction of moves that were accepted.
+    pub acceptance_rate: f64,
+    /// The number of energy bins.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_bins: Option<usize>,
+    /// The maximum of `lnw`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_S: Option<f64>,
+    /// The lowest energy SAD considers important.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub too_lo: Option<f64>,
+    /// The highest energy SAD considers important.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub too_hi: Option<f64>,
+    /// The round trips between the extremes of the important range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub round_trips: Option<u64>,
+    /// The number of energy bins that have been visited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occupied_bins: Option<usize>,
+    /// The number of unvisited bins between visited ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holes: Option<usize>,
+    /// The width `too_hi - too_lo` of SAD's important range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<f64>,
+    /// The estimated error in each `lnw`, from [`EnergyMC::lnw_error`],
+    /// if any have been estimated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lnw_error: Option<Vec<Option<f64>>>,
+    /// Canonical properties at the requested temperatures.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical: Option<Vec<CanonicalRow>>,
+    /// The integrated autocorrelation time of the energy in moves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autocorrelation_time: Option<f64>,
+    /// The energies where `lnw` fell during an update, with the
+    /// largest fall at each, if we checked with `check_lnw_increases`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lnw_decreases: Option<Vec<(f64, f64)>>,
+    /// The energy of the lowest energy configuration found, if we
+    /// recorded it with `record_best`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_energy: Option<f64>,
+    /// The move at which that configuration was found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_found: Option<u64>,
+    /// The mean energies from Metropolis runs at the requested
+    /// temperatures, if we asked for them with `validation_moves`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation: Option<Vec<ValidationRow>>,
+}
+
+impl FinalSummary {
+    fn key_values(&self) -> Vec<(&'static str, String)> {
+        let mut kv = vec![
+            ("moves", self.moves.to_string()),
+            ("acceptance_rate", self.acceptance_rate.to_string()),
+        ];
+        if let Some(n) = self.num_bins {
+            kv.push(("num_bins", n.to_string()));
+        }
+        if let Some(s) = self.max_S {
+            kv.push(("max_S", s.to_string()));
+        }
+        if let Some(e) = self.too_lo {
+            kv.push(("too_lo", e.to_string()));
+        }
+        if let Some(e) = self.too_hi {
+            kv.push(("too_hi", e.to_string()));
+        }
+        if let Some(n) = self.round_trips {
+            kv.push(("round_trips", n.to_string()));
+        }
+        if let Some(n) = self.occupied_bins {
+            kv.push(("occupied_bins", n.to_string()));
+        }
+        if let Some(n) = self.holes {
+            kv.push(("holes", n.to_string()));
+        }
+        if let Some(e) = self.span {
+            kv.push(("span", e.to_string()));
+        }
+        if let Some(ref errors) = self.lnw_error {
+            let errors: Vec<String> = errors
+                .iter()
+                .map(|e| e.map(|e| e.to_string()).unwrap_or_default())
+                .collect();
+            kv.push(("lnw_error", errors.join(",")));
+        }
+        if let Some(ref rows) = self.canonical {
+            let column = |f: fn(&CanonicalRow) -> f64| {
+                rows.iter().map(|r| f(r).to_string()).collect::<Vec<_>>().join(",")
+            };
+            kv.push(("canonical_T", column(|r| r.T)));
+            kv.push(("canonical_F", column(|r| r.F)));
+            kv.push(("canonical_E", column(|r| r.E)));
+            kv.push(("canonical_C_v", column(|r| r.C_v)));
+            kv.push(("canonical_S", column(|r| r.S)));
+        }
+        if let Some(tau) = self.autocorrelation_time {
+            kv.push(("autocorrelation_time", tau.to_string()));
+        }
+        if let Some(ref falls) = self.lnw_decreases {
+            let falls: Vec<String> = falls.iter().map(|(e, f)| format!("{}:{}", e, f)).collect();
+            kv.push(("lnw_decreases", falls.join(",")));
+        }
+        if let (Some(e), Some(found)) = (self.best_energy, self.best_found) {
+            kv.push(("best_energy", e.to_string()));
+            kv.push(("best_found", found.to_string()));
+        }
+        if let Some(ref rows) = self.validation {
+            let column = |f: fn(&ValidationRow) -> f64| {
+                rows.iter().map(|r| f(r).to_string()).collect::<Vec<_>>().join(",")
+            };
+            kv.push(("validation_T", column(|r| r.T)));
+            kv.push(("validation_E", column(|r| r.E)));
+            kv.push(("validation_metropolis_E", column(|r| r.metropolis_E)));
+            kv.push(("validation_error", column(|r| r.error)));
+        }
+        kv
+    }
+    /// Format the summary for printing.
+    pub fn format(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Human => {
+                let mut s = format!(
+                    "Finished after {:.3} moves, {:.2}% accepted",
+                    PrettyFloat(self.moves as f64),
+                    PrettyFloat(100.0 * self.acceptance_rate)
+                );
+                if let Some(n) = self.num_bins {
+                    s.push_str(&format!("\n    {} energy bins", n));
+                }
+                if let Some(max_S) = self.max_S {
+                    s.push_str(&format!("\n    max_S = {:.5}", PrettyFloat(max_S)));
+                }
+                if let (Some(lo), Some(hi)) = (self.too_lo, self.too_hi) {
+                    s.push_str(&format!(
+                        "\n    SAD important energies {:.5} to {:.5}",
+                        PrettyFloat(lo),
+                        PrettyFloat(hi)
+                    ));
+                }
+                if let Some(n) = self.round_trips {
+                    s.push_str(&format!("\n    {} round trips", n));
+                }
+                if let (Some(n), Some(holes)) = (self.occupied_bins, self.holes) {
+                    s.push_str(&format!("\n    {} energies found, {} holes", n, holes));
+                }
+                if let Some(span) = self.span {
+                    s.push_str(&format!("\n    important span {:.5}", PrettyFloat(span)));
+                }
+                if let Some(ref errors) = self.lnw_error {
+                    let largest = errors.iter().flatten().cloned().fold(0.0, f64::max);
+                    s.push_str(&format!("\n    largest lnw error {:.3}", PrettyFloat(largest)));
+                }
+                if let Some(ref rows) = self.canonical {
+                    s.push_str(&format!(
+                        "\n    {:>10} {:>10} {:>10} {:>10} {:>10}",
+                        "T", "F", "<E>", "C_v", "S"
+                    ));
+                    for r in rows {
+                        s.push_str(&format!(
+                            "\n    {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                            r.T, r.F, r.E, r.C_v, r.S
+                        ));
+                    }
+                }
+                if let Some(tau) = self.autocorrelation_time {
+                    s.push_str(&format!(
+                        "\n    energy autocorrelation time {:.3} moves",
+                        PrettyFloat(tau)
+                    ));
+                }
+                if let Some(ref falls) = self.lnw_decreases {
+                    if falls.is_empty() {
+                        s.push_str("\n    lnw never fell");
+                    } else {
+                        let largest = falls.iter().map(|&(_, f)| f).fold(0.0, f64::max);
+                        s.push_str(&format!(
+                            "\n    lnw fell at {} energies, by up to {:.3}",
+                            falls.len(),
+                            PrettyFloat(largest)
+                        ));
+                    }
+                }
+                if let (Some(e), Some(found)) = (self.best_energy, self.best_found) {
+                    s.push_str(&format!(
+                        "\n    lowest energy configuration {:.5} after {:.3} moves",
+                        PrettyFloat(e),
+                        PrettyFloat(found as f64)
+                    ));
+                }
+                if let Some(ref rows) = self.validation {
+                    s.push_str(&format!(
+                        "\n    {:>10} {:>10} {:>10} {:>10} {:>10}",
+                        "T", "<E>", "Metropolis", "error", "sigmas"
+                    ));
+                    for r in rows {
+                        s.push_str(&format!(
+                            "\n    {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.2}",
+                            r.T,
+                            r.E,
+                            r.metropolis_E,
+                            r.error,
+                            r.discrepancy()
+                        ));
+                    }
+                }
+                s
+            }
+            ReportFormat::KeyValue => self
+                .key_values()
+                .into_iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ReportFormat::Json => {
+                serde_json::to_string(self).expect("error formatting final report")
+            }
+        }
+    }
+}
+
+/// A plugin that prints a summary of the run when it finishes, either
+/// because it has made all its moves or because it was asked to stop.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FinalReport {
+    format: Option<ReportFormat>,
+    verbosity: u8,
+    #[serde(default)]
+    temperatures: Vec<Energy>,
+    #[serde(default)]
+    validation_moves: Option<u64>,
+}
+
+impl From<FinalReportParams> for FinalReport {
+    fn from(params: FinalReportParams) -> Self {
+        FinalReport {
+            format: params.final_report,
+            verbosity: params.final_report_verbosity.unwrap_or(1),
+            temperatures: params.report_temperatures,
+            validation_moves: params.validation_moves,
+        }
+    }
+}
+impl FinalReport {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: FinalReportParams) {
+        *self = Self::from(params);
+    }
+    /// The summary of `mc` at our verbosity.
+    pub fn summary<S: System>(&self, mc: &EnergyMC<S>) -> FinalSummary {
+        let run = mc.summary();
+        let verbose = self.verbosity >= 2;
+        let range = if verbose { run.important_range } else { None };
+        let in_epsilon = |e: Energy| *(e / units::EPSILON).value();
+        let errors = mc.lnw_error();
+        FinalSummary {
+            moves: run.moves,
+            acceptance_rate: run.acceptance_rate,
+            num_bins: if verbose { Some(run.num_bins) } else { None },
+            max_S: if verbose { Some(run.max_S) } else { None },
+            too_lo: range.map(|(lo, _)| in_epsilon(lo)),
+            too_hi: range.map(|(_, hi)| in_epsilon(hi)),
+            round_trips: if verbose { Some(run.round_trips) } else { None },
+            occupied_bins: if verbose { Some(run.occupied_bins) } else { None },
+            holes: if verbose { Some(run.holes) } else { None },
+            span: range.map(|(lo, hi)| in_epsilon(hi - lo)),
+            lnw_error: if verbose && errors.iter().any(|e| e.is_some()) {
+                Some(errors)
+            } else {
+                None
+            },
+            canonical: if self.temperatures.is_empty() {
+                None
+            } else {
+                Some(FinalReport::canonical_table(mc, &self.temperatures))
+            },
+            autocorrelation_time: mc.energy_autocorrelation_time(),
+            lnw_decreases: mc.lnw_decreases().map(|falls| {
+                falls
+                    .into_iter()
+                    .map(|(e, f)| (*(e / units::EPSILON).value(), f))
+                    .collect()
+            }),
+            best_energy: mc.best.as_ref().map(|b| in_epsilon(b.energy)),
+            best_found: mc.best.as_ref().map(|b| b.found),
+            validation: None,
+        }
+    }
+    /// The canonical properties of `mc` at each of `temperatures` that
+    /// we can trust, i.e. for SAD those at or above `min_T`.
+    fn canonical_table<S: System>(mc: &EnergyMC<S>, temperatures: &[Energy]) -> Vec<CanonicalRow> {
+        let min_T = match mc.method {
+            Method::Sad { min_T, .. } => Some(min_T),
+            _ => None,
+        };
+        let zero = mc
+            .bins
+            .histogram
+            .iter()
+            .position(|&h| h > 0)
+            .map(|i| *mc.bins.lnw[i].value())
+            .unwrap_or(0.0);
+        temperatures
+            .iter()
+            .filter(|&&T| match min_T {
+                Some(min_T) => T >= min_T,
+                None => true,
+            })
+            .map(|&T| {
+                let (E, variance) = mc.canonical_moments(T, 1);
+                let F = mc.free_energy(T);
+                let S = (E - F) / T;
+                CanonicalRow {
+                    T: *(T / units::EPSILON).value(),
+                    F: *((F + T * zero) / units::EPSILON).value(),
+                    E: *(E / units::EPSILON).value(),
+                    C_v: *(variance / (T * T)).value(),
+                    S: *S.value() - zero,
+                }
+            })
+            .collect()
+    }
+}
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
+    for FinalReport
+{
+    fn save(&self, mc: &EnergyMC<S>, _sys: &S) {
+        if let Some(format) = self.format {
+            if mc.is_finishing() {
+                mc.flush_files();
+                let mut summary = self.summary(mc);
+                if let Some(moves) = self.validation_moves {
+                    match mc.validate_with_metropolis(&self.temperatures, moves) {
+                        Ok(rows) => summary.validation = Some(rows),
+                        Err(e) => println!("Warning: cannot validate with Metropolis: {}", e),
+                    }
+                }
+                println!("{}", summary.format(format));
+            }
+        }
+    }
+}
+
+/// Where the entropies of two runs disagree, found from their
+/// [`DosSnapshot`]s by [`DosDiff::between`] or
+/// [`EnergyMC::diff_dos`].  Energies are bin centers in units of
+/// epsilon.  Each run's `lnw` is only known up to a constant, so both
+/// are shifted to zero at the lowest energy that both runs visited,
+/// and only energies that both runs visited are compared.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DosDiff {
+    /// The largest absolute difference in `lnw`.
+    pub max_abs: f64,
+    /// The root mean square difference in `lnw`.
+    pub rms: f64,
+    /// The energy with the largest difference.
+    pub worst_energy: f64,
+    /// How many bins were compared.
+    pub compared: usize,
+    /// The visited energies that lie outside the range visited by the
+    /// second run, as (lowest, highest) ranges.
+    pub only_first: Vec<(f64, f64)>,
+    /// The visited energies that lie outside the range visited by the
+    /// first run.
+    pub only_second: Vec<(f64, f64)>,
+}
+
+impl DosDiff {
+    /// Compare the entropies in two snapshots, which must share a bin
+    /// width and have grids that line up, but may cover different
+    /// energies.
+    pub fn between(first: &DosSnapshot, second: &DosSnapshot) -> Result<DosDiff, String> {
+        if first.width != second.width {
+            return Err(format!(
+                "the bin widths differ: {} and {}",
+                first.width.pretty(),
+                second.width.pretty()
+            ));
+        }
+        let width = first.width;
+        let offset = *((second.min - first.min) / width).value();
+        if (offset - offset.round()).abs() > 1e-6 {
+            return Err(format!(
+                "the energy bins are misaligned by {:.3} bins",
+                offset - offset.round()
+            ));
+        }
+        // We index both grids by the bin number in the first.
+        let offset = offset.round() as i64;
+        let get = |k: i64, dos: &DosSnapshot, start: i64| {
+            let i = (k - start) as usize;
+            if k < start || i >= dos.lnw.len() || dos.histogram[i] == 0 {
+                None
+            } else {
+                Some(dos.lnw[i])
+            }
+        };
+        let first_at = |k: i64| get(k, first, 0);
+        let second_at = |k: i64| get(k, second, offset);
+        let visited = |dos: &DosSnapshot, start: i64| {
+            let lo = dos.histogram.iter().position(|&h| h > 0)?;
+            let hi = dos.histogram.iter().rposition(|&h| h > 0)?;
+            Some((lo as i64 + start, hi as i64 + start))
+        };
+        let (first_lo, first_hi) = visited(first, 0).ok_or("the first run has no visited bins")?;
+        let (second_lo, second_hi) =
+            visited(second, offset).ok_or("the second run has no visited bins")?;
+        let lo = first_lo.max(second_lo);
+        let hi = first_hi.min(second_hi);
+        let both: Vec<(i64, f64)> = (lo..=hi)
+            .filter_map(|k| match (first_at(k), second_at(k)) {
+                (Some(a), Some(b)) => Some((k, a - b)),
+                _ => None,
+            })
+            .collect();
+        if both.is_empty() {
+            return Err("the runs have no visited energies in common".to_string());
+        }
+        let zero = both[0].1;
+        let energy = |k: i64| *((first.min + (k as f64 + 0.5) * width) / units::EPSILON).value();
+        let mut diff = DosDiff {
+            max_abs: 0.0,
+            rms: 0.0,
+            worst_energy: energy(both[0].0),
+            compared: both.len(),
+            only_first: Vec::new(),
+            only_second: Vec::new(),
+        };
+        for &(k, d) in &both {
+            let d = d - zero;
+            diff.rms += d * d;
+            if d.abs() > diff.max_abs {
+                diff.max_abs = d.abs();
+                diff.worst_energy = energy(k);
+            }
+        }
+        diff.rms = (diff.rms / both.len() as f64).sqrt();
+        let outside = |run_lo: i64, run_hi: i64| {
+            let mut ranges = Vec::new();
+            if run_lo < lo {
+                ranges.push((energy(run_lo), energy(lo - 1)));
+            }
+            if run_hi > hi {
+                ranges.push((energy(hi + 1), energy(run_hi)));
+            }
+            ranges
+        };
+        diff.only_first = outside(first_lo, first_hi);
+        diff.only_second = outside(second_lo, second_hi);
+        Ok(diff)
+    }
+}
+
+impl ::std::fmt::Display for DosDiff {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(
+            f,
+            "max |dlnw| {:.3} at E = {:.5}, rms {:.3} over {} bins",
+            PrettyFloat(self.max_abs),
+            PrettyFloat(self.worst_energy),
+            PrettyFloat(self.rms),
+            self.compared
+        )?;
+        for &(lo, hi) in &self.only_first {
+            write!(f, "\n    only in first: {:.5} to {:.5}", PrettyFloat(lo), PrettyFloat(hi))?;
+        }
+        for &(lo, hi) in &self.only_second {
+            write!(f, "\n    only in second: {:.5} to {:.5}", PrettyFloat(lo), PrettyFloat(hi))?;
+        }
+        Ok(())
+    }
+}
+
+/// The parameters for noticing when SAD stops finding new energies.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct StagnationParams {
+    /// Warn if SAD's important energy range has not grown in this many
+    /// moves.
+    pub stagnation_moves: Option<u64>,
+    /// Stop (saving first) rather than just warning when stagnant.
+    pub exit_on_stagnation: bool,
+}
+
+/// A plugin that warns when neither `too_lo` nor `too_hi` has changed
+/// for a long time.  This usually means the walker cannot reach lower
+/// energies with the current moves, so bigger moves or a better
+/// starting configuration are needed.  It does nothing for methods
+/// other than SAD.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Stagnation {
+    #[serde(skip, default)]
+    warned: std::cell::Cell<bool>,
+    /// How many moves without change count as stagnant.
+    limit: Option<u64>,
+    /// Whether to stop the run when stagnant.
+    exit: bool,
+}
+
+impl From<StagnationParams> for Stagnation {
+    fn from(params: StagnationParams) -> Self {
+        Stagnation {
+            warned: std::cell::Cell::new(false),
+            limit: params.stagnation_moves,
+            exit: params.exit_on_stagnation,
+        }
+    }
+}
+impl Stagnation {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: StagnationParams) {
+        *self = Self::from(params);
+    }
+    /// Has the important range of `sad` stayed put for too long?
+    pub fn is_stagnant(&self, moves: u64, sad: SadState) -> bool {
+        match self.limit {
+            Some(limit) => moves - sad.t_lo.max(sad.t_hi) >= limit,
+            None => false,
+        }
+    }
+}
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
+    for Stagnation
+{
+    fn run(&self, mc: &EnergyMC<S>, _sys: &S) -> plugin::Action {
+        if let Some(sad) = mc.sad_state() {
+            if !self.is_stagnant(mc.moves, sad) {
+                self.warned.set(false);
+                return plugin::Action::None;
+            }
+            if !self.warned.get() {
+                self.warned.set(true);
+                println!(
+                    "Warning: SAD's important range {:.5} to {:.5} has not changed since move {}",
+                    sad.too_lo.pretty(),
+                    sad.too_hi.pretty(),
+                    sad.t_lo.max(sad.t_hi),
+                );
+                println!("    Maybe try larger moves or a better starting configuration.");
+            }
+            if self.exit {
+                return plugin::Action::Exit;
+            }
+        }
+        plugin::Action::None
+    }
+    fn run_period(&self) -> plugin::TimeToRun {
+        match self.limit {
+            Some(limit) => plugin::TimeToRun::Period(::std::cmp::max(limit / 16, 1)),
+            None => plugin::TimeToRun::Never,
+        }
+    }
+}
+
+/// The parameters for stopping once no new energies are being found.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct ExhaustionParams {
+    /// Stop (saving first) once no new energy has been found in this
+    /// many moves, provided the histogram is flat.
+    pub exhausted_after: Option<u64>,
+    /// How flat the histogram must be to stop.  By default min/mean
+    /// must reach 0.8.
+    pub exhausted_flatness: Option<Flatness>,
+}
+
+/// A plugin that stops the run once exploration looks finished: no
+/// energy has been seen for the first time in a long while, and the
+/// histogram over the important energies is reasonably flat.  This is
+/// meant for open-ended runs without a `max_iter`.
+///
+/// Unlike [`Stagnation`], which only looks at whether SAD's range is
+/// growing and fires even when the histogram is far from flat, this
+/// waits until the energies we have are well sampled.  If both are
+/// set to exit, whichever fires first ends the run, so a stagnation
+/// limit shorter than `exhausted_after` will usually win.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Exhaustion {
+    /// How many moves without a new energy count as exhausted.
+    after: Option<u64>,
+    /// The flatness needed.
+    #[serde(default)]
+    criterion: Flatness,
+}
+
+impl From<ExhaustionParams> for Exhaustion {
+    fn from(params: ExhaustionParams) -> Self {
+        Exhaustion {
+            after: params.exhausted_after,
+            criterion: params.exhausted_flatness.unwrap_or_default(),
+        }
+    }
+}
+impl Exhaustion {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: ExhaustionParams) {
+        *self = Self::from(params);
+    }
+    /// Has `mc` gone long enough without a new energy, with a flat
+    /// enough histogram, to stop?
+    pub fn is_exhausted<S: System>(&self, mc: &EnergyMC<S>) -> bool {
+        match self.after {
+            Some(after) => {
+                mc.moves - mc.time_L >= after
+                    && self.criterion.is_flat(&mc.important_histogram())
+            }
+            None => false,
+        }
+    }
+}
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
+    for Exhaustion
+{
+    fn run(&self, mc: &EnergyMC<S>, _sys: &S) -> plugin::Action {
+        if self.is_exhausted(mc) {
+            println!(
+                "No new energies in {:.3} moves, with {} {:.2}, so we are done.",
+                PrettyFloat((mc.moves - mc.time_L) as f64),
+                self.criterion.name(),
+                PrettyFloat(self.criterion.measure(&mc.important_histogram()))
+            );
+            return plugin::Action::Exit;
+        }
+        plugin::Action::None
+    }
+    fn run_period(&self) -> plugin::TimeToRun {
+        match self.after {
+            Some(after) => plugin::TimeToRun::Period(::std::cmp::max(after / 16, 1)),
+            None => plugin::TimeToRun::Never,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Logger;
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
+    for Logger
+{
+    fn log(&self, mc: &EnergyMC<S>, _sys: &S) {
+        mc.method.report(&mc);
+        let rates: Vec<f64> = mc.acceptance_by_bin().into_iter().flatten().collect();
+        if !rates.is_empty() {
+            let min = rates.iter().cloned().fold(1.0, f64::min);
+            let max = rates.iter().cloned().fold(0.0, f64::max);
+            let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+            println!(
+                "    acceptance by energy: {:.2}% to {:.2}%, mean {:.2}%",
+                PrettyFloat(100.0 * min),
+                PrettyFloat(100.0 * max),
+                PrettyFloat(100.0 * mean),
+            );
+        }
+        if let Some((mean, error)) = mc.block_mean_energy() {
+            println!(
+                "    <E> = {:.6} +/- {:.2} from {} blocks",
+                mean.pretty(),
+                error.pretty(),
+                mc.energy_blocks.means.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+fn empty_bins(min: Energy, width: Energy, num: usize) -> Bins {
+    Bins {
+        min,
+        width,
+        edges: None,
+        histogram: vec![0; num].into(),
+        t_found: vec![0; num].into(),
+        lnw: vec![Unitless::new(0.0); num].into(),
+        energy_total: vec![Energy::new(0.0); num].into(),
+        energy_squared_total: vec![EnergySquared::new(0.0); num].into(),
+        extra: std::collections::HashMap::new(),
+    }
+}
+
+#[test]
+fn energy_out_of_bins_is_an_error() {
+    let bins = empty_bins(Energy::new(-1.0), Energy::new(1.0), 3);
+    assert_eq!(bins.state_to_index(State { E: Energy::new(-0.5) }), Ok(0));
+    assert_eq!(bins.state_to_index(State { E: Energy::new(1.5) }), Ok(2));
+    assert_eq!(
+        bins.state_to_index(State { E: Energy::new(-1.5) }),
+        Err(EnergyError::BelowMin {
+            energy: Energy::new(-1.5),
+            min: Energy::new(-1.0),
+        })
+    );
+    assert_eq!(
+        bins.state_to_index(State { E: Energy::new(2.5) }),
+        Err(EnergyError::AboveMax {
+            energy: Energy::new(2.5),
+            max: Energy::new(2.0),
+        })
+    );
+    assert_eq!(
+        bins.state_to_index(State {
+            E: Energy::new(std::f64::NAN)
+        }),
+        Err(EnergyError::NotANumber)
+    );
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A system that always finds a lower energy.
+struct Runaway {
+    E: Energy,
+    proposed: Energy,
+}
+
+#[cfg(test)]
+impl System for Runaway {
+    fn energy(&self) -> Energy {
+        self.E
+    }
+    fn compute_energy(&self) -> Energy {
+        self.E
+    }
+    fn randomize(&mut self, _: &mut crate::rng::MyRng) -> Energy {
+        self.E
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        Some(units::EPSILON)
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        1
+    }
+    fn dimensionality(&self) -> u64 {
+        1
+    }
+}
+
+#[cfg(test)]
+impl ConfirmSystem for Runaway {
+    fn confirm(&mut self) {
+        self.E = self.proposed;
+    }
+}
+
+#[cfg(test)]
+impl MovableSystem for Runaway {
+    fn plan_move(
+        &mut self,
+        _: &mut crate::rng::MyRng,
+        _: Length,
+        _: MoveDistribution,
+    ) -> Option<Energy> {
+        self.proposed = self.E - units::EPSILON;
+        Some(self.proposed)
+    }
+    fn planned_displacement(&self) -> Option<vector3d::Vector3d<Length>> {
+        // It always runs the same way.
+        let zero = Length::new(0.0);
+        Some(vector3d::Vector3d::new(-units::SIGMA, zero, zero))
+    }
+    fn max_size(&self) -> Length {
+        units::SIGMA
+    }
+}
+
+#[test]
+fn runaway_is_bounded_by_max_bins() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.max_bins = Some(10);
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("runaway.yaml"));
+    for _ in 0..100 {
+        mc.move_once();
+    }
+    assert_eq!(mc.bins.lnw.len(), 10);
+    assert_eq!(mc.bins.histogram.len(), 10);
+    assert_eq!(mc.system.energy(), -9.0 * units::EPSILON);
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A system that proposes a fixed list of energies in turn, and no
+/// move at all once they run out, so that tests can follow the weight
+/// updates by hand.  It also counts how often it is asked its energy,
+/// and how many swaps it makes, each of which leaves it where it is.
+/// Moves along an axis are refused if they step further than the box
+/// is `thickness` along it, and otherwise make the next proposal, or
+/// stay put once there are none left.
+struct Replay {
+    E: Energy,
+    proposed: Energy,
+    proposals: VecDeque<Energy>,
+    #[serde(skip)]
+    energy_calls: std::cell::Cell<u64>,
+    swaps: u64,
+    thickness: [f64; 3],
+}
+
+#[cfg(test)]
+impl System for Replay {
+    fn energy(&self) -> Energy {
+        self.energy_calls.set(self.energy_calls.get() + 1);
+        self.E
+    }
+    fn compute_energy(&self) -> Energy {
+        self.E
+    }
+    fn randomize(&mut self, _: &mut crate::rng::MyRng) -> Energy {
+        self.E
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        Some(units::EPSILON)
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        1
+    }
+    fn dimensionality(&self) -> u64 {
+        1
+    }
+}
+
+#[cfg(test)]
+impl ConfirmSystem for Replay {
+    fn confirm(&mut self) {
+        self.E = self.proposed;
+    }
+}
+
+#[cfg(test)]
+impl MovableSystem for Replay {
+    fn plan_move(
+        &mut self,
+        _: &mut crate::rng::MyRng,
+        _: Length,
+        _: MoveDistribution,
+    ) -> Option<Energy> {
+        self.proposed = self.proposals.pop_front()?;
+        Some(self.proposed)
+    }
+    fn plan_move_along(
+        &mut self,
+        rng: &mut crate::rng::MyRng,
+        axis: usize,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        if (distribution.scalar(rng) * d.value_unsafe).abs() > self.thickness[axis] {
+            return None;
+        }
+        self.proposed = self.proposals.pop_front().unwrap_or(self.E);
+        Some(self.proposed)
+    }
+    fn can_move_along(&self) -> bool {
+        true
+    }
+    fn swap_species(&mut self, _: &mut crate::rng::MyRng) -> Option<Energy> {
+        self.swaps += 1;
+        self.proposed = self.E;
+        Some(self.proposed)
+    }
+    fn can_swap_species(&self) -> bool {
+        true
+    }
+    fn max_size(&self) -> Length {
+        units::SIGMA
+    }
+}
+
+/// A run starting at energy `start` that makes one move to each of
+/// `proposals` in turn, in units of epsilon.
+#[cfg(test)]
+fn replay_run(
+    dir: &tempfile::TempDir,
+    params: EnergyMCParams,
+    start: f64,
+    proposals: &[f64],
+) -> EnergyMC<Replay> {
+    let system = Replay {
+        E: start * units::EPSILON,
+        proposed: start * units::EPSILON,
+        proposals: proposals.iter().map(|&e| e * units::EPSILON).collect(),
+        energy_calls: std::cell::Cell::new(0),
+        swaps: 0,
+        thickness: [std::f64::INFINITY; 3],
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("replay.yaml"));
+    for _ in proposals {
+        mc.move_once();
+    }
+    mc
+}
+
+#[cfg(test)]
+fn sad_with_unit_min_T() -> EnergyMCParams {
+    EnergyMCParamsBuilder::new()
+        .seed(0)
+        .sad(units::EPSILON)
+        .quiet(true)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn sad_lowers_too_lo_by_hand() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // Moving down out of the range is always accepted, since we sample
+    // canonically at min_T there.  The first visit to -1 changes
+    // nothing, and gamma is zero until the range has any width.
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &[-1.0]);
+    assert_eq!(mc.bins.histogram, vec![1, 1]);
+    assert_eq!(mc.bins.lnw, vec![Unitless::new(0.0); 2]);
+    assert_eq!(mc.sad_state().unwrap().too_lo, Energy::new(0.0));
+
+    // The second visit makes -1 the most visited energy, so it becomes
+    // important.  Its lnw would be lnw(0) - 1/min_T, but lnw is never
+    // set below zero here.
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &[-1.0, -1.0]);
+    assert_eq!(mc.bins.histogram, vec![2, 1]);
+    assert_eq!(mc.bins.lnw, vec![Unitless::new(0.0); 2]);
+    assert_eq!(mc.last_gamma(), 0.0);
+    let sad = mc.sad_state().unwrap();
+    assert_eq!(sad.too_lo, -units::EPSILON);
+    assert_eq!(sad.too_hi, Energy::new(0.0));
+    assert_eq!(sad.num_states, 2);
+    assert_eq!((sad.tL, sad.t_lo, sad.tF), (2, 2, 1));
+}
+
+#[test]
+fn sad_raises_too_hi_by_hand() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let gamma = |t: f64, latest_parameter: f64, tF: f64, num_states: f64| {
+        (latest_parameter + t / tF) / (latest_parameter + t / num_states * (t / tF))
+    };
+    // After the moves of sad_lowers_too_lo_by_hand, the third move to
+    // 0 is within the range, so it adds gamma to lnw(0).
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &[-1.0, -1.0, 0.0]);
+    let lnw0 = gamma(3.0, 1.0, 1.0, 2.0);
+    assert_eq!(mc.last_gamma(), lnw0);
+    assert_eq!(mc.bins.lnw, vec![Unitless::new(0.0), Unitless::new(lnw0)]);
+
+    // Moves above too_hi are sampled as if at too_hi, so with no max_T
+    // they are always accepted, and gamma is not added there.
+    let moves = [-1.0, -1.0, 0.0, 1.0, 1.0];
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &moves);
+    assert_eq!(mc.bins.histogram, vec![2, 2, 2]);
+    assert_eq!(mc.bins.lnw[2], Unitless::new(0.0));
+    assert_eq!(mc.sad_state().unwrap().too_hi, Energy::new(0.0));
+
+    // The third visit to 1 makes it the most visited, so too_hi rises
+    // to it, and it takes the lnw of the old too_hi.
+    let moves = [-1.0, -1.0, 0.0, 1.0, 1.0, 1.0];
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &moves);
+    assert_eq!(mc.last_gamma(), gamma(6.0, 1.0, 1.0, 2.0));
+    assert_eq!(mc.bins.histogram, vec![2, 2, 3]);
+    assert_eq!(
+        mc.bins.lnw,
+        vec![Unitless::new(0.0), Unitless::new(lnw0), Unitless::new(lnw0)]
+    );
+    let sad = mc.sad_state().unwrap();
+    assert_eq!((sad.too_lo, sad.too_hi), (-units::EPSILON, units::EPSILON));
+    assert_eq!(sad.num_states, 3);
+    // tF is when we found the newest important energy.
+    assert_eq!((sad.tL, sad.t_hi, sad.tF), (6, 6, 4));
+
+    // Now gamma uses the new range, of width 2/min_T.
+    let moves = [-1.0, -1.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &moves);
+    assert_eq!(mc.last_gamma(), gamma(7.0, 2.0, 4.0, 3.0));
+    assert_eq!(mc.bins.lnw[2], Unitless::new(lnw0 + gamma(7.0, 2.0, 4.0, 3.0)));
+}
+
+#[test]
+fn max_entropy_index_follows_new_low_energies() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // The third move puts the maximum entropy at 0, and every later
+    // move finds a new lowest energy, adding a bin at the front.
+    let mut moves = vec![-1.0, -1.0, 0.0];
+    for k in 2..30 {
+        moves.push(-(k as f64));
+    }
+    let mut mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &moves[..3]);
+    assert!(mc.max_S > Unitless::new(0.0));
+    mc.system.proposals = moves[3..].iter().map(|&e| e * units::EPSILON).collect();
+    for _ in 3..moves.len() {
+        let len = mc.bins.lnw.len();
+        mc.move_once();
+        assert_eq!(mc.bins.lnw.len(), len + 1);
+        assert_eq!(mc.bins.lnw[mc.max_S_index], mc.max_S);
+        assert_eq!(mc.index_to_state(mc.max_S_index).E, Energy::new(0.0));
+    }
+}
+
+/// A logger that keeps the most recent records at or above `level`
+/// from modules under `target`, for tests to look at.
+#[cfg(test)]
+struct CapturingLogger {
+    level: log::Level,
+    target: &'static str,
+    records: std::sync::Mutex<Vec<(std::thread::ThreadId, log::Level, String)>>,
+}
+
+/// The most records a `CapturingLogger` keeps.
+#[cfg(test)]
+const CAPTURED_RECORDS: usize = 1 << 12;
+
+#[cfg(test)]
+impl log::Log for CapturingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with(self.target)
+    }
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut records = self.records.lock().unwrap();
+        if records.len() == CAPTURED_RECORDS {
+            records.remove(0);
+        }
+        records.push((
+            std::thread::current().id(),
+            record.level(),
+            format!("{}", record.args()),
+        ));
+    }
+    fn flush(&self) {}
+}
+
+#[test]
+fn finding_a_new_energy_is_logged() {
+    static LOGGER: CapturingLogger = CapturingLogger {
+        level: log::Level::Info,
+        target: module_path!(),
+        records: std::sync::Mutex::new(Vec::new()),
+    };
+    // Only one logger may ever be set, so this is the only test that
+    // installs one.
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        system,
+        dir.path().join("runaway.yaml"),
+    );
+    for _ in 0..3 {
+        mc.move_once();
+    }
+    let me = std::thread::current().id();
+    let found: Vec<String> = LOGGER
+        .records
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(t, level, _)| *t == me && *level == log::Level::Info)
+        .map(|(_, _, msg)| msg.clone())
+        .filter(|msg| msg.starts_with("found new energy"))
+        .collect();
+    assert_eq!(found.len(), 3, "records: {:?}", found);
+    assert!(found[2].contains("after 3 moves"));
+}
+
+#[test]
+fn moves_above_the_ceiling_are_rejected() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.energy_ceiling = Some(-5.0 * units::EPSILON);
+    let system = || Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    // We cannot start above the ceiling, since we could never return.
+    let err = EnergyMC::try_from_params(params.clone(), system(), dir.path().join("runaway.yaml"))
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        EnergyMcError::Energy(EnergyError::AboveCeiling { .. })
+    ));
+
+    params.energy_ceiling = Some(Energy::new(0.0));
+    let mut mc = EnergyMC::from_params(params, system(), dir.path().join("runaway.yaml"));
+    for _ in 0..3 {
+        mc.move_once();
+    }
+    // Moves down from the ceiling are fine.
+    assert!(mc.accepted_moves > 0);
+    assert!(mc.system.energy() < Energy::new(0.0));
+    let lnw_len = mc.bins.lnw.len();
+    assert_eq!(
+        mc.prepare_for_state(State {
+            E: 3.0 * units::EPSILON
+        }),
+        Err(EnergyError::AboveCeiling {
+            energy: 3.0 * units::EPSILON,
+            ceiling: Energy::new(0.0),
+        })
+    );
+    assert_eq!(mc.bins.lnw.len(), lnw_len);
+    assert_eq!(mc.bins.histogram.len(), lnw_len);
+}
+
+#[test]
+fn stagnation_is_noticed_when_no_energies_can_be_reached() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // Every move goes down, and none may go below zero, so we are stuck.
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.min_allowed_energy = Some(-0.5 * units::EPSILON);
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("stuck.yaml"));
+    mc.stagnation = Stagnation::from(StagnationParams {
+        stagnation_moves: Some(1000),
+        exit_on_stagnation: true,
+    });
+    for _ in 0..999 {
+        mc.move_once();
+    }
+    let sad = mc.sad_state().unwrap();
+    assert!(!mc.stagnation.is_stagnant(mc.moves, sad));
+    assert_eq!(
+        Plugin::<EnergyMC<Runaway>>::run(&mc.stagnation, &mc, &mc.system),
+        plugin::Action::None
+    );
+    mc.moves += 1;
+    assert!(mc.stagnation.is_stagnant(mc.moves, sad));
+    assert_eq!(
+        Plugin::<EnergyMC<Runaway>>::run(&mc.stagnation, &mc, &mc.system),
+        plugin::Action::Exit
+    );
+}
+
+#[test]
+fn exhaustion_waits_for_a_flat_histogram() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, -2.5, &[1.0, 2.0, 3.0, 0.0], &[10, 9, 10, 1]);
+    if let Method::Sad {
+        ref mut too_lo,
+        ref mut too_hi,
+        ..
+    } = mc.method
+    {
+        *too_lo = Energy::new(-2.0);
+        *too_hi = Energy::new(0.0);
+    }
+    let exhaustion = Exhaustion::from(ExhaustionParams {
+        exhausted_after: Some(1000),
+        exhausted_flatness: None,
+    });
+    // The sparse bin above too_hi does not count against flatness.
+    assert!((mc.histogram_flatness() - 0.93).abs() < 0.01);
+    mc.time_L = 100;
+    mc.moves = 1099;
+    assert!(!exhaustion.is_exhausted(&mc));
+    mc.moves = 1100;
+    assert!(exhaustion.is_exhausted(&mc));
+    assert_eq!(
+        Plugin::<EnergyMC<Runaway>>::run(&exhaustion, &mc, &mc.system),
+        plugin::Action::Exit
+    );
+    mc.bins.histogram[1] = 2;
+    assert!(!exhaustion.is_exhausted(&mc));
+    assert_eq!(
+        Plugin::<EnergyMC<Runaway>>::run(&exhaustion, &mc, &mc.system),
+        plugin::Action::None
+    );
+}
+
+#[test]
+fn flatness_metrics_disagree_on_crafted_histograms() {
+    let min_over_mean = Flatness::MinOverMean(0.8);
+    let rel_std_dev = Flatness::RelStdDev(0.1);
+
+    // One tall bin: the minimum is 0.9 of the mean, but the relative
+    // standard deviation is 0.3.
+    let mut tall = vec![9; 9];
+    tall.push(19);
+    assert!((min_over_mean.measure(&tall) - 0.9).abs() < 1e-12);
+    assert!((rel_std_dev.measure(&tall) - 0.3).abs() < 1e-12);
+    assert!(min_over_mean.is_flat(&tall));
+    assert!(!rel_std_dev.is_flat(&tall));
+
+    // One nearly empty bin among many: the minimum is a tenth of the
+    // mean, but the spread is small.
+    let mut low = vec![10; 99];
+    low.push(1);
+    assert!(!min_over_mean.is_flat(&low));
+    assert!(rel_std_dev.measure(&low) < 0.1);
+    assert!(rel_std_dev.is_flat(&low));
+
+    for f in &[min_over_mean, rel_std_dev] {
+        assert!(f.is_flat(&[5, 5, 5]));
+        assert!(!f.is_flat(&[]));
+    }
+}
+
+#[test]
+fn exhaustion_can_use_the_relative_standard_deviation() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, -0.5, &[0.0; 10], &[9, 9, 9, 9, 9, 9, 9, 9, 9, 19]);
+    mc.method = Method::Samc { t0: 1.0 };
+    mc.time_L = 0;
+    mc.moves = 1000;
+    let with = |criterion| {
+        Exhaustion::from(ExhaustionParams {
+            exhausted_after: Some(1000),
+            exhausted_flatness: Some(criterion),
+        })
+    };
+    assert!(with(Flatness::MinOverMean(0.8)).is_exhausted(&mc));
+    assert!(!with(Flatness::RelStdDev(0.1)).is_exhausted(&mc));
+    assert!(with(Flatness::RelStdDev(0.5)).is_exhausted(&mc));
+}
+
+#[test]
+fn wl_checks_the_spread_without_a_rising_minimum() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, 0.0, &[0.0; 4], &[10, 10, 10, 9]);
+    mc.wl_flatness = Flatness::RelStdDev(0.1);
+    mc.method = Method::WL {
+        gamma: 1.0,
+        lowest_hist: 9,
+        highest_hist: 10,
+        total_hist: 39,
+        num_states: 4.0,
+        hist: VecDeque::from(vec![10, 10, 10, 9]),
+        min_energy: mc.bins.min,
+        inv_t: false,
+        min_gamma: None,
+    };
+    // Visiting a full bin leaves the minimum at 9, but [11, 10, 10, 9]
+    // has a relative spread of 0.07.
+    let e = mc.index_to_state(0);
+    mc.update_weights(e);
+    match mc.method {
+        Method::WL { gamma, .. } => assert_eq!(gamma, 0.5),
+        _ => panic!("expected WL"),
+    }
+}
+
+#[test]
+fn explicit_energy_bin_overrides_delta_energy() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.energy_bin = Some(0.25 * units::EPSILON);
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mc = EnergyMC::from_params(params, system, dir.path().join("runaway.yaml"));
+    assert_eq!(mc.bins.width, 0.25 * units::EPSILON);
+}
+
+#[test]
+fn swap_moves_keep_the_composition() {
+    use crate::system::binary_square::{BinarySquareWell, BinarySquareWellParams, Species};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut system = BinarySquareWellParams::default();
+    system.N = 20;
+    system.N_B = 5;
+    system.unlike_depth = Unitless::new(0.0);
+    let params = EnergyMCParamsBuilder::new()
+        .seed(1)
+        .move_weights(1.0, 1.0)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        BinarySquareWell::from(system),
+        dir.path().join("mixture.yaml"),
+    );
+    let species = mc.system.species.clone();
+    for _ in 0..2000 {
+        mc.move_once();
+    }
+    assert_ne!(mc.system.species, species);
+    assert_eq!(mc.system.count(Species::B), 5);
+    mc.system.verify_energy();
+}
+
+#[test]
+fn sweeps_are_converted_to_moves() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParamsBuilder::new().seed(0).max_iter(10).build().unwrap();
+    params.sweeps = true;
+    params._sad_trace.sad_trace_interval = Some(2);
+    params._method = MethodParams::_Canonical { T: Energy::new(2.0) };
+    params.energy_block_size = Some(3);
+    let ising = Ising::from(IsingParams { N: 4 });
+    assert_eq!(ising.natural_sweep_length(), 16);
+    let moves = params.clone().sweeps_to_moves(16);
+    assert_eq!(moves._report.max_iter, Some(160));
+    assert_eq!(moves._sad_trace.sad_trace_interval, Some(32));
+    assert_eq!(moves.energy_block_size, Some(48));
+    assert!(!moves.sweeps);
+    let empty = params.clone().sweeps_to_moves(0);
+    assert_eq!(empty.energy_block_size, Some(3));
+    assert_eq!(empty.validate(), Ok(()));
+    let mc = EnergyMC::from_params(params, ising, dir.path().join("ising.yaml"));
+    assert_eq!(mc.energy_blocks.block_size, 48);
+}
+
+#[test]
+fn lattice_energy_bins_should_be_multiples_of_the_spacing() {
+    use crate::system::ising::{Ising, IsingParams};
+    let ising = Ising::from(IsingParams { N: 4 });
+    let check = |width: f64| {
+        let mut params = EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        };
+        params.energy_bin = Some(width * units::EPSILON);
+        params.check_energy_bin(&ising)
+    };
+    assert_eq!(check(4.0), Ok(None));
+    assert_eq!(check(8.0), Ok(None));
+    assert!(check(6.0).unwrap().is_some());
+    assert!(check(2.0).unwrap().is_some());
+    assert_eq!(EnergyMCParams::default().check_energy_bin(&ising), Ok(None));
+}
+
+#[test]
+fn energy_bin_can_be_a_fraction_of_min_T() {
+    use crate::system::lennard_jones::{LennardJones, LennardJonesParams};
+    let mut lj_params = LennardJonesParams::default();
+    lj_params.N = 10;
+    let lj = LennardJones::from(lj_params);
+    let params = EnergyMCParamsBuilder::new()
+        .seed(0)
+        .sad(0.2 * units::EPSILON)
+        .energy_bin_fraction_of_min_T(0.1)
+        .build()
+        .unwrap();
+    let width = params.energy_bin_for(&lj);
+    assert!((width - 0.02 * units::EPSILON).value_unsafe.abs() < 1e-15);
+    // A lattice keeps its own energy spacing.
+    let ising = crate::system::ising::Ising::from(crate::system::ising::IsingParams { N: 4 });
+    assert_eq!(params.energy_bin_for(&ising), ising.delta_energy().unwrap());
+
+    let both = EnergyMCParamsBuilder::new()
+        .sad(0.2 * units::EPSILON)
+        .energy_bin_fraction_of_min_T(0.1)
+        .energy_bin(units::EPSILON)
+        .build();
+    assert!(both.unwrap_err().contains("cannot both"));
+    let wl = EnergyMCParamsBuilder::new()
+        .wl(None)
+        .energy_bin_fraction_of_min_T(0.1)
+        .build();
+    assert!(wl.is_err());
+}
+
+#[test]
+fn continuous_energy_bins_must_not_be_too_small() {
+    use crate::system::lennard_jones::{LennardJones, LennardJonesParams};
+    let mut lj_params = LennardJonesParams::default();
+    lj_params.N = 10;
+    let lj = LennardJones::from(lj_params);
+    // The lowest possible energy is -45, so this is 45 bins.
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.energy_bin = Some(units::EPSILON);
+    assert_eq!(params.check_energy_bin(&lj), Ok(None));
+    params.max_bins = Some(40);
+    assert!(params.check_energy_bin(&lj).is_err());
+    params.max_bins = None;
+    params.energy_bin = Some(1e-7 * units::EPSILON);
+    assert!(params.check_energy_bin(&lj).is_err());
+}
+
+#[test]
+fn extending_to_low_energies_keeps_bins_aligned() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        system,
+        dir.path().join("runaway.yaml"),
+    );
+    mc.bins.lnw[0] = Unitless::new(7.0);
+    // With front insertion into a Vec this would be quadratic.
+    for k in 1..100_000 {
+        mc.prepare_for_state(State {
+            E: -(k as f64) * units::EPSILON,
+        })
+        .unwrap();
+    }
+    assert_eq!(mc.bins.lnw.len(), 100_000);
+    assert_eq!(mc.bins.t_found.len(), 100_000);
+    let i = mc.index(State {
+        E: Energy::new(0.0),
+    });
+    assert_eq!(i, 99_999);
+    assert_eq!(mc.bins.lnw[i], Unitless::new(7.0));
+    assert_eq!(mc.bins.histogram[i], 1);
+    assert_eq!(mc.bins.histogram[0], 0);
+}
+
+#[cfg(test)]
+fn synthetic_run(dir: &tempfile::TempDir, min: f64, lnw: &[f64], hist: &[u64]) -> EnergyMC<Runaway> {
+    let system = Runaway {
+        E: Energy::new(min),
+        proposed: Energy::new(min),
+    };
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        system,
+        dir.path().join("synthetic.yaml"),
+    );
+    mc.bins = empty_bins(Energy::new(min), units::EPSILON, lnw.len());
+    mc.bins.lnw = lnw.iter().map(|&x| Unitless::new(x)).collect();
+    mc.bins.histogram = hist.iter().cloned().collect();
+    mc.recompute_occupied();
+    mc.max_S = Unitless::new(lnw.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max));
+    mc
+}
+
+#[test]
+fn optimized_ensemble_of_a_flat_landscape_is_flat() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, 0.0, &[0.0; 5], &[8; 5]);
+    assert!(mc.optimized_ensemble_lnw().is_none());
+    // Even visits, and a fraction heading up that falls steadily.
+    let mut d = DirectionCounts::default();
+    for (i, &up) in [8, 6, 4, 2, 0].iter().enumerate() {
+        let key = mc.transition_key(mc.index_to_state(i));
+        d.up.insert(key, up);
+        d.down.insert(key, 8 - up);
+    }
+    mc.directions = Some(d.clone());
+    let dos = mc.optimized_ensemble_lnw().unwrap();
+    assert_eq!(dos.min, mc.bins.min);
+    assert_eq!(dos.num_runs, vec![1; 5]);
+    for &lnw in dos.mean_lnw.iter() {
+        assert!((lnw - dos.mean_lnw[0]).abs() < 1e-12, "{:?}", dos.mean_lnw);
+    }
+
+    // Twice the visits in the middle, heading both ways, lowers the
+    // weight there.
+    let key = mc.transition_key(mc.index_to_state(2));
+    d.up.insert(key, 8);
+    d.down.insert(key, 8);
+    mc.directions = Some(d);
+    let dos = mc.optimized_ensemble_lnw().unwrap();
+    let extra = dos.mean_lnw[2] - dos.mean_lnw[0];
+    assert!((extra - 0.5 * 2f64.ln()).abs() < 1e-12, "{:?}", dos.mean_lnw);
+}
+
+#[test]
+fn merge_aligns_runs_with_different_ranges() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // The same density of states, with different additive constants,
+    // seen over different energy ranges.
+    let a = synthetic_run(&dir, -3.0, &[1.0, 2.0, 3.0], &[1, 1, 1]);
+    let b = synthetic_run(&dir, -2.0, &[12.0, 13.0, 13.0], &[1, 1, 0]);
+    let dos = EnergyMC::merge(&[a, b]);
+    assert_eq!(dos.min, Energy::new(-3.0));
+    assert_eq!(dos.mean_lnw.len(), 4);
+    assert_eq!(dos.num_runs, vec![1, 2, 2, 0]);
+    assert_eq!(dos.mean_lnw, vec![-2.0, -1.0, 0.0, 0.0]);
+    assert_eq!(dos.std_lnw, vec![0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(dos.energy(3), Energy::new(0.0));
+}
+
+#[test]
+fn merge_computes_standard_error() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let a = synthetic_run(&dir, 0.0, &[-1.0, 0.0], &[1, 1]);
+    let b = synthetic_run(&dir, 0.0, &[-3.0, 0.0], &[1, 1]);
+    let dos = EnergyMC::merge(&[a, b]);
+    assert_eq!(dos.mean_lnw, vec![-2.0, 0.0]);
+    assert!((dos.std_lnw[0] - 2f64.sqrt()).abs() < 1e-12);
+    assert!((dos.std_error[0] - 1.0).abs() < 1e-12);
+    assert_eq!(dos.std_error[1], 0.0);
+}
+
+#[test]
+#[should_panic]
+fn merge_rejects_mismatched_bins() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let a = synthetic_run(&dir, 0.0, &[0.0], &[1]);
+    let mut b = synthetic_run(&dir, 0.0, &[0.0], &[1]);
+    b.bins.width = 2.0 * units::EPSILON;
+    EnergyMC::merge(&[a, b]);
+}
+
+#[test]
+fn seeding_lnw_checks_the_grid() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let prior = synthetic_run(&dir, -2.5, &[0.0, 8.0, 9.0, 8.5], &[1, 1, 1, 1]);
+    let guess = EnergyMC::merge(&[prior]);
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        system,
+        dir.path().join("seeded.yaml"),
+    );
+    mc.seed_lnw(&guess).unwrap();
+    assert_eq!(mc.bins.min, Energy::new(-2.5));
+    assert_eq!(mc.bins.lnw.len(), 4);
+    assert_eq!(mc.bins.histogram.iter().sum::<u64>(), 1);
+    assert_eq!(mc.max_S_index, 2);
+    let sad = mc.sad_state().unwrap();
+    assert_eq!(sad.too_hi, Energy::new(0.0));
+    // lnw rises by 8 from -2 to -1, which is colder than min_T = 0.2.
+    assert_eq!(sad.too_lo, Energy::new(-1.0));
+    assert_eq!(sad.num_states, 2);
+
+    let mut wrong = guess.clone();
+    wrong.width = 0.5 * units::EPSILON;
+    assert!(mc.seed_lnw(&wrong).is_err());
+    let mut misaligned = guess.clone();
+    misaligned.min = Energy::new(-2.25);
+    assert!(mc.seed_lnw(&misaligned).is_err());
+}
+
+#[cfg(test)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A system whose incremental energy update has a small bug.
+struct Drifting {
+    E: Energy,
+    true_E: Energy,
+}
+
+#[cfg(test)]
+impl System for Drifting {
+    fn energy(&self) -> Energy {
+        self.E
+    }
+    fn compute_energy(&self) -> Energy {
+        self.true_E
+    }
+    fn randomize(&mut self, _: &mut crate::rng::MyRng) -> Energy {
+        self.E
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        Some(units::EPSILON)
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        1
+    }
+    fn dimensionality(&self) -> u64 {
+        1
+    }
+}
+
+#[cfg(test)]
+impl ConfirmSystem for Drifting {
+    fn confirm(&mut self) {
+        // Each move goes down by one, but we mistakenly add a bit.
+        self.E -= 0.999 * units::EPSILON;
+        self.true_E -= units::EPSILON;
+    }
+}
+
+#[cfg(test)]
+impl MovableSystem for Drifting {
+    fn plan_move(
+        &mut self,
+        _: &mut crate::rng::MyRng,
+        _: Length,
+        _: MoveDistribution,
+    ) -> Option<Energy> {
+        Some(self.E - units::EPSILON)
+    }
+    fn max_size(&self) -> Length {
+        units::SIGMA
+    }
+}
+
+#[test]
+fn energy_check_passes_on_correct_energy() {
+    let check = plugin::EnergyCheck::default();
+    let system = Runaway {
+        E: Energy::new(-3.0),
+        proposed: Energy::new(0.0),
+    };
+    assert_eq!(check.check(&system), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "energy drift")]
+fn energy_check_catches_drift() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._energy_check.energy_check_interval = Some(10);
+    let system = Drifting {
+        E: Energy::new(0.0),
+        true_E: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("drifting.yaml"));
+    for _ in 0..100 {
+        mc.move_once();
+    }
+}
+
+#[test]
+#[should_panic(expected = "rather than")]
+fn move_balance_catches_a_wrong_confirm() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.check_move_symmetry = true;
+    let system = Drifting {
+        E: Energy::new(0.0),
+        true_E: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("drifting.yaml"));
+    mc.move_once();
+}
+
+#[test]
+#[should_panic(expected = "component x")]
+fn move_balance_catches_one_way_moves() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.check_move_symmetry = true;
+    // One bin wide enough that every move is accepted.
+    params.energy_bin = Some(1e6 * units::EPSILON);
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("runaway.yaml"));
+    mc.run_for(2 * MOVE_SYMMETRY_SAMPLES);
+}
+
+#[test]
+fn round_trips_need_both_extremes() {
+    let e = |x: f64| x * units::EPSILON;
+    let mut rt = RoundTrips::default();
+    let range = Some((e(-2.0), e(2.0)));
+    // Starting at the top does not count as half a trip.
+    for &x in &[2.0, 0.0, -2.0, 0.0, 1.0] {
+        rt.visit(e(x), range);
+    }
+    assert_eq!(rt.count, 0);
+    for &x in &[2.0, -1.0, -2.0, 2.0, 2.0, -2.0] {
+        rt.visit(e(x), range);
+    }
+    assert_eq!(rt.count, 2);
+
+    // Without an important range we use the extremes seen so far.
+    let mut rt = RoundTrips::default();
+    for &x in &[0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0] {
+        rt.visit(e(x), None);
+    }
+    assert_eq!(rt.count, 2);
+}
+
+#[test]
+fn sad_trace_records_the_window() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let save_as = dir.path().join("trace.yaml");
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._sad_trace.sad_trace_interval = Some(10);
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, save_as.clone());
+    for _ in 0..100 {
+        mc.move_once();
+    }
+    let trace = std::fs::read_to_string(plugin::SadTrace::path(&save_as)).unwrap();
+    let lines: Vec<_> = trace.lines().collect();
+    assert_eq!(lines[0], "moves,too_lo,too_hi,min_important_energy,n_found,tL,tF");
+    assert_eq!(lines.len(), 11);
+    let last: Vec<f64> = lines[10].split(',').map(|x| x.parse().unwrap()).collect();
+    let sad = mc.sad_state().unwrap();
+    assert_eq!(last[0], 100.0);
+    assert_eq!(last[1], sad.too_lo.value_unsafe);
+    assert_eq!(last[3], mc.min_important_energy().unwrap().value_unsafe);
+    assert_eq!(last[4], sad.num_states as f64);
+}
+
+#[test]
+fn samc_gamma_decays_after_t0() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::Samc { t0: 100.0 };
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("samc.yaml"));
+    for _ in 0..100 {
+        mc.move_once();
+        assert_eq!(mc.last_gamma(), 1.0);
+    }
+    for _ in 0..100 {
+        mc.move_once();
+    }
+    assert_eq!(mc.last_gamma(), 0.5);
+    mc.move_once();
+    assert!(mc.last_gamma() < 0.5);
+}
+
+#[test]
+fn added_plugins_are_run() {
+    use crate::system::ising::{Ising, IsingParams};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    struct Counter(Arc<AtomicU64>);
+    impl<MC: MonteCarlo> Plugin<MC> for Counter {
+        fn run(&self, _mc: &MC, _sys: &MC::System) -> plugin::Action {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            plugin::Action::None
+        }
+        fn run_period(&self) -> plugin::TimeToRun {
+            plugin::TimeToRun::Period(10)
+        }
+    }
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..100 {
+        mc.move_once();
+    }
+    let count = Arc::new(AtomicU64::new(0));
+    mc.add_plugin(Box::new(Counter(count.clone())));
+    for _ in 0..100 {
+        mc.move_once();
+    }
+    // Periods are an upper bound, so we may run more often than asked.
+    assert!(count.load(Ordering::SeqCst) >= 10);
+}
+
+#[test]
+fn run_for_returns_when_a_plugin_asks_to_stop() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new().seed(0).build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("stop.yaml"),
+    );
+    assert!(mc.run_for(100));
+    mc.time_limit = plugin::TimeLimit::new(std::time::Duration::from_secs(0));
+    assert!(!mc.run_for(1000));
+    assert!(mc.moves < 1100);
+    // We saved before stopping.
+    assert!(dir.path().join("stop.yaml").exists());
+}
+
+#[test]
+fn snapshot_matches_the_run_when_taken() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let run = |name: &str| {
+        EnergyMC::from_params(
+            EnergyMCParamsBuilder::new().seed(5).build().unwrap(),
+            Ising::from(IsingParams { N: 4 }),
+            dir.path().join(name),
+        )
+    };
+    let mut mc = run("a.yaml");
+    mc.run_for(500);
+    let snapshot = mc.snapshot().unwrap();
+    mc.run_for(500);
+    let mut then = run("b.yaml");
+    then.run_for(500);
+    assert_eq!(snapshot, then.snapshot().unwrap());
+    assert_eq!(snapshot.moves, 500);
+    assert_eq!(snapshot.histogram.iter().sum::<u64>(), 501);
+    assert_ne!(snapshot, mc.snapshot().unwrap());
+    let i = then.index(State::new(&then.system));
+    assert_eq!(snapshot.energy(i), then.index_to_state(i).E);
+}
+
+#[test]
+fn run_for_continues_in_chunks() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = || {
+        EnergyMCParamsBuilder::new()
+            .seed(3)
+            .max_iter(50)
+            .build()
+            .unwrap()
+    };
+    let mut chunked = EnergyMC::from_params(
+        params(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("chunked.yaml"),
+    );
+    for _ in 0..3 {
+        chunked.run_for(100);
+    }
+    let mut whole = EnergyMC::from_params(
+        params(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("whole.yaml"),
+    );
+    whole.run_for(300);
+    assert_eq!(chunked.moves, 300);
+    assert_eq!(chunked.moves, whole.moves);
+    assert_eq!(chunked.accepted_moves, whole.accepted_moves);
+    assert_eq!(chunked.bins.histogram, whole.bins.histogram);
+    assert_eq!(chunked.bins.lnw, whole.bins.lnw);
+    assert_eq!(chunked.system.energy(), whole.system.energy());
+    // The save plugin still checkpointed along the way.
+    assert!(dir.path().join("chunked.yaml").exists());
+}
+
+#[test]
+fn plugins_run_and_save_in_priority_order() {
+    use crate::system::ising::{Ising, IsingParams};
+    use std::sync::{Arc, Mutex};
+    struct Recorder {
+        name: &'static str,
+        priority: i32,
+        action: plugin::Action,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+    impl<MC: MonteCarlo> Plugin<MC> for Recorder {
+        fn run(&self, _mc: &MC, _sys: &MC::System) -> plugin::Action {
+            self.events.lock().unwrap().push(format!("run {}", self.name));
+            self.action
+        }
+        fn save(&self, _mc: &MC, _sys: &MC::System) {
+            self.events.lock().unwrap().push(format!("save {}", self.name));
+        }
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+    }
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    mc.move_once();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    // The low priority plugin is added first and asks for the save, but
+    // the high priority one still runs and saves first.
+    mc.add_plugin(Box::new(Recorder {
+        name: "low",
+        priority: -1,
+        action: plugin::Action::Save,
+        events: events.clone(),
+    }));
+    mc.add_plugin(Box::new(Recorder {
+        name: "high",
+        priority: 1,
+        action: plugin::Action::None,
+        events: events.clone(),
+    }));
+    mc.move_once();
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["run high", "run low", "save high", "save low"]
+    );
+}
+
+#[test]
+fn min_important_energy_follows_sad() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        system.clone(),
+        dir.path().join("sad.yaml"),
+    );
+    let mut previous = mc.min_important_energy().unwrap();
+    for _ in 0..100 {
+        mc.move_once();
+        let e = mc.min_important_energy().unwrap();
+        assert!(e <= previous);
+        assert_eq!(e, mc.sad_state().unwrap().too_lo);
+        previous = e;
+    }
+    assert!(previous < Energy::new(0.0));
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::Samc { t0: 100.0 };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("samc.yaml"));
+    mc.move_once();
+    assert_eq!(mc.min_important_energy(), None);
+}
+
+#[test]
+fn normalized_lnw_is_zero_at_the_ground_state() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..100000 {
+        mc.move_once();
+    }
+    let lnw = mc.normalized_lnw();
+    assert_eq!(lnw.len(), mc.bins.lnw.len());
+    let ground = mc.bins.histogram.iter().position(|&h| h > 0).unwrap();
+    assert_eq!(lnw[ground], Unitless::new(0.0));
+    // Differences are unchanged.
+    for i in 0..lnw.len() {
+        let raw = mc.bins.lnw[i] - mc.bins.lnw[ground];
+        assert!((lnw[i] - raw).value_unsafe.abs() < 1e-12);
+    }
+}
+
+#[test]
+fn acceptance_by_bin_survives_a_checkpoint() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let save_as = dir.path().join("ising.yaml");
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        save_as.clone(),
+    );
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    assert_eq!(mc.proposals_at.len(), mc.bins.histogram.len());
+    assert_eq!(mc.proposals_at.iter().sum::<u64>(), mc.moves);
+    assert_eq!(mc.accepts_at.iter().sum::<u64>(), mc.accepted_moves);
+    for r in mc.acceptance_by_bin().into_iter().flatten() {
+        assert!(r >= 0.0 && r <= 1.0);
+    }
+    mc.checkpoint();
+    let resumed: EnergyMC<Ising> = load_from(&save_as).unwrap();
+    assert_eq!(resumed.proposals_at, mc.proposals_at);
+    assert_eq!(resumed.accepts_at, mc.accepts_at);
+}
+
+#[test]
+fn free_energy_of_a_two_level_system() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // Energies 0, 1 and 2 with degeneracies 1 and 3 in the important
+    // range.  The last bin is outside it, so it must be ignored.
+    let mut mc = synthetic_run(&dir, -0.5, &[0.0, 3.0_f64.ln(), 5.0], &[1, 1, 1]);
+    if let Method::Sad {
+        ref mut too_lo,
+        ref mut too_hi,
+        ..
+    } = mc.method
+    {
+        *too_lo = Energy::new(0.0);
+        *too_hi = Energy::new(1.0);
+    }
+    for &t in &[0.1, 0.5, 1.0, 10.0] {
+        let T = Energy::new(t);
+        let z = 1.0 + 3.0 * (-1.0 / t).exp();
+        let f = -t * z.ln();
+        let mean_e = 3.0 * (-1.0 / t).exp() / z;
+        let s = (mean_e - f) / t;
+        assert!((mc.free_energy(T).value_unsafe - f).abs() < 1e-12);
+        assert!((mc.entropy(T).value_unsafe - s).abs() < 1e-12);
+    }
+    // At high temperature the entropy approaches ln 4.
+    assert!((mc.entropy(Energy::new(1e6)).value_unsafe - 4.0_f64.ln()).abs() < 1e-5);
+    // Log-sum-exp keeps very low temperatures finite.
+    assert_eq!(mc.free_energy(Energy::new(1e-3)).value_unsafe, 0.0);
+}
+
+#[test]
+fn canonical_distribution_of_a_two_level_system() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // Energies 0 and 1 with degeneracies 1 and 3 in the important
+    // range, and one more bin above it that must be left out.
+    let mut mc = synthetic_run(&dir, -0.5, &[0.0, 3.0_f64.ln(), 5.0], &[1, 1, 1]);
+    if let Method::Sad {
+        ref mut too_lo,
+        ref mut too_hi,
+        ..
+    } = mc.method
+    {
+        *too_lo = Energy::new(0.0);
+        *too_hi = Energy::new(1.0);
+    }
+    for &t in &[0.5, 2.0] {
+        let p = mc.canonical_distribution(Energy::new(t));
+        assert_eq!(p.len(), 2);
+        assert_eq!(p[0].0, Energy::new(0.0));
+        assert_eq!(p[1].0, Energy::new(1.0));
+        let total: f64 = p.iter().map(|&(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        let ratio = 3.0 * (-1.0 / t).exp();
+        assert!((p[1].1 / p[0].1 - ratio).abs() < 1e-12);
+    }
+    // Below T = 1/ln 3 the ground state is the more likely, and above
+    // it the excited states.
+    let peak = |t: f64| {
+        mc.canonical_distribution(Energy::new(t))
+            .into_iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap()
+            .0
+    };
+    assert_eq!(peak(0.5), Energy::new(0.0));
+    assert_eq!(peak(2.0), Energy::new(1.0));
+    // Log-sum-exp keeps very low temperatures finite.
+    let cold = mc.canonical_distribution(Energy::new(1e-3));
+    assert_eq!(cold[0].1, 1.0);
+    assert_eq!(cold[1].1, 0.0);
+}
+
+#[test]
+fn burn_in_leaves_the_weights_flat() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .sad(units::EPSILON)
+        .seed(1)
+        .quiet(true)
+        .burn_in_moves(1000)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    let start = mc.system.energy();
+    let mut energies = Vec::new();
+    for _ in 0..1000 {
+        mc.move_once();
+        let e = mc.system.energy();
+        if !energies.contains(&e) {
+            energies.push(e);
+        }
+        assert!(mc.bins.lnw.iter().all(|&lnw| lnw == Unitless::new(0.0)));
+    }
+    assert!(energies.len() > 2, "burn-in only saw {:?}", energies);
+    assert_eq!(mc.moves, 1000);
+    assert_eq!(mc.accepted_moves, 1000);
+    // Only the starting configuration is counted.
+    assert_eq!(mc.bins.histogram.iter().sum::<u64>(), 1);
+    assert_eq!(mc.learning_time(), 0);
+
+    // Learning begins with the next move, at time one.
+    mc.move_once();
+    assert_eq!(mc.moves, 1001);
+    assert_eq!(mc.learning_time(), 1);
+    assert_eq!(mc.bins.histogram.iter().sum::<u64>(), 2);
+    let e = mc.system.energy();
+    let i = mc.index(State::new(&mc.system));
+    assert_eq!(mc.bins.t_found[i], if e == start { 0 } else { 1 });
+}
+
+#[test]
+fn lowering_min_t_extends_the_important_range() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::Sad {
+        min_T: 10.0 * units::EPSILON,
+        max_T: None,
+    };
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 8 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..100000 {
+        mc.move_once();
+    }
+    let hot_too_lo = mc.min_important_energy().unwrap();
+    assert_eq!(
+        mc.set_min_T(20.0 * units::EPSILON),
+        Err(MinTError::WouldRaise {
+            current: 10.0 * units::EPSILON,
+            requested: 20.0 * units::EPSILON,
+        })
+    );
+    for &bad in &[0.0, -1.0, f64::NAN] {
+        match mc.set_min_T(bad * units::EPSILON) {
+            Err(MinTError::NotPositive(_)) => (),
+            e => panic!("expected NotPositive, not {:?}", e),
+        }
+    }
+    mc.set_min_T(1.0 * units::EPSILON).unwrap();
+    if let Method::Sad { min_T, .. } = mc.method {
+        assert_eq!(min_T, 1.0 * units::EPSILON);
+    }
+    for _ in 0..100000 {
+        mc.move_once();
+    }
+    let cold_too_lo = mc.min_important_energy().unwrap();
+    assert!(cold_too_lo < hot_too_lo);
+    let sad = mc.sad_state().unwrap();
+    assert!(sad.too_lo <= sad.too_hi);
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::Samc { t0: 100.0 };
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("samc.yaml"),
+    );
+    assert_eq!(mc.set_min_T(1.0 * units::EPSILON), Err(MinTError::NotSad));
+    assert_eq!(mc.set_max_T(None), Err(MinTError::NotSad));
+}
+
+#[test]
+fn max_T_is_updated_on_resume() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let save_as = dir.path().join("ising.yaml");
+    let sad = |max_T: Option<f64>| EnergyMCParams {
+        seed: Some(0),
+        _method: MethodParams::Sad {
+            min_T: 1.0 * units::EPSILON,
+            max_T: max_T.map(|t| t * units::EPSILON),
+        },
+        ..EnergyMCParams::default()
+    };
+    let ising = Ising::from(IsingParams { N: 4 });
+    let mut mc = EnergyMC::from_params(sad(None), ising, save_as.clone());
+    mc.run_for(1000);
+    mc.checkpoint();
+    let mc = EnergyMC::<Ising>::resume(&save_as, sad(Some(5.0))).unwrap();
+    assert_eq!(mc.method_info().max_T, Some(5.0 * units::EPSILON));
+
+    // Unless we keep the saved method.
+    mc.checkpoint();
+    let params = EnergyMCParams {
+        keep_method: true,
+        ..sad(None)
+    };
+    let mc = EnergyMC::<Ising>::resume(&save_as, params).unwrap();
+    assert_eq!(mc.method_info().max_T, Some(5.0 * units::EPSILON));
+
+    let mut mc = mc;
+    match mc.set_max_T(Some(0.5 * units::EPSILON)) {
+        Err(MinTError::BadMaxT { .. }) => (),
+        e => panic!("expected BadMaxT, not {:?}", e),
+    }
+    assert_eq!(mc.method_info().max_T, Some(5.0 * units::EPSILON));
+    mc.set_max_T(None).unwrap();
+    assert_eq!(mc.method_info().max_T, None);
+}
+
+#[test]
+fn metropolis_agrees_with_sad_on_ising() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .sad(units::EPSILON)
+        .seed(3)
+        .quiet(true)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    mc.run_for(500_000);
+    // Well below the maximum entropy, where SAD's lnw is converged.
+    let temperatures = [0.5 * units::EPSILON, 1.5 * units::EPSILON, 2.5 * units::EPSILON];
+    let rows = mc.validate_with_metropolis(&temperatures, 50_000).unwrap();
+    // The temperature below min_T is left out.
+    assert_eq!(rows.iter().map(|r| r.T).collect::<Vec<_>>(), vec![1.5, 2.5]);
+    for r in rows {
+        assert!(r.error > 0.0);
+        assert!(r.discrepancy().abs() < 5.0);
+    }
+    assert!(mc.validate_with_metropolis(&temperatures, 1).is_err());
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._final_report.validation_moves = Some(1000);
+    assert!(params.validate().is_err());
+    params._final_report.report_temperatures = vec![units::EPSILON];
+    assert!(params.validate().is_err());
+    params._final_report.final_report = Some(ReportFormat::Json);
+    assert_eq!(params.validate(), Ok(()));
+    params._final_report.validation_moves = Some(1);
+    assert!(params.validate().is_err());
+}
+
+#[test]
+fn validate_rejects_impossible_params() {
+    assert_eq!(EnergyMCParams::default().validate(), Ok(()));
+    let with_method = |m| {
+        let mut params = EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        };
+        params._method = m;
+        params.validate()
+    };
+    assert!(with_method(MethodParams::Sad {
+        min_T: Energy::new(0.0),
+        max_T: None,
+    })
+    .unwrap_err()
+    .contains("min_T"));
+    assert!(with_method(MethodParams::Sad {
+        min_T: Energy::new(-1.0),
+        max_T: None,
+    })
+    .is_err());
+    assert!(with_method(MethodParams::Samc { t0: 0.0 })
+        .unwrap_err()
+        .contains("t0"));
+    assert!(with_method(MethodParams::_Canonical {
+        T: Energy::new(0.0)
+    })
+    .is_err());
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.energy_bin = Some(Energy::new(0.0));
+    assert!(params.validate().unwrap_err().contains("energy_bin"));
+    params.energy_bin = Some(Energy::new(std::f64::NAN));
+    assert!(params.validate().is_err());
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.min_allowed_energy = Some(Energy::new(1.0));
+    params.max_allowed_energy = Some(Energy::new(-1.0));
+    assert!(params.validate().unwrap_err().contains("max_allowed_energy"));
+}
+
+#[test]
+fn temperatures_from_the_command_line_are_in_epsilon() {
+    let parse = |args: &[&str]| {
+        let mut argv = vec!["sad"];
+        argv.extend_from_slice(args);
+        EnergyMCParams::from_iter(argv).unwrap()
+    };
+    let params = parse(&["--sad-min-T", "0.2"]);
+    match params._method {
+        MethodParams::Sad { min_T, max_T } => {
+            assert_eq!(min_T, 0.2 * units::EPSILON);
+            assert_eq!(max_T, None);
+        }
+        ref m => panic!("expected SAD, not {:?}", m),
+    }
+    assert_eq!(params.validate(), Ok(()));
+
+    let params = parse(&["--sad-min-T", "0.2", "--sad-max-T", "3"]);
+    match params._method {
+        MethodParams::Sad { max_T, .. } => assert_eq!(max_T, Some(3.0 * units::EPSILON)),
+        ref m => panic!("expected SAD, not {:?}", m),
+    }
+    assert_eq!(params.validate(), Ok(()));
+
+    assert!(parse(&["--sad-min-T", "-0.2"]).validate().is_err());
+    assert!(parse(&["--sad-min-T", "0.2", "--sad-max-T", "0.1"])
+        .validate()
+        .is_err());
+    let with_method = |m| {
+        let mut params = EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        };
+        params._method = m;
+        params.validate()
+    };
+    let infinity = Energy::new(std::f64::INFINITY);
+    assert!(with_method(MethodParams::Sad {
+        min_T: infinity,
+        max_T: None,
+    })
+    .unwrap_err()
+    .contains("finite"));
+    assert!(with_method(MethodParams::Sad {
+        min_T: Energy::new(0.2),
+        max_T: Some(infinity),
+    })
+    .is_err());
+    assert!(with_method(MethodParams::_Canonical { T: infinity }).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Invalid parameters")]
+fn from_params_panics_on_invalid_params() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::Samc { t0: 0.0 };
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    EnergyMC::from_params(params, system, dir.path().join("bad.yaml"));
+}
+
+#[test]
+fn try_from_params_names_each_failure() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let system = || Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let attempt = |params: EnergyMCParams, name: &str| {
+        EnergyMC::try_from_params(params, system(), dir.path().join(name)).map(|_| ())
+    };
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::Samc { t0: 0.0 };
+    match attempt(params, "bad.yaml") {
+        Err(EnergyMcError::InvalidParams(e)) => assert!(e.contains("t0")),
+        e => panic!("expected InvalidParams, not {:?}", e),
+    }
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.max_bins = Some(5);
+    params.fixed_min_energy = Some(Energy::new(-10.0));
+    params.fixed_max_energy = Some(Energy::new(10.0));
+    match attempt(params, "window.yaml") {
+        Err(EnergyMcError::Energy(EnergyError::TooManyBins { max_bins: 5, .. })) => (),
+        e => panic!("expected TooManyBins, not {:?}", e),
+    }
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.max_bins = Some(5);
+    params.energy_bin = Some(units::EPSILON);
+    let deep = Runaway {
+        E: -100.0 * units::EPSILON,
+        proposed: -100.0 * units::EPSILON,
+    };
+    match EnergyMC::try_from_params(params.clone(), deep, dir.path().join("deep.yaml")) {
+        Err(EnergyMcError::InvalidParams(e)) => assert!(e.contains("bins")),
+        e => panic!("expected InvalidParams, not {:?}", e.map(|_| ())),
+    }
+    params.max_bins = Some(0);
+    match attempt(params, "none.yaml") {
+        Err(EnergyMcError::InvalidParams(e)) => assert!(e.contains("max_bins")),
+        e => panic!("expected InvalidParams, not {:?}", e),
+    }
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.atoms_per_move = Some(2);
+    match attempt(params, "several.yaml") {
+        Err(EnergyMcError::InvalidParams(e)) => assert!(e.contains("atoms_per_move")),
+        e => panic!("expected InvalidParams, not {:?}", e),
+    }
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    let missing = dir.path().join("missing.yaml");
+    params.initial_lnw = Some(missing.clone());
+    match attempt(params, "seeded.yaml") {
+        Err(EnergyMcError::InitialLnw { path, .. }) => assert_eq!(path, missing),
+        e => panic!("expected InitialLnw, not {:?}", e),
+    }
+
+    match EnergyMC::<Runaway>::resume(&missing, EnergyMCParams::default()) {
+        Err(EnergyMcError::SerializationFailed {
+            error: SaveError::Io(_),
+            ..
+        }) => (),
+        e => panic!("expected SerializationFailed, not {:?}", e.map(|_| ())),
+    }
+
+    let mut mc = EnergyMC::try_from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        system(),
+        dir.path().join("run.txt"),
+    )
+    .unwrap();
+    match mc.try_checkpoint() {
+        Err(EnergyMcError::SerializationFailed {
+            error: SaveError::UnknownFormat(_),
+            ..
+        }) => (),
+        e => panic!("expected SerializationFailed, not {:?}", e),
+    }
+    mc.save_as = dir.path().join("run.yaml");
+    mc.try_checkpoint().unwrap();
+    let resumed = EnergyMC::<Runaway>::resume(&mc.save_as, EnergyMCParams::default()).unwrap();
+    assert_eq!(resumed.moves, mc.moves);
+}
+
+#[test]
+fn resuming_a_version_one_file_fills_in_defaults() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, -2.5, &[1.0, 2.0, 3.0], &[4, 5, 6]);
+    assert_eq!(mc.version, SAVE_FORMAT_VERSION);
+    mc.save_as = dir.path().join("run.yaml");
+    mc.try_checkpoint().unwrap();
+
+    // Strip what a file saved before version 2 would not have had.
+    let edit = |f: &dyn Fn(&mut serde_yaml::Mapping)| {
+        let text = std::fs::read_to_string(&mc.save_as).unwrap();
+        let mut yaml: serde_yaml::Value = serde_yaml::from_str(&text).unwrap();
+        f(yaml.as_mapping_mut().unwrap());
+        let path = dir.path().join("old.yaml");
+        std::fs::write(&path, serde_yaml::to_string(&yaml).unwrap()).unwrap();
+        path
+    };
+    let old = edit(&|m| {
+        for key in &[
+            "version",
+            "occupied",
+            "proposals_at",
+            "accepts_at",
+            "lnw_increment_total",
+            "lnw_increment_squared",
+            "seed",
+        ] {
+            assert!(m.remove(&serde_yaml::Value::from(*key)).is_some(), "no {}", key);
+        }
+    });
+    let loaded: EnergyMC<Runaway> = load_from(&old).unwrap();
+    assert_eq!(loaded.version, 1);
+    assert!(loaded.proposals_at.is_empty());
+
+    let resumed = EnergyMC::<Runaway>::resume(&old, EnergyMCParams::default()).unwrap();
+    assert_eq!(resumed.version, SAVE_FORMAT_VERSION);
+    assert_eq!(resumed.occupied, Some((0, 2)));
+    assert_eq!(resumed.proposals_at, VecDeque::from(vec![0; 3]));
+    assert_eq!(resumed.accepts_at, VecDeque::from(vec![0; 3]));
+    assert_eq!(resumed.lnw_increment_total.len(), 3);
+    assert_eq!(resumed.seed, None);
+    assert_eq!(resumed.bins.histogram, mc.bins.histogram);
+    assert_eq!(resumed.bins.lnw, mc.bins.lnw);
+
+    let newer = edit(&|m| {
+        m.insert(
+            "version".into(),
+            serde_yaml::Value::Number((SAVE_FORMAT_VERSION + 1).into()),
+        );
+    });
+    match EnergyMC::<Runaway>::resume(&newer, EnergyMCParams::default()) {
+        Err(EnergyMcError::IncompatibleVersion { found, supported }) => {
+            assert_eq!(found, SAVE_FORMAT_VERSION + 1);
+            assert_eq!(supported, SAVE_FORMAT_VERSION);
+        }
+        e => panic!("expected IncompatibleVersion, not {:?}", e.map(|_| ())),
+    }
+}
+
+#[test]
+fn method_info_reflects_the_configured_method() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let info = |params: EnergyMCParams| {
+        let system = Runaway {
+            E: Energy::new(0.0),
+            proposed: Energy::new(0.0),
+        };
+        EnergyMC::from_params(params, system, dir.path().join("run.yaml")).method_info()
+    };
+    let builder = || EnergyMCParamsBuilder::new().seed(0).quiet(true);
+
+    let sad = info(
+        builder()
+            .sad_between(Energy::new(0.5), Energy::new(2.0))
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(sad.kind, MethodKind::Sad);
+    assert_eq!(sad.min_T, Some(Energy::new(0.5)));
+    assert_eq!(sad.max_T, Some(Energy::new(2.0)));
+    let state = sad.sad.unwrap();
+    assert_eq!((state.too_lo, state.too_hi), (Energy::new(0.0), Energy::new(0.0)));
+    assert_eq!((sad.t0, sad.min_gamma, sad.inv_t, sad.temperature), (None, None, None, None));
+
+    let samc = info(builder().samc(100.0).build().unwrap());
+    assert_eq!(samc.kind, MethodKind::Samc);
+    assert_eq!(samc.t0, Some(100.0));
+    assert_eq!(samc.gamma, 1.0);
+    assert_eq!((samc.min_T, samc.sad), (None, None));
+
+    let wl = info(builder().wl(Some(1e-6)).build().unwrap());
+    assert_eq!(wl.kind, MethodKind::WangLandau);
+    assert_eq!(wl.min_gamma, Some(1e-6));
+    assert_eq!(wl.inv_t, Some(false));
+    assert_eq!(wl.gamma, 1.0);
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params._method = MethodParams::_Canonical { T: Energy::new(3.0) };
+    let canonical = info(params);
+    assert_eq!(canonical.kind, MethodKind::Canonical);
+    assert_eq!(canonical.temperature, Some(Energy::new(3.0)));
+    assert_eq!(canonical.gamma, 0.0);
+}
+
+#[test]
+fn builder_only_overrides_what_is_set() {
+    let params = EnergyMCParamsBuilder::new().seed(0).samc(100.0).build().unwrap();
+    let default = EnergyMCParams::default();
+    assert_eq!(params.seed, default.seed);
+    assert_eq!(params._report.max_iter, default._report.max_iter);
+    match params._method {
+        MethodParams::Samc { t0 } => assert_eq!(t0, 100.0),
+        _ => panic!("expected samc"),
+    }
+    assert!(EnergyMCParamsBuilder::new().samc(0.0).build().is_err());
+}
+
+#[test]
+fn config_files_round_trip_and_yield_to_flags() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("params.yaml");
+    let mut params = EnergyMCParamsBuilder::new()
+        .samc(1000.0)
+        .seed(7)
+        .energy_bin(0.5 * units::EPSILON)
+        .max_iter(100)
+        .build()
+        .unwrap();
+    params._report.quiet = true;
+    crate::mc::save_to(&path, &params).unwrap();
+    let read = EnergyMCParams::from_config_file(&path).unwrap();
+    assert_eq!(format!("{:?}", read), format!("{:?}", params));
+
+    // Anything left out of the file takes its default.
+    std::fs::write(&path, "seed: 7\n_report:\n  max_iter: 100\n").unwrap();
+    let read = EnergyMCParams::from_config_file(&path).unwrap();
+    assert_eq!(read.seed, Some(7));
+    assert_eq!(read._report.max_iter, Some(100));
+    assert!(!read._report.quiet);
+    assert_eq!(read.energy_bin, None);
+    assert!(matches!(read._method, MethodParams::Sad { .. }));
+
+    let flags = EnergyMCParams::from_iter(vec![
+        "sad",
+        "--sad-min-T",
+        "0.2",
+        "--max-iter",
+        "5",
+        "--config",
+        path.to_str().unwrap(),
+    ])
+    .unwrap()
+    .with_config_file()
+    .unwrap();
+    assert_eq!(flags._report.max_iter, Some(5));
+    assert_eq!(flags.seed, Some(7));
+
+    // The method always comes from the flags, unless we ask to keep
+    // the file's.
+    std::fs::write(&path, "_method:\n  Samc:\n    t0: 1000\n").unwrap();
+    let with = |extra: &[&str]| {
+        let mut args = vec![
+            "sad",
+            "--sad-min-T",
+            "0.2",
+            "--config",
+            path.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        EnergyMCParams::from_iter(args)
+            .unwrap()
+            .with_config_file()
+            .unwrap()
+    };
+    assert!(matches!(with(&[])._method, MethodParams::Sad { .. }));
+    match with(&["--keep-method"])._method {
+        MethodParams::Samc { t0 } => assert_eq!(t0, 1000.0),
+        m => panic!("expected the file's SAMC, not {:?}", m),
+    }
+}
+
+#[test]
+fn custom_acceptance_rule_can_accept_everything() {
+    use crate::system::ising::{Ising, IsingParams};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    let dir = tempfile::TempDir::new().unwrap();
+    let ising = Ising::from(IsingParams { N: 4 });
+    let mut mc = EnergyMC::from_params(sad_with_unit_min_T(), ising, dir.path().join("ising.yaml"));
+    mc.run_for(1000);
+    assert!(mc.accepted_moves < mc.moves);
+
+    let ising = Ising::from(IsingParams { N: 4 });
+    let mut mc = EnergyMC::from_params(sad_with_unit_min_T(), ising, dir.path().join("ising.yaml"));
+    let calls = Arc::new(AtomicU64::new(0));
+    let counter = calls.clone();
+    mc.set_acceptance_rule(Some(Box::new(
+        move |_: State, e2: State, bins: &Bins, _: &mut crate::rng::MyRng| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            bins.state_to_index(e2).is_ok()
+        },
+    )));
+    mc.run_for(1000);
+    assert_eq!(mc.accepted_moves, mc.moves);
+    assert_eq!(calls.load(Ordering::SeqCst), 1000);
+    mc.set_acceptance_rule(None);
+    mc.run_for(1000);
+    assert!(mc.accepted_moves < mc.moves);
+}
+
+#[test]
+fn min_visits_leaves_the_tails_out_of_the_mean_energy() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // The outer bins were only seen once or twice.
+    let mut mc = synthetic_run(&dir, 0.0, &[0.0; 4], &[1, 100, 100, 2]);
+    mc.method = Method::Samc { t0: 1.0 };
+    let T = Energy::new(1.0);
+    let mean = |es: &[f64]| {
+        let z: f64 = es.iter().map(|e| (-e).exp()).sum();
+        es.iter().map(|e| e * (-e).exp()).sum::<f64>() / z
+    };
+    let everything = mean(&[0.5, 1.5, 2.5, 3.5]);
+    assert!((mc.mean_energy(T).value_unsafe - everything).abs() < 1e-12);
+    mc.min_visits = 2;
+    assert!((mc.mean_energy(T).value_unsafe - mean(&[1.5, 2.5, 3.5])).abs() < 1e-12);
+    mc.min_visits = 5;
+    assert!((mc.mean_energy(T).value_unsafe - mean(&[1.5, 2.5])).abs() < 1e-12);
+    assert!(mc.is_rare(0) && mc.is_rare(3) && !mc.is_rare(1));
+    // Flagging keeps them in.
+    mc.rare_bins = RareBins::Flag;
+    assert!((mc.mean_energy(T).value_unsafe - everything).abs() < 1e-12);
+}
+
+#[test]
+fn canonical_averages_do_not_depend_on_the_number_of_threads() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // Enough bins for several reduction chunks, with a rugged entropy.
+    let num = 5 * REDUCTION_CHUNK + 123;
+    let lnw: Vec<f64> = (0..num)
+        .map(|i| 1e-3 * i as f64 + (i as f64 * 0.7).sin())
+        .collect();
+    let mut mc = synthetic_run(&dir, 0.0, &lnw, &vec![1; num]);
+    mc.method = Method::Samc { t0: 1.0 };
+    let averages = |threads: usize| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            [0.5, 20.0, 1e4]
+                .iter()
+                .map(|&t| {
+                    let T = Energy::new(t);
+                    (
+                        mc.free_energy(T).value_unsafe.to_bits(),
+                        mc.mean_energy(T).value_unsafe.to_bits(),
+                        mc.heat_capacity_interpolated(T, 3).value_unsafe.to_bits(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+    let serial = averages(1);
+    assert_eq!(serial, averages(2));
+    assert_eq!(serial, averages(7));
+}
+
+#[test]
+fn interpolated_averages_converge_on_a_fine_grid() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // A Gaussian density of states centered on zero, with unit width.
+    let width = 0.02;
+    let num = 1000;
+    let min = -0.5 * width * num as f64;
+    let lnw: Vec<f64> = (0..num)
+        .map(|i| {
+            let e = min + (i as f64 + 0.5) * width;
+            -0.5 * e * e
+        })
+        .collect();
+    let hist = vec![1; num];
+    let mut mc = synthetic_run(&dir, min, &lnw, &hist);
+    mc.bins.width = Energy::new(width);
+    mc.method = Method::Samc { t0: 1.0 };
+    let T = Energy::new(2.0);
+    // Analytically, <E> = -1/T and C_V = 1/T^2.
+    let discrete_e = mc.mean_energy(T).value_unsafe;
+    let smooth_e = mc.mean_energy_interpolated(T, 8).value_unsafe;
+    assert!((discrete_e - smooth_e).abs() < 1e-4);
+    assert!((smooth_e + 0.5).abs() < 1e-3);
+    let discrete_c = mc.heat_capacity(T).value_unsafe;
+    let smooth_c = mc.heat_capacity_interpolated(T, 8).value_unsafe;
+    assert!((discrete_c - smooth_c).abs() < 1e-3);
+    assert!((smooth_c - 0.25).abs() < 1e-2);
+    // A single subdivision is the discrete sum.
+    assert_eq!(mc.heat_capacity_interpolated(T, 1), mc.heat_capacity(T));
+}
+
+#[test]
+fn unspecified_seed_is_random_but_recorded() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let run = |seed: Option<u64>| {
+        let mut params = EnergyMCParams::default();
+        params.seed = seed;
+        let mut mc = EnergyMC::from_params(
+            params,
+            Ising::from(IsingParams { N: 4 }),
+            dir.path().join("ising.yaml"),
+        );
+        let energies: Vec<_> = (0..1000)
+            .map(|_| {
+                mc.move_once();
+                mc.system.energy()
+            })
+            .collect();
+        (mc.seed.unwrap(), energies)
+    };
+    let (seed_a, a) = run(None);
+    let (seed_b, b) = run(None);
+    assert_ne!(seed_a, seed_b);
+    assert_ne!(a, b);
+    let (seed_c, c) = run(Some(seed_a));
+    assert_eq!(seed_c, seed_a);
+    assert_eq!(c, a);
+}
+
+#[test]
+fn transition_matrix_solves_detailed_balance() {
+    // Three levels with degeneracies 1, 2 and 4, where from each level
+    // a move is proposed to a random one of the seven states.
+    let g = [1.0, 2.0, 4.0];
+    let mut tm = TransitionMatrix::default();
+    for i in 0..3 {
+        for j in 0..3 {
+            for _ in 0..(g[i] * g[j] * 100.0) as usize {
+                tm.record(i as i64 - 5, j as i64 - 5);
+            }
+        }
+    }
+    // A level we have only ever left cannot be connected.
+    tm.record(10, -5);
+    let S = tm.ln_dos();
+    assert_eq!(S.len(), 3);
+    assert_eq!(S[&-5], 0.0);
+    assert!((S[&-4] - 2f64.ln()).abs() < 1e-10);
+    assert!((S[&-3] - 4f64.ln()).abs() < 1e-10);
+}
+
+#[test]
+fn transition_matrix_agrees_with_samc() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .samc(1000.0)
+        .seed(3)
+        .collect_transition_matrix()
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..1000000 {
+        mc.move_once();
+    }
+    let tm = mc.dos_from_transition_matrix().unwrap();
+    let lnw = mc.normalized_lnw();
+    for i in mc.canonical_bins() {
+        let tm = tm[i].unwrap();
+        assert!(
+            (tm - lnw[i]).value_unsafe.abs() < 0.5,
+            "at {}: {} vs {}",
+            mc.index_to_state(i),
+            tm.value_unsafe,
+            lnw[i].value_unsafe
+        );
+    }
+
+    // Without asking for it, we do not pay for a matrix.
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("sad.yaml"),
+    );
+    mc.move_once();
+    assert_eq!(mc.dos_from_transition_matrix(), None);
+}
+
+#[test]
+fn block_error_shrinks_with_more_blocks() {
+    let mut rng = crate::rng::MyRng::seed_from_u64(11);
+    let error_with = |rng: &mut crate::rng::MyRng, blocks: u64| {
+        let mut b = BlockAverages::new(100);
+        for _ in 0..100 * blocks {
+            b.visit(Energy::new(crate::rng::normal(rng)));
+        }
+        assert!(b.means.len() < MAX_ENERGY_BLOCKS);
+        assert_eq!(b.means.len() as u64 * b.block_size, 100 * blocks);
+        b.standard_error().unwrap().value_unsafe
+    };
+    let few = error_with(&mut rng, 100);
+    let many = error_with(&mut rng, 1600);
+    // Independent samples with unit variance.
+    assert!((few - 0.01).abs() < 0.003);
+    assert!((many - 0.0025).abs() < 0.0005);
+    assert!((few / many - 4.0).abs() < 1.2);
+
+    let mut b = BlockAverages::new(10);
+    for _ in 0..15 {
+        b.visit(Energy::new(1.0));
+    }
+    assert_eq!(b.mean(), Some(Energy::new(1.0)));
+    assert_eq!(b.standard_error(), None);
+
+    // Merging keeps the mean of the blocks.
+    let mut b = BlockAverages::new(1);
+    for i in 0..MAX_ENERGY_BLOCKS {
+        b.visit(Energy::new(i as f64));
+    }
+    assert_eq!(b.block_size, 2);
+    assert_eq!(b.means.len(), MAX_ENERGY_BLOCKS / 2);
+    assert_eq!(b.means[0], Energy::new(0.5));
+    assert_eq!(b.mean(), Some(Energy::new((MAX_ENERGY_BLOCKS - 1) as f64 / 2.0)));
+}
+
+#[test]
+fn intervals_must_be_times() {
+    let mut params = EnergyMCParams::default();
+    for &bad in [-1.0, std::f64::NAN, std::f64::INFINITY].iter() {
+        params._progress.progress_interval = Some(bad);
+        assert!(params.validate().unwrap_err().contains("progress_interval"));
+    }
+    params._progress.progress_interval = Some(0.0);
+    assert_eq!(params.validate(), Ok(()));
+    for &bad in [-1.0, std::f64::NAN, std::f64::INFINITY].iter() {
+        params._live_plot.live_plot_interval = Some(bad);
+        assert!(params.validate().unwrap_err().contains("live_plot_interval"));
+    }
+    params._live_plot.live_plot_interval = Some(0.5);
+    assert_eq!(params.validate(), Ok(()));
+}
+
+#[test]
+fn energy_blocks_need_a_canonical_run() {
+    let mut params = EnergyMCParams { seed: Some(0), ..EnergyMCParams::default() };
+    params.energy_block_size = Some(10);
+    assert!(params.validate().is_err());
+    params._method = MethodParams::_Canonical { T: Energy::new(2.0) };
+    assert!(params.validate().is_ok());
+}
+
+#[test]
+fn autocorrelation_time_of_an_ar1_sequence() {
+    let mut rng = crate::rng::MyRng::seed_from_u64(3);
+    // x' = phi x + sqrt(1 - phi^2) noise has rho(k) = phi^k, so that
+    // tau = (1 + phi)/(1 - phi).
+    let tau_with = |rng: &mut crate::rng::MyRng, phi: f64, lags: usize| {
+        let mut a = Autocorrelation::new(lags);
+        let mut x = 0.0;
+        for _ in 0..200_000 {
+            x = phi * x + (1.0 - phi * phi).sqrt() * crate::rng::normal(rng);
+            a.visit(Energy::new(7.0 + x));
+        }
+        a.time()
+    };
+    let tau = tau_with(&mut rng, 0.8, 100).unwrap();
+    assert!((tau - 9.0).abs() < 1.0, "tau = {}", tau);
+    let tau = tau_with(&mut rng, 0.0, 100).unwrap();
+    assert!((tau - 1.0).abs() < 0.1, "tau = {}", tau);
+    // With tau = 199 the window needs far more lags than we keep.
+    assert_eq!(tau_with(&mut rng, 0.99, 100), None);
+
+    let mut a = Autocorrelation::new(10);
+    for _ in 0..20 {
+        a.visit(Energy::new(1.0));
+    }
+    assert_eq!(a.time(), None);
+}
+
+#[test]
+fn npy_export_writes_the_bins() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, -2.5, &[0.0, 1.5, 3.0], &[4, 0, 9]);
+    let prefix = dir.path().join("run");
+    mc.npy_export = NpyExport::from(NpyExportParams {
+        npy_prefix: Some(prefix.clone()),
+        npy_state_counts: true,
+    });
+    Plugin::<EnergyMC<Runaway>>::save(&mc.npy_export, &mc, &mc.system);
+    let f64s = |name| -> Vec<f64> {
+        let (dict, words) = crate::npy::read_words(NpyExport::path(&prefix, name));
+        assert!(dict.contains("'shape': (3,)"));
+        words.iter().map(|&w| f64::from_le_bytes(w)).collect()
+    };
+    assert_eq!(f64s("energy"), vec![-2.0, -1.0, 0.0]);
+    assert_eq!(f64s("lnw"), vec![0.0, 1.5, 3.0]);
+    let (dict, words) = crate::npy::read_words(NpyExport::path(&prefix, "histogram"));
+    assert!(dict.contains("'<u8'"));
+    let hist: Vec<u64> = words.iter().map(|&w| u64::from_le_bytes(w)).collect();
+    assert_eq!(hist, vec![4, 0, 9]);
+    // Runaway has no known total, so the lowest bin has one state.
+    assert_eq!(f64s("states"), vec![1.0, 0.0, 20.0]);
+}
+
+#[test]
+fn lnw_file_holds_just_the_entropy() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let dos = CompactDos {
+        min: Energy::new(-2.5),
+        width: Energy::new(0.5),
+        lnw: vec![0.0, 1.5, -3.25],
+    };
+    let path = dir.path().join("dos.lnw.bin");
+    save_to(&path, &dos).unwrap();
+    assert_eq!(CompactDos::load(&path).unwrap(), dos);
+
+    let mut params = sad_with_unit_min_T();
+    params._lnw_file.lnw_file_interval = Some(100);
+    let save_as = dir.path().join("ising.yaml");
+    let mut mc = EnergyMC::from_params(params, Ising::from(IsingParams { N: 4 }), save_as.clone());
+    mc.run_for(1000);
+    let path = LnwFile::path(&save_as);
+    assert!(CompactDos::load(&path).is_ok());
+    Plugin::<EnergyMC<Ising>>::save(&mc.lnw_file, &mc, &mc.system);
+    let dos = CompactDos::load(&path).unwrap();
+    assert_eq!(dos.min, mc.bins.min);
+    assert_eq!(dos.width, mc.bins.width);
+    let lnw: Vec<f64> = mc.bins.lnw.iter().map(|l| *l.value()).collect();
+    assert_eq!(dos.lnw, lnw);
+    assert_eq!(dos.energy(0), mc.index_to_state(0).E);
+    mc.checkpoint();
+    let size = |p: &std::path::Path| std::fs::metadata(p).unwrap().len();
+    assert!(10 * size(&path) < size(&save_as));
+}
+
+#[test]
+fn final_report_json_has_the_verbose_fields() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, -2.5, &[0.0, 1.5, 3.0], &[4, 0, 9]);
+    mc.moves = 100;
+    mc.accepted_moves = 25;
+    let report = FinalReport::from(FinalReportParams {
+        final_report: Some(ReportFormat::Json),
+        final_report_verbosity: Some(2),
+        report_temperatures: Vec::new(),
+        validation_moves: None,
+    });
+    let json: serde_json::Value =
+        serde_json::from_str(&report.summary(&mc).format(ReportFormat::Json)).unwrap();
+    assert_eq!(json["moves"], 100);
+    assert_eq!(json["acceptance_rate"], 0.25);
+    assert_eq!(json["num_bins"], 3);
+    assert_eq!(json["max_S"], *mc.max_S.value());
+    assert_eq!(json["round_trips"], 0);
+    assert_eq!(json["occupied_bins"], 2);
+    assert_eq!(json["holes"], 1);
+    assert!(json["span"].is_number());
+    assert!(json["too_lo"].is_number());
+    assert!(json["too_hi"].is_number());
+
+    let terse = FinalReport::from(FinalReportParams {
+        final_report: Some(ReportFormat::Json),
+        final_report_verbosity: None,
+        report_temperatures: Vec::new(),
+        validation_moves: None,
+    });
+    let json: serde_json::Value =
+        serde_json::from_str(&terse.summary(&mc).format(ReportFormat::Json)).unwrap();
+    assert_eq!(json["moves"], 100);
+    assert!(json.get("num_bins").is_none());
+}
+
+#[test]
+fn occupied_bins_follow_bin_extensions() {
+    use crate::system::gaussian_dos::{GaussianDos, GaussianDosParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    // This has no energy_bounds, so its bins grow as it finds energies.
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        GaussianDos::from(GaussianDosParams { K: 20, s: 5.0 }),
+        dir.path().join("gaussian.yaml"),
+    );
+    let scan = |h: &VecDeque<u64>| {
+        (
+            h.iter().position(|&n| n > 0),
+            h.iter().rposition(|&n| n > 0),
+        )
+    };
+    assert_eq!((mc.min_occupied(), mc.max_occupied()), (Some(0), Some(0)));
+    let mut extensions = 0;
+    for _ in 0..20000 {
+        let len = mc.bins.histogram.len();
+        mc.move_once();
+        if mc.bins.histogram.len() != len {
+            extensions += 1;
+        }
+        assert_eq!(
+            (mc.min_occupied(), mc.max_occupied()),
+            scan(&mc.bins.histogram)
+        );
+    }
+    assert!(extensions > 2);
+
+    // Runaway only ever finds lower energies, so every move shifts
+    // the bins.
+    let mut mc = synthetic_run(&dir, -0.5, &[0.0], &[1]);
+    for _ in 0..10 {
+        mc.move_once();
+    }
+    assert_eq!(mc.bins.histogram.len(), 11);
+    assert_eq!((mc.min_occupied(), mc.max_occupied()), (Some(0), Some(10)));
+}
+
+#[test]
+fn bins_are_made_out_to_known_energy_bounds() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    // Bins of width 4 centered on -32, -28, ..., 32.
+    assert_eq!(mc.bins.min, -34.0 * units::EPSILON);
+    assert_eq!(mc.bins.lnw.len(), 17);
+    assert_eq!(mc.bins.histogram.len(), 17);
+    assert_eq!(mc.proposals_at.len(), 17);
+    assert_eq!(
+        (mc.min_occupied(), mc.max_occupied()),
+        (
+            mc.state_to_index(State::new(&mc.system)).ok(),
+            mc.state_to_index(State::new(&mc.system)).ok()
+        )
+    );
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    assert_eq!(mc.bins.lnw.len(), 17);
+    assert_eq!(
+        mc.prepare_for_state(State {
+            E: 36.0 * units::EPSILON
+        }),
+        Err(EnergyError::BeyondBounds {
+            energy: 36.0 * units::EPSILON,
+            bound: 32.0 * units::EPSILON,
+        })
+    );
+    assert_eq!(mc.bins.lnw.len(), 17);
+}
+
+#[test]
+fn wl_on_an_unbounded_system_only_resets_for_new_bins() {
+    use crate::system::gaussian_dos::{GaussianDos, GaussianDosParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    // This has no energy_bounds, so bins are made for proposals
+    // whether or not they are accepted.
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new()
+            .seed(0)
+            .wl(None)
+            .quiet(true)
+            .build()
+            .unwrap(),
+        GaussianDos::from(GaussianDosParams { K: 20, s: 5.0 }),
+        dir.path().join("gaussian.yaml"),
+    );
+    mc.run_for(1000);
+    let n = mc.bins.lnw.len();
+    let bins_min = mc.bins.min;
+    if let Method::WL {
+        ref mut gamma,
+        ref mut lowest_hist,
+        ref mut hist,
+        ref mut min_energy,
+        ..
+    } = mc.method
+    {
+        *gamma = 0.5;
+        *lowest_hist = 1;
+        *hist = VecDeque::from(vec![1; n]);
+        *min_energy = bins_min;
+    }
+    // The first visit to a bin we already had is not a new energy.
+    let e = State::new(&mc.system);
+    let i = mc.index(e);
+    mc.bins.histogram[i] = 1;
+    mc.update_weights(e);
+    match mc.method {
+        Method::WL { gamma, .. } => assert!(gamma < 1.0),
+        _ => panic!("expected WL"),
+    }
+}
+
+#[test]
+fn moves_between_plugins_do_not_change_the_run() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let run = |block: Option<u64>, moves: u64| {
+        let mut builder = EnergyMCParamsBuilder::new().seed(7).quiet(true);
+        if let Some(block) = block {
+            builder = builder.moves_between_plugins(block);
+        }
+        let mut mc = EnergyMC::from_params(
+            builder.build().unwrap(),
+            Ising::from(IsingParams { N: 4 }),
+            dir.path().join("ising.yaml"),
+        );
+        while mc.moves < moves {
+            mc.move_block();
+        }
+        mc
+    };
+    let blocked = run(Some(64), 10000);
+    assert!(blocked.moves >= 10000);
+    let single = run(None, blocked.moves);
+    assert_eq!(single.moves, blocked.moves);
+    assert_eq!(single.snapshot().unwrap(), blocked.snapshot().unwrap());
+    assert_eq!(single.accepted_moves, blocked.accepted_moves);
+    assert_eq!(single.sad_state(), blocked.sad_state());
+    assert_eq!(single.system.energy(), blocked.system.energy());
+}
+
+#[test]
+fn rebinning_keeps_the_total_weight() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    let before = mc.snapshot().unwrap();
+    mc.rebin(1).unwrap();
+    assert_eq!(mc.snapshot().unwrap(), before);
+    assert!(mc.rebin(0).is_err());
+
+    let total_weight = |mc: &EnergyMC<Ising>| -> f64 {
+        (0..mc.bins.lnw.len())
+            .filter(|&i| mc.bins.histogram[i] > 0)
+            .map(|i| mc.bins.lnw[i].value().exp())
+            .sum()
+    };
+    let weight = total_weight(&mc);
+    let moves: u64 = mc.bins.histogram.iter().sum();
+    mc.rebin(2).unwrap();
+    // 17 bins of width 4 become 9 of width 8.
+    assert_eq!(mc.bins.lnw.len(), 9);
+    assert_eq!(mc.bins.width, 8.0 * units::EPSILON);
+    assert_eq!(mc.bins.min, before.min);
+    assert_eq!(mc.proposals_at.len(), 9);
+    assert!((total_weight(&mc) / weight - 1.0).abs() < 1e-12);
+    assert_eq!(mc.bins.histogram.iter().sum::<u64>(), moves);
+    let sad = mc.sad_state().unwrap();
+    for &e in &[sad.too_lo, sad.too_hi] {
+        assert_eq!(mc.index_to_state(mc.index(State { E: e })).E, e);
+    }
+    for _ in 0..1000 {
+        mc.move_once();
+    }
+    assert_eq!(mc.bins.lnw.len(), 9);
+}
+
+#[test]
+fn rebinning_wl_recounts_its_energies() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new().seed(0).wl(None).build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    mc.run_for(10000);
+    mc.rebin(2).unwrap();
+    let visited = mc.bins.histogram.iter().filter(|&&h| h > 0).count();
+    match mc.method {
+        Method::WL { num_states, .. } => assert_eq!(num_states, visited as f64),
+        _ => panic!("expected WL"),
+    }
+}
+
+#[test]
+fn a_fixed_window_never_grows() {
+    use crate::system::gaussian_dos::{GaussianDos, GaussianDosParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.fixed_min_energy = Some(-5.0 * units::EPSILON);
+    params.fixed_max_energy = Some(5.0 * units::EPSILON);
+    let mut mc = EnergyMC::from_params(
+        params,
+        GaussianDos::from(GaussianDosParams { K: 20, s: 5.0 }),
+        dir.path().join("gaussian.yaml"),
+    );
+    assert_eq!(mc.bins.min, -5.5 * units::EPSILON);
+    assert_eq!(mc.bins.lnw.len(), 11);
+    let capacities = |mc: &EnergyMC<GaussianDos>| {
+        (
+            mc.bins.lnw.capacity(),
+            mc.bins.histogram.capacity(),
+            mc.proposals_at.capacity(),
+        )
+    };
+    let before = capacities(&mc);
+    for _ in 0..20000 {
+        mc.move_once();
+        assert!(mc.system.energy().value_unsafe.abs() <= 5.0);
+    }
+    assert_eq!(mc.bins.min, -5.5 * units::EPSILON);
+    assert_eq!(mc.bins.lnw.len(), 11);
+    assert_eq!(capacities(&mc), before);
+    assert!(mc.bins.histogram.iter().all(|&h| h > 0));
+    assert_eq!(
+        mc.prepare_for_state(State {
+            E: 6.0 * units::EPSILON
+        }),
+        Err(EnergyError::OutsideWindow {
+            energy: 6.0 * units::EPSILON,
+            min: -5.0 * units::EPSILON,
+            max: 5.0 * units::EPSILON,
+        })
+    );
+
+    let mut params = EnergyMCParams {
+        seed: Some(0),
+        ..EnergyMCParams::default()
+    };
+    params.fixed_min_energy = Some(-5.0 * units::EPSILON);
+    assert!(params.validate().is_err());
+    params.fixed_max_energy = Some(-6.0 * units::EPSILON);
+    assert!(params.validate().is_err());
+
+    // Starting outside the window, we could never get in.
+    params.fixed_max_energy = Some(-3.0 * units::EPSILON);
+    match EnergyMC::try_from_params(
+        params,
+        GaussianDos::from(GaussianDosParams { K: 20, s: 5.0 }),
+        dir.path().join("outside.yaml"),
+    ) {
+        Err(EnergyMcError::Energy(EnergyError::OutsideWindow { energy, .. })) => {
+            assert_eq!(energy, Energy::new(0.0))
+        }
+        e => panic!("expected OutsideWindow, not {:?}", e.map(|_| ())),
+    }
+}
+
+#[test]
+fn energy_trace_records_every_interval() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let traced = |interval: Option<u64>, name: &str| {
+        let path = dir.path().join(name);
+        let mut params = EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        };
+        params._energy_trace = plugin::EnergyTraceParams {
+            energy_trace: Some(path.clone()),
+            energy_trace_interval: interval,
+        };
+        let mut mc = EnergyMC::from_params(
+            params,
+            Ising::from(IsingParams { N: 4 }),
+            dir.path().join("ising.yaml"),
+        );
+        for _ in 0..10000 {
+            mc.move_once();
+        }
+        mc.energy_trace.flush();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let energies: Vec<f64> = text.lines().map(|l| l.parse().unwrap()).collect();
+        assert!(energies.iter().all(|&e| (-32.0..=32.0).contains(&e)));
+        energies.len()
+    };
+    assert_eq!(traced(Some(7), "seven.dat"), 10000 / 7);
+    // By default we record once a sweep of 16 moves.
+    assert_eq!(traced(None, "sweep.dat"), 10000 / 16);
+}
+
+#[test]
+fn a_crash_loses_only_the_lines_not_yet_flushed() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let lines_left = |flush_lines: Option<usize>, name: &str| {
+        let trace = dir.path().join(format!("{}.dat", name));
+        let save_as = dir.path().join(format!("{}.yaml", name));
+        let mut params = EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        };
+        params._energy_trace = plugin::EnergyTraceParams {
+            energy_trace: Some(trace.clone()),
+            energy_trace_interval: Some(1),
+        };
+        params._sad_trace.sad_trace_interval = Some(10);
+        params._flush.flush_lines = flush_lines;
+        // Save after 1, 2, 4, ... 256 moves, which flushes the traces.
+        params._save.save_time = None;
+        let mut mc =
+            EnergyMC::from_params(params, Ising::from(IsingParams { N: 4 }), save_as.clone());
+        for _ in 0..500 {
+            mc.move_once();
+        }
+        // Crash, without saving or flushing.
+        drop(mc);
+        let count = |path: &std::path::Path| {
+            std::fs::read_to_string(path)
+                .map(|text| text.lines().count())
+                .unwrap_or(0)
+        };
+        (count(&trace), count(&plugin::SadTrace::path(&save_as)))
+    };
+    // Writing every line loses nothing.
+    assert_eq!(lines_left(Some(1), "every"), (500, 51));
+    // By default the energy trace is buffered, and the SAD trace not.
+    assert_eq!(lines_left(None, "default"), (256, 51));
+    // After the last save, 200 energies fill the buffer once, but the
+    // SAD trace never gets that far.
+    assert_eq!(lines_left(Some(200), "some"), (456, 26));
+}
+
+#[test]
+fn lnw_error_adds_increments_in_quadrature() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, -0.5, &[0.0, 0.0, 0.0], &[1, 1, 1]);
+    mc.lnw_increment_total = vec![0.0; 3].into();
+    mc.lnw_increment_squared = vec![0.0; 3].into();
+    for &g in &[0.3, 0.4] {
+        mc.record_lnw_increment(0, g);
+    }
+    mc.record_lnw_increment(2, 0.1);
+    let errors = mc.lnw_error();
+    assert!((errors[0].unwrap() - 0.5).abs() < 1e-12);
+    assert_eq!(errors[1], None);
+    assert!((errors[2].unwrap() - 0.1).abs() < 1e-12);
+    let (total, variance) = mc.lnw_increments()[0].unwrap();
+    assert!((total - 0.7).abs() < 1e-12);
+    assert!((variance - 0.25).abs() < 1e-12);
+
+    // The sums grow along with the bins.
+    mc.prepare_for_state(State {
+        E: Energy::new(-3.0),
+    })
+    .unwrap();
+    assert_eq!(mc.lnw_increment_total.len(), mc.bins.histogram.len());
+    let errors = mc.lnw_error();
+    assert_eq!(errors[0], None);
+    assert!((errors[3].unwrap() - 0.5).abs() < 1e-12);
+}
+
+#[test]
+fn samc_counts_increments_only_once_gamma_falls() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new().seed(1).samc(1000.0).build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..1000 {
+        mc.move_once();
+    }
+    assert!(mc.lnw_error().iter().all(|e| e.is_none()));
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    let counted: f64 = mc.lnw_increments().iter().flatten().map(|&(total, _)| total).sum();
+    let expected: f64 = (1001..=11000).map(|t| 1000.0 / t as f64).sum();
+    assert!((counted - expected).abs() < 1e-9);
+}
+
+#[test]
+fn dos_diff_aligns_shifted_grids() {
+    let entropy = |e: f64| -0.1 * e * e;
+    let snapshot = |min: f64, lnw: Vec<f64>| DosSnapshot {
+        moves: 0,
+        min: Energy::new(min),
+        width: Energy::new(1.0),
+        histogram: vec![1; lnw.len()],
+        lnw,
+    };
+    // The first run covers -10 to -3, the second -8 to 0 with a
+    // different constant, a hole, and one energy that disagrees.
+    let first = snapshot(-10.5, (0..8).map(|i| entropy(-10.0 + i as f64)).collect());
+    let mut second = snapshot(-8.5, (0..9).map(|i| entropy(-8.0 + i as f64) + 5.0).collect());
+    second.histogram[1] = 0;
+    second.lnw[3] += 0.3;
+    let diff = DosDiff::between(&first, &second).unwrap();
+    assert!((diff.max_abs - 0.3).abs() < 1e-12);
+    assert_eq!(diff.worst_energy, -5.0);
+    assert_eq!(diff.compared, 5);
+    assert!((diff.rms - (0.09f64 / 5.0).sqrt()).abs() < 1e-12);
+    assert_eq!(diff.only_first, vec![(-10.0, -9.0)]);
+    assert_eq!(diff.only_second, vec![(-2.0, 0.0)]);
+
+    let misaligned = snapshot(-8.0, second.lnw.clone());
+    assert!(DosDiff::between(&first, &misaligned).is_err());
+    let disjoint = snapshot(0.5, second.lnw.clone());
+    assert!(DosDiff::between(&first, &disjoint).is_err());
+}
+
+#[test]
+fn dos_diff_of_a_run_with_itself_is_zero() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mc = synthetic_run(&dir, -0.5, &[1.0, 0.0, 2.0, 3.0], &[1, 0, 1, 1]);
+    let diff = mc.diff_dos(&mc).unwrap();
+    assert_eq!((diff.max_abs, diff.rms, diff.compared), (0.0, 0.0, 3));
+    assert!(diff.only_first.is_empty() && diff.only_second.is_empty());
+}
+
+#[test]
+fn interior_holes_are_detected() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // Unvisited bins at the ends are not holes.
+    let mc = synthetic_run(&dir, -2.5, &[0.0, 1.0, 0.0, 2.0, 0.0], &[0, 3, 0, 5, 0]);
+    assert_eq!(mc.occupied_bins_and_holes(), (2, 1));
+    assert!((mc.explored_fraction() - 2.0 / 3.0).abs() < 1e-12);
+
+    let mc = synthetic_run(&dir, -2.5, &[0.0, 1.0, 2.0], &[0, 3, 5]);
+    assert_eq!(mc.occupied_bins_and_holes(), (2, 0));
+    assert_eq!(mc.explored_fraction(), 1.0);
+}
+
+#[test]
+fn final_report_tabulates_a_two_level_system() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // A ground state and three states one epsilon above it, seen with
+    // an arbitrary additive constant in lnw.
+    let mut mc = synthetic_run(&dir, -0.5, &[7.0, 7.0 + 3.0_f64.ln()], &[1, 1]);
+    if let Method::Sad {
+        ref mut too_lo,
+        ref mut too_hi,
+        ref mut min_T,
+        ..
+    } = mc.method
+    {
+        *too_lo = Energy::new(0.0);
+        *too_hi = Energy::new(1.0);
+        *min_T = Energy::new(0.2);
+    }
+    let report = FinalReport::from(FinalReportParams {
+        final_report: Some(ReportFormat::Json),
+        final_report_verbosity: None,
+        report_temperatures: vec![Energy::new(0.1), Energy::new(0.5), Energy::new(2.0)],
+        validation_moves: None,
+    });
+    let rows = report.summary(&mc).canonical.unwrap();
+    // The temperature below min_T is left out.
+    assert_eq!(rows.iter().map(|r| r.T).collect::<Vec<_>>(), vec![0.5, 2.0]);
+    for r in rows {
+        let t = r.T;
+        let z = 1.0 + 3.0 * (-1.0 / t).exp();
+        let mean_e = 3.0 * (-1.0 / t).exp() / z;
+        assert!((r.F - -t * z.ln()).abs() < 1e-12);
+        assert!((r.E - mean_e).abs() < 1e-12);
+        // With energies 0 and 1, <E^2> = <E>.
+        assert!((r.C_v - (mean_e - mean_e * mean_e) / (t * t)).abs() < 1e-12);
+        assert!((r.S - (mean_e + t * z.ln()) / t).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn final_report_key_values_parse() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mc = synthetic_run(&dir, -2.5, &[0.0, 1.5, 3.0], &[4, 0, 9]);
+    let report = FinalReport::from(FinalReportParams {
+        final_report: Some(ReportFormat::KeyValue),
+        final_report_verbosity: Some(2),
+        report_temperatures: Vec::new(),
+        validation_moves: None,
+    });
+    let text = report.summary(&mc).format(ReportFormat::KeyValue);
+    let kv: std::collections::HashMap<&str, &str> = text
+        .lines()
+        .map(|l| {
+            let mut parts = l.splitn(2, '=');
+            (parts.next().unwrap(), parts.next().unwrap())
+        })
+        .collect();
+    assert_eq!(kv["num_bins"], "3");
+    assert!(kv["acceptance_rate"].parse::<f64>().is_ok());
+}
+
+#[test]
+fn checking_lnw_flags_only_updates_that_lower_it() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .seed(0)
+        .sad(units::EPSILON)
+        .quiet(true)
+        .check_lnw_increases()
+        .build()
+        .unwrap();
+    // Widening SAD's range resets lnw outside it, which is not a fall.
+    let mc = replay_run(&dir, params, 0.0, &[-1.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0]);
+    assert_eq!(mc.lnw_decreases(), Some(Vec::new()));
+
+    let mut mc = synthetic_run(&dir, 0.0, &[0.0, 1.0, 2.0], &[4; 3]);
+    assert_eq!(mc.lnw_decreases(), None);
+    mc.lnw_decreases = Some(BTreeMap::new());
+    mc.moves = 4;
+    // With t0 = 1 after 4 moves, gamma is 1/4.
+    mc.method = Method::Samc { t0: 1.0 };
+    mc.checked_update_weights(State { E: Energy::new(1.5) });
+    assert_eq!(mc.bins.lnw[1], Unitless::new(1.25));
+    assert_eq!(mc.lnw_decreases(), Some(Vec::new()));
+    // A negative t0 makes gamma -1/2, as a broken update might.
+    mc.method = Method::Samc { t0: -2.0 };
+    mc.checked_update_weights(State { E: Energy::new(1.5) });
+    assert_eq!(mc.bins.lnw[1], Unitless::new(0.75));
+    assert_eq!(mc.lnw_decreases(), Some(vec![(Energy::new(1.5), 0.5)]));
+}
+
+#[test]
+fn non_finite_lnw_is_clamped_to_its_last_value() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = synthetic_run(&dir, 0.0, &[0.0, 1.0, 2.0], &[4; 3]);
+    mc.non_finite_lnw = Some(NonFiniteLnw::Clamp);
+    // An enormous gamma overflows on the second update.
+    mc.method = Method::Samc { t0: -f64::MAX };
+    mc.moves = 2;
+    let e = State { E: Energy::new(1.5) };
+    let change = mc.update_weights(e);
+    let old = mc.bins.lnw[1];
+    assert_eq!(old, Unitless::new(-f64::MAX / 2.0));
+    mc.check_lnw_finite(&change, NonFiniteLnw::Clamp);
+    assert_eq!(mc.bins.lnw[1], old);
+    mc.moves = 1;
+    let change = mc.update_weights(e);
+    assert!(mc.bins.lnw[1].value().is_infinite());
+    mc.check_lnw_finite(&change, NonFiniteLnw::Clamp);
+    assert_eq!(mc.bins.lnw[1], old);
+    // Only the bins an update wrote are checked.
+    mc.bins.lnw[2] = Unitless::new(std::f64::NAN);
+    mc.check_lnw_finite(&change, NonFiniteLnw::Clamp);
+    assert!(mc.bins.lnw[2].value().is_nan());
+
+    // When SAD resets bins around the one we are at, that bin keeps
+    // its weight from before the update.
+    let change = LnwChange {
+        bin: (2, Unitless::new(1.0)),
+        reset: (1, vec![Unitless::new(0.0); 3]),
+    };
+    let before: Vec<_> = change.before().collect();
+    assert_eq!(
+        before,
+        vec![
+            (2, Unitless::new(1.0)),
+            (1, Unitless::new(0.0)),
+            (3, Unitless::new(0.0))
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "lnw became -inf")]
+fn non_finite_lnw_can_abort_the_run() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .seed(0)
+        .samc(1.0)
+        .quiet(true)
+        .non_finite_lnw(NonFiniteLnw::Abort)
+        .build()
+        .unwrap();
+    let mut mc = replay_run(&dir, params, 0.0, &[0.0, 0.0]);
+    mc.method = Method::Samc { t0: -f64::MAX };
+    for _ in 0..10 {
+        mc.move_once();
+    }
+}
+
+#[test]
+fn joint_histogram_is_sparse_and_clamps_to_its_range() {
+    let mut joint = JointHistogram::new("x".into(), 0.0, 1.0, 4);
+    assert_eq!(joint.bin(0.3), 1);
+    assert_eq!(joint.bin(-5.0), 0);
+    assert_eq!(joint.bin(5.0), 3);
+    assert_eq!(joint.bin(std::f64::NAN), 0);
+    assert_eq!(joint.bin_center(1), 0.375);
+    for &x in &[0.3, 0.3, 0.9, 2.0] {
+        joint.record(7, x);
+    }
+    joint.record(-2, 0.1);
+    assert_eq!(joint.counts.len(), 2);
+    assert_eq!(joint.counts[&7].len(), 2);
+    assert_eq!(joint.observable_counts(), vec![1, 2, 0, 2]);
+}
+
+#[test]
+fn joint_observable_range_needs_an_observable() {
+    let mut params = EnergyMCParams::default();
+    params.joint_observable_bins = Some(10);
+    let e = params.validate().unwrap_err();
+    assert!(e.contains("need a joint_observable"), "{}", e);
+    params.joint_observable = Some("magnetization".to_string());
+    assert_eq!(params.validate(), Ok(()));
+}
+
+#[test]
+fn each_move_asks_the_system_for_its_energy_once() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new().seed(0).samc(1.0).quiet(true).build().unwrap();
+    let mut mc = replay_run(&dir, params, 0.0, &[]);
+    for i in 0..1000 {
+        let e = if i % 2 == 0 { -1.0 } else { 0.0 };
+        mc.system.proposals.push_back(e * units::EPSILON);
+    }
+    mc.system.energy_calls.set(0);
+    for _ in 0..1000 {
+        mc.step();
+    }
+    assert_eq!(mc.system.energy_calls.get(), 1000);
+    // Some moves were rejected, and each was still counted at the
+    // energy the system ended up with, as was the starting state.
+    assert!(mc.accepted_moves > 0 && mc.accepted_moves < 1000);
+    assert_eq!(mc.bins.histogram.iter().sum::<u64>(), 1001);
+    for (i, &h) in mc.bins.histogram.iter().enumerate() {
+        assert_eq!(mc.bins.energy_total[i], h as f64 * mc.index_to_state(i).E);
+    }
+}
+
+#[test]
+fn weights_are_updated_where_an_accepted_move_lands() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // With t0 this large, gamma is one for each of these moves.
+    let params = EnergyMCParamsBuilder::new().seed(0).samc(1000.0).quiet(true).build().unwrap();
+    let mut mc = replay_run(&dir, params, 0.0, &[]);
+    let lnw_at = |mc: &EnergyMC<Replay>, e: Energy| {
+        mc.state_to_index(State { E: e })
+            .map(|i| *mc.bins.lnw[i].value())
+            .unwrap_or(0.0)
+    };
+    // Each move is to an energy whose lnw is no higher than where it
+    // starts, so SAMC accepts it.
+    for &to in &[-1.0, -2.0, -1.0] {
+        let to = to * units::EPSILON;
+        let from = mc.system.energy();
+        let (lnw_from, lnw_to) = (lnw_at(&mc, from), lnw_at(&mc, to));
+        mc.system.proposals.push_back(to);
+        mc.move_once();
+        assert_eq!(mc.system.energy(), to);
+        assert_eq!(lnw_at(&mc, to), lnw_to + 1.0, "moving to {}", to.pretty());
+        assert_eq!(lnw_at(&mc, from), lnw_from, "moving from {}", from.pretty());
+    }
+}
+
+#[test]
+fn dry_run_predicts_the_bins_of_a_confined_run() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let ising = Ising::from(IsingParams { N: 4 });
+    let estimate = EnergyMCParams::default().estimate_memory(&ising).unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        ising,
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    assert_eq!(estimate.num_bins, mc.bins.lnw.len());
+    assert_eq!(estimate.min, mc.bins.min);
+    assert!(!estimate.capped);
+    assert!(estimate.arrays.contains(&("histogram".to_string(), 17 * 8)));
+    assert!(estimate.arrays.contains(&("magnetization totals".to_string(), 17 * 8)));
+    let ising = Ising::from(IsingParams { N: 4 });
+    assert_eq!(
+        EnergyMC::<Ising>::dry_run(&EnergyMCParams::default(), &ising),
+        None
+    );
+    let dry = EnergyMCParams {
+        dry_run: true,
+        ..EnergyMCParams::default()
+    };
+    assert_eq!(
+        EnergyMC::<Ising>::dry_run(&dry, &ising),
+        Some(Ok(estimate.to_string()))
+    );
+
+    // Runaway has no bounds, but is confined to a fixed window.
+    let system = || Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    let params = || {
+        let mut params = EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        };
+        params.fixed_min_energy = Some(Energy::new(-5.2));
+        params.fixed_max_energy = Some(Energy::new(3.7));
+        params
+    };
+    let estimate = params().estimate_memory(&system()).unwrap();
+    let mut mc = EnergyMC::from_params(params(), system(), dir.path().join("runaway.yaml"));
+    for _ in 0..100 {
+        mc.move_once();
+    }
+    assert_eq!(mc.system.energy(), Energy::new(-5.0));
+    assert_eq!(estimate.num_bins, 10);
+    assert_eq!(estimate.num_bins, mc.bins.lnw.len());
+    assert_eq!(estimate.min, mc.bins.min);
+    assert_eq!(estimate.total_bytes(), 10 * (10 * 8 + 1));
+
+    assert!(EnergyMCParams::default().estimate_memory(&system()).is_err());
+}
+
+#[test]
+fn moves_are_chosen_in_proportion_to_their_weights() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .seed(0)
+        .samc(1.0)
+        .move_weights(3.0, 1.0)
+        .quiet(true)
+        .build()
+        .unwrap();
+    // With no energies to replay, only the swaps move.
+    let mut mc = replay_run(&dir, params, 0.0, &[]);
+    let moves = 40000;
+    for _ in 0..moves {
+        mc.step();
+    }
+    let fraction = mc.system.swaps as f64 / moves as f64;
+    assert!((fraction - 0.25).abs() < 0.01, "swapped {}", fraction);
+
+    // Ising has only one species, so it cannot swap.
+    let params = EnergyMCParamsBuilder::new().seed(0).move_weights(1.0, 1.0).build().unwrap();
+    let ising = Ising::from(IsingParams { N: 4 });
+    assert!(EnergyMC::try_from_params(params, ising, dir.path().join("ising.yaml")).is_err());
+}
+
+#[test]
+fn adapting_bins_splits_kinks_and_merges_straight_lines() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let lnw = [0.0, 1.0, 2.0, 3.0, 4.0, 3.0, 2.0, 1.0, 0.0];
+    let mut mc = synthetic_run(&dir, 0.0, &lnw, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    let n = lnw.len();
+    mc.have_visited_since_maxentropy = vec![false; n].into();
+    mc.round_trips = vec![0; n].into();
+    mc.proposals_at = vec![0; n].into();
+    mc.accepts_at = vec![0; n].into();
+    mc.lnw_increment_total = vec![0.0; n].into();
+    mc.lnw_increment_squared = vec![0.0; n].into();
+    let total_weight = |mc: &EnergyMC<Runaway>| -> f64 {
+        (0..mc.bins.lnw.len())
+            .filter(|&i| mc.bins.histogram[i] > 0)
+            .map(|i| mc.bins.lnw[i].value().exp())
+            .sum()
+    };
+    let weight = total_weight(&mc);
+
+    mc.adapt_bins(1.0, 0.01).unwrap();
+    // Only the peak curves, and the straight runs either side of it
+    // are merged in pairs as far as they go.
+    let edges: Vec<f64> = mc.bins.edges.as_ref().unwrap().iter().map(|e| e.value_unsafe).collect();
+    assert_eq!(edges, vec![0.0, 1.0, 3.0, 4.0, 4.5, 5.0, 7.0, 8.0, 9.0]);
+    assert_eq!(
+        mc.bins.histogram.iter().cloned().collect::<Vec<_>>(),
+        vec![1, 5, 4, 2, 3, 13, 8, 9]
+    );
+    assert!((total_weight(&mc) / weight - 1.0).abs() < 1e-12);
+    assert_eq!(mc.proposals_at.len(), 8);
+    assert_eq!(mc.bins.lnw[3], mc.bins.lnw[4]);
+
+    let index = |mc: &EnergyMC<Runaway>, e: f64| mc.state_to_index(State { E: Energy::new(e) });
+    assert_eq!(index(&mc, 4.2).unwrap(), 3);
+    assert_eq!(index(&mc, 4.7).unwrap(), 4);
+    assert_eq!(index(&mc, 4.5).unwrap(), 4);
+    assert_eq!(index(&mc, 2.5).unwrap(), 1);
+    assert_eq!(index(&mc, 8.9).unwrap(), 7);
+    assert!(index(&mc, 9.0).is_err());
+    assert_eq!(mc.index_to_state(1).E, Energy::new(2.0));
+    assert!(mc.adapt_bins(1.0, 0.01).is_err());
+    assert!(mc.rebin(2).is_err());
+    assert!(mc.snapshot().is_err());
+
+    // New bins at either end have the original width.
+    mc.prepare_for_state(State {
+        E: Energy::new(9.5),
+    })
+    .unwrap();
+    mc.prepare_for_state(State {
+        E: Energy::new(-0.5),
+    })
+    .unwrap();
+    let edges = mc.bins.edges.clone().unwrap();
+    assert_eq!(edges.len(), mc.bins.lnw.len() + 1);
+    assert_eq!((edges[0], edges[edges.len() - 1]), (Energy::new(-1.0), Energy::new(10.0)));
+    assert_eq!(index(&mc, 4.2).unwrap(), 4);
+    assert_eq!(mc.bins.histogram[0], 0);
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A continuous system on `[0, 8]` with `ln g(E) = -3|E - 4|`, whose
+/// kink at 4 needs finer bins than the straight lines either side.
+/// Like `GaussianDos`, it accepts moves itself in proportion to `g`.
+struct Peaked {
+    E: Energy,
+    proposed: Energy,
+}
+
+#[cfg(test)]
+impl Peaked {
+    fn exact_lng(e: f64) -> f64 {
+        -3.0 * (e - 4.0).abs()
+    }
+    /// The exact `ln` of the number of states between `lo` and `hi`.
+    fn exact_ln_states(lo: f64, hi: f64) -> f64 {
+        let (lo, hi) = (lo.max(0.0), hi.min(8.0));
+        let steps = 1000;
+        let de = (hi - lo) / steps as f64;
+        let total: f64 = (0..steps)
+            .map(|k| Self::exact_lng(lo + (k as f64 + 0.5) * de).exp() * de)
+            .sum();
+        total.ln()
+    }
+}
+
+#[cfg(test)]
+impl System for Peaked {
+    fn energy(&self) -> Energy {
+        self.E
+    }
+    fn compute_energy(&self) -> Energy {
+        self.E
+    }
+    fn randomize(&mut self, _: &mut crate::rng::MyRng) -> Energy {
+        self.E
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        None
+    }
+    fn energy_bounds(&self) -> (Option<Energy>, Option<Energy>) {
+        (Some(Energy::new(0.0)), Some(Energy::new(8.0)))
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        1000
+    }
+    fn dimensionality(&self) -> u64 {
+        1
+    }
+}
+
+#[cfg(test)]
+impl ConfirmSystem for Peaked {
+    fn confirm(&mut self) {
+        self.E = self.proposed;
+    }
+}
+
+#[cfg(test)]
+impl MovableSystem for Peaked {
+    fn plan_move(
+        &mut self,
+        rng: &mut crate::rng::MyRng,
+        _: Length,
+        _: MoveDistribution,
+    ) -> Option<Energy> {
+        let e = self.E + (rng.gen::<f64>() - 0.5) * 0.5 * units::EPSILON;
+        if e < Energy::new(0.0) || e > Energy::new(8.0) {
+            return None;
+        }
+        let ln_ratio = Self::exact_lng(e.value_unsafe) - Self::exact_lng(self.E.value_unsafe);
+        self.proposed = if ln_ratio >= 0.0 || rng.gen::<f64>() < ln_ratio.exp() {
+            e
+        } else {
+            self.E
+        };
+        Some(self.proposed)
+    }
+    fn max_size(&self) -> Length {
+        units::SIGMA
+    }
+}
+
+#[test]
+fn discrete_energies_cannot_be_adapted() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .adapt_bins(1000, 1.0, 0.3)
+        .build()
+        .unwrap();
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    match EnergyMC::try_from_params(params, system, dir.path().join("runaway.yaml")) {
+        Err(EnergyMcError::InvalidParams(e)) => assert!(e.contains("discrete"), "{}", e),
+        _ => panic!("adapted the bins of a system with discrete energies"),
+    }
+}
+
+#[test]
+fn adapted_bins_converge_on_a_kink() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let system = Peaked {
+        E: Energy::new(4.0),
+        proposed: Energy::new(4.0),
+    };
+    let params = EnergyMCParamsBuilder::new()
+        .samc(1e3)
+        .seed(1)
+        .energy_bin(0.5 * units::EPSILON)
+        .adapt_bins(200_000, 1.0, 0.3)
+        .quiet(true)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("peaked.yaml"));
+    for _ in 0..2_000_000 {
+        mc.move_once();
+    }
+    assert!(mc.bins.edges.is_some());
+    assert!(mc.adapt.is_none());
+    // The bin holding the kink was split, and only bins near it were.
+    let at_kink = mc.state_to_index(State { E: Energy::new(4.0) }).unwrap();
+    assert!(mc.bins.bin_width(at_kink) < 0.5 * units::EPSILON);
+    for i in 0..mc.bins.lnw.len() {
+        if mc.bins.bin_width(i) < 0.5 * units::EPSILON {
+            assert!((mc.index_to_state(i).E - Energy::new(4.0)).value_unsafe.abs() < 1.0);
+        }
+    }
+    let lnw = mc.normalized_lnw();
+    let ground = mc.bins.histogram.iter().position(|&h| h > 0).unwrap();
+    let exact = |i: usize| {
+        Peaked::exact_ln_states(mc.bins.edge(i).value_unsafe, mc.bins.edge(i + 1).value_unsafe)
+    };
+    for (i, &l) in lnw.iter().enumerate() {
+        if mc.bins.histogram[i] > 0 {
+            let expected = exact(i) - exact(ground);
+            println!("{}: {} vs {}", mc.index_to_state(i).E.pretty(), l.pretty(), expected);
+            assert!((*l.value() - expected).abs() < 0.25);
+        }
+    }
+}
+
+#[test]
+fn record_best_keeps_the_lowest_energy_configuration() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .seed(1)
+        .record_best()
+        .quiet(true)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    assert_eq!(mc.best_configuration().unwrap().found, 0);
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    let best = mc.best_configuration().unwrap();
+    let lowest = mc.bins.histogram.iter().position(|&h| h > 0).unwrap();
+    assert_eq!(best.energy, mc.index_to_state(lowest).E);
+    assert_eq!(best.system.compute_energy(), best.energy);
+    assert!(best.found > 0 && best.found <= mc.moves);
+    let summary = FinalReport::default().summary(&mc);
+    assert_eq!(summary.best_found, Some(best.found));
+
+    // It is kept beside the save file rather than in it.
+    mc.try_checkpoint().unwrap();
+    let saved: serde_yaml::Value = load_from(&mc.save_as).unwrap();
+    assert!(saved.get("best").is_none());
+    let resumed = EnergyMC::<Ising>::resume(&mc.save_as, EnergyMCParams::default()).unwrap();
+    let kept = resumed.best_configuration().unwrap();
+    assert_eq!((kept.energy, kept.found), (best.energy, best.found));
+
+    // Without asking, nothing is kept.
+    let mc = EnergyMC::from_params(
+        EnergyMCParams {
+            seed: Some(0),
+            ..EnergyMCParams::default()
+        },
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("plain.yaml"),
+    );
+    assert!(mc.best_configuration().is_none());
+    // Runaway cannot copy itself.
+    let params = EnergyMCParamsBuilder::new().seed(0).record_best().build().unwrap();
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    assert!(EnergyMC::try_from_params(params, system, dir.path().join("runaway.yaml")).is_err());
+}
+
+#[test]
+fn run_summary_describes_a_replayed_run() {
+    let dir = tempfile::TempDir::new().unwrap();
+    // With t0 this large, gamma is one, so each move lands where lnw
+    // is no higher and is accepted.  The last two have nowhere to go.
+    let params = EnergyMCParamsBuilder::new().seed(0).samc(1000.0).quiet(true).build().unwrap();
+    let mut mc = replay_run(&dir, params, 0.0, &[-1.0, -2.0, -1.0, 0.0]);
+    mc.move_once();
+    mc.move_once();
+    let run = mc.summary();
+    assert_eq!(run.moves, 6);
+    assert_eq!(run.rejected_moves, 2);
+    assert_eq!(run.acceptance_rate, 4.0 / 6.0);
+    assert_eq!(run.method.kind, MethodKind::Samc);
+    assert_eq!(run.method.t0, Some(1000.0));
+    assert_eq!(run.important_range, None);
+    // lnw is 1 at -2, 2 at -1, and 3 at 0.
+    assert_eq!(run.max_S, 3.0);
+    assert_eq!((run.num_bins, run.occupied_bins, run.holes), (3, 3, 0));
+    assert_eq!(run.round_trips, mc.num_round_trips());
+
+    let report = FinalReport::from(FinalReportParams {
+        final_report: Some(ReportFormat::Json),
+        final_report_verbosity: Some(2),
+        report_temperatures: Vec::new(),
+        validation_moves: None,
+    });
+    let summary = report.summary(&mc);
+    assert_eq!(summary.moves, run.moves);
+    assert_eq!(summary.num_bins, Some(3));
+    assert_eq!(summary.max_S, Some(3.0));
+
+    let mc = replay_run(&dir, sad_with_unit_min_T(), 0.0, &[-1.0, -1.0, 0.0]);
+    let sad = mc.sad_state().unwrap();
+    let run = mc.summary();
+    assert_eq!(run.method.kind, MethodKind::Sad);
+    assert_eq!(run.important_range, Some((sad.too_lo, sad.too_hi)));
+    assert_eq!(report.summary(&mc).too_lo, Some(sad.too_lo.value_unsafe));
+}
+
+#[test]
+fn anisotropic_moves_tune_each_axis_separately() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .seed(1)
+        .sad(units::EPSILON)
+        .acceptance_rate(0.5)
+        .anisotropic_moves()
+        .quiet(true)
+        .build()
+        .unwrap();
+    // A box much thinner along z, with ever lower energies to find.
+    // SAD tunes the move scales whenever one becomes important, which
+    // it does once it has been visited more than any other, so each is
+    // visited longer than the last.
+    let mut mc = replay_run(&dir, params, 0.0, &[]);
+    mc.system.thickness = [1.0, 1.0, 0.01];
+    for k in 0..40 {
+        let level = -(k as f64) * units::EPSILON;
+        let stays = std::iter::repeat(level).take(1000 + 50 * k);
+        mc.system.proposals.extend(stays);
+        mc.system.proposals.push_back(level - units::EPSILON);
+    }
+    while !mc.system.proposals.is_empty() {
+        mc.move_once();
+    }
+    // A Gaussian step is within w of zero half the time when its rms
+    // is w/0.674, i.e. 1.48 w.
+    let axes = mc.axis_scales.clone().unwrap();
+    let thickness = mc.system.thickness.iter();
+    let rates = axes.acceptance_rates.iter();
+    for ((scale, w), rate) in axes.scales.iter().zip(thickness).zip(rates) {
+        let ratio = scale.value_unsafe / w;
+        assert!(ratio > 1.1 && ratio < 2.0, "scale is {} times {}", ratio, w);
+        assert!((rate - 0.5).abs() < 0.1, "acceptance rate is {}", rate);
+    }
+    assert!(axes.moves.iter().all(|&n| n > 30000));
+
+    // Systems that cannot move along an axis refuse.
+    let params = EnergyMCParamsBuilder::new()
+        .anisotropic_moves()
+        .build()
+        .unwrap();
+    let system = Runaway {
+        E: Energy::new(0.0),
+        proposed: Energy::new(0.0),
+    };
+    assert!(EnergyMC::try_from_params(params, system, dir.path().join("runaway.yaml")).is_err());
+
+    let mut both = EnergyMCParams::default();
+    both.anisotropic_moves = true;
+    both.atoms_per_move = Some(2);
+    assert!(both.validate().is_err());
 }
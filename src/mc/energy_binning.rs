@@ -545,7 +545,9 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
         // energy that is high enough.
         if let Some(maxe) = params.max_allowed_energy {
             for _ in 0..1e8 as u64 {
-                if let Some(newe) = system.plan_move(&mut rng, 0.05 * units::SIGMA) {
+                if let Some(newe) =
+                    system.plan_move(&mut rng, 0.05 * units::SIGMA, MoveDistribution::Gaussian)
+                {
                     if newe < system.energy() {
                         system.confirm();
                     }
@@ -603,7 +605,11 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
         }
         let recent_scale = (1.0 / self.moves as f64).sqrt();
         self.acceptance_rate *= 1. - recent_scale;
-        if let Some(e2) = self.system.plan_move(&mut self.rng, self.translation_scale) {
+        if let Some(e2) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
             let mut out_of_bounds = false;
             if let Some(maxe) = self.max_allowed_energy {
                 out_of_bounds = e2 > maxe && e2 > e1;
@@ -291,6 +291,8 @@ impl Bins {
                 newbins.have_visited_since_maxentropy[j] = self.have_visited_since_maxentropy[i];
                 newbins.round_trips[j] = self.round_trips[i];
             }
+            // The maximum entropy is now at a different index.
+            newbins.max_S_index = newbins.state_to_index(self.index_to_state(self.max_S_index));
             *self = newbins;
             true
         } else {
@@ -543,10 +545,11 @@ impl<S: GrandSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo
 
         if self.rng.gen::<f64>() > self.addremove_probability {
             self.bins.num_translation_attempts[e1.N] += 1;
-            if let Some(e2) = self
-                .system
-                .plan_move(&mut self.rng, self.bins.translation_scale[e1.N])
-            {
+            if let Some(e2) = self.system.plan_move(
+                &mut self.rng,
+                self.bins.translation_scale[e1.N],
+                MoveDistribution::Gaussian,
+            ) {
                 let mut out_of_bounds = false;
                 if let Some(maxe) = self.max_allowed_energy {
                     out_of_bounds = e2 > maxe && e2 > e1.E;
@@ -180,16 +180,10 @@ impl<S: Clone + ConfirmSystem + MovableSystem + serde::Serialize + serde::de::De
                         .unwrap()
                 }
                 if let Some(ref save_as) = save_as {
-                    if let Ok(f) = ::std::fs::File::open(save_as) {
-                        let mut s = match save_as.extension().and_then(|x| x.to_str()) {
-                            Some("yaml") => serde_yaml::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("json") => serde_json::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("cbor") => serde_cbor::from_reader::<Self, _>(&f)
-                                .expect("error parsing save-as file"),
-                            _ => panic!("I don't know how to read file {:?}", f),
-                        };
+                    if save_as.exists() {
+                        let mut s: Self = load_from(save_as).unwrap_or_else(|e| {
+                            panic!("error parsing save-as file {:?}: {}", save_as, e)
+                        });
                         println!("Resuming from file {:?}", save_as);
                         s.system.update_caches();
                         s.report.update_from(_mc._report);
@@ -202,21 +196,8 @@ impl<S: Clone + ConfirmSystem + MovableSystem + serde::Serialize + serde::de::De
                 let save_as = save_as.unwrap_or(::std::path::PathBuf::from("resume.yaml"));
                 Self::from_params(_mc, _sys.into(), save_as)
             }
-            Params::ResumeFrom(p) => {
-                let f = ::std::fs::File::open(&p).expect(&format!("error reading file {:?}", &p));
-                match p.extension().and_then(|x| x.to_str()) {
-                    Some("yaml") => {
-                        serde_yaml::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("json") => {
-                        serde_json::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("cbor") => {
-                        serde_cbor::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    _ => panic!("I don't know how to read file {:?}", f),
-                }
-            }
+            Params::ResumeFrom(p) => load_from(&p)
+                .unwrap_or_else(|e| panic!("error reading checkpoint {:?}: {}", p, e)),
         }
     }
     /// Create a simulation checkpoint.
@@ -241,14 +222,8 @@ impl<S: Clone + ConfirmSystem + MovableSystem + serde::Serialize + serde::de::De
             );
         }
 
-        let f = AtomicFile::create(&self.save_as)
-            .expect(&format!("error creating file {:?}", self.save_as));
-        match self.save_as.extension().and_then(|x| x.to_str()) {
-            Some("yaml") => serde_yaml::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("json") => serde_json::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("cbor") => serde_cbor::to_writer(&f, self).expect("error writing checkpoint?!"),
-            _ => panic!("I don't know how to create file {:?}", self.save_as),
-        }
+        save_to(&self.save_as, &*self)
+            .unwrap_or_else(|e| panic!("error writing checkpoint {:?}: {}", self.save_as, e));
     }
 
     fn e_to_idx(&self, energy: Energy) -> usize {
@@ -264,7 +239,10 @@ impl<S: Clone + ConfirmSystem + MovableSystem + serde::Serialize + serde::de::De
         self.histogram[i1] += 1;
         self.total_energy[i1] += e1;
         let distance = self.system.max_size() * self.rng.gen_range(-10.0, 0.0f64).exp();
-        if let Some(e2) = self.system.plan_move(&mut self.rng, distance) {
+        if let Some(e2) = self
+            .system
+            .plan_move(&mut self.rng, distance, MoveDistribution::Gaussian)
+        {
             let i2 = self.e_to_idx(e2);
             let lnw1 = self.lnw[i1];
             let lnw2 = self.lnw[i2];
@@ -204,7 +204,11 @@ impl<S: MovableSystem + Clone> Replica<S> {
         let system = &mut self.system;
         let lowest_max_energy = &mut self.lowest_max_energy;
         if self.max_energy.value_unsafe.is_finite() {
-            if let Some(e) = system.plan_move(&mut self.rng, self.translation_scale) {
+            if let Some(e) = system.plan_move(
+                &mut self.rng,
+                self.translation_scale,
+                MoveDistribution::Gaussian,
+            ) {
                 if e < self.max_energy {
                     system.confirm();
                     self.accepted_count += 1;
@@ -415,16 +419,10 @@ impl<
                         .unwrap()
                 }
                 if let Some(ref save_as) = save_as {
-                    if let Ok(f) = ::std::fs::File::open(save_as) {
-                        let mut s = match save_as.extension().and_then(|x| x.to_str()) {
-                            Some("yaml") => serde_yaml::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("json") => serde_json::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("cbor") => serde_cbor::from_reader::<Self, _>(&f)
-                                .expect("error parsing save-as file"),
-                            _ => panic!("I don't know how to read file {:?}", f),
-                        };
+                    if save_as.exists() {
+                        let mut s: Self = load_from(save_as).unwrap_or_else(|e| {
+                            panic!("error parsing save-as file {:?}: {}", save_as, e)
+                        });
                         println!("Resuming from file {:?}", save_as);
                         for r in s.replicas.iter_mut() {
                             r.system.update_caches();
@@ -439,21 +437,8 @@ impl<
                 let save_as = save_as.unwrap_or(::std::path::PathBuf::from("resume.yaml"));
                 Self::from_params(_mc, _sys.into(), save_as)
             }
-            Params::ResumeFrom(p) => {
-                let f = ::std::fs::File::open(&p).expect(&format!("error reading file {:?}", &p));
-                match p.extension().and_then(|x| x.to_str()) {
-                    Some("yaml") => {
-                        serde_yaml::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("json") => {
-                        serde_json::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("cbor") => {
-                        serde_cbor::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    _ => panic!("I don't know how to read file {:?}", f),
-                }
-            }
+            Params::ResumeFrom(p) => load_from(&p)
+                .unwrap_or_else(|e| panic!("error reading checkpoint {:?}: {}", p, e)),
         }
     }
     /// Create a simulation checkpoint.
@@ -490,14 +475,8 @@ impl<
             );
         };
 
-        let f = AtomicFile::create(&self.save_as)
-            .expect(&format!("error creating file {:?}", self.save_as));
-        match self.save_as.extension().and_then(|x| x.to_str()) {
-            Some("yaml") => serde_yaml::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("json") => serde_json::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("cbor") => serde_cbor::to_writer(&f, self).expect("error writing checkpoint?!"),
-            _ => panic!("I don't know how to create file {:?}", self.save_as),
-        }
+        save_to(&self.save_as, &*self)
+            .unwrap_or_else(|e| panic!("error writing checkpoint {:?}: {}", self.save_as, e));
     }
 
     /// Run a simulation
@@ -485,7 +485,11 @@ impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCar
         let e1 = self.system.energy();
         let recent_scale = (1.0 / self.moves as f64).sqrt();
         self.acceptance_rate *= 1. - recent_scale;
-        if let Some(e2) = self.system.plan_move(&mut self.rng, self.translation_scale) {
+        if let Some(e2) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
             if !self.reject_move(e1, e2) {
                 self.accepted_moves += 1;
                 self.acceptance_rate += recent_scale;
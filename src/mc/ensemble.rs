@@ -0,0 +1,65 @@
+//! Run an ensemble of independent simulations to get error bars.
+
+use super::energy::{AlignedDos, EnergyMC, EnergyMCParams};
+use super::*;
+use crate::system::*;
+
+use rayon::prelude::*;
+
+fn run_path(save_as: &std::path::Path, i: usize) -> std::path::PathBuf {
+    let stem = save_as
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("ensemble");
+    let ext = save_as.extension().and_then(|s| s.to_str()).unwrap_or("yaml");
+    save_as.with_file_name(format!("{}-{}.{}", stem, i, ext))
+}
+
+/// Run `num_runs` independent simulations for `moves` moves each, in
+/// parallel if `parallel` is set, and align and average their `lnw` by
+/// energy using [`EnergyMC::merge`].  Run `i` gets the seed
+/// `base_seed + i` (wrapping), and `save_as` with `-i` added to its
+/// name as its save path, which is only written if its save plugin
+/// fires during those moves.  Panics under the same conditions as
+/// [`EnergyMC::merge`].
+pub fn run_ensemble<S>(
+    params: &EnergyMCParams,
+    system: &S,
+    save_as: &std::path::Path,
+    base_seed: u64,
+    num_runs: usize,
+    moves: u64,
+    parallel: bool,
+) -> AlignedDos
+where
+    S: MovableSystem + Clone + Send + Sync + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let run = |i: usize| -> EnergyMC<S> {
+        let mut params = params.clone();
+        params.seed = Some(base_seed.wrapping_add(i as u64));
+        let mut mc = EnergyMC::from_params(params, system.clone(), run_path(save_as, i));
+        mc.run_for(moves);
+        mc
+    };
+    let runs: Vec<EnergyMC<S>> = if parallel {
+        (0..num_runs).into_par_iter().map(run).collect()
+    } else {
+        (0..num_runs).map(run).collect()
+    };
+    EnergyMC::merge(&runs)
+}
+
+#[test]
+fn same_base_seed_gives_same_ensemble() {
+    use crate::system::ising::{Ising, IsingParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParams::default();
+    let system = Ising::from(IsingParams { N: 4 });
+    let a = run_ensemble(&params, &system, &dir.path().join("a.yaml"), 42, 3, 20000, true);
+    let b = run_ensemble(&params, &system, &dir.path().join("b.yaml"), 42, 3, 20000, false);
+    assert_eq!(a, b);
+    assert!(a.num_runs.iter().any(|&n| n == 3));
+    // Different seeds should actually explore differently.
+    let c = run_ensemble(&params, &system, &dir.path().join("c.yaml"), 43, 3, 20000, true);
+    assert_ne!(a, c);
+}
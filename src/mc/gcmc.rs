@@ -0,0 +1,284 @@
+//! Metropolis Monte Carlo at fixed temperature and chemical potential.
+//!
+//! Along with the usual atom moves, this inserts and removes atoms, so
+//! the number of atoms can find its own value, as needed to study
+//! adsorption or phase coexistence.
+
+#![allow(non_snake_case)]
+
+use super::*;
+use crate::system::*;
+
+use super::plugin::Plugin;
+use crate::prettyfloat::PrettyFloat;
+use dimensioned::Dimensionless;
+use rand::{Rng, SeedableRng};
+use std::default::Default;
+
+/// The parameters needed to configure a grand canonical simulation.
+#[derive(Debug, AutoArgs)]
+pub struct GcmcParams {
+    /// The temperature.
+    pub T: Energy,
+    /// The chemical potential, relative to that of an ideal gas with
+    /// one atom per cubic sigma at the same temperature.
+    pub mu: Energy,
+    /// The seed for the random number generator.
+    pub seed: Option<u64>,
+    /// The typical distance to move an atom.
+    pub translation_scale: Option<Length>,
+    /// The fraction of moves which insert or remove an atom (default
+    /// 1/2).  Each is equally likely.
+    pub exchange_fraction: Option<f64>,
+    /// Parameters for reporting and terminating.
+    pub _report: plugin::ReportParams,
+    /// Parameters for saving.
+    pub _save: plugin::SaveParams,
+}
+
+impl Default for GcmcParams {
+    fn default() -> Self {
+        GcmcParams {
+            T: 1.0 * units::EPSILON,
+            mu: -1.0 * units::EPSILON,
+            seed: None,
+            translation_scale: None,
+            exchange_fraction: None,
+            _report: plugin::ReportParams::default(),
+            _save: plugin::SaveParams::default(),
+        }
+    }
+}
+
+/// A Monte Carlo simulation at fixed temperature and chemical potential.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Gcmc<S> {
+    /// The system we are simulating.
+    pub system: S,
+    /// The temperature.
+    pub T: Energy,
+    /// The chemical potential.
+    pub mu: Energy,
+    /// The number of moves that have been made.
+    pub moves: u64,
+    /// The number of moves that have been accepted.
+    pub accepted_moves: u64,
+    /// The number of insertions and removals that have been tried.
+    pub exchange_moves: u64,
+    /// The number of insertions and removals that have been accepted.
+    pub accepted_exchange_moves: u64,
+    /// The typical distance to move an atom.
+    pub translation_scale: Length,
+    /// The fraction of moves which insert or remove an atom.
+    pub exchange_fraction: f64,
+    /// The sum of the energy over every move.
+    pub energy_total: Energy,
+    /// The sum of the number of atoms over every move.
+    pub number_total: u64,
+    /// The random number generator.
+    pub rng: crate::rng::MyRng,
+    /// Where to save the resume file.
+    pub save_as: ::std::path::PathBuf,
+    report: plugin::Report,
+    save: plugin::Save,
+    manager: plugin::PluginManager,
+}
+
+impl<S: GrandSystem + VolumeSystem> Gcmc<S> {
+    /// The mean energy so far.
+    pub fn mean_energy(&self) -> Energy {
+        self.energy_total / self.moves as f64
+    }
+    /// The mean number of atoms so far.
+    pub fn mean_number(&self) -> f64 {
+        self.number_total as f64 / self.moves as f64
+    }
+    /// Should we accept adding an atom to `n` atoms, changing the
+    /// energy from `e1` to `e2`?  The atom is put at a uniformly random
+    /// position, so the ratio of weights is `V/(N+1) exp((mu - dE)/T)`.
+    fn accept_insertion(&mut self, n: usize, e1: Energy, e2: Energy) -> bool {
+        let v = *(self.system.volume() / units::VOLUME).value();
+        let ln_ratio = (v / (n as f64 + 1.0)).ln() + *((self.mu - (e2 - e1)) / self.T).value();
+        ln_ratio >= 0.0 || self.rng.gen::<f64>() < ln_ratio.exp()
+    }
+    /// Should we accept removing one of `n` atoms, changing the energy
+    /// from `e1` to `e2`?  This is the reverse of an insertion, so the
+    /// ratio of weights is `N/V exp(-(mu + dE)/T)`.
+    fn accept_removal(&mut self, n: usize, e1: Energy, e2: Energy) -> bool {
+        let v = *(self.system.volume() / units::VOLUME).value();
+        let ln_ratio = (n as f64 / v).ln() - *((self.mu + (e2 - e1)) / self.T).value();
+        ln_ratio >= 0.0 || self.rng.gen::<f64>() < ln_ratio.exp()
+    }
+}
+
+impl<S: GrandSystem + VolumeSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo
+    for Gcmc<S>
+{
+    type Params = GcmcParams;
+    type System = S;
+    fn from_params(params: GcmcParams, system: S, save_as: ::std::path::PathBuf) -> Self {
+        if !(params.T > Energy::new(0.0)) {
+            panic!(
+                "Invalid parameters: need a positive T, not {}",
+                params.T.pretty()
+            );
+        }
+        let exchange_fraction = params.exchange_fraction.unwrap_or(0.5);
+        if !(exchange_fraction >= 0.0 && exchange_fraction <= 1.0) {
+            panic!(
+                "Invalid parameters: exchange_fraction must be between 0 and 1, not {}",
+                exchange_fraction
+            );
+        }
+        let translation_scale = params
+            .translation_scale
+            .unwrap_or_else(|| system.natural_move_scale());
+        Gcmc {
+            system,
+            T: params.T,
+            mu: params.mu,
+            moves: 0,
+            accepted_moves: 0,
+            exchange_moves: 0,
+            accepted_exchange_moves: 0,
+            translation_scale,
+            exchange_fraction,
+            energy_total: Energy::new(0.0),
+            number_total: 0,
+            rng: crate::rng::MyRng::seed_from_u64(crate::rng::seed_or_random(params.seed)),
+            save_as,
+            report: plugin::Report::from(params._report),
+            save: plugin::Save::from(params._save),
+            manager: plugin::PluginManager::new(),
+        }
+    }
+
+    fn update_from_params(&mut self, params: Self::Params) {
+        self.report.update_from(params._report);
+        self.save.update_from(params._save);
+    }
+
+    fn move_once(&mut self) {
+        self.moves += 1;
+        let e1 = self.system.energy();
+        let n = GrandSystem::num_atoms(&self.system);
+        if self.rng.gen::<f64>() < self.exchange_fraction {
+            self.exchange_moves += 1;
+            if self.rng.gen::<bool>() {
+                if let Some(e2) = self.system.plan_add(&mut self.rng) {
+                    if self.accept_insertion(n, e1, e2) {
+                        self.accepted_exchange_moves += 1;
+                        self.system.confirm();
+                    } else {
+                        self.system.reject();
+                    }
+                }
+            } else if n > 0 {
+                let e2 = self.system.plan_remove(&mut self.rng);
+                if self.accept_removal(n, e1, e2) {
+                    self.accepted_exchange_moves += 1;
+                    self.system.confirm();
+                } else {
+                    self.system.reject();
+                }
+            }
+        } else if let Some(e2) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
+            if e2 <= e1 || self.rng.gen::<f64>() < (-*((e2 - e1) / self.T).value()).exp() {
+                self.accepted_moves += 1;
+                self.system.confirm();
+            } else {
+                self.system.reject();
+            }
+        }
+        self.energy_total += self.system.energy();
+        self.number_total += GrandSystem::num_atoms(&self.system) as u64;
+
+        let plugins = [&self.report as &dyn Plugin<Self>, &Logger, &self.save];
+        self.manager.run(self, &self.system, &plugins);
+    }
+    fn system(&self) -> &Self::System {
+        &self.system
+    }
+    fn system_mut(&mut self) -> &mut Self::System {
+        &mut self.system
+    }
+    fn num_moves(&self) -> u64 {
+        self.moves
+    }
+    fn num_accepted_moves(&self) -> u64 {
+        self.accepted_moves + self.accepted_exchange_moves
+    }
+    fn save_as(&self) -> ::std::path::PathBuf {
+        self.save_as.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Logger;
+impl<S: GrandSystem + VolumeSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<Gcmc<S>>
+    for Logger
+{
+    fn log(&self, mc: &Gcmc<S>, _sys: &S) {
+        if mc.moves > 0 {
+            println!(
+                "    T = {:.3}, mu = {:.3}: <E> = {:.6}, <N> = {:.6}, exchanges accepted {:.2}%",
+                mc.T.pretty(),
+                mc.mu.pretty(),
+                mc.mean_energy().pretty(),
+                PrettyFloat(mc.mean_number()),
+                PrettyFloat(
+                    100.0 * mc.accepted_exchange_moves as f64 / mc.exchange_moves.max(1) as f64
+                ),
+            );
+        }
+    }
+}
+
+#[test]
+fn dilute_square_well_has_the_ideal_gas_number() {
+    use crate::system::optsquare::{CellDimensionsGivenNumber, SquareWell, SquareWellNParams};
+    let T = 2.0 * units::EPSILON;
+    let mut sw_params = SquareWellNParams::default();
+    sw_params.N = 5;
+    sw_params._dim = CellDimensionsGivenNumber::FillingFraction(Unitless::new(0.0022));
+    let system = SquareWell::from(sw_params);
+    // An ideal gas has <N> = V exp(mu/T), so aim for five atoms.
+    let volume = *(system.volume() / units::VOLUME).value();
+    let expected = 5.0;
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = GcmcParams::default();
+    params.T = T;
+    params.mu = T * (expected / volume).ln();
+    params.translation_scale = Some(2.0 * units::SIGMA);
+    params.seed = Some(1);
+    let mut mc = Gcmc::from_params(params, system, dir.path().join("gcmc.yaml"));
+    for _ in 0..20000 {
+        mc.move_once();
+    }
+    mc.energy_total = Energy::new(0.0);
+    mc.number_total = 0;
+    mc.moves = 0;
+    for _ in 0..200000 {
+        mc.move_once();
+    }
+    println!("<N> = {}, expected {}", mc.mean_number(), expected);
+    assert!((mc.mean_number() / expected - 1.0).abs() < 0.05);
+}
+
+#[test]
+#[should_panic(expected = "exchange_fraction must be between 0 and 1")]
+fn exchange_fraction_is_a_fraction() {
+    use crate::system::optsquare::{SquareWell, SquareWellNParams};
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = GcmcParams::default();
+    params.exchange_fraction = Some(1.5);
+    Gcmc::from_params(
+        params,
+        SquareWell::from(SquareWellNParams::default()),
+        dir.path().join("gcmc.yaml"),
+    );
+}
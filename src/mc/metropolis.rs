@@ -0,0 +1,313 @@
+//! Plain Metropolis Monte Carlo at a fixed temperature.
+//!
+//! This is useful as an independent check on the flat-histogram
+//! methods, since canonical averages can be compared directly.
+
+#![allow(non_snake_case)]
+
+use super::*;
+use crate::system::*;
+
+use super::energy::ReportFormat;
+use super::plugin::Plugin;
+use crate::prettyfloat::PrettyFloat;
+use dimensioned::Dimensionless;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+use std::default::Default;
+
+/// The parameters needed to configure a Metropolis simulation.
+#[derive(Debug, AutoArgs)]
+pub struct MetropolisParams {
+    /// The temperature.
+    pub T: Energy,
+    /// The seed for the random number generator.
+    pub seed: Option<u64>,
+    /// The energy binsize for the histogram.
+    pub energy_bin: Option<Energy>,
+    /// The typical distance to move an atom.
+    pub translation_scale: Option<Length>,
+    /// Print the mean energy and heat capacity in this format when the
+    /// run finishes.
+    pub final_report: Option<ReportFormat>,
+    /// Parameters for reporting and terminating.
+    pub _report: plugin::ReportParams,
+    /// Parameters for saving.
+    pub _save: plugin::SaveParams,
+    /// Parameters for the time limit.
+    pub _time_limit: plugin::TimeLimitParams,
+}
+
+impl Default for MetropolisParams {
+    fn default() -> Self {
+        MetropolisParams {
+            T: 1.0 * units::EPSILON,
+            seed: None,
+            energy_bin: None,
+            translation_scale: None,
+            final_report: None,
+            _report: plugin::ReportParams::default(),
+            _save: plugin::SaveParams::default(),
+            _time_limit: plugin::TimeLimitParams::default(),
+        }
+    }
+}
+
+/// A canonical Monte Carlo simulation at fixed temperature.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Metropolis<S> {
+    /// The system we are simulating.
+    pub system: S,
+    /// The temperature.
+    pub T: Energy,
+    /// The number of moves that have been made.
+    pub moves: u64,
+    /// The number of moves that have been accepted.
+    pub accepted_moves: u64,
+    /// The typical distance to move an atom.
+    pub translation_scale: Length,
+    /// The sum of the energy over every move.
+    pub energy_total: Energy,
+    /// The sum of the energy squared over every move.
+    pub energy_squared_total: EnergySquared,
+    /// The lowest energy of the histogram.
+    pub min_energy: Energy,
+    /// The energy binsize of the histogram.
+    pub energy_bin: Energy,
+    /// The number of moves spent in each energy bin.
+    pub histogram: VecDeque<u64>,
+    /// The sum and number of measurements of each observable from
+    /// `System::data_to_collect`.
+    #[serde(default)]
+    pub observables: HashMap<Interned, (f64, u64)>,
+    /// The random number generator.
+    pub rng: crate::rng::MyRng,
+    /// Where to save the resume file.
+    pub save_as: ::std::path::PathBuf,
+    report: plugin::Report,
+    save: plugin::Save,
+    #[serde(default)]
+    time_limit: plugin::TimeLimit,
+    #[serde(default)]
+    final_report: Option<ReportFormat>,
+    manager: plugin::PluginManager,
+}
+
+impl<S: MovableSystem> Metropolis<S> {
+    /// The mean energy so far.
+    pub fn mean_energy(&self) -> Energy {
+        self.energy_total / self.moves as f64
+    }
+    /// The heat capacity (in units of the Boltzmann constant) so far.
+    pub fn heat_capacity(&self) -> Unitless {
+        let mean = self.mean_energy();
+        let variance = self.energy_squared_total / self.moves as f64 - mean * mean;
+        variance / (self.T * self.T)
+    }
+    /// The canonical averages so far, for the final report.
+    pub fn summary(&self) -> MetropolisSummary {
+        MetropolisSummary {
+            T: *(self.T / units::EPSILON).value(),
+            moves: self.moves,
+            acceptance_rate: self.accepted_moves as f64 / self.moves as f64,
+            mean_energy: *(self.mean_energy() / units::EPSILON).value(),
+            heat_capacity: *self.heat_capacity().value(),
+        }
+    }
+    /// Are we about to stop, because we have made all our moves, run
+    /// out of time, or been asked to shut down?
+    pub fn is_finishing(&self) -> bool {
+        self.report.am_all_done(self.moves, self.accepted_moves)
+            || self.time_limit.has_expired()
+            || plugin::shutdown_requested()
+    }
+    /// The mean of an observable collected by
+    /// `System::data_to_collect`, if it has been measured.
+    pub fn mean_observable(&self, name: Interned) -> Option<f64> {
+        match self.observables.get(&name) {
+            Some(&(total, count)) if count > 0 => Some(total / count as f64),
+            _ => None,
+        }
+    }
+    /// The energy at the bottom of bin `i` of the histogram.
+    pub fn bin_energy(&self, i: usize) -> Energy {
+        self.min_energy + (i as f64) * self.energy_bin
+    }
+    fn bin_for(&mut self, e: Energy) -> usize {
+        if self.histogram.is_empty() {
+            self.min_energy = (*(e / self.energy_bin).value()).floor() * self.energy_bin;
+            self.histogram.push_back(0);
+        }
+        while e < self.min_energy {
+            self.min_energy -= self.energy_bin;
+            self.histogram.push_front(0);
+        }
+        let i = *((e - self.min_energy) / self.energy_bin).value() as usize;
+        while i >= self.histogram.len() {
+            self.histogram.push_back(0);
+        }
+        i
+    }
+}
+
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo
+    for Metropolis<S>
+{
+    type Params = MetropolisParams;
+    type System = S;
+    fn from_params(params: MetropolisParams, system: S, save_as: ::std::path::PathBuf) -> Self {
+        let energy_bin = params
+            .energy_bin
+            .unwrap_or(system.delta_energy().unwrap_or(Energy::new(1.0)));
+        let translation_scale = params
+            .translation_scale
+            .unwrap_or_else(|| system.natural_move_scale());
+        Metropolis {
+            system,
+            T: params.T,
+            moves: 0,
+            accepted_moves: 0,
+            translation_scale,
+            energy_total: Energy::new(0.0),
+            energy_squared_total: EnergySquared::new(0.0),
+            min_energy: Energy::new(0.0),
+            energy_bin,
+            histogram: VecDeque::new(),
+            observables: HashMap::new(),
+            rng: crate::rng::MyRng::seed_from_u64(params.seed.unwrap_or(0)),
+            save_as,
+            report: plugin::Report::from(params._report),
+            save: plugin::Save::from(params._save),
+            time_limit: plugin::TimeLimit::from(params._time_limit),
+            final_report: params.final_report,
+            manager: plugin::PluginManager::new(),
+        }
+    }
+
+    fn update_from_params(&mut self, params: Self::Params) {
+        self.report.update_from(params._report);
+        self.save.update_from(params._save);
+        self.time_limit.update_from(params._time_limit);
+        self.final_report = params.final_report;
+    }
+
+    fn move_once(&mut self) {
+        self.moves += 1;
+        let e1 = self.system.energy();
+        if let Some(e2) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
+            if e2 <= e1 || self.rng.gen::<f64>() < (-*((e2 - e1) / self.T).value()).exp() {
+                self.accepted_moves += 1;
+                self.system.confirm();
+            }
+        }
+        let e = self.system.energy();
+        self.energy_total += e;
+        self.energy_squared_total += e * e;
+        let i = self.bin_for(e);
+        self.histogram[i] += 1;
+        for (k, d) in self.system.data_to_collect(self.moves).into_iter() {
+            let entry = self.observables.entry(k).or_insert((0.0, 0));
+            entry.0 += d;
+            entry.1 += 1;
+        }
+
+        let plugins = [
+            &self.report as &dyn Plugin<Self>,
+            &Logger,
+            &FinalReport,
+            &self.save,
+            &self.time_limit,
+        ];
+        self.manager.run(self, &self.system, &plugins);
+    }
+    fn system(&self) -> &Self::System {
+        &self.system
+    }
+    fn system_mut(&mut self) -> &mut Self::System {
+        &mut self.system
+    }
+    fn num_moves(&self) -> u64 {
+        self.moves
+    }
+    fn num_accepted_moves(&self) -> u64 {
+        self.accepted_moves
+    }
+    fn save_as(&self) -> ::std::path::PathBuf {
+        self.save_as.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Logger;
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<Metropolis<S>>
+    for Logger
+{
+    fn log(&self, mc: &Metropolis<S>, _sys: &S) {
+        if mc.moves > 0 {
+            println!(
+                "    T = {:.3}: <E> = {:.6}, C_V = {:.4}, accepted {:.2}%",
+                mc.T.pretty(),
+                mc.mean_energy().pretty(),
+                PrettyFloat(*mc.heat_capacity().value()),
+                PrettyFloat(100.0 * mc.accepted_moves as f64 / mc.moves as f64),
+            );
+        }
+    }
+}
+
+/// The canonical averages of a Metropolis run, as printed in its final
+/// report.  Energies are in units of epsilon.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetropolisSummary {
+    /// The temperature.
+    pub T: f64,
+    /// The number of moves made.
+    pub moves: u64,
+    /// The fraction of moves that were accepted.
+    pub acceptance_rate: f64,
+    /// The mean energy.
+    pub mean_energy: f64,
+    /// The heat capacity in units of the Boltzmann constant.
+    pub heat_capacity: f64,
+}
+
+impl MetropolisSummary {
+    /// Format the summary for printing.
+    pub fn format(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Human => format!(
+                "Finished after {:.3} moves, {:.2}% accepted\n    T = {:.5}: <E> = {:.6}, C_V = {:.5}",
+                PrettyFloat(self.moves as f64),
+                PrettyFloat(100.0 * self.acceptance_rate),
+                PrettyFloat(self.T),
+                PrettyFloat(self.mean_energy),
+                PrettyFloat(self.heat_capacity),
+            ),
+            ReportFormat::KeyValue => format!(
+                "T={}\nmoves={}\nacceptance_rate={}\nmean_energy={}\nheat_capacity={}",
+                self.T, self.moves, self.acceptance_rate, self.mean_energy, self.heat_capacity
+            ),
+            ReportFormat::Json => {
+                serde_json::to_string(self).expect("error formatting final report")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FinalReport;
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<Metropolis<S>>
+    for FinalReport
+{
+    fn save(&self, mc: &Metropolis<S>, _sys: &S) {
+        if let Some(format) = mc.final_report {
+            if mc.moves > 0 && mc.is_finishing() {
+                println!("{}", mc.summary().format(format));
+            }
+        }
+    }
+}
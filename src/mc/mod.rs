@@ -1,16 +1,22 @@
 //! These are different Monte Carlo algorithms.
 
+pub mod anneal;
 pub mod binning;
 pub mod energy;
+pub mod ensemble;
 pub mod tempering;
 pub mod energy_binning;
 pub mod energy_number;
 pub mod energy_production;
 pub mod energy_replicas;
 pub mod energy_transposed;
+pub mod gcmc;
 pub mod grand_transposed;
+pub mod metropolis;
+pub mod npt;
 pub mod number;
 pub mod plugin;
+pub mod windows;
 
 use crate::system::*;
 use auto_args::AutoArgs;
@@ -33,6 +39,82 @@ enum Params<MP, SP> {
 
 const VERSION: &str = git_version::git_describe!("--always", "--dirty");
 
+/// What a dry run found: a description of what the run would need,
+/// or why it could not work.
+pub type DryRun = Result<String, String>;
+
+/// An error saving or loading a simulation file.
+#[derive(Debug)]
+pub enum SaveError {
+    /// The file extension does not name a format we know.
+    UnknownFormat(::std::path::PathBuf),
+    /// We could not read or write the file.
+    Io(::std::io::Error),
+    /// The contents could not be converted to or from the format.
+    Format(String),
+}
+
+impl ::std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            SaveError::UnknownFormat(p) => write!(
+                f,
+                "I don't know how to handle file {:?} (try .yaml, .json, .cbor or .bin)",
+                p
+            ),
+            SaveError::Io(e) => write!(f, "{}", e),
+            SaveError::Format(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl ::std::error::Error for SaveError {}
+
+impl From<::std::io::Error> for SaveError {
+    fn from(e: ::std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+fn format_error<E: ::std::fmt::Display>(e: E) -> SaveError {
+    SaveError::Format(e.to_string())
+}
+
+/// Atomically write `x` to `path` in the format given by its extension:
+/// `yaml`, `json`, `cbor`, or `bin` (bincode, which is the most compact
+/// and fastest for large runs).
+pub fn save_to<T: serde::Serialize>(path: &::std::path::Path, x: &T) -> Result<(), SaveError> {
+    let extension = path.extension().and_then(|x| x.to_str());
+    match extension {
+        Some("yaml") | Some("json") | Some("cbor") | Some("bin") => (),
+        _ => return Err(SaveError::UnknownFormat(path.to_path_buf())),
+    }
+    let f = AtomicFile::create(path)?;
+    match extension {
+        Some("yaml") => serde_yaml::to_writer(&f, x).map_err(format_error),
+        Some("json") => serde_json::to_writer(&f, x).map_err(format_error),
+        Some("cbor") => serde_cbor::to_writer(&f, x).map_err(format_error),
+        _ => bincode::serialize_into(&f, x).map_err(format_error),
+    }
+}
+
+/// Read a `T` from `path` in the format given by its extension, as
+/// written by [`save_to`].
+pub fn load_from<T: serde::de::DeserializeOwned>(path: &::std::path::Path) -> Result<T, SaveError> {
+    let extension = path.extension().and_then(|x| x.to_str());
+    match extension {
+        Some("yaml") | Some("json") | Some("cbor") | Some("bin") => (),
+        _ => return Err(SaveError::UnknownFormat(path.to_path_buf())),
+    }
+    let f = ::std::io::BufReader::new(::std::fs::File::open(path)?);
+    match extension {
+        Some("yaml") => serde_yaml::from_reader(f).map_err(format_error),
+        Some("json") => serde_json::from_reader(f).map_err(format_error),
+        Some("cbor") => serde_cbor::from_reader(f).map_err(format_error),
+        _ => bincode::deserialize_from(f).map_err(format_error),
+    }
+}
+
 /// A Monte Carlo algorithm.
 pub trait MonteCarlo: Sized + serde::Serialize + ::serde::de::DeserializeOwned {
     /// A type defining a new Monte Carlo.
@@ -51,8 +133,24 @@ pub trait MonteCarlo: Sized + serde::Serialize + ::serde::de::DeserializeOwned {
     /// extend a simulation.
     fn update_from_params(&mut self, _params: Self::Params) {}
 
-    /// Create a new simulation from command-line flags.
+    /// What a dry run of these parameters on `system` finds, if the
+    /// parameters ask for one instead of a simulation.  By default they
+    /// never do.
+    fn dry_run(_params: &Self::Params, _system: &Self::System) -> Option<DryRun> {
+        None
+    }
+
+    /// Create a new simulation from command-line flags.  This panics if
+    /// they ask for a dry run, which needs `from_args_or_dry_run`.
     fn from_args<S: AutoArgs + Into<Self::System>>() -> Self {
+        Self::from_args_or_dry_run::<S>()
+            .unwrap_or_else(|_| panic!("this program cannot make a dry run"))
+    }
+
+    /// Create a new simulation from command-line flags, as `from_args`
+    /// does, or if they ask for a dry run (see `dry_run`), return what
+    /// it found for the caller to report.
+    fn from_args_or_dry_run<S: AutoArgs + Into<Self::System>>() -> Result<Self, DryRun> {
         println!("git version: {}", VERSION);
         match <Params<Self::Params, S>>::from_args() {
             Params::_Params {
@@ -68,55 +166,42 @@ pub trait MonteCarlo: Sized + serde::Serialize + ::serde::de::DeserializeOwned {
                         .unwrap()
                 }
                 if let Some(ref save_as) = save_as {
-                    if let Ok(f) = ::std::fs::File::open(save_as) {
-                        let mut s = match save_as.extension().and_then(|x| x.to_str()) {
-                            Some("yaml") => serde_yaml::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("json") => serde_json::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("cbor") => serde_cbor::from_reader::<Self, _>(&f)
-                                .expect("error parsing save-as file"),
-                            _ => panic!("I don't know how to read file {:?}", f),
-                        };
+                    if save_as.exists() {
+                        let mut s: Self = load_from(save_as).unwrap_or_else(|e| {
+                            panic!("error parsing save-as file {:?}: {}", save_as, e)
+                        });
                         println!("Resuming from file {:?}", save_as);
                         s.update_from_params(_mc);
                         s.system_mut().update_caches();
-                        return s;
-                    } else {
-                        return Self::from_params(_mc, _sys.into(), save_as.clone());
+                        return Ok(s);
                     }
                 }
+                let system = _sys.into();
+                if let Some(dry_run) = Self::dry_run(&_mc, &system) {
+                    return Err(dry_run);
+                }
                 let save_as = save_as.unwrap_or(::std::path::PathBuf::from("resume.yaml"));
-                Self::from_params(_mc, _sys.into(), save_as)
+                Ok(Self::from_params(_mc, system, save_as))
             }
             Params::ResumeFrom(p) => {
-                let f = ::std::fs::File::open(&p).expect(&format!("error reading file {:?}", &p));
-                match p.extension().and_then(|x| x.to_str()) {
-                    Some("yaml") => {
-                        serde_yaml::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("json") => {
-                        serde_json::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("cbor") => {
-                        serde_cbor::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    _ => panic!("I don't know how to read file {:?}", f),
-                }
+                Ok(load_from(&p)
+                    .unwrap_or_else(|e| panic!("error reading checkpoint {:?}: {}", p, e)))
             }
         }
     }
 
+    /// Checkpoint and exit cleanly on SIGINT or SIGTERM.  This
+    /// replaces the process-wide signal handlers, which is why it is
+    /// not done by default.
+    #[cfg(feature = "signals")]
+    fn handle_signals(&self) {
+        plugin::install_signal_handlers();
+    }
+
     /// Create a simulation checkpoint.
     fn checkpoint(&self) {
-        let f = AtomicFile::create(self.save_as())
-            .expect(&format!("error creating file {:?}", self.save_as()));
-        match self.save_as().extension().and_then(|x| x.to_str()) {
-            Some("yaml") => serde_yaml::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("json") => serde_json::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("cbor") => serde_cbor::to_writer(&f, self).expect("error writing checkpoint?!"),
-            _ => panic!("I don't know how to create file {:?}", self.save_as()),
-        }
+        save_to(&self.save_as(), self)
+            .unwrap_or_else(|e| panic!("error writing checkpoint {:?}: {}", self.save_as(), e));
     }
 
     /// Make one random move, collecting appropriate statistics.
@@ -139,6 +224,19 @@ pub trait MonteCarlo: Sized + serde::Serialize + ::serde::de::DeserializeOwned {
     /// The number of accepted moves.
     fn num_accepted_moves(&self) -> u64;
 
+    /// The range of energies the method currently considers important,
+    /// if it has one.
+    fn important_energy_range(&self) -> Option<(Energy, Energy)> {
+        None
+    }
+
+    /// The current entropy, as `(energy, lnw, histogram)` for each
+    /// visited energy bin in order of energy, for plotting as we run.
+    /// Methods that do not find an entropy leave this empty.
+    fn entropy_curve(&self) -> Vec<(Energy, f64, u64)> {
+        Vec::new()
+    }
+
     /// The path to save the file at.
     fn save_as(&self) -> ::std::path::PathBuf;
 }
@@ -0,0 +1,255 @@
+//! Metropolis Monte Carlo at fixed temperature and pressure.
+//!
+//! Along with the usual atom moves, this makes moves that change the
+//! volume of the box, so the density can find its own value.
+
+#![allow(non_snake_case)]
+
+use super::*;
+use crate::system::*;
+
+use super::plugin::Plugin;
+use crate::prettyfloat::PrettyFloat;
+use dimensioned::Dimensionless;
+use rand::{Rng, SeedableRng};
+use std::default::Default;
+
+/// The parameters needed to configure a constant-pressure simulation.
+#[derive(Debug, AutoArgs)]
+pub struct NptParams {
+    /// The temperature.
+    pub T: Energy,
+    /// The pressure.
+    pub P: Pressure,
+    /// The seed for the random number generator.
+    pub seed: Option<u64>,
+    /// The typical distance to move an atom.
+    pub translation_scale: Option<Length>,
+    /// The largest change in volume to attempt, as a fraction of the
+    /// initial volume.
+    pub max_volume_change: Option<f64>,
+    /// The fraction of moves which change the volume (default 1/(N+1)).
+    pub volume_move_fraction: Option<f64>,
+    /// Parameters for reporting and terminating.
+    pub _report: plugin::ReportParams,
+    /// Parameters for saving.
+    pub _save: plugin::SaveParams,
+}
+
+impl Default for NptParams {
+    fn default() -> Self {
+        NptParams {
+            T: 1.0 * units::EPSILON,
+            P: 1.0 * units::EPSILON / units::VOLUME,
+            seed: None,
+            translation_scale: None,
+            max_volume_change: None,
+            volume_move_fraction: None,
+            _report: plugin::ReportParams::default(),
+            _save: plugin::SaveParams::default(),
+        }
+    }
+}
+
+/// A Monte Carlo simulation at fixed temperature and pressure.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Npt<S> {
+    /// The system we are simulating.
+    pub system: S,
+    /// The temperature.
+    pub T: Energy,
+    /// The pressure.
+    pub P: Pressure,
+    /// The number of moves that have been made.
+    pub moves: u64,
+    /// The number of moves that have been accepted.
+    pub accepted_moves: u64,
+    /// The number of volume moves that have been made.
+    pub volume_moves: u64,
+    /// The number of volume moves that have been accepted.
+    pub accepted_volume_moves: u64,
+    /// The typical distance to move an atom.
+    pub translation_scale: Length,
+    /// The largest change in volume to attempt.
+    pub max_volume_change: Volume,
+    /// The fraction of moves which change the volume.
+    pub volume_move_fraction: f64,
+    /// The sum of the energy over every move.
+    pub energy_total: Energy,
+    /// The sum of the volume over every move.
+    pub volume_total: Volume,
+    /// The random number generator.
+    pub rng: crate::rng::MyRng,
+    /// Where to save the resume file.
+    pub save_as: ::std::path::PathBuf,
+    report: plugin::Report,
+    save: plugin::Save,
+    manager: plugin::PluginManager,
+}
+
+impl<S: VolumeSystem> Npt<S> {
+    /// The mean energy so far.
+    pub fn mean_energy(&self) -> Energy {
+        self.energy_total / self.moves as f64
+    }
+    /// The mean volume so far.
+    pub fn mean_volume(&self) -> Volume {
+        self.volume_total / self.moves as f64
+    }
+    /// Should we accept a volume change from `v1` to `v2`, changing
+    /// the energy from `e1` to `e2`?  Volumes are proposed uniformly,
+    /// so the weight is `V^N exp(-(E + PV)/T)`.
+    fn accept_volume_change(&mut self, e1: Energy, e2: Energy, v1: Volume, v2: Volume) -> bool {
+        let n = self.system.num_atoms() as f64;
+        let ln_ratio =
+            -*((e2 - e1 + self.P * (v2 - v1)) / self.T).value() + n * (*(v2 / v1).value()).ln();
+        ln_ratio >= 0.0 || self.rng.gen::<f64>() < ln_ratio.exp()
+    }
+}
+
+impl<S: VolumeSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo for Npt<S> {
+    type Params = NptParams;
+    type System = S;
+    fn from_params(params: NptParams, system: S, save_as: ::std::path::PathBuf) -> Self {
+        let max_volume_change = params.max_volume_change.unwrap_or(0.01) * system.volume();
+        let volume_move_fraction = params
+            .volume_move_fraction
+            .unwrap_or(1.0 / (system.num_atoms() as f64 + 1.0));
+        Npt {
+            system,
+            T: params.T,
+            P: params.P,
+            moves: 0,
+            accepted_moves: 0,
+            volume_moves: 0,
+            accepted_volume_moves: 0,
+            translation_scale: params.translation_scale.unwrap_or(0.05 * units::SIGMA),
+            max_volume_change,
+            volume_move_fraction,
+            energy_total: Energy::new(0.0),
+            volume_total: Volume::new(0.0),
+            rng: crate::rng::MyRng::seed_from_u64(params.seed.unwrap_or(0)),
+            save_as,
+            report: plugin::Report::from(params._report),
+            save: plugin::Save::from(params._save),
+            manager: plugin::PluginManager::new(),
+        }
+    }
+
+    fn update_from_params(&mut self, params: Self::Params) {
+        self.report.update_from(params._report);
+        self.save.update_from(params._save);
+    }
+
+    fn move_once(&mut self) {
+        self.moves += 1;
+        let e1 = self.system.energy();
+        if self.rng.gen::<f64>() < self.volume_move_fraction {
+            self.volume_moves += 1;
+            let v1 = self.system.volume();
+            let v2 = v1 + self.max_volume_change * self.rng.gen_range(-1.0, 1.0);
+            if v2 > Volume::new(0.0) {
+                if let Some(e2) = self.system.plan_change_volume(v2) {
+                    if self.accept_volume_change(e1, e2, v1, v2) {
+                        self.accepted_volume_moves += 1;
+                        self.system.confirm();
+                    } else {
+                        self.system.reject();
+                    }
+                }
+            }
+        } else if let Some(e2) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
+            if e2 <= e1 || self.rng.gen::<f64>() < (-*((e2 - e1) / self.T).value()).exp() {
+                self.accepted_moves += 1;
+                self.system.confirm();
+            } else {
+                self.system.reject();
+            }
+        }
+        self.energy_total += self.system.energy();
+        self.volume_total += self.system.volume();
+
+        let plugins = [&self.report as &dyn Plugin<Self>, &Logger, &self.save];
+        self.manager.run(self, &self.system, &plugins);
+    }
+    fn system(&self) -> &Self::System {
+        &self.system
+    }
+    fn system_mut(&mut self) -> &mut Self::System {
+        &mut self.system
+    }
+    fn num_moves(&self) -> u64 {
+        self.moves
+    }
+    fn num_accepted_moves(&self) -> u64 {
+        self.accepted_moves + self.accepted_volume_moves
+    }
+    fn save_as(&self) -> ::std::path::PathBuf {
+        self.save_as.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Logger;
+impl<S: VolumeSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<Npt<S>> for Logger {
+    fn log(&self, mc: &Npt<S>, _sys: &S) {
+        if mc.moves > 0 {
+            println!(
+                "    T = {:.3}, P = {:.3}: <E> = {:.6}, <V> = {:.6}, volume moves accepted {:.2}%",
+                mc.T.pretty(),
+                mc.P.pretty(),
+                mc.mean_energy().pretty(),
+                mc.mean_volume().pretty(),
+                PrettyFloat(
+                    100.0 * mc.accepted_volume_moves as f64 / mc.volume_moves.max(1) as f64
+                ),
+            );
+        }
+    }
+}
+
+#[test]
+fn dilute_square_well_obeys_the_ideal_gas_law() {
+    use crate::system::optsquare::{CellDimensionsGivenNumber, SquareWell, SquareWellNParams};
+    let natoms = 5;
+    let T = 2.0 * units::EPSILON;
+    let P = 0.01 * units::EPSILON / units::VOLUME;
+    let mut sw_params = SquareWellNParams::default();
+    sw_params.N = natoms;
+    sw_params._dim = CellDimensionsGivenNumber::FillingFraction(Unitless::new(0.0022));
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = NptParams::default();
+    params.T = T;
+    params.P = P;
+    params.translation_scale = Some(2.0 * units::SIGMA);
+    params.max_volume_change = Some(0.5);
+    params.volume_move_fraction = Some(0.5);
+    let mut mc = Npt::from_params(
+        params,
+        SquareWell::from(sw_params),
+        dir.path().join("npt.yaml"),
+    );
+    for _ in 0..20000 {
+        mc.move_once();
+    }
+    mc.energy_total = Energy::new(0.0);
+    mc.volume_total = Volume::new(0.0);
+    mc.moves = 0;
+    for _ in 0..200000 {
+        mc.move_once();
+    }
+    // With volumes proposed uniformly, an ideal gas has <V> = (N+1)T/P.
+    // The second virial correction is well under a percent here.
+    let expected = (natoms as f64 + 1.0) * T / P;
+    let ratio = *(mc.mean_volume() / expected).value();
+    println!(
+        "<V> = {}, expected {}",
+        mc.mean_volume().pretty(),
+        expected.pretty()
+    );
+    assert!((ratio - 1.0).abs() < 0.05);
+}
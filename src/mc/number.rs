@@ -559,10 +559,11 @@ impl<S: GrandSystem + serde::Serialize + serde::de::DeserializeOwned> MonteCarlo
 
         if self.rng.gen::<f64>() > self.addremove_probability {
             self.bins.num_translation_attempts[e1.N] += 1;
-            if let Some(e2) = self
-                .system
-                .plan_move(&mut self.rng, self.bins.translation_scale[e1.N])
-            {
+            if let Some(e2) = self.system.plan_move(
+                &mut self.rng,
+                self.bins.translation_scale[e1.N],
+                MoveDistribution::Gaussian,
+            ) {
                 let e2 = State { E: e2, N: e1.N };
                 self.bins.prepare_for_state(e2);
                 if !self.reject_move(e1, e2) {
@@ -3,9 +3,13 @@
 
 use super::*;
 
+use super::energy::{EnergyMC, SadState};
 use crate::prettyfloat::PrettyFloat;
-use std::cell::Cell;
+use crate::system::*;
+use dimensioned::Abs;
+use std::cell::{Cell, RefCell};
 use std::default::Default;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time;
 
 /// A `Plugin` is an object that can be used to configure a MonteCarlo
@@ -37,6 +41,12 @@ pub trait Plugin<MC: MonteCarlo> {
     /// care about.  This is called in response to `Action::Save`,
     /// `Action::Log` and `Action::Exit`.
     fn log(&self, _mc: &MC, _sys: &MC::System) {}
+    /// Plugins with higher priority are run (and logged and saved)
+    /// before those with lower priority.  Plugins with equal priority
+    /// keep the order in which they were given.
+    fn priority(&self) -> i32 {
+        0
+    }
 }
 
 /// A time when we want to be run.
@@ -69,6 +79,73 @@ impl Action {
     }
 }
 
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Ask every running simulation to save and exit after its current
+/// move.  This is safe to call from a signal handler.
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Has a shutdown been requested?
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Install handlers for SIGINT and SIGTERM which call
+/// [`request_shutdown`], so that the simulation checkpoints before
+/// exiting.
+///
+/// This modifies the process-wide signal handlers, so it is up to the
+/// program (not the library) to decide whether to call it.
+#[cfg(feature = "signals")]
+pub fn install_signal_handlers() {
+    for &signal in &[signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        // Safe because request_shutdown only stores to an atomic.
+        unsafe { signal_hook::low_level::register(signal, request_shutdown) }
+            .expect("unable to install signal handler");
+    }
+}
+
+/// A list of extra plugins added at run time, e.g. by a library user.
+/// These are not saved in checkpoints, so they must be added again
+/// after resuming.
+pub struct PluginList<MC>(Vec<Box<dyn Plugin<MC> + Send>>);
+
+impl<MC> Default for PluginList<MC> {
+    fn default() -> Self {
+        PluginList(Vec::new())
+    }
+}
+impl<MC> ::std::fmt::Debug for PluginList<MC> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "PluginList({} plugins)", self.0.len())
+    }
+}
+impl<MC> PluginList<MC> {
+    /// Add a plugin to the list.
+    pub fn push(&mut self, p: Box<dyn Plugin<MC> + Send>) {
+        self.0.push(p);
+    }
+    /// The plugins in this list.
+    pub fn plugins(&self) -> &[Box<dyn Plugin<MC> + Send>] {
+        &self.0
+    }
+}
+
+fn in_priority_order<'a, MC: MonteCarlo>(
+    builtin: &[&'a dyn Plugin<MC>],
+    extra: &'a [Box<dyn Plugin<MC> + Send>],
+) -> Vec<&'a dyn Plugin<MC>> {
+    let mut plugins: Vec<&dyn Plugin<MC>> = builtin
+        .iter()
+        .cloned()
+        .chain(extra.iter().map(|p| &**p as &dyn Plugin<MC>))
+        .collect();
+    plugins.sort_by_key(|p| ::std::cmp::Reverse(p.priority()));
+    plugins
+}
+
 /// A helper to enable Monte Carlo implementations to easily run their
 /// plugins without duplicating code.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -90,11 +167,71 @@ impl PluginManager {
     /// Run all the plugins, if needed.  This should always be called
     /// with the same set of plugins.  If you want different sets of
     /// plugins, use different managers.
+    ///
+    /// Every plugin is run in order of [`Plugin::priority`], even after
+    /// an earlier one asks to exit.  The strongest action requested by
+    /// any plugin is then taken: on `Action::Save` or `Action::Exit` the
+    /// simulation is checkpointed and then every plugin gets to save (in
+    /// the same order) before we exit.
     pub fn run<MC: MonteCarlo>(&self, mc: &MC, sys: &MC::System, plugins: &[&dyn Plugin<MC>]) {
-        let moves = self.moves.get() + 1;
+        self.run_with_extra(mc, sys, plugins, &[]);
+    }
+    /// Run the built-in plugins together with a list of extra plugins,
+    /// such as those in a [`PluginList`].
+    pub fn run_with_extra<MC: MonteCarlo>(
+        &self,
+        mc: &MC,
+        sys: &MC::System,
+        builtin: &[&dyn Plugin<MC>],
+        extra: &[Box<dyn Plugin<MC> + Send>],
+    ) {
+        self.run_after_moves(1, mc, sys, builtin, extra);
+    }
+    /// The number of moves until the plugins next need to run.  A Monte
+    /// Carlo may make up to this many moves before calling
+    /// [`PluginManager::run_after_moves`] without changing when any
+    /// plugin runs.
+    pub fn moves_until_due(&self) -> u64 {
+        self.period.get().saturating_sub(self.moves.get()).max(1)
+    }
+    /// Like [`PluginManager::run_with_extra`], but after `moves` moves
+    /// rather than one, which should be no more than
+    /// [`PluginManager::moves_until_due`].
+    pub fn run_after_moves<MC: MonteCarlo>(
+        &self,
+        moves: u64,
+        mc: &MC,
+        sys: &MC::System,
+        builtin: &[&dyn Plugin<MC>],
+        extra: &[Box<dyn Plugin<MC> + Send>],
+    ) {
+        if self.run_or_stop(moves, mc, sys, builtin, extra) == Action::Exit {
+            ::std::process::exit(0);
+        }
+    }
+    /// Like [`PluginManager::run_after_moves`], but rather than exiting
+    /// the program when a plugin (or a shutdown request) asks us to
+    /// stop, return `Action::Exit` after saving, so that the caller can
+    /// return.  Otherwise this returns `Action::None`.
+    pub fn run_or_stop<MC: MonteCarlo>(
+        &self,
+        moves: u64,
+        mc: &MC,
+        sys: &MC::System,
+        builtin: &[&dyn Plugin<MC>],
+        extra: &[Box<dyn Plugin<MC> + Send>],
+    ) -> Action {
+        if shutdown_requested() {
+            let plugins = in_priority_order(builtin, extra);
+            if self.save_if_shutdown_requested(mc, sys, &plugins) {
+                return Action::Exit;
+            }
+        }
+        let moves = self.moves.get() + moves;
         self.moves.set(moves);
         if moves >= self.period.get() {
             self.moves.set(0);
+            let plugins = in_priority_order(builtin, extra);
             let mut todo = plugin::Action::None;
             for p in plugins.iter() {
                 todo = todo.and(p.run(mc, sys));
@@ -106,21 +243,10 @@ impl PluginManager {
                 }
             }
             if todo >= plugin::Action::Save {
-                let time = time::Instant::now();
-                mc.checkpoint();
-                for p in plugins.iter() {
-                    p.save(mc, sys);
-                }
-                let saving_time = time.elapsed().as_secs();
-                if saving_time > 5 {
-                    println!(
-                        "        checkpointing took {}",
-                        format_duration(saving_time)
-                    );
-                }
+                self.save(mc, sys, &plugins);
             }
             if todo >= plugin::Action::Exit {
-                ::std::process::exit(0);
+                return Action::Exit;
             }
             // run plugins every trillion iterations minimum
             let mut new_period = 1u64 << 40;
@@ -141,6 +267,37 @@ impl PluginManager {
             }
             self.period.set(new_period);
         }
+        Action::None
+    }
+    fn save<MC: MonteCarlo>(&self, mc: &MC, sys: &MC::System, plugins: &[&dyn Plugin<MC>]) {
+        let time = time::Instant::now();
+        mc.checkpoint();
+        for p in plugins.iter() {
+            p.save(mc, sys);
+        }
+        let saving_time = time.elapsed().as_secs();
+        if saving_time > 5 {
+            println!(
+                "        checkpointing took {}",
+                format_duration(saving_time)
+            );
+        }
+    }
+    /// If a shutdown has been requested (see [`request_shutdown`]),
+    /// checkpoint and return `true`.  The caller should then exit.
+    pub fn save_if_shutdown_requested<MC: MonteCarlo>(
+        &self,
+        mc: &MC,
+        sys: &MC::System,
+        plugins: &[&dyn Plugin<MC>],
+    ) -> bool {
+        if shutdown_requested() {
+            println!("Shutting down after {} moves.", mc.num_moves());
+            self.save(mc, sys, plugins);
+            true
+        } else {
+            false
+        }
     }
 }
 
@@ -159,7 +316,7 @@ pub struct Report {
 
 /// The parameters to define the report information as well as stop
 /// time (which is part of the report).
-#[derive(AutoArgs, Debug, Clone)]
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone)]
 pub struct ReportParams {
     /// The maximum number of iterations to run.
     pub max_iter: Option<u64>,
@@ -203,6 +360,16 @@ impl Report {
         self.quiet = other.quiet;
     }
 
+    /// A copy of this report that never asks us to stop, for running a
+    /// fixed number of moves regardless of `max_iter`.
+    pub fn without_limits(&self) -> Report {
+        Report {
+            max_iter: TimeToRun::Never,
+            max_independent_samples: None,
+            ..self.clone()
+        }
+    }
+
     /// Print a log message
     pub fn print(&self, moves: u64, independent_samples: u64) {
         if self.quiet {
@@ -323,7 +490,7 @@ pub struct Save {
 }
 
 /// The parameter to define the save schedule
-#[derive(AutoArgs, Debug, Clone)]
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone)]
 pub struct SaveParams {
     /// Maximum time between saves in hours
     pub save_time: Option<f64>,
@@ -404,6 +571,281 @@ impl<MC: MonteCarlo> Plugin<MC> for Save {
     }
 }
 
+/// A plugin that stops the simulation (saving first) once a wall-clock
+/// time limit is reached.  The clock starts when the plugin is created
+/// or loaded, i.e. when the simulation starts or resumes, so the limit
+/// applies to each job separately.  The clock is only read every few
+/// thousand moves, so it is cheap to leave on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeLimit {
+    #[serde(skip, default = "time::Instant::now")]
+    start: time::Instant,
+    /// The maximum time to run.
+    limit: Option<time::Duration>,
+}
+
+/// The parameters to define the time limit.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone)]
+pub struct TimeLimitParams {
+    /// Maximum wall-clock time to run in hours
+    pub time_limit: Option<f64>,
+}
+
+impl Default for TimeLimitParams {
+    fn default() -> Self {
+        TimeLimitParams { time_limit: None }
+    }
+}
+impl Default for TimeLimit {
+    fn default() -> Self {
+        TimeLimit::from(TimeLimitParams::default())
+    }
+}
+impl From<TimeLimitParams> for TimeLimit {
+    fn from(params: TimeLimitParams) -> Self {
+        TimeLimit {
+            start: time::Instant::now(),
+            limit: params
+                .time_limit
+                .map(|h| time::Duration::from_secs_f64(60. * 60. * h)),
+        }
+    }
+}
+/// How many moves between looking at the clock.
+const TIME_LIMIT_CHECK_MOVES: u64 = 1 << 12;
+impl TimeLimit {
+    /// Create a time limit of the given duration, starting now.
+    pub fn new(limit: time::Duration) -> Self {
+        TimeLimit {
+            start: time::Instant::now(),
+            limit: Some(limit),
+        }
+    }
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: TimeLimitParams) {
+        *self = Self::from(params);
+    }
+    /// Has the time run out?
+    pub fn has_expired(&self) -> bool {
+        match self.limit {
+            Some(limit) => self.start.elapsed() >= limit,
+            None => false,
+        }
+    }
+}
+impl<MC: MonteCarlo> Plugin<MC> for TimeLimit {
+    fn run(&self, _mc: &MC, _sys: &MC::System) -> Action {
+        if self.has_expired() {
+            println!("Reached the time limit, stopping.");
+            Action::Exit
+        } else {
+            Action::None
+        }
+    }
+    fn run_period(&self) -> TimeToRun {
+        if self.limit.is_some() {
+            TimeToRun::Period(TIME_LIMIT_CHECK_MOVES)
+        } else {
+            TimeToRun::Never
+        }
+    }
+}
+
+/// A plugin that prints a line of progress to stderr every so often (in
+/// wall-clock time), with an estimate of the time remaining if we know
+/// how many moves we will make.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Progress {
+    #[serde(skip, default)]
+    start: Cell<Option<(time::Instant, u64)>>,
+    #[serde(skip, default)]
+    last_print: Cell<Option<time::Instant>>,
+    /// The time between progress lines.
+    interval: Option<time::Duration>,
+    /// The total number of moves we will make, if we know it.
+    max_iter: Option<u64>,
+}
+
+/// The parameters to define progress reports.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct ProgressParams {
+    /// Print progress to stderr this often, in seconds.
+    pub progress_interval: Option<f64>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new(ProgressParams::default(), None)
+    }
+}
+/// How many moves between looking at the clock for progress.
+const PROGRESS_CHECK_MOVES: u64 = 1 << 12;
+impl Progress {
+    /// Report progress as requested by `params`, towards `max_iter`
+    /// moves if that is known.
+    pub fn new(params: ProgressParams, max_iter: Option<u64>) -> Self {
+        Progress {
+            start: Cell::new(None),
+            last_print: Cell::new(None),
+            interval: params
+                .progress_interval
+                .map(time::Duration::from_secs_f64),
+            max_iter,
+        }
+    }
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: ProgressParams, max_iter: Option<u64>) {
+        *self = Self::new(params, max_iter);
+    }
+    /// The line to print after `moves` moves made at `rate` moves per
+    /// second.
+    fn line(&self, moves: u64, rate: f64, range: Option<(Energy, Energy)>) -> String {
+        let mut line = format!("progress: {:.3} moves", PrettyFloat(moves as f64));
+        if let Some(max) = self.max_iter {
+            line.push_str(&format!(" ({:.1}%)", 100.0 * moves as f64 / max as f64));
+        }
+        if let Some((lo, hi)) = range {
+            line.push_str(&format!(", range {:.5} to {:.5}", lo.pretty(), hi.pretty()));
+        }
+        if let Some(max) = self.max_iter {
+            if rate > 0.0 && max > moves {
+                let remaining = (max - moves) as f64 / rate;
+                line.push_str(&format!(", {} remaining", format_duration(remaining as u64)));
+            }
+        }
+        line
+    }
+}
+impl<MC: MonteCarlo> Plugin<MC> for Progress {
+    fn run(&self, mc: &MC, _sys: &MC::System) -> Action {
+        if let Some(interval) = self.interval {
+            let now = time::Instant::now();
+            let moves = mc.num_moves();
+            let (start, start_moves) = match self.start.get() {
+                Some(s) => s,
+                None => {
+                    self.start.set(Some((now, moves)));
+                    self.last_print.set(Some(now));
+                    return Action::None;
+                }
+            };
+            let last = self.last_print.get().unwrap_or(start);
+            if now.duration_since(last) >= interval {
+                self.last_print.set(Some(now));
+                let elapsed = now.duration_since(start).as_secs_f64();
+                let rate = (moves - start_moves) as f64 / elapsed;
+                eprintln!("{}", self.line(moves, rate, mc.important_energy_range()));
+            }
+        }
+        Action::None
+    }
+    fn run_period(&self) -> TimeToRun {
+        if self.interval.is_some() {
+            TimeToRun::Period(PROGRESS_CHECK_MOVES)
+        } else {
+            TimeToRun::Never
+        }
+    }
+}
+
+/// A plugin that periodically recomputes the energy from scratch, to
+/// catch floating-point drift (or bugs) in the incremental energy.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EnergyCheck {
+    #[serde(skip, default)]
+    next_check: Cell<u64>,
+    /// How many moves between checks.
+    interval: Option<u64>,
+    /// The relative error we are willing to tolerate.
+    tolerance: f64,
+}
+
+/// The parameters to define the energy check.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone)]
+pub struct EnergyCheckParams {
+    /// Recompute the energy from scratch this often (in moves).
+    pub energy_check_interval: Option<u64>,
+    /// The relative error in the energy to tolerate (default 1e-10).
+    pub energy_check_tolerance: Option<f64>,
+}
+
+impl Default for EnergyCheckParams {
+    fn default() -> Self {
+        EnergyCheckParams {
+            energy_check_interval: None,
+            energy_check_tolerance: None,
+        }
+    }
+}
+impl Default for EnergyCheck {
+    fn default() -> Self {
+        EnergyCheck::from(EnergyCheckParams::default())
+    }
+}
+impl From<EnergyCheckParams> for EnergyCheck {
+    fn from(params: EnergyCheckParams) -> Self {
+        EnergyCheck {
+            next_check: Cell::new(params.energy_check_interval.unwrap_or(0)),
+            interval: params.energy_check_interval,
+            tolerance: params.energy_check_tolerance.unwrap_or(1e-10),
+        }
+    }
+}
+impl EnergyCheck {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: EnergyCheckParams) {
+        *self = Self::from(params);
+    }
+    /// Compare the cached and the recomputed energy, returning the
+    /// recomputed energy if they disagree.
+    pub fn check<S: System>(&self, sys: &S) -> Result<(), Energy> {
+        let cached = sys.energy();
+        let computed = sys.compute_energy();
+        let scale = if cached.abs() > units::EPSILON {
+            cached.abs()
+        } else {
+            units::EPSILON
+        };
+        if (computed - cached).abs() > self.tolerance * scale {
+            Err(computed)
+        } else {
+            Ok(())
+        }
+    }
+}
+impl<MC: MonteCarlo> Plugin<MC> for EnergyCheck {
+    fn run(&self, mc: &MC, sys: &MC::System) -> Action {
+        if let Some(interval) = self.interval {
+            if self.next_check.get() == 0 {
+                // We have just resumed.
+                self.next_check.set(mc.num_moves() + interval);
+            }
+            if mc.num_moves() >= self.next_check.get() {
+                self.next_check.set(mc.num_moves() + interval);
+                if let Err(computed) = self.check(sys) {
+                    panic!(
+                        "energy drift after {} moves: cached energy {} but recomputed {}",
+                        mc.num_moves(),
+                        sys.energy().pretty(),
+                        computed.pretty()
+                    );
+                }
+            }
+        }
+        Action::None
+    }
+    fn run_period(&self) -> TimeToRun {
+        if self.interval.is_some() {
+            TimeToRun::TotalMoves(self.next_check.get())
+        } else {
+            TimeToRun::Never
+        }
+    }
+}
+
 /// A plugin that schedules movie backups
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Movie {
@@ -413,7 +855,7 @@ pub struct Movie {
 }
 
 /// The parameter to define the movie schedule
-#[derive(AutoArgs, Debug, Clone)]
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone)]
 pub struct MovieParams {
     /// 2.0 means a frame every time iterations double.
     pub movie_time: Option<f64>,
@@ -490,6 +932,435 @@ impl<MC: MonteCarlo> Plugin<MC> for Movie {
     }
 }
 
+/// The parameters for tracing the evolution of SAD.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone)]
+pub struct SadTraceParams {
+    /// Append the SAD state to a csv file this often (in moves).
+    pub sad_trace_interval: Option<u64>,
+}
+
+impl Default for SadTraceParams {
+    fn default() -> Self {
+        SadTraceParams {
+            sad_trace_interval: None,
+        }
+    }
+}
+
+/// A plugin that periodically appends `moves`, `too_lo`, `too_hi`,
+/// `min_important_energy`, `n_found` (the number of distinct energies
+/// found in the important range), `tL` and `tF` to a csv file next to
+/// the save file.  It does nothing for methods other than SAD.  By
+/// default each line is written as soon as it is made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SadTrace {
+    #[serde(skip, default)]
+    next_output: Cell<u64>,
+    /// How many moves between trace lines.
+    interval: Option<u64>,
+    #[serde(default = "SadTrace::default_buffer")]
+    buffer: AppendBuffer,
+}
+
+impl Default for SadTrace {
+    fn default() -> Self {
+        SadTrace::new(SadTraceParams::default(), &FlushParams::default())
+    }
+}
+impl SadTrace {
+    /// Trace SAD as requested by `params`.
+    pub fn new(params: SadTraceParams, flush: &FlushParams) -> Self {
+        SadTrace {
+            next_output: Cell::new(params.sad_trace_interval.unwrap_or(0)),
+            interval: params.sad_trace_interval,
+            buffer: AppendBuffer::new(flush, 1)
+                .with_header("moves,too_lo,too_hi,min_important_energy,n_found,tL,tF"),
+        }
+    }
+    fn default_buffer() -> AppendBuffer {
+        SadTrace::default().buffer
+    }
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: SadTraceParams, flush: &FlushParams) {
+        *self = Self::new(params, flush);
+    }
+    /// The file the trace is written to.
+    pub fn path(save_as: &std::path::Path) -> std::path::PathBuf {
+        save_as.with_extension("sad-trace.csv")
+    }
+    fn append(
+        &self,
+        moves: u64,
+        sad: SadState,
+        min_important_energy: Energy,
+        save_as: &std::path::Path,
+    ) {
+        let line = format!(
+            "{},{},{},{},{},{},{}",
+            moves,
+            sad.too_lo.value_unsafe,
+            sad.too_hi.value_unsafe,
+            min_important_energy.value_unsafe,
+            sad.num_states,
+            sad.tL,
+            sad.tF
+        );
+        self.buffer.push(&SadTrace::path(save_as), &line);
+    }
+    /// Append any buffered lines to the trace.
+    pub fn flush(&self, save_as: &std::path::Path) {
+        self.buffer.flush(&SadTrace::path(save_as));
+    }
+}
+impl<S: MovableSystem + serde::Serialize + serde::de::DeserializeOwned> Plugin<EnergyMC<S>>
+    for SadTrace
+{
+    fn run(&self, mc: &EnergyMC<S>, _sys: &S) -> Action {
+        if let (Some(interval), Some(sad), Some(lowest)) =
+            (self.interval, mc.sad_state(), mc.min_important_energy())
+        {
+            if mc.moves >= self.next_output.get() {
+                self.next_output.set(mc.moves + interval);
+                self.append(mc.moves, sad, lowest, &mc.save_as);
+            }
+        }
+        Action::None
+    }
+    fn run_period(&self) -> TimeToRun {
+        match self.interval {
+            Some(_) => TimeToRun::TotalMoves(self.next_output.get()),
+            None => TimeToRun::Never,
+        }
+    }
+    fn save(&self, mc: &EnergyMC<S>, _sys: &S) {
+        self.flush(&mc.save_as);
+    }
+}
+
+/// The parameters for how plugins that append lines to files (such as
+/// the energy and SAD traces) buffer them.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct FlushParams {
+    /// Write out appended lines once this many have built up.  One
+    /// writes each line at once, so a crash loses nothing, but is
+    /// slower.  Each trace has its own default.
+    pub flush_lines: Option<usize>,
+}
+
+/// Lines waiting to be appended to a file, shared by every plugin
+/// that appends to files.  The lines are written once `flush_lines`
+/// of them have built up, whenever we save, and when the run finishes.
+/// Lines still buffered if we crash are lost.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AppendBuffer {
+    /// How many lines to buffer before writing them.
+    flush_lines: usize,
+    /// A line to start the file with, when we create it.
+    header: Option<String>,
+    #[serde(skip, default)]
+    lines: RefCell<String>,
+    #[serde(skip, default)]
+    num_lines: Cell<usize>,
+}
+
+impl AppendBuffer {
+    /// A buffer flushed as requested by `params`, or by default every
+    /// `default_lines` lines.
+    pub fn new(params: &FlushParams, default_lines: usize) -> Self {
+        AppendBuffer {
+            flush_lines: params.flush_lines.unwrap_or(default_lines).max(1),
+            header: None,
+            lines: RefCell::new(String::new()),
+            num_lines: Cell::new(0),
+        }
+    }
+    /// Start the file with `header` if it does not already exist.
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.header = Some(header.to_string());
+        self
+    }
+    /// Append `line` to the file at `path`, writing it out if enough
+    /// lines have built up.
+    pub fn push(&self, path: &std::path::Path, line: &str) {
+        {
+            let mut lines = self.lines.borrow_mut();
+            lines.push_str(line);
+            lines.push('\n');
+        }
+        self.num_lines.set(self.num_lines.get() + 1);
+        if self.num_lines.get() >= self.flush_lines {
+            self.flush(path);
+        }
+    }
+    /// Append any buffered lines to the file at `path`.
+    pub fn flush(&self, path: &std::path::Path) {
+        use std::io::Write;
+        let mut lines = self.lines.borrow_mut();
+        if lines.is_empty() {
+            return;
+        }
+        let is_new = !path.exists();
+        let mut f = ::std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap_or_else(|e| panic!("error opening {:?}: {}", path, e));
+        if let (true, Some(header)) = (is_new, &self.header) {
+            writeln!(f, "{}", header).unwrap_or_else(|e| panic!("error writing {:?}: {}", path, e));
+        }
+        f.write_all(lines.as_bytes())
+            .unwrap_or_else(|e| panic!("error writing {:?}: {}", path, e));
+        lines.clear();
+        self.num_lines.set(0);
+    }
+}
+
+/// A plugin that records the energy (in units of epsilon) every so
+/// many moves, for working out autocorrelation times offline.  The
+/// energies are buffered (see [`AppendBuffer`]) and appended to the
+/// file one per line whenever the buffer fills, when we save, and when
+/// the run finishes.  Just after a save the file therefore ends at the
+/// checkpoint, but a run that crashes may have written energies from
+/// after its last checkpoint, which are then written again on resuming.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EnergyTrace {
+    path: Option<::std::path::PathBuf>,
+    /// The number of moves between samples.
+    interval: u64,
+    #[serde(skip, default)]
+    next: Cell<u64>,
+    #[serde(default)]
+    buffer: AppendBuffer,
+}
+
+/// The parameters to define the energy trace.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct EnergyTraceParams {
+    /// Append the energy to this file, one per line.
+    pub energy_trace: Option<::std::path::PathBuf>,
+    /// Record the energy this often, in moves (default once a sweep).
+    pub energy_trace_interval: Option<u64>,
+}
+
+/// How many energies to buffer before writing them out, by default.
+const ENERGY_TRACE_BUFFER: usize = 1 << 12;
+impl EnergyTrace {
+    /// Trace the energy as requested by `params`, by default once
+    /// every `sweep` moves.
+    pub fn new(params: EnergyTraceParams, flush: &FlushParams, sweep: u64) -> Self {
+        EnergyTrace {
+            path: params.energy_trace,
+            interval: params.energy_trace_interval.unwrap_or(sweep).max(1),
+            next: Cell::new(0),
+            buffer: AppendBuffer::new(flush, ENERGY_TRACE_BUFFER),
+        }
+    }
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: EnergyTraceParams, flush: &FlushParams, sweep: u64) {
+        self.flush();
+        *self = Self::new(params, flush, sweep);
+    }
+    /// Append any buffered energies to the file.
+    pub fn flush(&self) {
+        if let Some(ref path) = self.path {
+            self.buffer.flush(path);
+        }
+    }
+}
+impl<MC: MonteCarlo> Plugin<MC> for EnergyTrace {
+    fn run(&self, mc: &MC, sys: &MC::System) -> Action {
+        if let Some(ref path) = self.path {
+            let moves = mc.num_moves();
+            if moves > 0 && moves % self.interval == 0 {
+                let e = *(sys.energy() / units::EPSILON).value();
+                self.buffer.push(path, &e.to_string());
+            }
+            self.next.set((moves / self.interval + 1) * self.interval);
+        }
+        Action::None
+    }
+    fn run_period(&self) -> TimeToRun {
+        if self.path.is_none() {
+            TimeToRun::Never
+        } else if self.next.get() == 0 {
+            // We have just started or resumed.
+            TimeToRun::Period(1)
+        } else {
+            TimeToRun::TotalMoves(self.next.get())
+        }
+    }
+    fn save(&self, _mc: &MC, _sys: &MC::System) {
+        self.flush();
+    }
+}
+
+/// A plugin that draws a coarse plot of the current entropy (and
+/// optionally the histogram) against energy on the terminal every so
+/// often, in wall-clock time.  On a terminal each plot is drawn over
+/// the last one.  When stdout is not a terminal the plots are just
+/// printed one after another, without any escape codes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LivePlot {
+    /// The time between plots.
+    interval: Option<time::Duration>,
+    /// The number of rows in the entropy plot.
+    height: usize,
+    /// Whether to plot the histogram below the entropy.
+    histogram: bool,
+    #[serde(skip, default)]
+    last_draw: Cell<Option<time::Instant>>,
+    /// The number of lines we last drew, which we move back over to
+    /// draw the next plot.
+    #[serde(skip, default)]
+    lines_drawn: Cell<usize>,
+}
+
+/// The parameters to define live plots.
+#[derive(Serialize, Deserialize, AutoArgs, Debug, Clone, Default)]
+pub struct LivePlotParams {
+    /// Plot the entropy on the terminal this often, in seconds.
+    pub live_plot_interval: Option<f64>,
+    /// The number of rows in the live plot (default 12).
+    pub live_plot_height: Option<usize>,
+    /// Also plot the histogram.
+    pub live_plot_histogram: bool,
+}
+
+/// How many moves between looking at the clock for live plots.
+const LIVE_PLOT_CHECK_MOVES: u64 = 1 << 12;
+impl From<LivePlotParams> for LivePlot {
+    fn from(params: LivePlotParams) -> Self {
+        LivePlot {
+            interval: params
+                .live_plot_interval
+                .map(time::Duration::from_secs_f64),
+            height: params.live_plot_height.unwrap_or(12).max(2),
+            histogram: params.live_plot_histogram,
+            last_draw: Cell::new(None),
+            lines_drawn: Cell::new(0),
+        }
+    }
+}
+impl LivePlot {
+    /// Allows a resuming simulation to get updated parameters from the
+    /// flags.
+    pub fn update_from(&mut self, params: LivePlotParams) {
+        *self = Self::from(params);
+    }
+    /// The lines of the plot of `curve`, as from
+    /// `MonteCarlo::entropy_curve`, `width` characters wide.
+    fn lines(&self, curve: &[(Energy, f64, u64)], width: usize) -> Vec<String> {
+        let energies: Vec<f64> = curve.iter().map(|&(e, _, _)| e.value_unsafe).collect();
+        let lnw: Vec<(f64, f64)> = energies
+            .iter()
+            .zip(curve.iter())
+            .map(|(&e, &(_, s, _))| (e, s))
+            .collect();
+        let (lo, hi) = (energies[0], energies[energies.len() - 1]);
+        let mut lines = vec![format!(
+            "lnw from E = {:.5} to {:.5}:",
+            PrettyFloat(lo),
+            PrettyFloat(hi)
+        )];
+        lines.extend(render_plot(&lnw, width, self.height));
+        if self.histogram {
+            let counts: Vec<(f64, f64)> = energies
+                .iter()
+                .zip(curve.iter())
+                .map(|(&e, &(_, _, h))| (e, h as f64))
+                .collect();
+            lines.push("histogram:".to_string());
+            lines.extend(render_plot(&counts, width, (self.height / 2).max(2)));
+        }
+        lines
+    }
+}
+impl<MC: MonteCarlo> Plugin<MC> for LivePlot {
+    fn run(&self, mc: &MC, _sys: &MC::System) -> Action {
+        use std::io::{IsTerminal, Write};
+        if let Some(interval) = self.interval {
+            let now = time::Instant::now();
+            if let Some(last) = self.last_draw.get() {
+                if now.duration_since(last) < interval {
+                    return Action::None;
+                }
+            }
+            self.last_draw.set(Some(now));
+            let curve = mc.entropy_curve();
+            if curve.is_empty() {
+                return Action::None;
+            }
+            let width = ::std::env::var("COLUMNS")
+                .ok()
+                .and_then(|c| c.parse::<usize>().ok())
+                .unwrap_or(80)
+                .max(10);
+            let lines = self.lines(&curve, width);
+            let stdout = ::std::io::stdout();
+            let terminal = stdout.is_terminal();
+            let mut out = stdout.lock();
+            let mut text = String::new();
+            if terminal && self.lines_drawn.get() > 0 {
+                // Move up over the last plot and clear it.
+                text.push_str(&format!("\x1b[{}A\x1b[J", self.lines_drawn.get()));
+            }
+            for l in lines.iter() {
+                text.push_str(l);
+                text.push('\n');
+            }
+            // A plot we cannot draw is no reason to stop the run.
+            out.write_all(text.as_bytes()).ok();
+            out.flush().ok();
+            self.lines_drawn.set(lines.len());
+        }
+        Action::None
+    }
+    fn run_period(&self) -> TimeToRun {
+        if self.interval.is_some() {
+            TimeToRun::Period(LIVE_PLOT_CHECK_MOVES)
+        } else {
+            TimeToRun::Never
+        }
+    }
+}
+
+/// Plot the points `data`, as `(x, y)` sorted by `x`, in `height` rows
+/// of `width` characters.  The points are put into columns by `x`, and
+/// each column shows the greatest `y` that falls in it, scaled so the
+/// smallest and greatest `y` are on the bottom and top rows.  Columns
+/// with no points are left blank.
+pub fn render_plot(data: &[(f64, f64)], width: usize, height: usize) -> Vec<String> {
+    let mut grid = vec![vec![' '; width]; height];
+    if !data.is_empty() && width > 0 && height > 0 {
+        let (xlo, xhi) = (data[0].0, data[data.len() - 1].0);
+        let ylo = data.iter().map(|&(_, y)| y).fold(std::f64::INFINITY, f64::min);
+        let yhi = data.iter().map(|&(_, y)| y).fold(std::f64::NEG_INFINITY, f64::max);
+        let mut columns: Vec<Option<f64>> = vec![None; width];
+        for &(x, y) in data {
+            let c = if xhi > xlo {
+                (((x - xlo) / (xhi - xlo)) * (width - 1) as f64).round() as usize
+            } else {
+                0
+            };
+            let c = c.min(width - 1);
+            columns[c] = Some(columns[c].map_or(y, |old| old.max(y)));
+        }
+        for (c, y) in columns.iter().enumerate() {
+            if let Some(y) = *y {
+                let r = if yhi > ylo {
+                    (((y - ylo) / (yhi - ylo)) * (height - 1) as f64).round() as usize
+                } else {
+                    0
+                };
+                grid[height - 1 - r.min(height - 1)][c] = '*';
+            }
+        }
+    }
+    grid.into_iter().map(|row| row.into_iter().collect()).collect()
+}
+
 fn format_duration(secs: u64) -> String {
     let mins = secs / 60;
     let hours = mins / 60;
@@ -565,3 +1436,57 @@ fn test_format_duration() {
         format_duration(60 * 60 * (24 * 20 + 13) + 5 * 60).as_str()
     );
 }
+
+#[test]
+fn time_limit_stops_after_the_limit() {
+    let limit = TimeLimit::new(time::Duration::from_millis(50));
+    std::thread::sleep(time::Duration::from_millis(60));
+    assert!(limit.has_expired());
+    // Resuming starts the clock again.
+    let resumed: TimeLimit =
+        serde_yaml::from_str(&serde_yaml::to_string(&limit).unwrap()).unwrap();
+    assert!(!resumed.has_expired());
+
+    let no_limit = TimeLimit::default();
+    assert!(!no_limit.has_expired());
+}
+
+#[test]
+fn progress_line_estimates_time_remaining() {
+    let progress = Progress::new(
+        ProgressParams {
+            progress_interval: Some(1.0),
+        },
+        Some(1000),
+    );
+    let line = progress.line(250, 5.0, None);
+    assert!(line.contains("(25.0%)"), "{}", line);
+    assert!(line.ends_with("2 minutes remaining"), "{}", line);
+    let range = Some((Energy::new(-3.0), Energy::new(-1.0)));
+    assert!(progress.line(250, 5.0, range).contains("range"));
+    let unbounded = Progress::new(ProgressParams::default(), None);
+    assert!(!unbounded.line(250, 5.0, None).contains("remaining"));
+}
+
+#[test]
+fn render_plot_fills_the_requested_grid() {
+    let data: Vec<(f64, f64)> = (0..200).map(|i| (i as f64, (i as f64 / 20.0).sin())).collect();
+    let rows = render_plot(&data, 50, 8);
+    assert_eq!(rows.len(), 8);
+    for row in rows.iter() {
+        assert_eq!(row.chars().count(), 50);
+    }
+    // Every column has a point, since there are more points than columns.
+    for c in 0..50 {
+        assert_eq!(rows.iter().filter(|r| r.chars().nth(c) == Some('*')).count(), 1);
+    }
+    assert!(rows[0].contains('*'));
+    assert!(rows[7].contains('*'));
+
+    // A rising line goes from the bottom left to the top right.
+    let line = render_plot(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)], 3, 3);
+    assert_eq!(line, vec!["  *", " * ", "*  "]);
+
+    let empty = render_plot(&[], 4, 2);
+    assert_eq!(empty, vec!["    ", "    "]);
+}
@@ -94,7 +94,11 @@ impl<S: MovableSystem> Replica<S> {
         self.system.energy()
     }
     fn run_once(&mut self) {
-        if let Some(e) = self.system.plan_move(&mut self.rng, self.translation_scale) {
+        if let Some(e) = self.system.plan_move(
+            &mut self.rng,
+            self.translation_scale,
+            MoveDistribution::Gaussian,
+        ) {
             let beta_delta_e = *((e - self.energy())/self.T).value();
             if beta_delta_e < 0.0 || self.rng.gen::<f64>() < (-beta_delta_e).exp() {
                 self.system.confirm();
@@ -136,6 +140,12 @@ pub struct MC<S> {
     pub replicas: Vec<Replica<S>>,
     /// The number of Canonical steps to take between swaps
     pub canonical_steps: u64,
+    /// The number of accepted swaps between replica `i` and `i+1`
+    #[serde(default)]
+    pub accepted_pair_swaps: Vec<u64>,
+    /// The number of rejected swaps between replica `i` and `i+1`
+    #[serde(default)]
+    pub rejected_pair_swaps: Vec<u64>,
     /// How frequently to save...
     save: plugin::Save,
     /// Movie state
@@ -162,15 +172,20 @@ impl<
 
         let mut replicas = Vec::<Replica<S>>::with_capacity(T.len());
         for t in T.iter().copied() {
+            // Each replica gets its own stream, so runs are reproducible
+            // without the replicas moving in lockstep.
             replicas.push(Replica::new(t, system.clone(), rng.clone()));
+            rng.jump();
             println!("Creating new Replica with temperature  {:3}", t);
-        } 
-        rng.jump();
+        }
+        let num_pairs = T.len().saturating_sub(1);
         MC {
             T,
             replicas,
             moves: 0,
             canonical_steps: params.canonical_steps,
+            accepted_pair_swaps: vec![0; num_pairs],
+            rejected_pair_swaps: vec![0; num_pairs],
 
             rng,
             save_as: save_as,
@@ -197,16 +212,10 @@ impl<
                         .unwrap()
                 }
                 if let Some(ref save_as) = save_as {
-                    if let Ok(f) = ::std::fs::File::open(save_as) {
-                        let mut s = match save_as.extension().and_then(|x| x.to_str()) {
-                            Some("yaml") => serde_yaml::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("json") => serde_json::from_reader::<_, Self>(&f)
-                                .expect("error parsing save-as file"),
-                            Some("cbor") => serde_cbor::from_reader::<Self, _>(&f)
-                                .expect("error parsing save-as file"),
-                            _ => panic!("I don't know how to read file {:?}", f),
-                        };
+                    if save_as.exists() {
+                        let mut s: Self = load_from(save_as).unwrap_or_else(|e| {
+                            panic!("error parsing save-as file {:?}: {}", save_as, e)
+                        });
                         println!("Resuming from file {:?}", save_as);
                         for r in s.replicas.iter_mut() {
                             r.system.update_caches();
@@ -221,21 +230,8 @@ impl<
                 let save_as = save_as.unwrap_or(::std::path::PathBuf::from("resume.yaml"));
                 Self::from_params(_mc, _sys.into(), save_as)
             }
-            Params::ResumeFrom(p) => {
-                let f = ::std::fs::File::open(&p).expect(&format!("error reading file {:?}", &p));
-                match p.extension().and_then(|x| x.to_str()) {
-                    Some("yaml") => {
-                        serde_yaml::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("json") => {
-                        serde_json::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    Some("cbor") => {
-                        serde_cbor::from_reader(&f).expect("error reading checkpoint?!")
-                    }
-                    _ => panic!("I don't know how to read file {:?}", f),
-                }
-            }
+            Params::ResumeFrom(p) => load_from(&p)
+                .unwrap_or_else(|e| panic!("error reading checkpoint {:?}: {}", p, e)),
         }
     }
     /// Create a simulation checkpoint.
@@ -258,14 +254,33 @@ impl<
             }
         }
 
-        let f = AtomicFile::create(&self.save_as)
-            .expect(&format!("error creating file {:?}", self.save_as));
-        match self.save_as.extension().and_then(|x| x.to_str()) {
-            Some("yaml") => serde_yaml::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("json") => serde_json::to_writer(&f, self).expect("error writing checkpoint?!"),
-            Some("cbor") => serde_cbor::to_writer(&f, self).expect("error writing checkpoint?!"),
-            _ => panic!("I don't know how to create file {:?}", self.save_as),
+        for (i, rate) in self.pair_swap_acceptance().into_iter().enumerate() {
+            println!(
+                "    swap {:.3} <-> {:.3}: {:.2}%",
+                self.T[i].pretty(),
+                self.T[i + 1].pretty(),
+                crate::prettyfloat::PrettyFloat(100.0 * rate)
+            );
         }
+
+        save_to(&self.save_as, &*self)
+            .unwrap_or_else(|e| panic!("error writing checkpoint {:?}: {}", self.save_as, e));
+    }
+
+    /// The fraction of attempted swaps between replica `i` and `i+1`
+    /// that were accepted, for tuning the temperature ladder.
+    pub fn pair_swap_acceptance(&self) -> Vec<f64> {
+        self.accepted_pair_swaps
+            .iter()
+            .zip(self.rejected_pair_swaps.iter())
+            .map(|(&a, &r)| {
+                if a + r == 0 {
+                    0.0
+                } else {
+                    a as f64 / (a + r) as f64
+                }
+            })
+            .collect()
     }
 
     /// Run a simulation
@@ -284,41 +299,41 @@ impl<
         });
 
         // Now let us try swapping if we can.
-        let iterator = if self.rng.gen::<bool>() {
-            // Try swapping odd ones
-            self.replicas.chunks_exact_mut(2)
-        } else {
-            // Try swapping even ones.
-            self.replicas[1..].chunks_exact_mut(2)
-        };
-        iterator.for_each(|chunk| {
-            // for chunk in iterator {
-            if let [r0, r1] = chunk {
-                // We will swap them if both systems accept the swap.
-                let de_db = *((r0.energy() - r1.energy())*(1./r0.T - 1./r1.T)).value();
-                if de_db >= 0. || r1.rng.gen::<f64>() < de_db.exp() {
-                    r0.accepted_swap_count += 1;
-                    r1.accepted_swap_count += 1;
-                    std::mem::swap(&mut r0.system, &mut r1.system);
-                }else{//else don't swap
-                    r0.rejected_swap_count += 1;
-                    r1.rejected_swap_count += 1;
-                }
-                let e0=r0.energy();
-                let e1=r1.energy();
-                r0.total_energy += e0;
-                r1.total_energy += e1;
+        let num_pairs = self.replicas.len().saturating_sub(1);
+        self.accepted_pair_swaps.resize(num_pairs, 0);
+        self.rejected_pair_swaps.resize(num_pairs, 0);
+        // Try swapping odd pairs or even pairs.
+        let first = if self.rng.gen::<bool>() { 0 } else { 1 };
+        for i in (first..num_pairs).step_by(2) {
+            let (lo, hi) = self.replicas.split_at_mut(i + 1);
+            let r0 = &mut lo[i];
+            let r1 = &mut hi[0];
+            // We will swap them if both systems accept the swap.
+            let de_db = *((r0.energy() - r1.energy()) * (1. / r0.T - 1. / r1.T)).value();
+            if de_db >= 0. || r1.rng.gen::<f64>() < de_db.exp() {
+                r0.accepted_swap_count += 1;
+                r1.accepted_swap_count += 1;
+                self.accepted_pair_swaps[i] += 1;
+                std::mem::swap(&mut r0.system, &mut r1.system);
+            } else {
+                r0.rejected_swap_count += 1;
+                r1.rejected_swap_count += 1;
+                self.rejected_pair_swaps[i] += 1;
+            }
+            let e0 = r0.energy();
+            let e1 = r1.energy();
+            r0.total_energy += e0;
+            r1.total_energy += e1;
 
-                r0.total_energy_squared += e0 * e0;
-                r1.total_energy_squared += e1 * e1;
-                if e0 >= Energy::new(0.){
-                    r0.ignored_count += 1
-                }
-                if e1 >= Energy::new(0.){
-                    r1.ignored_count += 1
-                }
+            r0.total_energy_squared += e0 * e0;
+            r1.total_energy_squared += e1 * e1;
+            if e0 >= Energy::new(0.) {
+                r0.ignored_count += 1
             }
-        });
+            if e1 >= Energy::new(0.) {
+                r1.ignored_count += 1
+            }
+        }
         let mut moves = self.moves;
         let these_moves = these_moves.load(std::sync::atomic::Ordering::Relaxed);
         self.moves += these_moves;
@@ -340,3 +355,38 @@ impl<
         }
     }
 }
+
+#[cfg(test)]
+fn mk_ising_tempering(
+    dir: &tempfile::TempDir,
+    seed: u64,
+) -> MC<crate::system::ising::Ising> {
+    let mut params = MCParams::default();
+    params.T = vec![1.5, 2.0, 2.5, 3.0];
+    params.seed = Some(seed);
+    MC::from_params(
+        params,
+        crate::system::ising::Ising::from(crate::system::ising::IsingParams { N: 4 }),
+        dir.path().join("tempering.yaml"),
+    )
+}
+
+#[test]
+fn pair_swap_rates_are_reproducible() {
+    let dir_a = tempfile::TempDir::new().unwrap();
+    let dir_b = tempfile::TempDir::new().unwrap();
+    let mut a = mk_ising_tempering(&dir_a, 7);
+    let mut b = mk_ising_tempering(&dir_b, 7);
+    for _ in 0..200 {
+        a.run_once();
+        b.run_once();
+    }
+    assert_eq!(a.accepted_pair_swaps, b.accepted_pair_swaps);
+    assert_eq!(a.rejected_pair_swaps, b.rejected_pair_swaps);
+    let rates = a.pair_swap_acceptance();
+    assert_eq!(rates.len(), 3);
+    for (i, &r) in rates.iter().enumerate() {
+        assert!(a.accepted_pair_swaps[i] + a.rejected_pair_swaps[i] > 0);
+        assert!(r > 0.0 && r <= 1.0, "pair {} has rate {}", i, r);
+    }
+}
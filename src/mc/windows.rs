@@ -0,0 +1,185 @@
+//! Sample a wide energy range in overlapping windows, and stitch their
+//! entropies together.
+
+use super::energy::{DosSnapshot, EnergyMC, EnergyMCParams};
+use super::*;
+use crate::system::*;
+
+use rayon::prelude::*;
+
+fn window_path(save_as: &std::path::Path, i: usize) -> std::path::PathBuf {
+    let stem = save_as
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("windows");
+    let ext = save_as.extension().and_then(|s| s.to_str()).unwrap_or("yaml");
+    save_as.with_file_name(format!("{}-window-{}.{}", stem, i, ext))
+}
+
+/// The parameters for window `i` of a windowed run.  Each window gets a
+/// seed of its own, counting up from `seed`, so that the windows do not
+/// all draw the same random numbers.
+fn window_params(
+    params: &EnergyMCParams,
+    (min, max): (Energy, Energy),
+    seed: u64,
+    i: usize,
+) -> EnergyMCParams {
+    let mut params = params.clone().restricted_to(min, max);
+    params.seed = Some(seed.wrapping_add(i as u64));
+    params
+}
+
+/// Run one simulation in each of `windows`, given as `(min, max)`
+/// energies, for `moves` moves each, and join their `lnw` using
+/// [`stitch`].  Each run only allows energies within its window, and
+/// rejects any move that would take it further outside.  Window `i`
+/// is saved next to `save_as` with `-window-i` added to its name, and
+/// is seeded with the seed in `params` (or a random one) plus `i`.
+pub fn run_windows<S>(
+    params: &EnergyMCParams,
+    system: &S,
+    save_as: &std::path::Path,
+    windows: &[(Energy, Energy)],
+    moves: u64,
+    parallel: bool,
+) -> Result<DosSnapshot, String>
+where
+    S: MovableSystem + Clone + Send + Sync + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let seed = crate::rng::seed_or_random(params.seed);
+    let run = |i: usize| -> Result<DosSnapshot, String> {
+        let params = window_params(params, windows[i], seed, i);
+        let mut mc = EnergyMC::from_params(params, system.clone(), window_path(save_as, i));
+        mc.run_for(moves);
+        mc.snapshot()
+    };
+    let runs: Result<Vec<DosSnapshot>, String> = if parallel {
+        (0..windows.len()).into_par_iter().map(run).collect()
+    } else {
+        (0..windows.len()).map(run).collect()
+    };
+    stitch(&runs?)
+}
+
+/// Join the entropies from runs over overlapping energy windows into
+/// one.  Each `lnw` is only known up to a constant, so working up from
+/// the lowest window we join each to the next at the bin in their
+/// overlap where their slopes (i.e. inverse temperatures) agree best,
+/// and shift the upper one to match the lower there.  Below the join
+/// we keep the lower window, and from the join up the upper one, along
+/// with its histogram.  The windows must share a bin width and have
+/// grids that line up.
+pub fn stitch(windows: &[DosSnapshot]) -> Result<DosSnapshot, String> {
+    if windows.is_empty() {
+        return Err("there are no windows to stitch".to_string());
+    }
+    let width = windows[0].width;
+    let mut min = windows[0].min;
+    for w in windows.iter() {
+        if w.width != width {
+            return Err(format!(
+                "the bin widths differ: {} and {}",
+                width.pretty(),
+                w.width.pretty()
+            ));
+        }
+        if w.min < min {
+            min = w.min;
+        }
+    }
+    // Put every window on a common grid, with None where it never
+    // visited, ordered by the lowest energy it visited.
+    let mut grids = Vec::new();
+    for w in windows.iter() {
+        let offset = *((w.min - min) / width).value();
+        if (offset - offset.round()).abs() > 1e-6 {
+            return Err("the windows have misaligned energy bins".to_string());
+        }
+        let offset = offset.round() as usize;
+        let mut grid = vec![None; offset];
+        grid.extend(
+            w.lnw
+                .iter()
+                .zip(w.histogram.iter())
+                .map(|(&lnw, &h)| if h > 0 { Some((lnw, h)) } else { None }),
+        );
+        let lowest = grid.iter().position(|x| x.is_some());
+        grids.push((lowest, grid));
+    }
+    grids.sort_by_key(|&(lowest, _)| lowest);
+    let num_bins = grids.iter().map(|(_, g)| g.len()).max().unwrap_or(0);
+    let mut joined: Vec<Option<(f64, u64)>> = vec![None; num_bins];
+    let mut first = true;
+    for (_, grid) in grids {
+        let both = |k: usize| match (joined.get(k).cloned().flatten(), grid.get(k).cloned().flatten()) {
+            (Some((a, _)), Some((b, _))) => Some((a, b)),
+            _ => None,
+        };
+        let (join, shift) = if first {
+            first = false;
+            (0, 0.0)
+        } else {
+            let overlap: Vec<usize> = (0..num_bins).filter(|&k| both(k).is_some()).collect();
+            if overlap.is_empty() {
+                return Err("adjacent windows have no visited energies in common".to_string());
+            }
+            let slope_mismatch = |k: usize| match (both(k), both(k + 1)) {
+                (Some((a0, b0)), Some((a1, b1))) => Some(((a1 - a0) - (b1 - b0)).abs()),
+                _ => None,
+            };
+            let join = overlap
+                .iter()
+                .filter_map(|&k| slope_mismatch(k).map(|m| (k, m)))
+                .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
+                .map(|(k, _)| k)
+                .unwrap_or(overlap[0]);
+            let (a, b) = both(join).unwrap();
+            (join, a - b)
+        };
+        for (k, x) in grid.into_iter().enumerate().skip(join) {
+            if let Some((lnw, h)) = x {
+                joined[k] = Some((lnw + shift, h));
+            }
+        }
+    }
+    Ok(DosSnapshot {
+        moves: windows.iter().map(|w| w.moves).sum(),
+        min,
+        width,
+        lnw: joined.iter().map(|x| x.map(|(lnw, _)| lnw).unwrap_or(0.0)).collect(),
+        histogram: joined.iter().map(|x| x.map(|(_, h)| h).unwrap_or(0)).collect(),
+    })
+}
+
+#[test]
+fn each_window_has_its_own_seed() {
+    let params = EnergyMCParams::default();
+    let window = (Energy::new(-2.0), Energy::new(2.0));
+    let seeds: Vec<_> = (0..3).map(|i| window_params(&params, window, 7, i).seed).collect();
+    assert_eq!(seeds, vec![Some(7), Some(8), Some(9)]);
+}
+
+#[test]
+fn stitching_recovers_a_shifted_entropy() {
+    let entropy = |e: f64| 3.0 - 0.1 * (e - 5.0) * (e - 5.0);
+    let window = |min: f64, n: usize, constant: f64| DosSnapshot {
+        moves: 10,
+        min: Energy::new(min),
+        width: Energy::new(1.0),
+        lnw: (0..n).map(|i| entropy(min + 0.5 + i as f64) + constant).collect(),
+        histogram: vec![1; n],
+    };
+    // Given out of order, with different constants.
+    let dos = stitch(&[window(3.5, 6, -2.0), window(-0.5, 5, 7.0)]).unwrap();
+    assert_eq!(dos.min, Energy::new(-0.5));
+    assert_eq!(dos.moves, 20);
+    assert_eq!(dos.lnw.len(), 10);
+    for (i, &lnw) in dos.lnw.iter().enumerate() {
+        let e = dos.energy(i).value_unsafe;
+        assert!((lnw - entropy(e) - 7.0).abs() < 1e-12, "wrong at {}", e);
+    }
+
+    assert!(stitch(&[window(-0.5, 3, 0.0), window(5.5, 3, 0.0)]).is_err());
+    assert!(stitch(&[window(-0.5, 5, 0.0), window(3.0, 3, 0.0)]).is_err());
+}
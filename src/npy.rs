@@ -0,0 +1,102 @@
+//! Writing one-dimensional arrays in the NumPy `.npy` format.
+//!
+//! The format is a short ascii header describing the type and shape,
+//! followed by the raw little-endian data, so `numpy.load` can read
+//! these files directly.
+
+use crate::atomicfile::AtomicFile;
+use std::io::{Result, Write};
+use std::path::Path;
+
+/// A number that can be stored in a `.npy` file.
+pub trait NpyElement: Copy {
+    /// The NumPy type description, e.g. `<f8`.
+    const DESCR: &'static str;
+    /// The little-endian bytes of this number.
+    fn to_le(self) -> [u8; 8];
+}
+
+impl NpyElement for f64 {
+    const DESCR: &'static str = "<f8";
+    fn to_le(self) -> [u8; 8] {
+        self.to_bits().to_le_bytes()
+    }
+}
+
+impl NpyElement for u64 {
+    const DESCR: &'static str = "<u8";
+    fn to_le(self) -> [u8; 8] {
+        self.to_le_bytes()
+    }
+}
+
+/// The header of a version 1.0 `.npy` file holding `len` elements.
+fn header<T: NpyElement>(len: usize) -> Vec<u8> {
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({},), }}",
+        T::DESCR,
+        len
+    );
+    // The data must start on a multiple of 64 bytes, and the header
+    // itself ends with a newline.
+    let prefix = 10;
+    while (prefix + dict.len() + 1) % 64 != 0 {
+        dict.push(' ');
+    }
+    dict.push('\n');
+    let mut h = b"\x93NUMPY\x01\x00".to_vec();
+    h.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    h.extend_from_slice(dict.as_bytes());
+    h
+}
+
+/// Write `data` to `path` as a one-dimensional `.npy` array.
+pub fn write<T: NpyElement, P: AsRef<Path>>(path: P, data: &[T]) -> Result<()> {
+    let mut bytes = header::<T>(data.len());
+    for &x in data {
+        bytes.extend_from_slice(&x.to_le());
+    }
+    let mut f = AtomicFile::create(path)?;
+    f.write_all(&bytes)
+}
+
+/// Read back a one-dimensional array of `<f8` or `<u8` written by
+/// `write`, as raw little-endian words.
+#[cfg(test)]
+pub fn read_words<P: AsRef<Path>>(path: P) -> (String, Vec<[u8; 8]>) {
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert_eq!((10 + len) % 64, 0);
+    let dict = String::from_utf8(bytes[10..10 + len].to_vec()).unwrap();
+    let words = bytes[10 + len..]
+        .chunks(8)
+        .map(|c| {
+            let mut w = [0; 8];
+            w.copy_from_slice(c);
+            w
+        })
+        .collect();
+    (dict, words)
+}
+
+#[test]
+fn npy_round_trip() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("x.npy");
+    write(&path, &[1.5, -2.0, std::f64::consts::PI]).unwrap();
+    let (dict, words) = read_words(&path);
+    assert_eq!(
+        dict.trim_end(),
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }"
+    );
+    assert!(dict.ends_with('\n'));
+    let xs: Vec<f64> = words.iter().map(|&w| f64::from_le_bytes(w)).collect();
+    assert_eq!(xs, vec![1.5, -2.0, std::f64::consts::PI]);
+
+    write(&path, &[7u64, 0, u64::max_value()]).unwrap();
+    let (dict, words) = read_words(&path);
+    assert!(dict.contains("'descr': '<u8'"));
+    let ns: Vec<u64> = words.iter().map(|&w| u64::from_le_bytes(w)).collect();
+    assert_eq!(ns, vec![7, 0, u64::max_value()]);
+}
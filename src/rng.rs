@@ -24,6 +24,10 @@ pub struct Xoroshiro128plusRng {
 }
 
 /// Our random number generator.
+///
+/// This serializes its full internal state rather than the seed it
+/// started from, so a run resumed from a checkpoint draws exactly the
+/// random numbers it would have drawn had it never stopped.
 pub type MyRng = rand_xoshiro::Xoroshiro128Plus;
 // pub type MyRng = Xoroshiro128plusRng;
 
@@ -116,6 +120,25 @@ pub fn vector<R: rand::Rng>(rng: &mut R) -> ::vector3d::Vector3d<f64> {
     )
 }
 
+/// Generate a random vector uniformly within a cube, with mean 0 and
+/// variance 1 in each direction.
+pub fn uniform_vector<R: rand::Rng>(rng: &mut R) -> ::vector3d::Vector3d<f64> {
+    vector3d::Vector3d::new(uniform(rng), uniform(rng), uniform(rng))
+}
+
+/// Generate a random number with a Gaussian distribution, with mean 0
+/// and variance 1.
+pub fn normal<R: rand::Rng>(rng: &mut R) -> f64 {
+    rng.sample(rand_distr::StandardNormal)
+}
+
+/// Generate a random number uniformly distributed with mean 0 and
+/// variance 1.
+pub fn uniform<R: rand::Rng>(rng: &mut R) -> f64 {
+    let half_width = 3.0_f64.sqrt();
+    rng.gen_range(-half_width, half_width)
+}
+
 impl SeedableRng for Xoroshiro128plusRng {
     type Seed = [u8; 16];
 
@@ -145,6 +168,12 @@ impl SeedableRng for Xoroshiro128plusRng {
     }
 }
 
+/// The seed to use for a run: the one asked for, or else a fresh one
+/// from the operating system's entropy source.
+pub fn seed_or_random(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(rand::random)
+}
+
 #[cfg(test)]
 mod tests {
     use super::Xoroshiro128plusRng;
@@ -162,6 +191,19 @@ mod tests {
         assert!(b != a);
     }
 
+    #[test]
+    fn test_my_rng_serializes_its_state() {
+        let mut rng = super::MyRng::seed_from_u64(7);
+        for _ in 0..100 {
+            rng.next_u64();
+        }
+        let saved = serde_json::to_string(&rng).unwrap();
+        let mut resumed: super::MyRng = serde_json::from_str(&saved).unwrap();
+        for _ in 0..16 {
+            assert_eq!(rng.next_u64(), resumed.next_u64());
+        }
+    }
+
     #[test]
     fn test_xoshiro_clone() {
         let seed = [1, 2, 3, 4, 5, 5, 7, 8, 8, 7, 6, 5, 4, 3, 2, 1];
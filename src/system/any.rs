@@ -16,12 +16,16 @@ pub enum AnyParams {
     Wca(wca::WcaNParams),
     /// a lj system
     Lj(lj::LjParams),
+    /// a periodic lennard-jones fluid
+    LennardJones(lennard_jones::LennardJonesParams),
     /// a water system
     Water(water::WaterParams),
     /// an Ising system
     Ising(ising::IsingParams),
     /// a square well system
     Sw(optsquare::SquareWellNParams),
+    /// a binary square well mixture
+    BinarySw(binary_square::BinarySquareWellParams),
     /// a two well system
     TwoWells(two_wells::Parameters),
 }
@@ -67,12 +71,16 @@ pub enum Any {
     Wca(wca::Wca),
     /// A lj system
     Lj(lj::Lj),
+    /// A periodic lennard-jones fluid
+    LennardJones(lennard_jones::LennardJones),
     /// A water system
     Water(water::Water),
     /// An Ising system
     Ising(ising::Ising),
     /// a square well system
     Sw(optsquare::SquareWell),
+    /// a binary square well mixture
+    BinarySw(binary_square::BinarySquareWell),
     /// a two wells system
     TwoWells(two_wells::TwoWells),
 }
@@ -83,9 +91,15 @@ impl From<AnyParams> for Any {
             AnyParams::Fake(parameters) => Any::Fake(fake::Fake::from(parameters)),
             AnyParams::Wca(parameters) => Any::Wca(wca::Wca::from(parameters)),
             AnyParams::Lj(parameters) => Any::Lj(lj::Lj::from(parameters)),
+            AnyParams::LennardJones(parameters) => {
+                Any::LennardJones(lennard_jones::LennardJones::from(parameters))
+            }
             AnyParams::Ising(parameters) => Any::Ising(ising::Ising::from(parameters)),
             AnyParams::FakeErfinv(parameters) => Any::FakeErfinv(erfinv::ErfInv::from(parameters)),
             AnyParams::Sw(parameters) => Any::Sw(optsquare::SquareWell::from(parameters)),
+            AnyParams::BinarySw(parameters) => {
+                Any::BinarySw(binary_square::BinarySquareWell::from(parameters))
+            }
             AnyParams::Water(parameters) => Any::Water(water::Water::from(parameters)),
             AnyParams::TwoWells(parameters) => Any::TwoWells(two_wells::TwoWells::from(parameters)),
         }
@@ -98,9 +112,11 @@ impl Any {
             Any::Fake(s) => s as &dyn MovableSystem,
             Any::Wca(s) => s as &dyn MovableSystem,
             Any::Lj(s) => s as &dyn MovableSystem,
+            Any::LennardJones(s) => s as &dyn MovableSystem,
             Any::Ising(s) => s as &dyn MovableSystem,
             Any::FakeErfinv(s) => s as &dyn MovableSystem,
             Any::Sw(s) => s as &dyn MovableSystem,
+            Any::BinarySw(s) => s as &dyn MovableSystem,
             Any::Water(s) => s as &dyn MovableSystem,
             Any::TwoWells(s) => s as &dyn MovableSystem,
         }
@@ -110,9 +126,11 @@ impl Any {
             Any::Fake(s) => s as &mut dyn MovableSystem,
             Any::Wca(s) => s as &mut dyn MovableSystem,
             Any::Lj(s) => s as &mut dyn MovableSystem,
+            Any::LennardJones(s) => s as &mut dyn MovableSystem,
             Any::Ising(s) => s as &mut dyn MovableSystem,
             Any::FakeErfinv(s) => s as &mut dyn MovableSystem,
             Any::Sw(s) => s as &mut dyn MovableSystem,
+            Any::BinarySw(s) => s as &mut dyn MovableSystem,
             Any::Water(s) => s as &mut dyn MovableSystem,
             Any::TwoWells(s) => s as &mut dyn MovableSystem,
         }
@@ -135,9 +153,21 @@ impl System for Any {
     fn min_moves_to_randomize(&self) -> u64 {
         self.movable().min_moves_to_randomize()
     }
+    fn natural_sweep_length(&self) -> u64 {
+        self.movable().natural_sweep_length()
+    }
     fn dimensionality(&self) -> u64 {
         self.movable().dimensionality()
     }
+    fn total_states(&self) -> Option<f64> {
+        self.movable().total_states()
+    }
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+    fn energy_bounds(&self) -> (Option<Energy>, Option<Energy>) {
+        self.movable().energy_bounds()
+    }
     fn data_to_collect(&self, iter: u64) -> Vec<(Interned, f64)> {
         self.movable().data_to_collect(iter)
     }
@@ -150,15 +180,56 @@ impl ConfirmSystem for Any {
     fn confirm(&mut self) {
         self.movable_mut().confirm()
     }
+    fn reject(&mut self) {
+        self.movable_mut().reject()
+    }
 }
 
 impl MovableSystem for Any {
-    fn plan_move(&mut self, rng: &mut MyRng, d: Length) -> Option<Energy> {
-        self.movable_mut().plan_move(rng, d)
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        self.movable_mut().plan_move(rng, d, distribution)
+    }
+    fn plan_move_n(
+        &mut self,
+        rng: &mut MyRng,
+        d: Length,
+        distribution: MoveDistribution,
+        n: usize,
+    ) -> Option<Energy> {
+        self.movable_mut().plan_move_n(rng, d, distribution, n)
+    }
+    fn can_move_several(&self) -> bool {
+        self.movable().can_move_several()
+    }
+    fn plan_move_along(
+        &mut self,
+        rng: &mut MyRng,
+        axis: usize,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        self.movable_mut().plan_move_along(rng, axis, d, distribution)
+    }
+    fn can_move_along(&self) -> bool {
+        self.movable().can_move_along()
+    }
+    fn swap_species(&mut self, rng: &mut MyRng) -> Option<Energy> {
+        self.movable_mut().swap_species(rng)
+    }
+    fn can_swap_species(&self) -> bool {
+        self.movable().can_swap_species()
     }
     fn max_size(&self) -> Length {
         self.movable().max_size()
     }
+    fn natural_move_scale(&self) -> Length {
+        self.movable().natural_move_scale()
+    }
 }
 
 impl From<AnyGrandParams> for AnyGrand {
@@ -206,24 +277,74 @@ impl System for AnyGrand {
     fn min_moves_to_randomize(&self) -> u64 {
         self.grand().min_moves_to_randomize()
     }
+    fn natural_sweep_length(&self) -> u64 {
+        self.grand().natural_sweep_length()
+    }
     fn dimensionality(&self) -> u64 {
         self.grand().dimensionality()
     }
+    fn total_states(&self) -> Option<f64> {
+        self.grand().total_states()
+    }
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
 }
 
 impl ConfirmSystem for AnyGrand {
     fn confirm(&mut self) {
         self.grand_mut().confirm()
     }
+    fn reject(&mut self) {
+        self.grand_mut().reject()
+    }
 }
 
 impl MovableSystem for AnyGrand {
-    fn plan_move(&mut self, rng: &mut MyRng, d: Length) -> Option<Energy> {
-        self.grand_mut().plan_move(rng, d)
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        self.grand_mut().plan_move(rng, d, distribution)
+    }
+    fn plan_move_n(
+        &mut self,
+        rng: &mut MyRng,
+        d: Length,
+        distribution: MoveDistribution,
+        n: usize,
+    ) -> Option<Energy> {
+        self.grand_mut().plan_move_n(rng, d, distribution, n)
+    }
+    fn can_move_several(&self) -> bool {
+        self.grand().can_move_several()
+    }
+    fn plan_move_along(
+        &mut self,
+        rng: &mut MyRng,
+        axis: usize,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        self.grand_mut().plan_move_along(rng, axis, d, distribution)
+    }
+    fn can_move_along(&self) -> bool {
+        self.grand().can_move_along()
+    }
+    fn swap_species(&mut self, rng: &mut MyRng) -> Option<Energy> {
+        self.grand_mut().swap_species(rng)
+    }
+    fn can_swap_species(&self) -> bool {
+        self.grand().can_swap_species()
     }
     fn max_size(&self) -> Length {
         self.grand().max_size()
     }
+    fn natural_move_scale(&self) -> Length {
+        self.grand().natural_move_scale()
+    }
 }
 
 impl GrandSystem for AnyGrand {
@@ -0,0 +1,372 @@
+//! A binary mixture of square-well atoms.
+//!
+//! Both species have the same diameter and well width, so swapping
+//! the species of two atoms never causes an overlap.  Like atoms
+//! attract with a well depth of one epsilon, and unlike atoms with a
+//! depth of `unlike_depth` epsilon.
+
+use super::*;
+
+use dimensioned::{Abs, Cbrt, Dimensionless};
+use rand::distributions::Uniform;
+use rand::prelude::*;
+use std::f64::consts::PI;
+use vector3d::Vector3d;
+
+use super::square::{CellDimensionsGivenNumber, SquareWell, SquareWellNParams};
+
+/// The species of an atom in a binary mixture.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Species {
+    /// The first species
+    A,
+    /// The second species
+    B,
+}
+
+/// Parameters needed to configure a binary square-well mixture.
+#[derive(Serialize, Deserialize, Debug, AutoArgs)]
+#[allow(non_snake_case)]
+pub struct BinarySquareWellParams {
+    /// The width of the well, relative to the diameter.
+    pub well_width: Unitless,
+    /// The size of the cell.
+    pub _dim: CellDimensionsGivenNumber,
+    /// The number of atoms.
+    pub N: usize,
+    /// How many of the atoms are of species B.
+    pub N_B: usize,
+    /// The depth of the well between unlike atoms, relative to that
+    /// between like atoms.
+    pub unlike_depth: Unitless,
+}
+
+impl Default for BinarySquareWellParams {
+    fn default() -> Self {
+        BinarySquareWellParams {
+            well_width: Unitless::new(1.3),
+            _dim: CellDimensionsGivenNumber::FillingFraction(Unitless::new(0.3)),
+            N: 100,
+            N_B: 50,
+            unlike_depth: Unitless::new(0.5),
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+/// A binary square-well mixture.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BinarySquareWell {
+    /// The well width.
+    well_width: Length,
+    /// The depth of the well between unlike atoms, relative to that
+    /// between like atoms.
+    unlike_depth: Unitless,
+    /// The atom positions
+    pub positions: Vec<Vector3d<Length>>,
+    /// The species of each atom
+    pub species: Vec<Species>,
+    /// The energy of the system
+    E: Energy,
+    /// The dimensions of the box.
+    box_diagonal: Vector3d<Length>,
+    /// The last change we made (and might want to undo).
+    possible_change: Change,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+enum Change {
+    Move {
+        which: usize,
+        to: Vector3d<Length>,
+        e: Energy,
+    },
+    /// The species have already been swapped, so undoing this means
+    /// swapping them back.
+    Swap {
+        a: usize,
+        b: usize,
+        old_e: Energy,
+    },
+    None,
+}
+
+impl BinarySquareWell {
+    fn depth(&self, s1: Species, s2: Species) -> Energy {
+        if s1 == s2 {
+            units::EPSILON
+        } else {
+            self.unlike_depth * units::EPSILON
+        }
+    }
+    /// The energy of atom `which` if it were at `r` with species `s`,
+    /// or `None` if it would overlap another atom.
+    fn atom_energy(&self, which: usize, r: Vector3d<Length>, s: Species) -> Option<Energy> {
+        let mut e = Energy::new(0.0);
+        for (j, (&r1, &s1)) in self.positions.iter().zip(self.species.iter()).enumerate() {
+            if j != which {
+                let dist2 = self.closest_distance2(r1, r);
+                if dist2 < units::SIGMA * units::SIGMA {
+                    return None;
+                } else if dist2 < self.well_width * self.well_width {
+                    e -= self.depth(s, s1);
+                }
+            }
+        }
+        Some(e)
+    }
+    /// Plan to move a specified atom.  Returns the energy, or `None`
+    /// if the atom could not be placed there.
+    pub fn move_atom(&mut self, which: usize, r: Vector3d<Length>) -> Option<Energy> {
+        let s = self.species[which];
+        let old = self.atom_energy(which, self.positions[which], s).unwrap();
+        if let Some(new) = self.atom_energy(which, r, s) {
+            let e = self.E - old + new;
+            self.possible_change = Change::Move { which, to: r, e };
+            Some(e)
+        } else {
+            self.possible_change = Change::None;
+            None
+        }
+    }
+    /// Swap the species of atoms `a` and `b` right away, returning the
+    /// new energy.  This must be confirmed or rejected.
+    pub fn swap_atoms(&mut self, a: usize, b: usize) -> Energy {
+        let old_e = self.E;
+        let before = self.atom_energy(a, self.positions[a], self.species[a]).unwrap()
+            + self.atom_energy(b, self.positions[b], self.species[b]).unwrap();
+        self.species.swap(a, b);
+        // The energy between a and b is counted twice on both sides,
+        // but is the same before and after, so it cancels.
+        let after = self.atom_energy(a, self.positions[a], self.species[a]).unwrap()
+            + self.atom_energy(b, self.positions[b], self.species[b]).unwrap();
+        self.E = old_e - before + after;
+        self.possible_change = Change::Swap { a, b, old_e };
+        self.E
+    }
+    /// The shortest distance squared between two positions in our
+    /// periodic cell.
+    pub fn closest_distance2(&self, r1: Vector3d<Length>, r2: Vector3d<Length>) -> Area {
+        let mut dr = r2 - r1;
+        dr.x -= self.box_diagonal.x * (*(dr.x / self.box_diagonal.x).value()).round();
+        dr.y -= self.box_diagonal.y * (*(dr.y / self.box_diagonal.y).value()).round();
+        dr.z -= self.box_diagonal.z * (*(dr.z / self.box_diagonal.z).value()).round();
+        dr.norm2()
+    }
+    fn put_in_cell(&self, mut r: Vector3d<Length>) -> Vector3d<Length> {
+        r.x -= self.box_diagonal.x * (*(r.x / self.box_diagonal.x).value()).floor();
+        r.y -= self.box_diagonal.y * (*(r.y / self.box_diagonal.y).value()).floor();
+        r.z -= self.box_diagonal.z * (*(r.z / self.box_diagonal.z).value()).floor();
+        r
+    }
+    /// The number of atoms of species `s`.
+    pub fn count(&self, s: Species) -> usize {
+        self.species.iter().filter(|&&x| x == s).count()
+    }
+}
+
+impl From<BinarySquareWellParams> for BinarySquareWell {
+    fn from(params: BinarySquareWellParams) -> BinarySquareWell {
+        assert!(params.N_B <= params.N);
+        let box_diagonal = match params._dim {
+            CellDimensionsGivenNumber::CellWidth(w) => {
+                Vector3d::new(w.x.abs(), w.y.abs(), w.z.abs())
+            }
+            CellDimensionsGivenNumber::CellVolume(v) => {
+                let w = v.cbrt();
+                Vector3d::new(w, w, w)
+            }
+            CellDimensionsGivenNumber::FillingFraction(f) => {
+                let atom_volume = PI * units::SIGMA * units::SIGMA * units::SIGMA / 6.0;
+                let w = ((params.N as f64) * atom_volume / f).cbrt();
+                Vector3d::new(w, w, w)
+            }
+        };
+        // The one-component fluid places the atoms for us.
+        let sw = SquareWell::from(SquareWellNParams {
+            well_width: params.well_width,
+            _dim: CellDimensionsGivenNumber::CellWidth(box_diagonal),
+            N: params.N,
+        });
+        let mut species = vec![Species::A; params.N];
+        for s in species.iter_mut().take(params.N_B) {
+            *s = Species::B;
+        }
+        let mut bsw = BinarySquareWell {
+            well_width: params.well_width * units::SIGMA,
+            unlike_depth: params.unlike_depth,
+            positions: sw.positions,
+            species,
+            E: Energy::new(0.0),
+            box_diagonal,
+            possible_change: Change::None,
+        };
+        bsw.E = bsw.compute_energy();
+        bsw
+    }
+}
+
+impl System for BinarySquareWell {
+    fn energy(&self) -> Energy {
+        self.E
+    }
+    fn compute_energy(&self) -> Energy {
+        let mut e = Energy::new(0.0);
+        for (i, (&r1, &s1)) in self.positions.iter().zip(self.species.iter()).enumerate() {
+            for (&r2, &s2) in self.positions[i + 1..].iter().zip(self.species[i + 1..].iter()) {
+                if self.closest_distance2(r1, r2) < self.well_width * self.well_width {
+                    e -= self.depth(s1, s2);
+                }
+            }
+        }
+        e
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        if self.unlike_depth.value_unsafe.fract() == 0.0 {
+            Some(units::EPSILON)
+        } else {
+            None
+        }
+    }
+    fn greatest_possible_energy(&self) -> Option<Energy> {
+        if self.unlike_depth >= Unitless::new(0.0) {
+            Some(Energy::new(0.0))
+        } else {
+            None
+        }
+    }
+    /// This shuffles the species, keeping the composition, but does
+    /// not move the atoms.
+    fn randomize(&mut self, rng: &mut MyRng) -> Energy {
+        self.species.shuffle(rng);
+        self.E = self.compute_energy();
+        self.E
+    }
+    fn verify_energy(&self) {
+        assert!((self.compute_energy() - self.E).value_unsafe.abs() < 1e-9);
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        self.positions.len() as u64
+    }
+    fn dimensionality(&self) -> u64 {
+        self.min_moves_to_randomize() * 3
+    }
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+}
+
+impl ConfirmSystem for BinarySquareWell {
+    fn confirm(&mut self) {
+        match self.possible_change {
+            Change::None => (),
+            Change::Move { which, to, e } => {
+                self.positions[which] = to;
+                self.E = e;
+            }
+            Change::Swap { .. } => (),
+        }
+        self.possible_change = Change::None;
+    }
+    fn reject(&mut self) {
+        if let Change::Swap { a, b, old_e } = self.possible_change {
+            self.species.swap(a, b);
+            self.E = old_e;
+        }
+        self.possible_change = Change::None;
+    }
+}
+
+impl MovableSystem for BinarySquareWell {
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        if !self.positions.is_empty() {
+            let which = rng.sample(Uniform::new(0, self.positions.len()));
+            let to =
+                self.put_in_cell(self.positions[which] + distribution.sample(rng) * mean_distance);
+            self.move_atom(which, to)
+        } else {
+            None
+        }
+    }
+    fn swap_species(&mut self, rng: &mut MyRng) -> Option<Energy> {
+        let bs: Vec<usize> = (0..self.species.len())
+            .filter(|&i| self.species[i] == Species::B)
+            .collect();
+        if bs.is_empty() || bs.len() == self.species.len() {
+            return None;
+        }
+        let a = loop {
+            let a = rng.sample(Uniform::new(0, self.species.len()));
+            if self.species[a] == Species::A {
+                break a;
+            }
+        };
+        let b = bs[rng.sample(Uniform::new(0, bs.len()))];
+        Some(self.swap_atoms(a, b))
+    }
+    fn can_swap_species(&self) -> bool {
+        self.species.contains(&Species::A) && self.species.contains(&Species::B)
+    }
+    fn max_size(&self) -> Length {
+        use dimensioned::Sqrt;
+        0.5 * self.box_diagonal.norm2().sqrt()
+    }
+    /// A fifth of the width of the attractive shell, as for
+    /// [`super::square::SquareWell`].
+    fn natural_move_scale(&self) -> Length {
+        if self.well_width > units::SIGMA {
+            0.2 * (self.well_width - units::SIGMA)
+        } else {
+            0.05 * units::SIGMA
+        }
+    }
+}
+
+#[test]
+fn rejecting_a_swap_restores_everything() {
+    let mut bsw = BinarySquareWell::from(BinarySquareWellParams::default());
+    let mut rng = MyRng::seed_from_u64(1);
+    for _ in 0..10000 {
+        bsw.plan_move(&mut rng, Length::new(0.5), MoveDistribution::Gaussian);
+        bsw.confirm();
+    }
+    bsw.verify_energy();
+    let mut changed = 0;
+    for _ in 0..1000 {
+        let positions = bsw.positions.clone();
+        let species = bsw.species.clone();
+        let e = bsw.energy();
+        let e2 = bsw.swap_species(&mut rng).unwrap();
+        assert_eq!(bsw.energy(), e2);
+        assert!((bsw.compute_energy() - e2).value_unsafe.abs() < 1e-9);
+        if e2 != e {
+            changed += 1;
+        }
+        bsw.reject();
+        assert_eq!(bsw.positions, positions);
+        assert_eq!(bsw.species, species);
+        assert_eq!(bsw.energy(), e);
+    }
+    assert!(changed > 0);
+    for _ in 0..1000 {
+        bsw.swap_species(&mut rng).unwrap();
+        bsw.confirm();
+    }
+    assert_eq!(bsw.count(Species::B), 50);
+    bsw.verify_energy();
+}
+
+#[test]
+fn a_single_species_cannot_swap() {
+    let mut params = BinarySquareWellParams::default();
+    params.N_B = 0;
+    let mut bsw = BinarySquareWell::from(params);
+    let mut rng = MyRng::seed_from_u64(1);
+    assert_eq!(bsw.swap_species(&mut rng), None);
+    assert_eq!(bsw.energy(), bsw.compute_energy());
+}
@@ -98,10 +98,15 @@ impl ConfirmSystem for ErfInv {
 }
 
 impl MovableSystem for ErfInv {
-    fn plan_move(&mut self, rng: &mut MyRng, d: Length) -> Option<Energy> {
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         let i = rng.gen_range(0, self.position.len());
         self.possible_change = self.position.clone();
-        let v: f64 = rng.sample(rand_distr::StandardNormal);
+        let v = distribution.scalar(rng);
         self.possible_change[i] += v * d.value_unsafe;
         if self.possible_change[i] >= 1.0 || self.possible_change[i] <= -1.0 {
             return None;
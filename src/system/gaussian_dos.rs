@@ -0,0 +1,135 @@
+//! A test system with a known Gaussian density of states.
+//!
+//! The energy takes the values `k ε` for `-K <= k <= K`, and the
+//! density of states is `g(k) ∝ exp(-k²/2s²)`, so the exact `ln g` is
+//! a quadratic.  Moves step `k` up or down by one, and the system
+//! itself accepts them with probability `min(1, g(k')/g(k))`, so that
+//! it behaves like a real system with this many microstates at each
+//! energy.  This makes it cheap to check that a method recovers the
+//! right entropy.
+
+use super::*;
+
+use rand::prelude::*;
+
+/// The parameters of a Gaussian density of states.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct GaussianDosParams {
+    /// The largest `|k|`.
+    pub K: i64,
+    /// The width of the Gaussian, in units of ε.
+    pub s: f64,
+}
+
+/// A system with a Gaussian density of states.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GaussianDos {
+    params: GaussianDosParams,
+    k: i64,
+    proposed: i64,
+}
+
+impl From<GaussianDosParams> for GaussianDos {
+    fn from(params: GaussianDosParams) -> Self {
+        GaussianDos {
+            params,
+            k: 0,
+            proposed: 0,
+        }
+    }
+}
+
+impl GaussianDos {
+    /// The exact `ln g(E)`, up to an additive constant.
+    pub fn exact_lnw(&self, e: Energy) -> Unitless {
+        let k = *(e / units::EPSILON).value();
+        Unitless::new(-k * k / (2.0 * self.params.s * self.params.s))
+    }
+}
+
+impl System for GaussianDos {
+    fn energy(&self) -> Energy {
+        self.k as f64 * units::EPSILON
+    }
+    fn compute_energy(&self) -> Energy {
+        self.energy()
+    }
+    fn randomize(&mut self, rng: &mut MyRng) -> Energy {
+        self.k = rng.gen_range(-self.params.K, self.params.K + 1);
+        self.energy()
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        Some(units::EPSILON)
+    }
+    fn lowest_possible_energy(&self) -> Option<Energy> {
+        Some(-self.params.K as f64 * units::EPSILON)
+    }
+    fn greatest_possible_energy(&self) -> Option<Energy> {
+        Some(self.params.K as f64 * units::EPSILON)
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        (self.params.K * self.params.K) as u64
+    }
+    fn dimensionality(&self) -> u64 {
+        1
+    }
+}
+
+impl ConfirmSystem for GaussianDos {
+    fn confirm(&mut self) {
+        self.k = self.proposed;
+    }
+}
+
+impl MovableSystem for GaussianDos {
+    fn plan_move(&mut self, rng: &mut MyRng, _: Length, _: MoveDistribution) -> Option<Energy> {
+        let k = if rng.gen::<bool>() {
+            self.k + 1
+        } else {
+            self.k - 1
+        };
+        if k.abs() > self.params.K {
+            return None;
+        }
+        let ln_ratio =
+            *(self.exact_lnw(k as f64 * units::EPSILON) - self.exact_lnw(self.energy())).value();
+        self.proposed = if ln_ratio >= 0.0 || rng.gen::<f64>() < ln_ratio.exp() {
+            k
+        } else {
+            self.k
+        };
+        Some(self.proposed as f64 * units::EPSILON)
+    }
+    fn max_size(&self) -> Length {
+        Length::new(1.0)
+    }
+}
+
+#[test]
+fn samc_finds_the_gaussian_dos() {
+    use crate::mc::energy::{EnergyMC, EnergyMCParams, MethodParams};
+    use crate::mc::MonteCarlo;
+    let system = GaussianDos::from(GaussianDosParams { K: 8, s: 2.5 });
+    let exact = system.clone();
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = EnergyMCParams::default();
+    params.seed = Some(0);
+    params._method = MethodParams::Samc { t0: 1e3 };
+    let mut mc = EnergyMC::from_params(params, system, dir.path().join("gaussian.yaml"));
+    for _ in 0..1_000_000 {
+        mc.move_once();
+    }
+    let lnw = mc.normalized_lnw();
+    let ground = mc.bins.histogram.iter().position(|&h| h > 0).unwrap();
+    let e0 = mc.index_to_state(ground).E;
+    assert_eq!(e0, -8.0 * units::EPSILON);
+    for (i, &l) in lnw.iter().enumerate() {
+        if mc.bins.histogram[i] > 0 {
+            let e = mc.index_to_state(i).E;
+            let expected = exact.exact_lnw(e) - exact.exact_lnw(e0);
+            println!("{}: {} vs {}", e.pretty(), l.pretty(), expected.pretty());
+            assert!((*(l - expected).value()).abs() < 0.15);
+        }
+    }
+}
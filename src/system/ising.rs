@@ -56,6 +56,20 @@ impl System for Ising {
     fn energy(&self) -> Energy {
         self.E
     }
+    /// The absolute magnetization per spin (the signed magnetization
+    /// averages to zero by symmetry).  Summing the spins costs as much
+    /// as a sweep of moves, so we only do it once per sweep.
+    fn data_to_collect(&self, iter: u64) -> Vec<(Interned, f64)> {
+        if iter % ((self.N * self.N) as u64) == 0 {
+            let m: i64 = self.S.iter().map(|&s| s as i64).sum();
+            vec![(
+                "magnetization".into(),
+                m.abs() as f64 / (self.N * self.N) as f64,
+            )]
+        } else {
+            Vec::new()
+        }
+    }
     fn compute_energy(&self) -> Energy {
         let mut e: Energy = units::EPSILON * 0.0;
         for i1 in 0..self.N {
@@ -76,6 +90,11 @@ impl System for Ising {
     fn delta_energy(&self) -> Option<Energy> {
         Some(4. * units::EPSILON)
     }
+    fn energy_bounds(&self) -> (Option<Energy>, Option<Energy>) {
+        // Each of the 2N² bonds contributes ±ε.
+        let bonds = 2.0 * (self.N * self.N) as f64;
+        (Some(-bonds * units::EPSILON), Some(bonds * units::EPSILON))
+    }
     fn randomize(&mut self, rng: &mut MyRng) -> Energy {
         for x in self.S.iter_mut() {
             *x = (rng.next_u64() as i8 & 1) * 2 - 1;
@@ -89,6 +108,12 @@ impl System for Ising {
     fn dimensionality(&self) -> u64 {
         self.min_moves_to_randomize()
     }
+    fn total_states(&self) -> Option<f64> {
+        Some(2f64.powi((self.N * self.N) as i32))
+    }
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
 }
 
 impl ConfirmSystem for Ising {
@@ -101,7 +126,7 @@ impl ConfirmSystem for Ising {
 }
 
 impl MovableSystem for Ising {
-    fn plan_move(&mut self, rng: &mut MyRng, _: Length) -> Option<Energy> {
+    fn plan_move(&mut self, rng: &mut MyRng, _: Length, _: MoveDistribution) -> Option<Energy> {
         let i = rng.gen_range(0, self.N);
         let j = rng.gen_range(0, self.N);
 
@@ -132,7 +157,7 @@ fn energy_works_with_N(N: usize) {
 
     let mut rng = crate::rng::MyRng::seed_from_u64(10137);
     for _ in 0..10000 {
-        ising.plan_move(&mut rng, Length::new(0.0));
+        ising.plan_move(&mut rng, Length::new(0.0), MoveDistribution::Gaussian);
         ising.confirm();
         assert_eq!(ising.energy(), ising.compute_energy());
     }
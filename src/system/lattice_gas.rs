@@ -99,7 +99,7 @@ impl ConfirmSystem for LatticeGas {
 }
 
 impl MovableSystem for LatticeGas {
-    fn plan_move(&mut self, _: &mut MyRng, _: Length) -> Option<Energy> {
+    fn plan_move(&mut self, _: &mut MyRng, _: Length, _: MoveDistribution) -> Option<Energy> {
         None
     }
     fn max_size(&self) -> Length {
@@ -0,0 +1,432 @@
+//! A periodic Lennard-Jones fluid with a cutoff.
+
+use super::*;
+
+use crate::prettyfloat::PrettyFloat;
+use dimensioned::{Abs, Dimensionless, Sqrt};
+use rand::distributions::Uniform;
+use rand::prelude::*;
+use vector3d::Vector3d;
+
+use super::optcell::{Cell, CellDimensions};
+use super::wca::CellDimensionsGivenNumber;
+
+/// The parameters needed to configure a periodic Lennard-Jones system.
+#[derive(Serialize, Deserialize, Debug, AutoArgs)]
+#[allow(non_snake_case)]
+pub struct LennardJonesParams {
+    /// The size of the cell.
+    pub _dim: CellDimensionsGivenNumber,
+    /// The number of atoms.
+    pub N: usize,
+    /// The distance beyond which the potential is zero (default 2.5 sigma).
+    pub r_cutoff: Option<Length>,
+}
+
+impl Default for LennardJonesParams {
+    fn default() -> Self {
+        LennardJonesParams {
+            _dim: CellDimensionsGivenNumber::ReducedDensity(units::Density::new(0.5)),
+            N: 100,
+            r_cutoff: None,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+/// A Lennard-Jones fluid in a periodic box.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LennardJones {
+    /// The energy of the system
+    E: Energy,
+    /// The estimated accumulated error so far in E
+    error: Energy,
+    /// The dimensions of the box.
+    pub cell: Cell,
+    /// The last change we made (and might want to undo).
+    possible_change: Change,
+    /// The rescaled cell for a planned volume change.
+    #[serde(skip, default)]
+    resized: Option<Cell>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+/// Define the types of changes that can be made to the system
+enum Change {
+    /// Move an atom already in the system
+    Move {
+        which: usize,
+        to: Vector3d<Length>,
+        e: Energy,
+        dabse: Energy,
+    },
+    /// Scale the box to the cell in `resized`
+    Resize { e: Energy },
+    /// Make no changes to the system
+    None,
+}
+
+/// The 12-6 potential, truncated (but not shifted) at the cutoff.
+fn potential(r_squared: Area, r_cutoff_squared: Area) -> Energy {
+    if r_squared < r_cutoff_squared {
+        let sig_sqr = units::SIGMA * units::SIGMA;
+        let x3 = (sig_sqr / r_squared).powi(3);
+        4.0 * units::EPSILON * (x3 * x3 - x3)
+    } else {
+        0.0 * units::EPSILON
+    }
+}
+
+impl LennardJones {
+    fn r_cutoff_squared(&self) -> Area {
+        self.cell.r_cutoff * self.cell.r_cutoff
+    }
+    /// Add an atom at a given location.  Returns the new energy.
+    fn add_atom_at(&mut self, r: Vector3d<Length>) -> Energy {
+        let rc2 = self.r_cutoff_squared();
+        let mut e = self.E;
+        for r1 in self.cell.maybe_interacting_atoms(r) {
+            e += potential((r1 - r).norm2(), rc2);
+        }
+        self.cell.add_atom_at(r);
+        self.E = e;
+        e
+    }
+    /// Move a specified atom.  Returns the new energy, or
+    /// `None` if the atom could not be placed there.
+    pub fn move_atom(&mut self, which: usize, r: Vector3d<Length>) -> Option<Energy> {
+        let rc2 = self.r_cutoff_squared();
+        let mut e = self.E;
+        let mut dabse = Energy::new(0.0);
+        let from = self.cell.positions[which];
+        for r1 in self.cell.maybe_interacting_atoms_excluding(r, which) {
+            let de = potential((r1 - r).norm2(), rc2);
+            e += de;
+            dabse += de.abs();
+        }
+        for r1 in self.cell.maybe_interacting_atoms_excluding(from, which) {
+            let de = potential((r1 - from).norm2(), rc2);
+            e -= de;
+            dabse += de.abs();
+        }
+        self.possible_change = Change::Move {
+            which,
+            to: r,
+            e,
+            dabse,
+        };
+        Some(e)
+    }
+    fn num_atoms(&self) -> usize {
+        self.cell.positions.len()
+    }
+    fn set_energy(&mut self, new_e: Energy, dabse: Energy) {
+        let single_error = if dabse > new_e.abs() {
+            1e-14 * dabse * self.num_atoms() as f64
+        } else {
+            1e-14 * new_e.abs() * self.num_atoms() as f64
+        };
+        self.error += single_error * self.num_atoms() as f64;
+        if self.error > self.expected_accuracy(new_e) {
+            self.E = self.compute_energy();
+            self.error = 1e-15 * self.E.abs() * self.num_atoms() as f64;
+        } else {
+            self.E = new_e;
+        }
+    }
+    fn expected_accuracy(&self, newe: Energy) -> Energy {
+        // Near zero energy the error is set by the size of the pair
+        // terms, not by the total.
+        (newe.abs() + units::EPSILON)
+            * 1e-13
+            * (self.num_atoms() as f64)
+            * (self.num_atoms() as f64)
+    }
+}
+
+impl From<LennardJonesParams> for LennardJones {
+    fn from(params: LennardJonesParams) -> LennardJones {
+        let n = params.N;
+        let dim: CellDimensions = match params._dim {
+            CellDimensionsGivenNumber::CellWidth(v) => CellDimensions::CellWidth(v),
+            CellDimensionsGivenNumber::CellVolume(v) => CellDimensions::CellVolume(v),
+            CellDimensionsGivenNumber::ReducedDensity(d) => {
+                CellDimensions::CellVolume((n as f64) / d)
+            }
+        };
+        let r_cutoff = params.r_cutoff.unwrap_or(2.5 * units::SIGMA);
+        let cell = Cell::new(&dim, r_cutoff);
+        if !cell.holds_interaction_length() {
+            panic!("The cell is not large enough for the cutoff radius, sorry!");
+        }
+        let mut lj = LennardJones {
+            E: 0.0 * units::EPSILON,
+            error: 0.0 * units::EPSILON,
+            cell,
+            possible_change: Change::None,
+            resized: None,
+        };
+        // Start on a simple cubic lattice, which at low density puts
+        // every atom beyond the cutoff of its neighbors.
+        let per_side = (n as f64).cbrt().ceil() as usize;
+        let a = Vector3d::new(
+            lj.cell.box_diagonal.x / per_side as f64,
+            lj.cell.box_diagonal.y / per_side as f64,
+            lj.cell.box_diagonal.z / per_side as f64,
+        );
+        'placing: for i in 0..per_side {
+            for j in 0..per_side {
+                for k in 0..per_side {
+                    if lj.num_atoms() == n {
+                        break 'placing;
+                    }
+                    lj.add_atom_at(Vector3d::new(
+                        a.x * i as f64,
+                        a.y * j as f64,
+                        a.z * k as f64,
+                    ));
+                }
+            }
+        }
+        lj.E = lj.compute_energy();
+        lj
+    }
+}
+
+impl System for LennardJones {
+    fn energy(&self) -> Energy {
+        self.E
+    }
+    fn compute_energy(&self) -> Energy {
+        let rc2 = self.r_cutoff_squared();
+        let mut e: Energy = units::EPSILON * 0.0;
+        for (which, &r1) in self.cell.positions.iter().enumerate() {
+            for r2 in self.cell.maybe_interacting_atoms_excluding(r1, which) {
+                e += potential((r1 - r2).norm2(), rc2);
+            }
+        }
+        e * 0.5
+    }
+    fn update_caches(&mut self) {
+        self.cell.update_caches();
+    }
+    fn delta_energy(&self) -> Option<Energy> {
+        None
+    }
+    fn lowest_possible_energy(&self) -> Option<Energy> {
+        let n = self.num_atoms() as f64;
+        Some(-0.5 * n * (n - 1.0) * units::EPSILON)
+    }
+    fn verify_energy(&self) {
+        let egood = self.compute_energy();
+        let expected = self.expected_accuracy(self.E);
+        if (egood - self.E).abs() > expected {
+            println!(
+                "Error in E {} is {} when it should be estimated {} < {}",
+                PrettyFloat(*(self.E / units::EPSILON).value()),
+                PrettyFloat(*((egood - self.E) / units::EPSILON).value()),
+                PrettyFloat(*(self.error / units::EPSILON).value()),
+                PrettyFloat(*(expected / units::EPSILON).value())
+            );
+            assert_eq!(egood, self.E);
+        }
+    }
+    fn randomize(&mut self, rng: &mut MyRng) -> Energy {
+        let natoms = self.num_atoms();
+        for _ in 0..natoms {
+            self.cell.remove_atom(0);
+        }
+        self.E = 0.0 * units::EPSILON;
+        for _ in 0..natoms {
+            let r = self.cell.put_in_cell(Vector3d::new(
+                Length::new(rng.sample(Uniform::new(0.0, self.cell.box_diagonal.x.value_unsafe))),
+                Length::new(rng.sample(Uniform::new(0.0, self.cell.box_diagonal.y.value_unsafe))),
+                Length::new(rng.sample(Uniform::new(0.0, self.cell.box_diagonal.z.value_unsafe))),
+            ));
+            self.add_atom_at(r);
+        }
+        self.E = self.compute_energy();
+        self.E
+    }
+    fn min_moves_to_randomize(&self) -> u64 {
+        self.num_atoms() as u64
+    }
+    fn dimensionality(&self) -> u64 {
+        self.min_moves_to_randomize() * 3
+    }
+    fn print_debug(&self) {
+        print!(" E: {:9.3}", self.E.pretty());
+    }
+}
+
+impl ConfirmSystem for LennardJones {
+    fn confirm(&mut self) {
+        match self.possible_change {
+            Change::None => (),
+            Change::Move {
+                which,
+                to,
+                e,
+                dabse,
+            } => {
+                self.cell.move_atom(which, to);
+                self.set_energy(e, dabse);
+                self.possible_change = Change::None;
+            }
+            Change::Resize { e } => {
+                self.cell = self.resized.take().unwrap();
+                self.E = e;
+                self.error = 1e-15 * self.E.abs() * self.num_atoms() as f64;
+                self.possible_change = Change::None;
+            }
+        }
+    }
+    fn reject(&mut self) {
+        self.possible_change = Change::None;
+        self.resized = None;
+    }
+    fn describe(&self) -> String {
+        format!("N = {} ", self.num_atoms())
+    }
+}
+
+impl VolumeSystem for LennardJones {
+    fn volume(&self) -> Volume {
+        self.cell.volume()
+    }
+    fn num_atoms(&self) -> usize {
+        self.cell.positions.len()
+    }
+    fn plan_change_volume(&mut self, v: Volume) -> Option<Energy> {
+        self.possible_change = Change::None;
+        let cell = self.cell.scaled_to_volume(v);
+        if !cell.holds_interaction_length() {
+            return None;
+        }
+        let rc2 = self.r_cutoff_squared();
+        let mut e: Energy = units::EPSILON * 0.0;
+        for (which, &r1) in cell.positions.iter().enumerate() {
+            for r2 in cell.maybe_interacting_atoms_excluding(r1, which) {
+                e += potential((r1 - r2).norm2(), rc2);
+            }
+        }
+        let e = e * 0.5;
+        self.resized = Some(cell);
+        self.possible_change = Change::Resize { e };
+        Some(e)
+    }
+}
+
+impl MovableSystem for LennardJones {
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        if self.cell.positions.len() > 0 {
+            let which = rng.sample(Uniform::new(0, self.cell.positions.len()));
+            let to = self.cell.put_in_cell(
+                unsafe { *self.cell.positions.get_unchecked(which) }
+                    + distribution.sample(rng) * mean_distance,
+            );
+            self.move_atom(which, to)
+        } else {
+            None
+        }
+    }
+    fn max_size(&self) -> Length {
+        self.cell.box_diagonal.norm2().sqrt()
+    }
+}
+
+#[cfg(test)]
+fn mk_lj(natoms: usize, density: f64) -> LennardJones {
+    let mut param = LennardJonesParams::default();
+    param._dim = CellDimensionsGivenNumber::ReducedDensity(units::Density::new(density));
+    param.N = natoms;
+    LennardJones::from(param)
+}
+
+#[test]
+fn energy_is_right() {
+    let mut lj = mk_lj(100, 0.5);
+    lj.verify_energy();
+    let mut rng = MyRng::seed_from_u64(1);
+    for _ in 0..10000 {
+        if let Some(e) = lj.plan_move(&mut rng, Length::new(0.1), MoveDistribution::Gaussian) {
+            if e < lj.energy() + 10.0 * units::EPSILON {
+                lj.confirm();
+            }
+        }
+    }
+    lj.verify_energy();
+}
+
+#[test]
+fn low_density_metropolis_matches_second_virial() {
+    let natoms = 100;
+    let density = 0.01;
+    let temperature = 2.0 * units::EPSILON;
+    let mut lj = mk_lj(natoms, density);
+    let mut rng = MyRng::seed_from_u64(1);
+
+    // At low density the energy is dominated by isolated pairs, so
+    // <U> = N(N-1)/2 * (1/V) * \int u(r) exp(-u(r)/T) d^3r.
+    let rc = 2.5;
+    let rmin = 0.5;
+    let steps = 100000;
+    let dr = (rc - rmin) / steps as f64;
+    let mut integral = 0.0;
+    for i in 0..steps {
+        let r = rmin + (i as f64 + 0.5) * dr;
+        let u = *(potential(Area::new(r * r), Area::new(rc * rc)) / units::EPSILON).value();
+        integral += 4.0 * std::f64::consts::PI * r * r * u * (-u / 2.0).exp() * dr;
+    }
+    let expected = 0.5 * (natoms as f64 - 1.0) * density * integral;
+
+    let metropolis = |lj: &mut LennardJones, rng: &mut MyRng| {
+        if let Some(e) = lj.plan_move(rng, Length::new(1.0), MoveDistribution::Gaussian) {
+            let de = e - lj.energy();
+            if de < 0.0 * units::EPSILON || rng.gen::<f64>() < (-*(de / temperature).value()).exp()
+            {
+                lj.confirm();
+            }
+        }
+    };
+    for _ in 0..100000 {
+        metropolis(&mut lj, &mut rng);
+    }
+    let mut total = 0.0;
+    let samples = 2000000;
+    for _ in 0..samples {
+        metropolis(&mut lj, &mut rng);
+        total += *(lj.energy() / units::EPSILON).value();
+    }
+    lj.verify_energy();
+    let mean = total / samples as f64;
+    println!("mean energy {} vs expected {}", mean, expected);
+    assert!((mean - expected).abs() < 0.1 * expected.abs());
+}
+
+#[test]
+fn volume_change_scales_positions() {
+    let mut lj = mk_lj(100, 0.1);
+    let v = lj.volume();
+    let before = lj.cell.positions.clone();
+    let e = lj.energy();
+    lj.plan_change_volume(2.0 * v).unwrap();
+    lj.reject();
+    assert!(lj.resized.is_none());
+    assert_eq!(lj.volume(), v);
+    assert_eq!(lj.energy(), e);
+
+    let e2 = lj.plan_change_volume(2.0 * v).unwrap();
+    lj.confirm();
+    assert_eq!(lj.energy(), e2);
+    lj.verify_energy();
+    let factor = 2.0_f64.cbrt();
+    for (a, b) in lj.cell.positions.iter().zip(before.iter()) {
+        assert!(*((*a - *b * factor).norm2() / units::SIGMA / units::SIGMA).value() < 1e-20);
+    }
+}
@@ -189,7 +189,9 @@ impl From<LjParams> for Lj {
             max_radius: params.radius,
         };
         for attempt in 0..100000000 {
-            if let Some(newe) = lj.plan_move(&mut rng, 0.03 * units::SIGMA) {
+            if let Some(newe) =
+                lj.plan_move(&mut rng, 0.03 * units::SIGMA, MoveDistribution::Gaussian)
+            {
                 if newe < lj.E {
                     println!("reduced energy to {:?} after {} attempts", newe, attempt);
                     lj.confirm();
@@ -362,11 +364,16 @@ impl ConfirmSystem for Lj {
 }
 
 impl MovableSystem for Lj {
-    fn plan_move(&mut self, rng: &mut MyRng, mean_distance: Length) -> Option<Energy> {
-        use crate::rng::vector;
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         if self.positions.len() > 0 {
             let which = rng.sample(Uniform::new(0, self.positions.len()));
-            let to = unsafe { *self.positions.get_unchecked(which) } + vector(rng) * mean_distance;
+            let to = unsafe { *self.positions.get_unchecked(which) }
+                + distribution.sample(rng) * mean_distance;
             self.move_atom(which, to)
         } else {
             None
@@ -403,7 +410,7 @@ fn energy_is_right(natoms: usize) {
     let maxe = (natoms as f64) * 16.0 * units::EPSILON;
     let mut i = 0.0;
     while i < 1000.0 {
-        if let Some(newe) = lj.plan_move(&mut rng, Length::new(1.0)) {
+        if let Some(newe) = lj.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian) {
             if newe < maxe || newe < old_energy {
                 lj.confirm();
                 println!(
@@ -8,8 +8,10 @@ pub mod cell;
 pub mod optcell;
 pub mod units;
 
+pub mod binary_square;
 pub mod ising;
 pub mod lattice_gas;
+pub mod lennard_jones;
 pub mod lj;
 pub mod optsquare;
 pub mod square;
@@ -20,6 +22,8 @@ pub mod any;
 
 pub mod erfinv;
 pub mod fake;
+#[cfg(test)]
+pub mod gaussian_dos;
 pub mod two_wells;
 
 pub use crate::mc::binning::Interned;
@@ -49,6 +53,9 @@ pub type Volume = units::Volume<f64>;
 /// A force
 pub type Force = units::Force<f64>;
 
+/// A pressure
+pub type Pressure = units::Pressure<f64>;
+
 /// A physical system, which has some energy, and to which we can make
 /// some changes.
 pub trait System {
@@ -77,6 +84,14 @@ pub trait System {
     fn lowest_possible_energy(&self) -> Option<Energy> {
         None
     }
+    /// The exact bounds `(lowest, greatest)` of the energy, where they
+    /// are known.  Unlike `lowest_possible_energy` and
+    /// `greatest_possible_energy`, which may be estimates, no state may
+    /// lie outside these, so `EnergyMC` sizes its bins to span them up
+    /// front and refuses any move beyond them.
+    fn energy_bounds(&self) -> (Option<Energy>, Option<Energy>) {
+        (None, None)
+    }
     /// Update any cached info after resume.  This is to make it
     /// possible for a system to avoid saving information that is easy
     /// and safe to recompute when restarting.
@@ -91,8 +106,39 @@ pub trait System {
     /// How many moves at the minimum could change all the coordinates of the
     /// system
     fn min_moves_to_randomize(&self) -> u64;
+    /// The number of moves in one sweep, so that run lengths and
+    /// intervals can be given in units that mean the same thing for
+    /// any size of system.  By default this is
+    /// `min_moves_to_randomize`, which is the number of atoms (or
+    /// spins) for most systems.
+    fn natural_sweep_length(&self) -> u64 {
+        self.min_moves_to_randomize()
+    }
     /// Dimensions of the configuration space
     fn dimensionality(&self) -> u64;
+    /// The total number of configurations, for systems with a finite
+    /// and known number of them.  This fixes the normalization of the
+    /// density of states.
+    fn total_states(&self) -> Option<f64> {
+        None
+    }
+    /// A copy of the current configuration, which `restore` can return
+    /// to later, so that a driver can keep the best configuration it
+    /// has seen.  This is `None` for systems that cannot copy theirs.
+    fn snapshot(&self) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    /// Return to a configuration copied by `snapshot`, along with its
+    /// energy.
+    fn restore(&mut self, snapshot: Self)
+    where
+        Self: Sized,
+    {
+        *self = snapshot;
+    }
     /// Print information for debugging purposes.
     fn print_debug(&self) {}
 }
@@ -101,6 +147,10 @@ pub trait System {
 pub trait ConfirmSystem: System {
     /// Confirm the change, whatever it may have been.
     fn confirm(&mut self);
+    /// Abandon a planned change.  This only needs to do something for
+    /// plans (like [`MovableSystem::plan_move_n`]) that modify the
+    /// system before they are confirmed.
+    fn reject(&mut self) {}
     /// Print something descriptive about this system.
     fn describe(&self) -> String {
         "".to_string()
@@ -114,9 +164,259 @@ pub trait MovableSystem: ConfirmSystem {
     /// infinite energy), `None` is returned, and no change is made to
     /// the system.  The atom is not actually moved until the change
     /// is confirmed.
-    fn plan_move(&mut self, _: &mut MyRng, mean_distance: Length) -> Option<Energy>;
+    ///
+    /// The displacement (if any) is drawn from `distribution` and
+    /// scaled by `mean_distance`.
+    ///
+    /// The new energy should be found incrementally, by adding the
+    /// change due to the moved atom's neighbors to the cached
+    /// `energy()`, so that a move costs O(1) rather than O(N).  The
+    /// Monte Carlo drivers never recompute the energy from scratch
+    /// except through `compute_energy` when checking for drift.
+    fn plan_move(
+        &mut self,
+        _: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy>;
+    /// Considers moving `n` randomly chosen atoms at once, and returns
+    /// the resulting energy, or `None` (leaving the system unchanged)
+    /// if any of the moves is impossible.  Unlike `plan_move`, the
+    /// atoms may be moved right away, so this must be followed by
+    /// either `confirm` or `reject`.  Systems that cannot move several
+    /// atoms at once (see `can_move_several`) only support `n = 1`.
+    fn plan_move_n(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+        n: usize,
+    ) -> Option<Energy> {
+        assert_eq!(n, 1, "this system can only move one atom at a time");
+        self.plan_move(rng, mean_distance, distribution)
+    }
+    /// Whether `plan_move_n` can move more than one atom at once.
+    fn can_move_several(&self) -> bool {
+        false
+    }
+    /// Considers moving an atom along `axis` (0, 1 or 2) alone, by a
+    /// displacement drawn from `distribution.scalar` and scaled by
+    /// `mean_distance`, as for `plan_move`.  This lets a driver tune a
+    /// separate scale along each axis, for systems much longer in some
+    /// directions than others.  Systems that cannot (see
+    /// `can_move_along`) by default make an ordinary `plan_move`.
+    fn plan_move_along(
+        &mut self,
+        rng: &mut MyRng,
+        axis: usize,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        let _ = axis;
+        self.plan_move(rng, mean_distance, distribution)
+    }
+    /// Whether `plan_move_along` really moves along the axis given.
+    fn can_move_along(&self) -> bool {
+        false
+    }
+    /// Swaps the species of two randomly chosen atoms of different
+    /// species, and returns the resulting energy.  Like `plan_move_n`
+    /// the swap is made right away, so it must be followed by either
+    /// `confirm` or `reject`, which undoes it.  Systems with only one
+    /// species return `None`, which is the default.
+    fn swap_species(&mut self, _: &mut MyRng) -> Option<Energy> {
+        None
+    }
+    /// Whether `swap_species` can ever succeed, i.e. whether this
+    /// system has atoms of more than one species.
+    fn can_swap_species(&self) -> bool {
+        false
+    }
+    /// The displacement of the atom moved by the move just planned,
+    /// before it is confirmed or rejected, for systems that can tell.
+    /// Drivers checking detailed balance use this to make sure
+    /// displacements are accepted as often as their reverses.  The
+    /// default is `None`, which skips that check.
+    fn planned_displacement(&self) -> Option<vector3d::Vector3d<Length>> {
+        None
+    }
     /// A maximum reasonable value for mean_distance, i.e. the size of the configuration space.
     fn max_size(&self) -> Length;
+    /// A reasonable default for mean_distance, used when the user
+    /// does not give a translation scale.  This should be small enough
+    /// that a typical move changes the energy by about one bin, so the
+    /// default is a twentieth of the atomic diameter.  Systems with a
+    /// natural length scale of their own should scale with it.
+    fn natural_move_scale(&self) -> Length {
+        0.05 * units::SIGMA
+    }
+}
+
+/// The distribution from which we draw the displacement of a move.
+/// Both are scaled so that each component has variance one, so the
+/// mean-square displacement is `3 mean_distance²` either way.
+#[derive(Serialize, Deserialize, Debug, AutoArgs, Clone, Copy, PartialEq, Eq)]
+pub enum MoveDistribution {
+    /// Move by a Gaussian displacement (the default)
+    Gaussian,
+    /// Move by a displacement uniform within a cube
+    Uniform,
+}
+
+impl Default for MoveDistribution {
+    fn default() -> Self {
+        MoveDistribution::Gaussian
+    }
+}
+
+impl MoveDistribution {
+    /// A random displacement with variance one in each direction.
+    pub fn sample(self, rng: &mut MyRng) -> vector3d::Vector3d<f64> {
+        match self {
+            MoveDistribution::Gaussian => crate::rng::vector(rng),
+            MoveDistribution::Uniform => crate::rng::uniform_vector(rng),
+        }
+    }
+    /// A random number with mean zero and variance one.
+    pub fn scalar(self, rng: &mut MyRng) -> f64 {
+        match self {
+            MoveDistribution::Gaussian => crate::rng::normal(rng),
+            MoveDistribution::Uniform => crate::rng::uniform(rng),
+        }
+    }
+    /// Check that displacements from this distribution are symmetric,
+    /// using [`check_symmetric`].
+    pub fn check_symmetry(self, rng: &mut MyRng, samples: usize) -> Result<(), String> {
+        check_symmetric(|rng| self.sample(rng), rng, samples)
+    }
+}
+
+/// Check that `samples` displacements from `draw` are symmetric under
+/// `d -> -d`, using [`SymmetryCounts`].  Our drivers accept a move
+/// from one configuration to another with the plain ratio of weights,
+/// which only satisfies detailed balance if the reverse move is
+/// proposed just as often, so an asymmetric displacement silently
+/// biases the density of states.
+pub fn check_symmetric<F: FnMut(&mut MyRng) -> vector3d::Vector3d<f64>>(
+    mut draw: F,
+    rng: &mut MyRng,
+    samples: usize,
+) -> Result<(), String> {
+    let mut counts = SymmetryCounts::default();
+    for _ in 0..samples {
+        counts.record(draw(rng));
+    }
+    counts.check()
+}
+
+/// Counts of displacements (in units of the move scale) by direction
+/// and size, to check that they are symmetric under `d -> -d`.
+///
+/// For each component we count positive and negative displacements
+/// in a few ranges of magnitude (taking the variance to be about one),
+/// and the check fails if the two counts in any range differ by more
+/// than five standard deviations.
+#[derive(Debug, Clone, Default)]
+pub struct SymmetryCounts {
+    positive: [[u64; 4]; 3],
+    negative: [[u64; 4]; 3],
+    samples: u64,
+}
+
+impl SymmetryCounts {
+    const EDGES: [f64; 3] = [0.5, 1.0, 2.0];
+    /// Count one displacement.
+    pub fn record(&mut self, d: vector3d::Vector3d<f64>) {
+        self.samples += 1;
+        for (c, &x) in [d.x, d.y, d.z].iter().enumerate() {
+            let k = Self::EDGES.iter().filter(|&&e| x.abs() >= e).count();
+            if x > 0.0 {
+                self.positive[c][k] += 1;
+            } else if x < 0.0 {
+                self.negative[c][k] += 1;
+            }
+        }
+    }
+    /// How many displacements have been counted.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+    /// Are the displacements counted so far symmetric?
+    pub fn check(&self) -> Result<(), String> {
+        for (c, (pos, neg)) in self.positive.iter().zip(self.negative.iter()).enumerate() {
+            for (k, (&p, &m)) in pos.iter().zip(neg.iter()).enumerate() {
+                let sigma = ((p + m) as f64).sqrt();
+                if (p as f64 - m as f64).abs() > 5.0 * sigma {
+                    let lo = if k == 0 { 0.0 } else { Self::EDGES[k - 1] };
+                    let hi = Self::EDGES.get(k).cloned().unwrap_or(std::f64::INFINITY);
+                    return Err(format!(
+                        "component {} has {} positive and {} negative displacements \
+                         with magnitude from {} to {}",
+                        "xyz".as_bytes()[c] as char,
+                        p,
+                        m,
+                        lo,
+                        hi
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn move_distributions_have_unit_variance() {
+    use rand::SeedableRng;
+    let mut rng = MyRng::seed_from_u64(0);
+    let n = 100000;
+    for &d in &[MoveDistribution::Gaussian, MoveDistribution::Uniform] {
+        let msd = (0..n).map(|_| d.sample(&mut rng).norm2()).sum::<f64>() / n as f64;
+        assert!((msd - 3.0).abs() < 0.05, "{:?} has msd {}", d, msd);
+        let var = (0..n).map(|_| d.scalar(&mut rng).powi(2)).sum::<f64>() / n as f64;
+        assert!((var - 1.0).abs() < 0.02, "{:?} has variance {}", d, var);
+    }
+}
+
+#[test]
+fn asymmetric_displacements_are_caught() {
+    use rand::SeedableRng;
+    let mut rng = MyRng::seed_from_u64(0);
+    for &d in &[MoveDistribution::Gaussian, MoveDistribution::Uniform] {
+        assert_eq!(d.check_symmetry(&mut rng, 100000), Ok(()));
+    }
+    // A small drift in one direction.
+    let drifting = |rng: &mut MyRng| {
+        let mut d = crate::rng::vector(rng);
+        d.y += 0.05;
+        d
+    };
+    let e = check_symmetric(drifting, &mut rng, 100000).unwrap_err();
+    assert!(e.contains("component y"), "{}", e);
+    // As many moves each way, but longer ones to the left.
+    let lopsided = |rng: &mut MyRng| {
+        let mut d = crate::rng::vector(rng);
+        if d.x < 0.0 {
+            d.x *= 1.5;
+        }
+        d
+    };
+    let e = check_symmetric(lopsided, &mut rng, 100000).unwrap_err();
+    assert!(e.contains("component x"), "{}", e);
+}
+
+/// A system whose volume can change, for constant-pressure simulations.
+pub trait VolumeSystem: MovableSystem {
+    /// The volume of the box.
+    fn volume(&self) -> Volume;
+    /// The number of atoms.
+    fn num_atoms(&self) -> usize;
+    /// Considers scaling the box (with every position in it) to have
+    /// volume `v`, and returns the resulting energy.  If the new volume
+    /// is impossible (e.g. atoms would overlap), `None` is returned.
+    /// As with `plan_move`, nothing changes until the change is
+    /// confirmed.
+    fn plan_change_volume(&mut self, v: Volume) -> Option<Energy>;
 }
 
 /// A system that can gain or lose atoms?
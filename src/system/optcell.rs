@@ -191,10 +191,32 @@ impl Cell {
         }
         r
     }
+    /// Whether the box is at least `r_cutoff` wide in every direction,
+    /// which the subcell lists need.
+    pub fn holds_interaction_length(&self) -> bool {
+        self.r_cutoff <= self.box_diagonal.x
+            && self.r_cutoff <= self.box_diagonal.y
+            && self.r_cutoff <= self.box_diagonal.z
+    }
     /// The volume of the cell
     pub fn volume(&self) -> Volume {
         self.box_diagonal[0] * self.box_diagonal[1] * self.box_diagonal[2]
     }
+    /// A copy of this cell scaled uniformly to volume `v`, keeping the
+    /// reduced coordinates of every atom.
+    pub fn scaled_to_volume(&self, v: Volume) -> Cell {
+        let factor = *(v / self.volume()).value();
+        let factor = factor.cbrt();
+        let mut cell = Cell {
+            box_diagonal: self.box_diagonal * factor,
+            r_cutoff: self.r_cutoff,
+            positions: self.positions.iter().map(|&r| r * factor).collect(),
+            num_subcells: Vector3d::default(),
+            subcells: Vec::new(),
+        };
+        cell.update_caches();
+        cell
+    }
     /// PUBLIC FOR TESTING ONLY! The shortest distance squared between two vectors.
     pub fn closest_distance2(&self, r1: Vector3d<Length>, r2: Vector3d<Length>) -> Area {
         let mut dr = r2 - r1;
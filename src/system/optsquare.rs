@@ -28,6 +28,13 @@ pub struct SquareWell {
     pub cell: Cell,
     /// The last change we made (and might want to undo).
     possible_change: Change,
+    /// The atoms moved by an unconfirmed `plan_move_n`, with their
+    /// old positions and the energy before each was moved.
+    #[serde(default)]
+    moved: Vec<(usize, Vector3d<Length>, Energy)>,
+    /// The rescaled cell for a planned volume change.
+    #[serde(skip, default)]
+    resized: Option<Cell>,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -46,6 +53,10 @@ enum Change {
         which: usize,
         e: Energy,
     },
+    /// The new cell is in `resized`.
+    Resize {
+        e: Energy,
+    },
     None,
 }
 
@@ -155,16 +166,15 @@ impl SquareWell {
 impl From<SquareWellParams> for SquareWell {
     fn from(params: SquareWellParams) -> SquareWell {
         let cell = Cell::new(&params._dim, params.well_width * units::SIGMA);
-        if cell.r_cutoff > cell.box_diagonal.x
-            || cell.r_cutoff > cell.box_diagonal.y
-            || cell.r_cutoff > cell.box_diagonal.z
-        {
+        if !cell.holds_interaction_length() {
             panic!("The cell is not large enough for the well width, sorry!");
         }
         SquareWell {
             E: 0.0 * units::EPSILON,
             cell,
             possible_change: Change::None,
+            moved: Vec::new(),
+            resized: None,
         }
     }
 }
@@ -203,15 +213,34 @@ impl System for SquareWell {
         todo!()
     }
     fn min_moves_to_randomize(&self) -> u64 {
-        self.num_atoms() as u64
+        self.cell.positions.len() as u64
     }
     fn dimensionality(&self) -> u64 {
         self.min_moves_to_randomize() * 3
     }
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
 }
 
 impl ConfirmSystem for SquareWell {
+    fn reject(&mut self) {
+        while let Some((which, from, e)) = self.moved.pop() {
+            self.cell.move_atom(which, from);
+            self.E = e;
+        }
+        self.possible_change = Change::None;
+        self.resized = None;
+    }
     fn confirm(&mut self) {
+        self.moved.clear();
+        self.make_change();
+    }
+}
+
+impl SquareWell {
+    /// Carry out the planned change without forgetting a batch move.
+    fn make_change(&mut self) {
         match self.possible_change {
             Change::None => (),
             Change::Move { which, to, e } => {
@@ -229,10 +258,46 @@ impl ConfirmSystem for SquareWell {
                 self.E = e;
                 self.possible_change = Change::None;
             }
+            Change::Resize { e } => {
+                self.cell = self.resized.take().unwrap();
+                self.E = e;
+                self.possible_change = Change::None;
+            }
         }
     }
 }
 
+impl VolumeSystem for SquareWell {
+    fn volume(&self) -> Volume {
+        self.cell.volume()
+    }
+    fn num_atoms(&self) -> usize {
+        self.cell.positions.len()
+    }
+    fn plan_change_volume(&mut self, v: Volume) -> Option<Energy> {
+        self.possible_change = Change::None;
+        let cell = self.cell.scaled_to_volume(v);
+        if !cell.holds_interaction_length() {
+            return None;
+        }
+        let mut e: Energy = units::EPSILON * 0.0;
+        for (which, &r1) in cell.positions.iter().enumerate() {
+            for r2 in cell.maybe_interacting_atoms_excluding(r1, which) {
+                let dist2 = (r1 - r2).norm2();
+                if dist2 < units::SIGMA * units::SIGMA {
+                    return None;
+                } else if dist2 < cell.r_cutoff * cell.r_cutoff {
+                    e -= units::EPSILON;
+                }
+            }
+        }
+        let e = e * 0.5;
+        self.resized = Some(cell);
+        self.possible_change = Change::Resize { e };
+        Some(e)
+    }
+}
+
 impl GrandSystem for SquareWell {
     fn plan_add(&mut self, rng: &mut MyRng) -> Option<Energy> {
         let r = self.cell.put_in_cell(Vector3d::new(
@@ -253,7 +318,7 @@ impl GrandSystem for SquareWell {
 
 impl GrandReplicaSystem for SquareWell {
     fn plan_swap_atom(&self, other: &Self, rng: &mut MyRng) -> Option<(usize, Energy, Energy)> {
-        let which = rng.sample(Uniform::new(0, self.num_atoms()));
+        let which = rng.sample(Uniform::new(0, self.cell.positions.len()));
         // The following clones are lazy and inefficient.  If we're using this seriously, we should
         // implement functions that don't mutate self to examine adding or removing an atom.
         let e_self = self.clone().remove_atom_number(which);
@@ -270,21 +335,86 @@ impl GrandReplicaSystem for SquareWell {
 }
 
 impl MovableSystem for SquareWell {
-    fn plan_move(&mut self, rng: &mut MyRng, mean_distance: Length) -> Option<Energy> {
-        use crate::rng::vector;
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         if self.cell.positions.len() > 0 {
             let which = rng.sample(Uniform::new(0, self.cell.positions.len()));
             let to = self.cell.put_in_cell(
-                unsafe { *self.cell.positions.get_unchecked(which) } + vector(rng) * mean_distance,
+                unsafe { *self.cell.positions.get_unchecked(which) }
+                    + distribution.sample(rng) * mean_distance,
             );
             self.move_atom(which, to)
         } else {
             None
         }
     }
+    fn plan_move_along(
+        &mut self,
+        rng: &mut MyRng,
+        axis: usize,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
+        if self.cell.positions.is_empty() {
+            return None;
+        }
+        let which = rng.sample(Uniform::new(0, self.cell.positions.len()));
+        let step = distribution.scalar(rng);
+        let d = match axis {
+            0 => Vector3d::new(step, 0.0, 0.0),
+            1 => Vector3d::new(0.0, step, 0.0),
+            _ => Vector3d::new(0.0, 0.0, step),
+        };
+        let to = self
+            .cell
+            .put_in_cell(self.cell.positions[which] + d * mean_distance);
+        self.move_atom(which, to)
+    }
+    fn plan_move_n(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+        n: usize,
+    ) -> Option<Energy> {
+        self.moved.clear();
+        for _ in 0..n {
+            let e = self.E;
+            if self.plan_move(rng, mean_distance, distribution).is_none() {
+                self.reject();
+                return None;
+            }
+            if let Change::Move { which, .. } = self.possible_change {
+                let from = self.cell.positions[which];
+                self.moved.push((which, from, e));
+            }
+            // Actually make this move, so the next one sees it.
+            self.make_change();
+        }
+        Some(self.E)
+    }
+    fn can_move_several(&self) -> bool {
+        true
+    }
+    fn can_move_along(&self) -> bool {
+        true
+    }
     fn max_size(&self) -> Length {
         self.cell.box_diagonal.norm2().sqrt()
     }
+    /// A fifth of the width of the attractive shell, as for
+    /// [`super::square::SquareWell`].
+    fn natural_move_scale(&self) -> Length {
+        if self.well_width > Unitless::new(1.0) {
+            0.2 * (self.well_width - Unitless::new(1.0)) * units::SIGMA
+        } else {
+            0.05 * units::SIGMA
+        }
+    }
 }
 
 fn max_balls_within(mut distance: Length) -> u64 {
@@ -452,7 +582,7 @@ fn closest_distance_matches(natoms: usize) {
     }
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     for &r1 in sw.cell.positions.iter() {
@@ -487,7 +617,7 @@ fn maybe_interacting_needs_no_shifting(natoms: usize) {
     let mut sw = mk_sw(natoms, 0.3);
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     for &r1 in sw.cell.positions.iter() {
@@ -538,7 +668,7 @@ fn maybe_interacting_includes_everything() {
     let mut sw = mk_sw(100, 0.3);
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm()
     }
     for &r1 in sw.cell.positions.iter() {
@@ -555,7 +685,7 @@ fn maybe_interacting_excluding_includes_everything(natoms: usize) {
             .verify_maybe_interacting_excluding_includes_everything(r1, which);
     }
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     println!("Finished moving stuff around...");
@@ -605,7 +735,7 @@ fn energy_is_right(natoms: usize, ff: f64) {
     assert_eq!(sw.energy(), sw.compute_energy());
     let mut rng = MyRng::seed_from_u64(1);
     for i in 0..1000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
         println!(
             "after move {}... {} vs {}",
@@ -624,3 +754,160 @@ fn mk_sw(natoms: usize, ff: f64) -> SquareWell {
     param.N = natoms;
     SquareWell::from(param)
 }
+
+#[test]
+fn reject_after_batch_move_restores_energy() {
+    let mut sw = mk_sw(30, 0.3);
+    let mut rng = MyRng::seed_from_u64(1);
+    for _ in 0..10000 {
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
+        sw.confirm();
+    }
+    let mut planned = 0;
+    for _ in 0..1000 {
+        let e = sw.energy();
+        let positions = sw.cell.positions.clone();
+        if let Some(e2) = sw.plan_move_n(&mut rng, Length::new(0.3), MoveDistribution::Gaussian, 5)
+        {
+            assert_eq!(e2, sw.energy());
+            planned += 1;
+        }
+        sw.reject();
+        assert_eq!(sw.energy(), e);
+        assert_eq!(sw.cell.positions, positions);
+    }
+    assert!(planned > 0);
+    // Confirming keeps the batch.
+    while sw.plan_move_n(&mut rng, Length::new(0.3), MoveDistribution::Gaussian, 5) == None {}
+    sw.confirm();
+    sw.reject();
+    sw.verify_energy();
+    assert_eq!(sw.energy(), sw.compute_energy());
+}
+
+#[test]
+fn volume_change_keeps_reduced_coordinates() {
+    let mut sw = mk_sw(30, 0.1);
+    let mut rng = MyRng::seed_from_u64(1);
+    for _ in 0..10000 {
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
+        sw.confirm();
+    }
+    let e = sw.energy();
+    let v = sw.volume();
+    let reduced = |sw: &SquareWell| -> Vec<Vector3d<f64>> {
+        let b = sw.cell.box_diagonal;
+        sw.cell
+            .positions
+            .iter()
+            .map(|r| Vector3d::new(*(r.x / b.x).value(), *(r.y / b.y).value(), *(r.z / b.z).value()))
+            .collect()
+    };
+    let before = reduced(&sw);
+
+    // An unconfirmed volume change leaves the box alone.
+    assert!(sw.plan_change_volume(1.5 * v).is_some());
+    sw.reject();
+    assert_eq!(sw.volume(), v);
+    assert_eq!(sw.energy(), e);
+    assert_eq!(reduced(&sw), before);
+
+    let e2 = sw.plan_change_volume(1.5 * v).unwrap();
+    sw.confirm();
+    assert!((*(sw.volume() / v).value() - 1.5).abs() < 1e-12);
+    assert_eq!(sw.energy(), e2);
+    sw.verify_energy();
+    for (a, b) in reduced(&sw).iter().zip(before.iter()) {
+        assert!((*a - *b).norm2() < 1e-20);
+    }
+
+    // Squeezing the atoms together is impossible.
+    assert_eq!(sw.plan_change_volume(1e-3 * v), None);
+}
+
+#[test]
+fn insert_then_remove_restores_the_energy() {
+    let mut sw = mk_sw(30, 0.3);
+    let mut rng = MyRng::seed_from_u64(1);
+    for _ in 0..10000 {
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
+        sw.confirm();
+    }
+    let e = sw.energy();
+    let positions = sw.cell.positions.clone();
+
+    // A rejected insertion changes nothing.
+    while sw.plan_add(&mut rng) == None {}
+    sw.reject();
+    assert_eq!(sw.energy(), e);
+    assert_eq!(GrandSystem::num_atoms(&sw), 30);
+
+    let mut inserted = 0;
+    for _ in 0..100 {
+        if let Some(e2) = sw.plan_add(&mut rng) {
+            sw.confirm();
+            assert_eq!(sw.energy(), e2);
+            assert_eq!(GrandSystem::num_atoms(&sw), 31);
+            sw.verify_energy();
+            // The new atom is the last one.
+            assert_eq!(sw.remove_atom_number(30), e);
+            sw.confirm();
+            inserted += 1;
+        }
+        assert_eq!(sw.energy(), e);
+        assert_eq!(GrandSystem::num_atoms(&sw), 30);
+        assert_eq!(sw.cell.positions, positions);
+    }
+    assert!(inserted > 0);
+    assert_eq!(sw.energy(), sw.compute_energy());
+}
+
+#[test]
+fn restoring_a_snapshot_returns_to_its_exact_energy() {
+    let mut sw = mk_sw(30, 0.3);
+    let mut rng = MyRng::seed_from_u64(1);
+    for _ in 0..10000 {
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
+        sw.confirm();
+    }
+    let snapshot = sw.snapshot().unwrap();
+    let e = sw.energy();
+    let positions = sw.cell.positions.clone();
+    for _ in 0..10000 {
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
+        sw.confirm();
+    }
+    assert_ne!(sw.cell.positions, positions);
+    sw.restore(snapshot);
+    assert_eq!(sw.energy(), e);
+    assert_eq!(sw.energy(), sw.compute_energy());
+    assert_eq!(sw.cell.positions, positions);
+    // The restored system carries on moving with a correct energy.
+    for _ in 0..1000 {
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
+        sw.confirm();
+    }
+    sw.verify_energy();
+}
+
+#[test]
+fn moves_along_an_axis_change_only_that_coordinate() {
+    let mut sw = mk_sw(30, 0.1);
+    let mut rng = MyRng::seed_from_u64(1);
+    let mut moved = 0;
+    for _ in 0..1000 {
+        let before = sw.cell.positions.clone();
+        if sw
+            .plan_move_along(&mut rng, 2, Length::new(0.3), MoveDistribution::Gaussian)
+            .is_some()
+        {
+            sw.confirm();
+            moved += 1;
+        }
+        for (r0, r1) in before.iter().zip(sw.cell.positions.iter()) {
+            assert_eq!((r0.x, r0.y), (r1.x, r1.y));
+        }
+    }
+    assert!(moved > 0);
+    assert_eq!(sw.energy(), sw.compute_energy());
+}
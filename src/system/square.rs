@@ -120,6 +120,11 @@ impl SquareWell {
     }
     /// PUBLIC FOR TESTING ONLY! The shortest distance squared between two vectors.
     pub fn closest_distance2(&self, r1: Vector3d<Length>, r2: Vector3d<Length>) -> Area {
+        self.closest_displacement(r1, r2).norm2()
+    }
+    /// The shortest displacement from `r1` to `r2`, through the
+    /// periodic boundaries.
+    fn closest_displacement(&self, r1: Vector3d<Length>, r2: Vector3d<Length>) -> Vector3d<Length> {
         let mut dr = r2 - r1;
         if dr.x < -0.5 * self.box_diagonal.x {
             while {
@@ -151,7 +156,7 @@ impl SquareWell {
                 dr.z -= self.box_diagonal.z;
             }
         }
-        dr.norm2()
+        dr
     }
     /// PUBLIC FOR TESTING ONLY! The shortest distance squared between two vectors.
     pub fn sloppy_closest_distance2(&self, r1: Vector3d<Length>, r2: Vector3d<Length>) -> Area {
@@ -309,6 +314,15 @@ impl System for SquareWell {
     fn dimensionality(&self) -> u64 {
         self.min_moves_to_randomize() * 3
     }
+    fn snapshot(&self) -> Option<Self> {
+        Some(SquareWell {
+            well_width: self.well_width,
+            positions: self.positions.clone(),
+            E: self.E,
+            box_diagonal: self.box_diagonal,
+            possible_change: self.possible_change,
+        })
+    }
 }
 
 impl ConfirmSystem for SquareWell {
@@ -353,20 +367,44 @@ impl GrandSystem for SquareWell {
 }
 
 impl MovableSystem for SquareWell {
-    fn plan_move(&mut self, rng: &mut MyRng, mean_distance: Length) -> Option<Energy> {
-        use crate::rng::vector;
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         if self.positions.len() > 0 {
             let which = rng.sample(Uniform::new(0, self.positions.len()));
-            let to = self.put_in_cell(self.positions[which] + vector(rng) * mean_distance);
+            let to =
+                self.put_in_cell(self.positions[which] + distribution.sample(rng) * mean_distance);
             self.move_atom(which, to)
         } else {
             None
         }
     }
+    fn planned_displacement(&self) -> Option<Vector3d<Length>> {
+        match self.possible_change {
+            Change::Move { which, to, .. } => {
+                Some(self.closest_displacement(self.positions[which], to))
+            }
+            _ => None,
+        }
+    }
     fn max_size(&self) -> Length {
         use dimensioned::Sqrt;
         0.5 * self.box_diagonal.norm2().sqrt()
     }
+    /// Moves a fifth of the width of the attractive shell, so that
+    /// most moves keep an atom in or out of its neighbors' wells and
+    /// the energy changes by a few bins at most.  Hard spheres have
+    /// no shell, so they move a twentieth of their diameter as usual.
+    fn natural_move_scale(&self) -> Length {
+        if self.well_width > units::SIGMA {
+            0.2 * (self.well_width - units::SIGMA)
+        } else {
+            0.05 * units::SIGMA
+        }
+    }
 }
 
 fn max_balls_within(mut distance: Length) -> u64 {
@@ -533,7 +571,7 @@ fn closest_distance_matches() {
     }
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     for &r1 in sw.positions.iter() {
@@ -550,6 +588,29 @@ fn closest_distance_matches() {
     }
 }
 
+#[test]
+fn natural_move_scale_follows_the_well() {
+    let scale = |w: f64| {
+        SquareWell::from(SquareWellNParams {
+            well_width: Unitless::new(w),
+            N: 10,
+            ..SquareWellNParams::default()
+        })
+        .natural_move_scale()
+    };
+    assert!((scale(1.3) - 0.06 * units::SIGMA).value_unsafe.abs() < 1e-12);
+    assert!((scale(1.6) - 2.0 * scale(1.3)).value_unsafe.abs() < 1e-12);
+    assert_eq!(scale(1.0), 0.05 * units::SIGMA);
+}
+
+#[test]
+fn a_sweep_moves_every_atom_once() {
+    use std::default::Default;
+    let sw = SquareWell::from(SquareWellNParams::default());
+    assert!(sw.num_atoms() > 1);
+    assert_eq!(sw.natural_sweep_length(), sw.num_atoms() as u64);
+}
+
 #[test]
 fn energy_is_right() {
     use std::default::Default;
@@ -558,7 +619,7 @@ fn energy_is_right() {
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100 {
         println!("making a move...");
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
         assert_eq!(sw.energy(), sw.compute_energy());
     }
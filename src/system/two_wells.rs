@@ -448,15 +448,19 @@ impl ConfirmSystem for TwoWells {
 }
 
 impl MovableSystem for TwoWells {
-    fn plan_move(&mut self, rng: &mut MyRng, d: Length) -> Option<Energy> {
-        use crate::rng::vector;
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        d: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         let index = 3 * rng.gen_range(0, self.position.len() / 3);
         let old_r = Vector3d::new(
             self.position[index],
             self.position[index + 1],
             self.position[index + 2],
         );
-        let r = vector(rng) * d + old_r;
+        let r = distribution.sample(rng) * d + old_r;
         let d_squared = self.d_squared - old_r.norm2() + r.norm2();
         let x1 = if index == 0 { r.x } else { self.position[0] };
         self.change = Change { index, values: r };
@@ -237,12 +237,16 @@ impl ConfirmSystem for Water {
 }
 
 impl MovableSystem for Water {
-    fn plan_move(&mut self, rng: &mut MyRng, mean_distance: Length) -> Option<Energy> {
-        use crate::rng::vector;
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         if self.molecules.len() > 0 {
             let which = rng.sample(Uniform::new(0, self.molecules.len()));
             let to = unsafe { *self.molecules.get_unchecked(which) }.position
-                + vector(rng) * mean_distance;
+                + distribution.sample(rng) * mean_distance;
             self.move_atom(which, to)
         } else {
             None
@@ -342,7 +346,8 @@ fn energy_is_right(natoms: usize) {
     let maxe = (natoms as f64) * 16.0 * units::EPSILON;
     let mut i = 0.0;
     while i < 1000.0 {
-        if let Some(newe) = water.plan_move(&mut rng, Length::new(1.0)) {
+        if let Some(newe) = water.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian)
+        {
             if newe < maxe || newe < old_energy {
                 water.confirm();
                 println!(
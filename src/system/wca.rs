@@ -339,12 +339,17 @@ impl GrandReplicaSystem for Wca {
 }
 
 impl MovableSystem for Wca {
-    fn plan_move(&mut self, rng: &mut MyRng, mean_distance: Length) -> Option<Energy> {
-        use crate::rng::vector;
+    fn plan_move(
+        &mut self,
+        rng: &mut MyRng,
+        mean_distance: Length,
+        distribution: MoveDistribution,
+    ) -> Option<Energy> {
         if self.cell.positions.len() > 0 {
             let which = rng.sample(Uniform::new(0, self.cell.positions.len()));
             let to = self.cell.put_in_cell(
-                unsafe { *self.cell.positions.get_unchecked(which) } + vector(rng) * mean_distance,
+                unsafe { *self.cell.positions.get_unchecked(which) }
+                    + distribution.sample(rng) * mean_distance,
             );
             self.move_atom(which, to)
         } else {
@@ -514,7 +519,7 @@ fn closest_distance_matches(natoms: usize) {
     }
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     for &r1 in sw.cell.positions.iter() {
@@ -549,7 +554,7 @@ fn maybe_interacting_needs_no_shifting(natoms: usize) {
     let mut sw = mk_wca(natoms, 0.3);
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     for &r1 in sw.cell.positions.iter() {
@@ -600,7 +605,7 @@ fn maybe_interacting_includes_everything() {
     let mut sw = mk_wca(100, 0.3);
     let mut rng = MyRng::seed_from_u64(1);
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm()
     }
     for &r1 in sw.cell.positions.iter() {
@@ -617,7 +622,7 @@ fn maybe_interacting_excluding_includes_everything(natoms: usize) {
             .verify_maybe_interacting_excluding_includes_everything(r1, which);
     }
     for _ in 0..100000 {
-        sw.plan_move(&mut rng, Length::new(1.0));
+        sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian);
         sw.confirm();
     }
     println!("Finished moving stuff around...");
@@ -670,7 +675,7 @@ fn energy_is_right(natoms: usize, ff: f64) {
     let maxe = (natoms as f64) * 16.0 * units::EPSILON;
     let mut i = 0.0;
     while i < 1000.0 {
-        if let Some(newe) = sw.plan_move(&mut rng, Length::new(1.0)) {
+        if let Some(newe) = sw.plan_move(&mut rng, Length::new(1.0), MoveDistribution::Gaussian) {
             if newe < maxe || newe < old_energy {
                 sw.confirm();
                 println!(
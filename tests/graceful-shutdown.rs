@@ -0,0 +1,33 @@
+extern crate sadmc;
+
+use sadmc::mc::energy::{EnergyMC, EnergyMCParamsBuilder};
+use sadmc::mc::plugin::{self, Plugin, PluginManager};
+use sadmc::mc::{load_from, MonteCarlo};
+use sadmc::system::ising::{Ising, IsingParams};
+
+// This lives in its own test binary, since the shutdown flag is global
+// and would stop any other simulation running in the same process.
+#[test]
+fn shutdown_request_saves_a_checkpoint() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let save_as = dir.path().join("ising.yaml");
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new().seed(0).build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        save_as.clone(),
+    );
+    for _ in 0..1000 {
+        mc.move_once();
+    }
+    std::fs::remove_file(&save_as).ok();
+
+    let manager = PluginManager::new();
+    let plugins: [&dyn Plugin<EnergyMC<Ising>>; 0] = [];
+    assert!(!manager.save_if_shutdown_requested(&mc, &mc.system, &plugins));
+    assert!(!save_as.exists());
+
+    plugin::request_shutdown();
+    assert!(manager.save_if_shutdown_requested(&mc, &mc.system, &plugins));
+    let saved: EnergyMC<Ising> = load_from(&save_as).unwrap();
+    assert_eq!(saved.moves, 1000);
+}
@@ -0,0 +1,723 @@
+extern crate sadmc;
+
+use std::collections::BTreeMap;
+
+use sadmc::mc::anneal::{Anneal, AnnealParams};
+use sadmc::mc::energy::{
+    AlignedDos, EnergyMC, EnergyMCParams, EnergyMCParamsBuilder, EnergyMcError, MethodKind,
+    MethodParams, State,
+};
+use sadmc::mc::metropolis::{Metropolis, MetropolisParams};
+use sadmc::mc::windows::run_windows;
+use sadmc::mc::MonteCarlo;
+use sadmc::system::ising::{Ising, IsingParams};
+use sadmc::system::{units, Interned, System, Unitless};
+
+/// The exact number of states g(E) of the 4 by 4 periodic Ising
+/// model, keyed by energy in units of epsilon.
+const ISING_4X4: &[(i64, u64)] = &[
+    (-32, 2),
+    (-24, 32),
+    (-20, 64),
+    (-16, 424),
+    (-12, 1728),
+    (-8, 6688),
+    (-4, 13568),
+    (0, 20524),
+    (4, 13568),
+    (8, 6688),
+    (12, 1728),
+    (16, 424),
+    (20, 64),
+    (24, 32),
+    (32, 2),
+];
+
+/// The exact g(E) of the 8 by 8 periodic Ising model, as tabulated by
+/// Beale (1996).
+const ISING_8X8: &[(i64, u64)] = &[
+    (-128, 2),
+    (-120, 128),
+    (-116, 256),
+    (-112, 4672),
+    (-108, 17920),
+    (-104, 145408),
+    (-100, 712960),
+    (-96, 4274576),
+    (-92, 22128384),
+    (-88, 118551552),
+    (-84, 610683392),
+    (-80, 3150447680),
+    (-76, 16043381504),
+    (-72, 80748258688),
+    (-68, 396915938304),
+    (-64, 1887270677624),
+    (-60, 8582140066816),
+    (-56, 36967268348032),
+    (-52, 149536933509376),
+    (-48, 564033837424064),
+    (-44, 1971511029384704),
+    (-40, 6350698012553216),
+    (-36, 18752030727310592),
+    (-32, 50483110303426544),
+    (-28, 123229776338119424),
+    (-24, 271209458049836032),
+    (-20, 535138987032308224),
+    (-16, 941564975390477248),
+    (-12, 1469940812209435392),
+    (-8, 2027486077172296064),
+    (-4, 2462494093546483712),
+    (0, 2627978003957146636),
+    (4, 2462494093546483712),
+    (8, 2027486077172296064),
+    (12, 1469940812209435392),
+    (16, 941564975390477248),
+    (20, 535138987032308224),
+    (24, 271209458049836032),
+    (28, 123229776338119424),
+    (32, 50483110303426544),
+    (36, 18752030727310592),
+    (40, 6350698012553216),
+    (44, 1971511029384704),
+    (48, 564033837424064),
+    (52, 149536933509376),
+    (56, 36967268348032),
+    (60, 8582140066816),
+    (64, 1887270677624),
+    (68, 396915938304),
+    (72, 80748258688),
+    (76, 16043381504),
+    (80, 3150447680),
+    (84, 610683392),
+    (88, 118551552),
+    (92, 22128384),
+    (96, 4274576),
+    (100, 712960),
+    (104, 145408),
+    (108, 17920),
+    (112, 4672),
+    (116, 256),
+    (120, 128),
+    (128, 2),
+];
+
+fn exact_counts(n: usize) -> &'static [(i64, u64)] {
+    match n {
+        4 => ISING_4X4,
+        8 => ISING_8X8,
+        _ => panic!("no exact density of states for n = {}", n),
+    }
+}
+
+/// The exact ln g(E) of an n by n periodic Ising model.
+fn exact_ln_dos(n: usize) -> BTreeMap<i64, f64> {
+    exact_counts(n)
+        .iter()
+        .map(|&(e, c)| (e, (c as f64).ln()))
+        .collect()
+}
+
+#[test]
+fn exact_dos_is_sane() {
+    for &n in &[4, 8] {
+        let counts = exact_counts(n);
+        let total: u128 = counts.iter().map(|&(_, c)| c as u128).sum();
+        assert_eq!(total, 1 << (n * n), "wrong total for n = {}", n);
+        let ground = -2 * (n * n) as i64;
+        assert_eq!(counts[0], (ground, 2));
+        assert!(counts.iter().all(|&(e, _)| e != ground + 4));
+        // The spectrum is symmetric about zero energy.
+        for (&(e, c), &(e2, c2)) in counts.iter().zip(counts.iter().rev()) {
+            assert_eq!((e, c), (-e2, c2));
+        }
+    }
+}
+
+#[test]
+fn exact_lnw_gives_the_exact_state_counts() {
+    for &n in &[4, 8] {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut mc = EnergyMC::from_params(
+            EnergyMCParamsBuilder::new().seed(1).build().unwrap(),
+            Ising::from(IsingParams { N: n }),
+            dir.path().join("ising.yaml"),
+        );
+        for h in mc.bins.histogram.iter_mut() {
+            *h = 0;
+        }
+        let index = |mc: &EnergyMC<Ising>, e: i64| {
+            mc.state_to_index(State {
+                E: e as f64 * units::EPSILON,
+            })
+            .unwrap()
+        };
+        for (&e, &s) in exact_ln_dos(n).iter() {
+            let i = index(&mc, e);
+            // The additive constant in lnw must not matter.
+            mc.bins.lnw[i] = Unitless::new(s + 7.25);
+            mc.bins.histogram[i] = 1;
+        }
+        let counts = mc.state_counts();
+        let total = 2f64.powi((n * n) as i32);
+        assert!(
+            (counts.iter().sum::<f64>() / total - 1.0).abs() < 1e-12,
+            "wrong total for n = {}",
+            n
+        );
+        for &(e, c) in exact_counts(n) {
+            let found = counts[index(&mc, e)];
+            assert!(
+                (found / c as f64 - 1.0).abs() < 1e-9,
+                "wrong at E = {}: {} vs {}",
+                e,
+                found,
+                c
+            );
+        }
+    }
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn sad_matches_the_beale_dos_of_an_8x8_lattice() {
+    let exact = exact_ln_dos(8);
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new()
+            .seed(1)
+            .quiet(true)
+            .build()
+            .unwrap(),
+        Ising::from(IsingParams { N: 8 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..10_000_000 {
+        mc.move_once();
+    }
+    let index = |e: i64| {
+        mc.state_to_index(State {
+            E: e as f64 * units::EPSILON,
+        })
+        .unwrap()
+    };
+    let lnw = |e: i64| mc.bins.lnw[index(e)].value_unsafe;
+    let offset = lnw(0) - exact[&0];
+    for (&e, &s) in exact.iter().filter(|&(&e, _)| e <= 0) {
+        assert!(mc.bins.histogram[index(e)] > 0, "never visited E = {}", e);
+        assert!(
+            (lnw(e) - offset - s).abs() < 1.0,
+            "lnw is off at E = {}: {} vs {}",
+            e,
+            lnw(e) - offset,
+            s
+        );
+    }
+}
+
+#[test]
+fn sad_finds_every_energy_of_a_4x4_lattice() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new()
+            .seed(1)
+            .quiet(true)
+            .build()
+            .unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..200_000 {
+        mc.move_once();
+    }
+    let index = |e: i64| {
+        mc.state_to_index(State {
+            E: e as f64 * units::EPSILON,
+        })
+        .unwrap()
+    };
+    for &(e, _) in ISING_4X4.iter().filter(|&&(e, _)| e <= 0) {
+        assert!(mc.bins.histogram[index(e)] > 0, "never visited E = {}", e);
+    }
+    // The exact lnw rises by 5.4 and then 3.9 between these.
+    let lnw = |e: i64| mc.bins.lnw[index(e)].value_unsafe;
+    assert!(lnw(-32) < lnw(-16) && lnw(-16) < lnw(0));
+}
+
+#[test]
+fn sad_seeded_with_the_exact_dos_stays_put() {
+    let exact = exact_ln_dos(4);
+    // Ising energies are multiples of 4, at the centers of our bins.
+    let num_bins = 17;
+    let guess = AlignedDos {
+        min: -34.0 * units::EPSILON,
+        width: 4.0 * units::EPSILON,
+        mean_lnw: (0..num_bins)
+            .map(|i| *exact.get(&(4 * i as i64 - 32)).unwrap_or(&0.0))
+            .collect(),
+        std_lnw: vec![0.0; num_bins],
+        std_error: vec![0.0; num_bins],
+        num_runs: (0..num_bins)
+            .map(|i| exact.contains_key(&(4 * i as i64 - 32)) as usize)
+            .collect(),
+    };
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new().seed(0).build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    mc.seed_lnw(&guess).unwrap();
+    let sad = mc.sad_state().unwrap();
+    assert_eq!(sad.too_lo, -32.0 * units::EPSILON);
+    assert_eq!(sad.too_hi, 0.0 * units::EPSILON);
+    for _ in 0..200_000 {
+        mc.move_once();
+    }
+    let index = |e: i64| {
+        mc.state_to_index(State {
+            E: e as f64 * units::EPSILON,
+        })
+        .unwrap()
+    };
+    let lnw = |e: i64| mc.bins.lnw[index(e)].value_unsafe;
+    let offset = lnw(0) - exact[&0];
+    for (&e, &s) in exact.iter().filter(|&(&e, _)| e <= 0) {
+        assert!((lnw(e) - offset - s).abs() < 0.3, "lnw moved at E = {}", e);
+    }
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn metropolis_matches_exact_ising_thermodynamics() {
+    let exact = exact_ln_dos(4);
+    let temperature = 3.0;
+    let z: f64 = exact
+        .iter()
+        .map(|(&e, &s)| (s - e as f64 / temperature).exp())
+        .sum();
+    let average = |f: &dyn Fn(f64) -> f64| -> f64 {
+        exact
+            .iter()
+            .map(|(&e, &s)| f(e as f64) * (s - e as f64 / temperature).exp())
+            .sum::<f64>()
+            / z
+    };
+    let mean_e = average(&|e| e);
+    let heat_capacity = (average(&|e| e * e) - mean_e * mean_e) / (temperature * temperature);
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = MetropolisParams::default();
+    params.T = temperature * units::EPSILON;
+    let mut mc = Metropolis::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..2_000_000 {
+        mc.move_once();
+    }
+    let summary = mc.summary();
+    assert_eq!(summary.T, temperature);
+    assert_eq!(summary.moves, mc.moves);
+    let mc_e = summary.mean_energy;
+    let mc_c = summary.heat_capacity;
+    assert!((mc_e - mean_e).abs() < 0.02 * mean_e.abs());
+    assert!((mc_c - heat_capacity).abs() < 0.05 * heat_capacity);
+    assert_eq!(mc.histogram.iter().sum::<u64>(), mc.moves);
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn reweighted_magnetization_matches_metropolis() {
+    let temperature = 3.0 * units::EPSILON;
+    let magnetization = Interned::from("magnetization");
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .wl(None)
+        .seed(1)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..2_000_000 {
+        mc.move_once();
+    }
+    let reweighted = mc.mean_observable(magnetization, temperature).unwrap();
+
+    let mut params = MetropolisParams::default();
+    params.T = temperature;
+    let mut canonical = Metropolis::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("metropolis.yaml"),
+    );
+    for _ in 0..2_000_000 {
+        canonical.move_once();
+    }
+    let direct = canonical.mean_observable(magnetization).unwrap();
+    assert!((reweighted - direct).abs() < 0.05 * direct);
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn reweighted_joint_histogram_matches_a_canonical_run() {
+    let temperature = 3.0 * units::EPSILON;
+    let dir = tempfile::TempDir::new().unwrap();
+    // With 16 spins |M|/N is a multiple of 1/8, so each value it can
+    // take is at the center of one of nine bins.
+    let with_joint = |builder: EnergyMCParamsBuilder| {
+        builder
+            .joint_histogram("magnetization", -1.0 / 16.0, 17.0 / 16.0, 9)
+            .seed(1)
+            .quiet(true)
+            .build()
+            .unwrap()
+    };
+    let mut mc = EnergyMC::from_params(
+        with_joint(EnergyMCParamsBuilder::new().wl(None)),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    mc.run_for(500_000);
+    let reweighted = mc.observable_distribution(temperature).unwrap();
+    assert!((reweighted.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+    let mut params = with_joint(EnergyMCParamsBuilder::new());
+    params._method = MethodParams::_Canonical { T: temperature };
+    let mut canonical = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("canonical.yaml"),
+    );
+    canonical.run_for(500_000);
+    let counts = canonical.joint_histogram().unwrap().observable_counts();
+    let total = counts.iter().sum::<u64>() as f64;
+    for (j, &c) in counts.iter().enumerate() {
+        let direct = c as f64 / total;
+        assert!(
+            (reweighted[j] - direct).abs() < 0.02,
+            "P(|M|/N = {}) is {} reweighted but {} direct",
+            j as f64 / 8.0,
+            reweighted[j],
+            direct
+        );
+    }
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn samc_hands_over_to_sad_on_resume() {
+    let exact = exact_ln_dos(4);
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .samc(1000.0)
+        .seed(1)
+        .quiet(true)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    mc.run_for(500_000);
+    mc.try_checkpoint().unwrap();
+    let lnw = mc.bins.lnw.clone();
+    let histogram = mc.bins.histogram.clone();
+
+    let sad = EnergyMCParamsBuilder::new().sad(units::EPSILON).quiet(true);
+    // Changing method has to be asked for.
+    let refused = EnergyMC::<Ising>::resume(&mc.save_as, sad.clone().build().unwrap());
+    assert!(matches!(refused, Err(EnergyMcError::InvalidParams(_))));
+    let sad = sad.switch_method().build().unwrap();
+    let mut mc = EnergyMC::<Ising>::resume(&mc.save_as, sad).unwrap();
+    assert_eq!(mc.method_info().kind, MethodKind::Sad);
+    assert_eq!(mc.bins.lnw, lnw);
+    assert_eq!(mc.bins.histogram, histogram);
+    // The entropy peaks at zero energy, and it is never colder than
+    // min_T all the way down to the ground state.
+    let range = mc.sad_state().unwrap();
+    assert_eq!(range.too_hi, 0.0 * units::EPSILON);
+    assert_eq!(range.too_lo, -32.0 * units::EPSILON);
+    let gamma = mc.method_info().gamma;
+    assert!(gamma > 0.0 && gamma < 1.0, "gamma is {}", gamma);
+
+    mc.run_for(500_000);
+    assert_eq!(mc.sad_state().unwrap().too_lo, -32.0 * units::EPSILON);
+    let index = |e: i64| {
+        mc.state_to_index(State {
+            E: e as f64 * units::EPSILON,
+        })
+        .unwrap()
+    };
+    let lnw = |e: i64| mc.bins.lnw[index(e)].value_unsafe;
+    let offset = lnw(0) - exact[&0];
+    for (&e, &s) in exact.iter().filter(|&(&e, _)| e <= 0) {
+        assert!(
+            (lnw(e) - offset - s).abs() < 0.5,
+            "lnw is off at E = {}: {} vs {}",
+            e,
+            lnw(e) - offset,
+            s
+        );
+    }
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn wltm_matches_exact_ising_dos() {
+    let exact = exact_ln_dos(4);
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .wltm(None)
+        .seed(1)
+        .build()
+        .unwrap();
+    let mut mc = EnergyMC::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("ising.yaml"),
+    );
+    for _ in 0..1_000_000 {
+        mc.move_once();
+    }
+    let index = |e: i64| {
+        mc.state_to_index(State {
+            E: e as f64 * units::EPSILON,
+        })
+        .unwrap()
+    };
+    let tm = mc.dos_from_transition_matrix().unwrap();
+    let tm = |e: i64| tm[index(e)].unwrap().value_unsafe;
+    let wl = |e: i64| mc.bins.lnw[index(e)].value_unsafe;
+    let tm_offset = tm(0) - exact[&0];
+    let wl_offset = wl(0) - exact[&0];
+    for (&e, &s) in exact.iter() {
+        assert!(
+            (tm(e) - tm_offset - s).abs() < 0.1,
+            "TM is off at E = {}",
+            e
+        );
+        assert!(
+            (wl(e) - wl_offset - s).abs() < 0.5,
+            "WL is off at E = {}",
+            e
+        );
+    }
+}
+
+#[test]
+fn annealing_finds_the_ising_ground_state() {
+    // The spins are antiferromagnetic, so the ground state of an even
+    // lattice is the checkerboard, with every bond satisfied.
+    let n = 6;
+    let max_iter = 200_000;
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = AnnealParams::default();
+    params.T_initial = 5.0 * units::EPSILON;
+    params.T_final = 0.05 * units::EPSILON;
+    params._report.max_iter = Some(max_iter);
+    let mut mc = Anneal::from_params(
+        params,
+        Ising::from(IsingParams { N: n }),
+        dir.path().join("ising.yaml"),
+    );
+    // The last move would end the process, so stop just short of it.
+    for _ in 1..max_iter {
+        mc.move_once();
+    }
+    let ground = -2.0 * (n * n) as f64 * units::EPSILON;
+    assert_eq!(mc.best_energy, ground);
+    assert_eq!(mc.best.energy(), ground);
+    assert_eq!(mc.best.compute_energy(), ground);
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn sad_with_a_max_temperature_stops_short_of_the_maximum_entropy() {
+    // Start from the ground state, so that too_hi has to climb.
+    let max_iter = 50_000;
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut params = AnnealParams::default();
+    params.T_initial = 5.0 * units::EPSILON;
+    params.T_final = 0.05 * units::EPSILON;
+    params._report.max_iter = Some(max_iter);
+    let mut anneal = Anneal::from_params(
+        params,
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("anneal.yaml"),
+    );
+    for _ in 1..max_iter {
+        anneal.move_once();
+    }
+    assert_eq!(anneal.best_energy, -32.0 * units::EPSILON);
+
+    let too_hi_trajectory = |params: EnergyMCParams, name: &str| {
+        let mut mc = EnergyMC::from_params(params, anneal.best.clone(), dir.path().join(name));
+        let mut too_hi = Vec::new();
+        for _ in 0..1000 {
+            for _ in 0..1000 {
+                mc.move_once();
+            }
+            too_hi.push(mc.sad_state().unwrap().too_hi.value_unsafe);
+        }
+        too_hi
+    };
+    let min_T = 0.2 * units::EPSILON;
+    let max_T = 4.0;
+    let old = too_hi_trajectory(
+        EnergyMCParamsBuilder::new()
+            .seed(1)
+            .sad(min_T)
+            .build()
+            .unwrap(),
+        "old.yaml",
+    );
+    let new = too_hi_trajectory(
+        EnergyMCParamsBuilder::new()
+            .seed(1)
+            .sad_between(min_T, max_T * units::EPSILON)
+            .build()
+            .unwrap(),
+        "new.yaml",
+    );
+    for too_hi in &[&old, &new] {
+        assert!(too_hi.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    // Without a max_T, too_hi climbs to the maximum entropy.
+    assert_eq!(*old.last().unwrap(), 0.0);
+    // With one, it stops near the most probable energy at max_T.
+    let exact = exact_ln_dos(4);
+    let free = |e: i64| exact[&e] - e as f64 / max_T;
+    let most_probable = *exact
+        .keys()
+        .max_by(|&&a, &&b| free(a).partial_cmp(&free(b)).unwrap())
+        .unwrap();
+    assert_eq!(most_probable, -8);
+    let last = *new.last().unwrap();
+    assert!(
+        last >= (most_probable - 4) as f64 && last <= (most_probable + 4) as f64,
+        "too_hi ended at {}",
+        last
+    );
+    assert!(new.iter().all(|&e| e <= (most_probable + 4) as f64));
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn windowed_sad_matches_a_single_run() {
+    let exact = exact_ln_dos(4);
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = EnergyMCParamsBuilder::new()
+        .seed(1)
+        .quiet(true)
+        .build()
+        .unwrap();
+    let system = Ising::from(IsingParams { N: 4 });
+    // Two windows overlapping from -20 to -12.
+    let windows = [
+        (-33.0 * units::EPSILON, -11.0 * units::EPSILON),
+        (-21.0 * units::EPSILON, 1.0 * units::EPSILON),
+    ];
+    let stitched = run_windows(
+        &params,
+        &system,
+        &dir.path().join("windows.yaml"),
+        &windows,
+        400_000,
+        true,
+    )
+    .unwrap();
+    let mut single = EnergyMC::from_params(params, system, dir.path().join("single.yaml"));
+    single.run_for(800_000);
+    let stitched_lnw = |e: i64| {
+        let i = (((e as f64) * units::EPSILON - stitched.min) / stitched.width).value_unsafe;
+        assert!(
+            stitched.histogram[i as usize] > 0,
+            "windows never visited E = {}",
+            e
+        );
+        stitched.lnw[i as usize]
+    };
+    let single_lnw = |e: i64| {
+        let i = single
+            .state_to_index(State {
+                E: e as f64 * units::EPSILON,
+            })
+            .unwrap();
+        single.bins.lnw[i].value_unsafe
+    };
+    let stitched_offset = stitched_lnw(0) - exact[&0];
+    let single_offset = single_lnw(0) - exact[&0];
+    for (&e, &s) in exact.iter().filter(|&(&e, _)| e <= 0) {
+        let a = stitched_lnw(e) - stitched_offset;
+        let b = single_lnw(e) - single_offset;
+        assert!((a - s).abs() < 0.5, "windows are off at E = {}", e);
+        assert!(
+            (a - b).abs() < 0.7,
+            "windows disagree with one run at E = {}",
+            e
+        );
+    }
+}
+
+#[test]
+#[ignore = "slow statistical check; run with --ignored"]
+fn too_hi_from_bounds_flattens_the_whole_range_sooner() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let top = 32.0 * units::EPSILON;
+    let builder = |seed: u64| EnergyMCParamsBuilder::new().seed(seed).quiet(true);
+    let seeded = EnergyMC::from_params(
+        builder(1).too_hi_from_bounds().build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("seeded.yaml"),
+    );
+    assert_eq!(seeded.sad_state().unwrap().too_hi, top);
+
+    // The moves until every energy from -32 to 32 has been visited at
+    // least a tenth as often as the average, up to a limit.
+    const LIMIT: u64 = 500_000;
+    let moves_to_flat = |params: EnergyMCParams| {
+        let mut mc = EnergyMC::from_params(
+            params,
+            Ising::from(IsingParams { N: 4 }),
+            dir.path().join("ising.yaml"),
+        );
+        while mc.moves < LIMIT {
+            mc.run_for(1000);
+            let counts: Vec<u64> = ISING_4X4
+                .iter()
+                .map(|&(e, _)| {
+                    mc.state_to_index(State {
+                        E: e as f64 * units::EPSILON,
+                    })
+                    .map(|i| mc.bins.histogram[i])
+                    .unwrap_or(0)
+                })
+                .collect();
+            let mean = counts.iter().sum::<u64>() as f64 / counts.len() as f64;
+            if counts.iter().all(|&c| c as f64 >= 0.1 * mean) {
+                break;
+            }
+        }
+        mc.moves
+    };
+    let seeds = 1..=6;
+    let with_bounds: u64 = seeds
+        .clone()
+        .map(|seed| moves_to_flat(builder(seed).too_hi_from_bounds().build().unwrap()))
+        .sum();
+    let without: u64 = seeds
+        .map(|seed| moves_to_flat(builder(seed).build().unwrap()))
+        .sum();
+    assert!(
+        with_bounds < without,
+        "{} moves with bounds, {} without",
+        with_bounds,
+        without
+    );
+}
@@ -0,0 +1,103 @@
+extern crate sadmc;
+
+use sadmc::mc::energy::{EnergyMC, EnergyMCParamsBuilder};
+use sadmc::mc::{load_from, save_to, MonteCarlo, SaveError};
+use sadmc::system::ising::{Ising, IsingParams};
+use sadmc::system::System;
+
+fn round_trip(extension: &str) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let save_as = dir.path().join(format!("ising.{}", extension));
+    let mut mc = EnergyMC::from_params(
+        EnergyMCParamsBuilder::new().seed(0).build().unwrap(),
+        Ising::from(IsingParams { N: 4 }),
+        save_as.clone(),
+    );
+    for _ in 0..10000 {
+        mc.move_once();
+    }
+    mc.checkpoint();
+    let resumed: EnergyMC<Ising> = load_from(&save_as).unwrap();
+    assert_eq!(resumed.moves, mc.moves);
+    assert_eq!(resumed.accepted_moves, mc.accepted_moves);
+    assert_eq!(resumed.system.energy(), mc.system.energy());
+    assert_eq!(resumed.bins.min, mc.bins.min);
+    assert_eq!(resumed.bins.histogram, mc.bins.histogram);
+    // json does not promise to round-trip floats to the last bit.
+    assert_eq!(resumed.bins.lnw.len(), mc.bins.lnw.len());
+    for (a, b) in resumed.bins.lnw.iter().zip(mc.bins.lnw.iter()) {
+        assert!((a.value_unsafe - b.value_unsafe).abs() <= 1e-12 * b.value_unsafe.abs());
+    }
+}
+
+#[test]
+fn yaml_round_trip() {
+    round_trip("yaml");
+}
+
+#[test]
+fn json_round_trip() {
+    round_trip("json");
+}
+
+#[test]
+fn cbor_round_trip() {
+    round_trip("cbor");
+}
+
+#[test]
+fn bincode_round_trip() {
+    round_trip("bin");
+}
+
+#[test]
+fn unknown_extension_is_an_error() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("ising.txt");
+    match save_to(&path, &vec![1.0, 2.0]) {
+        Err(SaveError::UnknownFormat(p)) => assert_eq!(p, path),
+        other => panic!("expected an unknown format error, got {:?}", other),
+    }
+    assert!(!path.exists());
+    match load_from::<Vec<f64>>(&path) {
+        Err(SaveError::UnknownFormat(_)) => (),
+        other => panic!("expected an unknown format error, got {:?}", other),
+    }
+}
+
+#[test]
+fn a_resumed_run_matches_an_uninterrupted_one() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let params = || EnergyMCParamsBuilder::new().seed(42).build().unwrap();
+    let mut whole = EnergyMC::from_params(
+        params(),
+        Ising::from(IsingParams { N: 4 }),
+        dir.path().join("whole.bin"),
+    );
+    for _ in 0..2000 {
+        whole.move_once();
+    }
+
+    let save_as = dir.path().join("halves.bin");
+    let mut first = EnergyMC::from_params(
+        params(),
+        Ising::from(IsingParams { N: 4 }),
+        save_as.clone(),
+    );
+    for _ in 0..1000 {
+        first.move_once();
+    }
+    first.checkpoint();
+    let mut resumed: EnergyMC<Ising> = load_from(&save_as).unwrap();
+    resumed.system_mut().update_caches();
+    for _ in 0..1000 {
+        resumed.move_once();
+    }
+
+    assert_eq!(resumed.moves, whole.moves);
+    assert_eq!(resumed.accepted_moves, whole.accepted_moves);
+    assert_eq!(resumed.system.energy(), whole.system.energy());
+    assert_eq!(resumed.bins.min, whole.bins.min);
+    assert_eq!(resumed.bins.histogram, whole.bins.histogram);
+    assert_eq!(resumed.bins.lnw, whole.bins.lnw);
+}
@@ -20,13 +20,15 @@ fn energies_agree() {
     sparam._dim = square::CellDimensionsGivenNumber::CellWidth(box_diag);
     let tempd = tempfile::TempDir::new().unwrap();
     let yaml_file = tempd.path().join("test.yaml");
+    let mut params = EnergyMCParams::default();
+    params.seed = Some(0);
     let mut mcnew = EnergyMC::from_params(
-        EnergyMCParams::default(),
+        params.clone(),
         optsquare::SquareWell::from(oparam),
         yaml_file.clone(),
     );
     let mut mcold = EnergyMC::from_params(
-        EnergyMCParams::default(),
+        params,
         square::SquareWell::from(sparam),
         yaml_file.clone(),
     );